@@ -0,0 +1,367 @@
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Hour from which the departures are considered as evening service
+const EVENING_START_HOUR: u32 = 20;
+
+/// Kind of service of a period of the day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceBand {
+    /// Hours with noticeably more departures than the rest of the day
+    Peak,
+    OffPeak,
+    /// Departures from 20:00 (including after midnight on the same service day)
+    Evening,
+}
+
+/// Consecutive hours of a service day sharing the same [ServiceBand]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequencyBand {
+    pub band: ServiceBand,
+    /// First hour of the band, hours after midnight are represented as 24, 25…
+    pub start_hour: u32,
+    /// Hour at which the band ends (exclusive)
+    pub end_hour: u32,
+    /// Number of departures from the first stop of the trips during the band
+    pub departures: usize,
+}
+
+/// Accessibility of the trips of a route running on a day of the week,
+/// see [Gtfs::route_accessibility]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayAccessibility {
+    pub weekday: Weekday,
+    /// Number of trips of the route running on this day of the week
+    pub trips: usize,
+    /// Trips with room for at least one wheelchair
+    pub wheelchair_accessible: usize,
+    /// Trips without wheelchair_accessible information
+    pub wheelchair_unknown: usize,
+    /// Trips with room for at least one bike
+    pub bikes_allowed: usize,
+    /// Trips without bikes_allowed information
+    pub bikes_unknown: usize,
+}
+
+/// Typical destination of the trips of a route in a direction, see [Gtfs::route_destinations]
+#[derive(Debug, Clone)]
+pub struct RouteDestination {
+    pub direction_id: Option<DirectionType>,
+    /// The most common trip_headsign of the trips, or the name of the most common last stop
+    /// when the trips have no headsign
+    pub headsign: Option<String>,
+    /// The most common last stop of the trips, or its parent station if it has one
+    pub last_stop: Option<Arc<Stop>>,
+    /// Number of trips of the route in this direction
+    pub trips: usize,
+}
+
+/// Most frequent value, the smallest one in case of tie
+fn most_common<'a>(values: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then(b.cmp(a)))
+        .map(|(value, _)| value)
+}
+
+impl DayAccessibility {
+    /// Share of the trips accessible with a wheelchair, None if no trip runs on this day
+    pub fn wheelchair_ratio(&self) -> Option<f64> {
+        Some(self.wheelchair_accessible as f64 / self.trips as f64).filter(|_| self.trips > 0)
+    }
+
+    /// Share of the trips allowing bikes, None if no trip runs on this day
+    pub fn bikes_ratio(&self) -> Option<f64> {
+        Some(self.bikes_allowed as f64 / self.trips as f64).filter(|_| self.trips > 0)
+    }
+}
+
+impl Gtfs {
+    /// Tells if a stop can be used with a wheelchair
+    ///
+    /// When a platform, entrance or boarding area has no information, the wheelchair_boarding
+    /// of its parent is used, as the specification asks.
+    pub fn wheelchair_boarding(&self, stop: &Stop) -> Availability {
+        let mut stop = stop;
+        // the boarding areas are in a platform, which is in the station
+        for _ in 0..3 {
+            let parent = stop
+                .parent_station
+                .as_ref()
+                .and_then(|id| self.stops.get(id));
+            match (stop.wheelchair_boarding, parent) {
+                (Availability::InformationNotAvailable, Some(parent)) => stop = parent,
+                (availability, _) => return availability,
+            }
+        }
+        stop.wheelchair_boarding
+    }
+
+    /// Trips running on the date from a stop to another one, that can be used with a
+    /// wheelchair, ordered by departure time
+    ///
+    /// The trip must have room for a wheelchair, and the stops where it is boarded and left
+    /// must be accessible, see [Gtfs::wheelchair_boarding]. The trips without this information
+    /// are left out. A station can be given for the stops, the trips calling at any of its
+    /// platforms are then considered.
+    pub fn accessible_connections(
+        &self,
+        from_stop_id: &str,
+        to_stop_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<&Trip>, Error> {
+        self.get_stop(from_stop_id)?;
+        self.get_stop(to_stop_id)?;
+        let accessible = |stop: &Stop| self.wheelchair_boarding(stop) == Availability::Available;
+        let mut connections = Vec::new();
+        for trip in self.trips_on(date) {
+            if trip.wheelchair_accessible != Some(WheelChairAccessibleType::AtLeastOneWheelChair) {
+                continue;
+            }
+            let stop_times = self.trip_stop_times(&trip.id)?;
+            let boarding = stop_times.iter().position(|st| {
+                st.pickup_type != Some(PickupDropOffType::NotAvailable)
+                    && self.is_in_station(&st.stop, from_stop_id)
+                    && accessible(&st.stop)
+            });
+            let boarding = match boarding {
+                Some(i) => i,
+                None => continue,
+            };
+            let departure = stop_times[boarding]
+                .departure_time
+                .or(stop_times[boarding].arrival_time);
+            let alighting = stop_times[boarding + 1..].iter().any(|st| {
+                st.drop_off_type != Some(PickupDropOffType::NotAvailable)
+                    && self.is_in_station(&st.stop, to_stop_id)
+                    && accessible(&st.stop)
+            });
+            if alighting {
+                connections.push((departure, trip));
+            }
+        }
+        // the trips are ordered by id, which stays the order of the trips leaving together
+        connections.sort_by_key(|(departure, _)| *departure);
+        Ok(connections.into_iter().map(|(_, trip)| trip).collect())
+    }
+
+    /// Summarizes the wheelchair and bike accessibility of the trips of a route, for each day
+    /// of the week starting on monday
+    ///
+    /// A trip is counted on a day of the week if its service runs on at least one such day.
+    /// Returns an empty vector if the route has no trip.
+    pub fn route_accessibility(&self, route_id: &str) -> Vec<DayAccessibility> {
+        let trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| trip.route_id == route_id)
+            .collect();
+        if trips.is_empty() {
+            return vec![];
+        }
+        let mut weekdays_of_services: HashMap<&str, [bool; 7]> = HashMap::new();
+        for trip in &trips {
+            weekdays_of_services
+                .entry(trip.service_id.as_str())
+                .or_insert_with(|| {
+                    let mut weekdays = [false; 7];
+                    for date in self.operating_days.active_dates(&trip.service_id) {
+                        weekdays[date.weekday().num_days_from_monday() as usize] = true;
+                    }
+                    weekdays
+                });
+        }
+
+        let week = [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+        ];
+        week.iter()
+            .map(|&weekday| {
+                let day = weekday.num_days_from_monday() as usize;
+                let mut accessibility = DayAccessibility {
+                    weekday,
+                    trips: 0,
+                    wheelchair_accessible: 0,
+                    wheelchair_unknown: 0,
+                    bikes_allowed: 0,
+                    bikes_unknown: 0,
+                };
+                for trip in &trips {
+                    if !weekdays_of_services[trip.service_id.as_str()][day] {
+                        continue;
+                    }
+                    accessibility.trips += 1;
+                    match trip.wheelchair_accessible {
+                        Some(WheelChairAccessibleType::AtLeastOneWheelChair) => {
+                            accessibility.wheelchair_accessible += 1
+                        }
+                        Some(WheelChairAccessibleType::NoAccessibilityInfo) | None => {
+                            accessibility.wheelchair_unknown += 1
+                        }
+                        Some(WheelChairAccessibleType::NotWheelChairAccessible) => {}
+                    }
+                    match trip.bikes_allowed {
+                        Some(BikesAllowedType::AtLeastOneBike) => accessibility.bikes_allowed += 1,
+                        Some(BikesAllowedType::NoBikeInfo) | None => {
+                            accessibility.bikes_unknown += 1
+                        }
+                        Some(BikesAllowedType::NoBikesAllowed) => {}
+                    }
+                }
+                accessibility
+            })
+            .collect()
+    }
+
+    /// Splits the service day of a route into peak, off-peak and evening bands
+    ///
+    /// The trips are counted by hour of departure from their first stop.
+    /// An hour before 20:00 is considered as peak if it has at least 75% of the departures of
+    /// the busiest hour, and more departures than the daytime average. An error is returned
+    /// if the stop times of a trip cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn frequency_bands(
+        &self,
+        route_id: &str,
+        date: NaiveDate,
+    ) -> Result<Vec<FrequencyBand>, Error> {
+        let departures = self.route_departures(route_id, None, date)?;
+        let (first, last) = match (departures.first(), departures.last()) {
+            (Some(first), Some(last)) => (first.departure_time / 3600, last.departure_time / 3600),
+            _ => return Ok(vec![]),
+        };
+
+        let mut by_hour = vec![0; (last - first + 1) as usize];
+        for departure in &departures {
+            by_hour[(departure.departure_time / 3600 - first) as usize] += 1;
+        }
+
+        let daytime: Vec<usize> = (first..EVENING_START_HOUR.max(first))
+            .take(by_hour.len())
+            .map(|hour| by_hour[(hour - first) as usize])
+            .collect();
+        let max = daytime.iter().copied().max().unwrap_or(0) as f64;
+        let mean = daytime.iter().sum::<usize>() as f64 / daytime.len().max(1) as f64;
+
+        let mut bands: Vec<FrequencyBand> = Vec::new();
+        for (hour, count) in (first..).zip(by_hour) {
+            let band = if hour >= EVENING_START_HOUR {
+                ServiceBand::Evening
+            } else if count as f64 >= 0.75 * max && count as f64 > mean {
+                ServiceBand::Peak
+            } else {
+                ServiceBand::OffPeak
+            };
+            match bands.last_mut() {
+                Some(previous) if previous.band == band => {
+                    previous.end_hour = hour + 1;
+                    previous.departures += count;
+                }
+                _ => bands.push(FrequencyBand {
+                    band,
+                    start_hour: hour,
+                    end_hour: hour + 1,
+                    departures: count,
+                }),
+            }
+        }
+        Ok(bands)
+    }
+
+    /// Number of trips using each service of calendar.txt and calendar_dates.txt
+    ///
+    /// The services used only by the timeframes of the fares are counted with 0 trips.
+    pub fn service_usage(&self) -> BTreeMap<&str, usize> {
+        let mut usage: BTreeMap<&str, usize> = self
+            .calendar
+            .keys()
+            .chain(self.calendar_dates.keys())
+            .map(|id| (id.as_str(), 0))
+            .collect();
+        for trip in self.trips.values() {
+            if let Some(count) = usage.get_mut(trip.service_id.as_str()) {
+                *count += 1;
+            }
+        }
+        usage
+    }
+
+    /// Services of calendar.txt and calendar_dates.txt used by no trip and no fare timeframe,
+    /// ordered by id
+    pub fn unused_services(&self) -> Vec<&str> {
+        self.service_usage()
+            .into_iter()
+            .filter(|(_, count)| *count == 0)
+            .map(|(id, _)| id)
+            .filter(|id| {
+                !self
+                    .timeframes
+                    .values()
+                    .flatten()
+                    .any(|timeframe| timeframe.service_id == *id)
+            })
+            .collect()
+    }
+
+    /// Infers the destinations of a route in each direction, by majority vote on the trips
+    ///
+    /// This gives labels for the directions even when some trips have no headsign or
+    /// inconsistent ones. The last stops are counted by station, so that the trips ending at
+    /// different platforms of the same station agree. The directions are sorted by direction_id,
+    /// the trips without direction_id come last. Returns an empty vector if the route has no trip,
+    /// and an error if the stop times of a trip cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn route_destinations(&self, route_id: &str) -> Result<Vec<RouteDestination>, Error> {
+        // the trips of each direction, and the last stop (or its station) of each trip
+        let mut by_direction: HashMap<Option<DirectionType>, (Vec<&Trip>, Vec<String>)> =
+            HashMap::new();
+        for trip in self.trips.values().filter(|trip| trip.route_id == route_id) {
+            let (trips, last_stops) = by_direction.entry(trip.direction_id).or_default();
+            trips.push(trip);
+            if let Some(last) = self.trip_stop_times(&trip.id)?.last() {
+                let stop = &last.stop;
+                let id = stop.parent_station.as_deref().unwrap_or(&stop.id);
+                // the placeholder stop of the GTFS-Flex stop times has an empty id
+                if !id.is_empty() {
+                    last_stops.push(id.to_owned());
+                }
+            }
+        }
+        let mut destinations: Vec<RouteDestination> = by_direction
+            .into_iter()
+            .map(|(direction_id, (trips, last_stops))| {
+                let last_stop = most_common(last_stops.iter().map(String::as_str))
+                    .and_then(|id| self.stops.get(id))
+                    .cloned();
+                let headsign = most_common(
+                    trips
+                        .iter()
+                        .filter_map(|trip| trip.trip_headsign.as_deref())
+                        .map(str::trim)
+                        .filter(|headsign| !headsign.is_empty()),
+                )
+                .map(str::to_owned)
+                .or_else(|| last_stop.as_ref().map(|stop| stop.name.clone()));
+                RouteDestination {
+                    direction_id,
+                    headsign,
+                    last_stop,
+                    trips: trips.len(),
+                }
+            })
+            .collect();
+        destinations.sort_by_key(|d| (d.direction_id.is_none(), d.direction_id.map(|d| d as u8)));
+        Ok(destinations)
+    }
+}
@@ -0,0 +1,245 @@
+use crate::objects::*;
+use crate::{Error, Gtfs, IdGenerator, RawGtfs};
+use std::convert::TryFrom;
+
+/// Builds a [Gtfs] from objects instead of files
+///
+/// The objects are the lines of the GTFS files. [GtfsBuilder::build] checks the references
+/// and computes the indexes of the [Gtfs], as when the files are read.
+///
+/// ```
+/// use gtfs_structures::*;
+///
+/// let gtfs = GtfsBuilder::new()
+///     .agency(Agency {
+///         name: "Transit".to_owned(),
+///         url: "https://example.com".to_owned(),
+///         timezone: "Europe/Paris".to_owned(),
+///         ..Default::default()
+///     })
+///     .stop(Stop {
+///         id: "a".to_owned(),
+///         name: "A".to_owned(),
+///         ..Default::default()
+///     })
+///     .build()
+///     .expect("invalid feed");
+/// assert_eq!(1, gtfs.stops.len());
+/// ```
+///
+/// The stops, routes, trips, calendars, levels, pathways and fares added with an empty id
+/// get a new id, given by the [GtfsBuilder::id_generator] if there is one.
+#[derive(Debug, Default)]
+pub struct GtfsBuilder {
+    agencies: Vec<Agency>,
+    stops: Vec<Stop>,
+    routes: Vec<Route>,
+    trips: Vec<RawTrip>,
+    stop_times: Vec<RawStopTime>,
+    frequencies: Vec<RawFrequency>,
+    calendar: Vec<Calendar>,
+    calendar_dates: Vec<CalendarDate>,
+    shapes: Vec<Shape>,
+    transfers: Vec<Transfer>,
+    pathways: Vec<Pathway>,
+    levels: Vec<Level>,
+    fare_attributes: Vec<FareAttribute>,
+    fare_rules: Vec<FareRule>,
+    feed_info: Vec<FeedInfo>,
+    attributions: Vec<Attribution>,
+    id_generator: Option<Box<dyn IdGenerator>>,
+}
+
+impl GtfsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the strategy giving the new ids, it is then kept as the [Gtfs::id_generator]
+    pub fn id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Some(Box::new(generator));
+        self
+    }
+
+    /// A new id for an object of the type, not used by the objects already added
+    ///
+    /// Without [GtfsBuilder::id_generator], the ids are sequential numbers prefixed by the
+    /// type of the object, like "stop_1", as given by [Gtfs::new_id].
+    pub fn new_id(&mut self, object_type: ObjectType) -> String {
+        let mut count = 0;
+        loop {
+            let id = match self.id_generator.as_mut() {
+                Some(generator) => generator.generate(object_type, ""),
+                None => {
+                    count += 1;
+                    format!("{:?}_{}", object_type, count).to_lowercase()
+                }
+            };
+            if !self.has_object(object_type, &id) {
+                return id;
+            }
+        }
+    }
+
+    fn has_object(&self, object_type: ObjectType, id: &str) -> bool {
+        fn has<T: Id>(objects: &[T], id: &str) -> bool {
+            objects.iter().any(|o| o.id() == id)
+        }
+        match object_type {
+            ObjectType::Agency => self.agencies.iter().any(|a| a.id.as_deref() == Some(id)),
+            ObjectType::Stop => has(&self.stops, id),
+            ObjectType::Route => has(&self.routes, id),
+            ObjectType::Trip => has(&self.trips, id),
+            ObjectType::Calendar => {
+                has(&self.calendar, id) || self.calendar_dates.iter().any(|d| d.service_id == id)
+            }
+            ObjectType::Shape => has(&self.shapes, id),
+            ObjectType::Fare => has(&self.fare_attributes, id),
+            ObjectType::Level => has(&self.levels, id),
+            ObjectType::Pathway => has(&self.pathways, id),
+            ObjectType::StopTime | ObjectType::FeedInfo => false,
+        }
+    }
+
+    /// Gives a new id to an object added without id
+    fn set_id(&mut self, id: &mut String, object_type: ObjectType) {
+        if id.is_empty() {
+            *id = self.new_id(object_type);
+        }
+    }
+
+    pub fn agency(mut self, agency: Agency) -> Self {
+        self.agencies.push(agency);
+        self
+    }
+
+    pub fn stop(mut self, mut stop: Stop) -> Self {
+        self.set_id(&mut stop.id, ObjectType::Stop);
+        self.stops.push(stop);
+        self
+    }
+
+    pub fn route(mut self, mut route: Route) -> Self {
+        self.set_id(&mut route.id, ObjectType::Route);
+        self.routes.push(route);
+        self
+    }
+
+    /// Adds a trip with its stop times, their trip_id are set to the id of the trip
+    pub fn trip(mut self, mut trip: RawTrip, stop_times: Vec<RawStopTime>) -> Self {
+        self.set_id(&mut trip.id, ObjectType::Trip);
+        self.stop_times
+            .extend(stop_times.into_iter().map(|stop_time| RawStopTime {
+                trip_id: trip.id.clone(),
+                ..stop_time
+            }));
+        self.trips.push(trip);
+        self
+    }
+
+    pub fn frequency(mut self, frequency: RawFrequency) -> Self {
+        self.frequencies.push(frequency);
+        self
+    }
+
+    pub fn calendar(mut self, mut calendar: Calendar) -> Self {
+        self.set_id(&mut calendar.id, ObjectType::Calendar);
+        self.calendar.push(calendar);
+        self
+    }
+
+    pub fn calendar_date(mut self, calendar_date: CalendarDate) -> Self {
+        self.calendar_dates.push(calendar_date);
+        self
+    }
+
+    /// Adds a point of a shape
+    pub fn shape_point(mut self, shape: Shape) -> Self {
+        self.shapes.push(shape);
+        self
+    }
+
+    pub fn transfer(mut self, transfer: Transfer) -> Self {
+        self.transfers.push(transfer);
+        self
+    }
+
+    pub fn pathway(mut self, mut pathway: Pathway) -> Self {
+        self.set_id(&mut pathway.id, ObjectType::Pathway);
+        self.pathways.push(pathway);
+        self
+    }
+
+    pub fn level(mut self, mut level: Level) -> Self {
+        self.set_id(&mut level.id, ObjectType::Level);
+        self.levels.push(level);
+        self
+    }
+
+    /// Adds a fare, its rules are added with [GtfsBuilder::fare_rule]
+    pub fn fare_attribute(mut self, mut fare: FareAttribute) -> Self {
+        self.set_id(&mut fare.id, ObjectType::Fare);
+        self.fare_attributes.push(fare);
+        self
+    }
+
+    pub fn fare_rule(mut self, rule: FareRule) -> Self {
+        self.fare_rules.push(rule);
+        self
+    }
+
+    pub fn feed_info(mut self, feed_info: FeedInfo) -> Self {
+        self.feed_info.push(feed_info);
+        self
+    }
+
+    pub fn attribution(mut self, attribution: Attribution) -> Self {
+        self.attributions.push(attribution);
+        self
+    }
+
+    /// Builds the [Gtfs], failing like [Gtfs::try_from] if an object references an unknown id
+    pub fn build(self) -> Result<Gtfs, Error> {
+        let mut gtfs = Gtfs::try_from(RawGtfs {
+            read_duration: 0,
+            calendar: Some(Ok(self.calendar)),
+            calendar_dates: Some(Ok(self.calendar_dates)),
+            stops: Ok(self.stops),
+            routes: Ok(self.routes),
+            trips: Ok(self.trips),
+            agencies: Ok(self.agencies),
+            shapes: Some(Ok(self.shapes)),
+            fare_attributes: Some(Ok(self.fare_attributes)),
+            fare_rules: Some(Ok(self.fare_rules)),
+            fare_media: None,
+            fare_products: None,
+            rider_categories: None,
+            fare_containers: None,
+            fare_leg_rules: None,
+            fare_transfer_rules: None,
+            areas: None,
+            stop_areas: None,
+            networks: None,
+            route_networks: None,
+            timeframes: None,
+            feed_info: Some(Ok(self.feed_info)),
+            stop_times: Ok(self.stop_times),
+            stop_time_store: None,
+            frequencies: Some(Ok(self.frequencies)),
+            transfers: Some(Ok(self.transfers)),
+            pathways: Some(Ok(self.pathways)),
+            levels: Some(Ok(self.levels)),
+            files: Vec::new(),
+            sha256: None,
+            translations: None,
+            attributions: Some(Ok(self.attributions)),
+            booking_rules: None,
+            location_groups: None,
+            location_group_stops: None,
+            locations: None,
+            warnings: Vec::new(),
+        })?;
+        gtfs.id_generator = self.id_generator;
+        Ok(gtfs)
+    }
+}
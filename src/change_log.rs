@@ -0,0 +1,205 @@
+use crate::objects::*;
+use crate::Gtfs;
+use std::sync::Arc;
+
+/// A modification made by the mutation methods of [Gtfs], see [Gtfs::record_changes]
+///
+/// Each event keeps the values needed to undo it and to make it again.
+#[derive(Debug, Clone)]
+pub enum ChangeEvent {
+    StopRemoved(Arc<Stop>),
+    RouteRemoved(Arc<Route>),
+    /// The trip is given as it was when removed
+    TripRemoved(Trip),
+    TripAdded(Trip),
+    /// The points of a shape no longer used by any trip were removed
+    ShapeRemoved {
+        shape_id: String,
+        points: Vec<Shape>,
+    },
+    /// The translation of a removed object was removed
+    TranslationRemoved {
+        key: TranslationByIdKey,
+        value: String,
+    },
+    StopTimesChanged {
+        trip_id: String,
+        before: Vec<StopTime>,
+        after: Vec<StopTime>,
+    },
+    RouteTypeChanged {
+        route_id: String,
+        before: RouteType,
+        after: RouteType,
+    },
+    CalendarDateAdded(CalendarDate),
+    CalendarRemoved(Arc<Calendar>),
+    /// All the calendar dates of a service were removed
+    CalendarDatesRemoved {
+        service_id: String,
+        dates: Vec<CalendarDate>,
+    },
+}
+
+impl Gtfs {
+    /// Starts or stops recording the changes made by the mutation methods
+    ///
+    /// The changes are recorded in [Gtfs::change_log], they can be used to implement undo and redo
+    /// with [Gtfs::undo_change] and [Gtfs::redo_change], or to keep an audit trail. Stopping
+    /// the recording drops the changes recorded so far.
+    pub fn record_changes(&mut self, record: bool) {
+        match (record, &self.change_log) {
+            (true, None) => self.change_log = Some(Vec::new()),
+            (false, _) => self.change_log = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    /// Returns the changes recorded since the last call, the recording goes on
+    pub fn take_changes(&mut self) -> Vec<ChangeEvent> {
+        self.change_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn log_change(&mut self, event: ChangeEvent) {
+        if let Some(log) = &mut self.change_log {
+            log.push(event);
+        }
+    }
+
+    /// Undoes a change. Several changes must be undone starting from the last one.
+    ///
+    /// Undoing a change is not recorded.
+    pub fn undo_change(&mut self, event: &ChangeEvent) {
+        match event {
+            ChangeEvent::StopRemoved(stop) => {
+                self.stops.insert(stop.id.clone(), Arc::clone(stop));
+            }
+            ChangeEvent::RouteRemoved(route) => {
+                self.routes.insert(route.id.clone(), Arc::clone(route));
+                self.link_trips();
+            }
+            ChangeEvent::TripRemoved(trip) => {
+                self.index_trip(trip);
+                self.index_route_stops(&trip.route_id, &trip.stop_times);
+                self.trips.insert(trip.id.clone(), trip.clone());
+            }
+            ChangeEvent::TripAdded(trip) => {
+                self.unindex_trip(trip);
+                self.trips.remove(&trip.id);
+                self.update_route_stop_index();
+            }
+            ChangeEvent::ShapeRemoved { shape_id, points } => {
+                self.shapes.insert(shape_id.clone(), points.clone());
+            }
+            ChangeEvent::TranslationRemoved { key, value } => {
+                self.translations_by_id.insert(key.clone(), value.clone());
+            }
+            ChangeEvent::StopTimesChanged {
+                trip_id, before, ..
+            } => {
+                if let Some(trip) = self.trips.get_mut(trip_id) {
+                    trip.stop_times = before.clone();
+                    let route_id = trip.route_id.clone();
+                    self.index_route_stops(&route_id, before);
+                }
+            }
+            ChangeEvent::RouteTypeChanged {
+                route_id, before, ..
+            } => {
+                if let Some(route) = self.routes.get_mut(route_id) {
+                    let route = Arc::make_mut(route);
+                    route.route_type = *before;
+                    if route.original_route_type == Some(*before) {
+                        route.original_route_type = None;
+                    }
+                    self.link_trips();
+                }
+            }
+            ChangeEvent::CalendarDateAdded(calendar_date) => {
+                let service_id = &calendar_date.service_id;
+                if let Some(dates) = self.calendar_dates.get_mut(service_id) {
+                    dates.retain(|d| d.date != calendar_date.date);
+                    if dates.is_empty() {
+                        self.calendar_dates.remove(service_id);
+                    }
+                }
+                self.update_operating_days();
+            }
+            ChangeEvent::CalendarRemoved(calendar) => {
+                self.calendar.insert(calendar.id.clone(), Arc::clone(calendar));
+                self.link_trips();
+                self.update_operating_days();
+            }
+            ChangeEvent::CalendarDatesRemoved { service_id, dates } => {
+                self.calendar_dates.insert(service_id.clone(), dates.clone());
+                self.update_operating_days();
+            }
+        }
+    }
+
+    /// Makes again a change that was undone with [Gtfs::undo_change]
+    ///
+    /// Several changes must be made again in the order they were recorded. This is not recorded.
+    pub fn redo_change(&mut self, event: &ChangeEvent) {
+        match event {
+            ChangeEvent::StopRemoved(stop) => {
+                self.stops.remove(&stop.id);
+            }
+            ChangeEvent::RouteRemoved(route) => {
+                self.routes.remove(&route.id);
+            }
+            ChangeEvent::TripRemoved(trip) => {
+                self.unindex_trip(trip);
+                self.trips.remove(&trip.id);
+                self.update_route_stop_index();
+            }
+            ChangeEvent::TripAdded(trip) => {
+                self.index_trip(trip);
+                self.index_route_stops(&trip.route_id, &trip.stop_times);
+                self.trips.insert(trip.id.clone(), trip.clone());
+            }
+            ChangeEvent::ShapeRemoved { shape_id, .. } => {
+                self.shapes.remove(shape_id);
+            }
+            ChangeEvent::TranslationRemoved { key, .. } => {
+                self.translations_by_id.remove(key);
+            }
+            ChangeEvent::StopTimesChanged { trip_id, after, .. } => {
+                if let Some(trip) = self.trips.get_mut(trip_id) {
+                    trip.stop_times = after.clone();
+                }
+                self.update_route_stop_index();
+            }
+            ChangeEvent::RouteTypeChanged {
+                route_id,
+                before,
+                after,
+            } => {
+                if let Some(route) = self.routes.get_mut(route_id) {
+                    let route = Arc::make_mut(route);
+                    route.original_route_type.get_or_insert(*before);
+                    route.route_type = *after;
+                    self.link_trips();
+                }
+            }
+            ChangeEvent::CalendarDateAdded(calendar_date) => {
+                self.calendar_dates
+                    .entry(calendar_date.service_id.clone())
+                    .or_default()
+                    .push(calendar_date.clone());
+                self.update_operating_days();
+            }
+            ChangeEvent::CalendarRemoved(calendar) => {
+                self.calendar.remove(&calendar.id);
+                self.update_operating_days();
+            }
+            ChangeEvent::CalendarDatesRemoved { service_id, .. } => {
+                self.calendar_dates.remove(service_id);
+                self.update_operating_days();
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Table of the connections of all the trips, sorted by departure time, for the routing
+//! algorithms scanning them like the Connection Scan Algorithm
+
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A vehicle going from a stop of a trip to the next one with times
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub departure_stop: Arc<Stop>,
+    pub arrival_stop: Arc<Stop>,
+    /// Departure from the first stop, in seconds since the start of the service day
+    pub departure_time: u32,
+    /// Arrival at the second stop, in seconds since the start of the service day
+    pub arrival_time: u32,
+    pub trip_id: Arc<str>,
+    /// Index of the service of the trip in [ConnectionTable::services]
+    pub service: usize,
+    /// Number of the run of the vehicle, the same for all the connections of a trip and
+    /// different for each departure of a trip repeated by frequencies
+    pub run: usize,
+    /// stop_sequence of the stop time of the departure
+    pub stop_sequence: u16,
+    /// The riders can board at the departure stop
+    pub pickup: bool,
+    /// The riders can alight at the arrival stop
+    pub drop_off: bool,
+}
+
+/// The connections of all the trips of a feed, ordered by departure time, see
+/// [Gtfs::build_connection_table]
+///
+/// The stop times without times are skipped, so a connection goes from a stop with times to
+/// the next one. The trips repeated by frequencies give connections for each departure.
+#[derive(Debug, Default, Clone)]
+pub struct ConnectionTable {
+    pub(crate) connections: Vec<Connection>,
+    pub(crate) services: Vec<Arc<str>>,
+}
+
+/// Tells if the riders can board or alight, the default being a regular stop
+fn allowed(pickup_drop_off: Option<PickupDropOffType>) -> bool {
+    !matches!(pickup_drop_off, Some(PickupDropOffType::NotAvailable))
+}
+
+impl ConnectionTable {
+    /// Builds the connections of all the trips of the feed
+    ///
+    /// An error is returned if the stop times of a trip cannot be loaded, see
+    /// [Gtfs::trip_stop_times].
+    pub fn new(gtfs: &Gtfs) -> Result<Self, Error> {
+        let mut connections = Vec::new();
+        let mut services: Vec<Arc<str>> = Vec::new();
+        let mut service_indexes: HashMap<&str, usize> = HashMap::new();
+        let mut run = 0;
+        for (trip_id, trip) in &gtfs.trips {
+            let stop_times = gtfs.trip_stop_times(trip_id)?;
+            let timed: Vec<(u32, u32, &StopTime)> = stop_times
+                .iter()
+                .filter_map(|st| {
+                    let arrival = st.arrival_time.or(st.departure_time)?;
+                    Some((arrival, st.departure_time.unwrap_or(arrival), st))
+                })
+                .collect();
+            let first = match timed.first() {
+                Some((_, departure, _)) => *departure,
+                None => continue,
+            };
+            let service = *service_indexes
+                .entry(trip.service_id.as_str())
+                .or_insert_with(|| {
+                    services.push(Arc::from(trip.service_id.as_str()));
+                    services.len() - 1
+                });
+            let shifts: Vec<u32> = match trip.frequencies.is_empty() {
+                true => vec![0],
+                false => trip
+                    .frequencies
+                    .iter()
+                    .flat_map(|f| {
+                        (f.start_time..f.end_time).step_by(f.headway_secs.max(1) as usize)
+                    })
+                    .map(|departure| departure.saturating_sub(first))
+                    .collect(),
+            };
+            let trip_id: Arc<str> = Arc::from(trip_id.as_str());
+            for shift in shifts {
+                for pair in timed.windows(2) {
+                    let ((_, departure, from), (arrival, _, to)) = (pair[0], pair[1]);
+                    connections.push(Connection {
+                        departure_stop: from.stop.clone(),
+                        arrival_stop: to.stop.clone(),
+                        departure_time: departure + shift,
+                        arrival_time: arrival + shift,
+                        trip_id: trip_id.clone(),
+                        service,
+                        run,
+                        stop_sequence: from.stop_sequence,
+                        pickup: allowed(from.pickup_type),
+                        drop_off: allowed(to.drop_off_type),
+                    });
+                }
+                run += 1;
+            }
+        }
+        // for the same departure time, the connections of a run stay in their order
+        connections.sort_by_key(|c| (c.departure_time, c.arrival_time, c.run, c.stop_sequence));
+        Ok(Self {
+            connections,
+            services,
+        })
+    }
+
+    /// All the connections, ordered by departure time, then by arrival time
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// The service_id of each [Connection::service]
+    pub fn services(&self) -> &[Arc<str>] {
+        &self.services
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// The connections leaving at `time` or later, found by binary search
+    pub fn departing_after(&self, time: u32) -> &[Connection] {
+        let start = self
+            .connections
+            .partition_point(|c| c.departure_time < time);
+        &self.connections[start..]
+    }
+
+    /// The connections of the trips running on the service day `date`, leaving at `time` or
+    /// later, ordered by departure time
+    ///
+    /// The times are those of the service day, so the trips of the day before still running
+    /// after midnight are not included.
+    pub fn running_on<'a>(
+        &'a self,
+        gtfs: &Gtfs,
+        date: NaiveDate,
+        time: u32,
+    ) -> impl Iterator<Item = &'a Connection> + 'a {
+        let active: Vec<bool> = self
+            .services
+            .iter()
+            .map(|service_id| gtfs.operating_days.is_active(service_id, date))
+            .collect();
+        self.departing_after(time)
+            .iter()
+            .filter(move |c| active[c.service])
+    }
+}
+
+impl Gtfs {
+    /// Builds the [Gtfs::connection_table] of all the trips, to scan their connections by
+    /// departure time in several routing queries
+    ///
+    /// The table must be built again after trips or stop times are added, changed or
+    /// removed.
+    ///
+    /// ```
+    /// let mut gtfs = gtfs_structures::Gtfs::from_path("fixtures/basic").unwrap();
+    /// gtfs.build_connection_table().unwrap();
+    /// let table = gtfs.connection_table.as_ref().unwrap();
+    /// let first = &table.connections()[0];
+    /// assert!(first.departure_time <= first.arrival_time);
+    /// ```
+    pub fn build_connection_table(&mut self) -> Result<(), Error> {
+        self.connection_table = Some(ConnectionTable::new(self)?);
+        Ok(())
+    }
+}
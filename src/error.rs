@@ -1,9 +1,38 @@
+use crate::objects::LocationType;
 use thiserror::Error;
 
+/// The row of a csv file that could not be read
 #[derive(Debug)]
 pub struct LineError {
     pub headers: Vec<String>,
     pub values: Vec<String>,
+    /// Column of the value that could not be read, if it is known
+    pub column: Option<String>,
+}
+
+impl LineError {
+    /// The value that could not be read, if its column is known
+    pub fn value(&self) -> Option<&str> {
+        let column = self.column.as_ref()?;
+        let i = self.headers.iter().position(|h| h == column)?;
+        self.values.get(i).map(String::as_str)
+    }
+}
+
+/// Where a csv file could not be read, like " at line 3, column 'arrival_time' (value '7:6')"
+fn csv_location(source: &csv::Error, line_in_error: &Option<LineError>) -> String {
+    let mut location = String::new();
+    if let Some(position) = source.position() {
+        location.push_str(&format!(" at line {}", position.line()));
+    }
+    if let Some(column) = line_in_error.as_ref().and_then(|line| line.column.as_ref()) {
+        let separator = if location.is_empty() { " in" } else { "," };
+        location.push_str(&format!("{} column '{}'", separator, column));
+    }
+    if let Some(value) = line_in_error.as_ref().and_then(LineError::value) {
+        location.push_str(&format!(" (value '{}')", value));
+    }
+    location
 }
 
 /// An error that can occur when processing GTFS data.
@@ -19,8 +48,18 @@ pub enum Error {
     InvalidTranslation(String),
     #[error("'{0}' is not a valid time")]
     InvalidTime(String),
+    #[error("'{0}' is not a known timezone")]
+    UnknownTimezone(String),
     #[error("'{0}' is not a valid color")]
     InvalidColor(String),
+    #[error("Invalid stop sequence: {0}")]
+    InvalidStopSequence(String),
+    #[error("the stop {stop_id} is a {found:?}, not a {expected:?}")]
+    InvalidLocationType {
+        stop_id: String,
+        expected: LocationType,
+        found: LocationType,
+    },
     #[error("impossible to read file")]
     IO(#[from] std::io::Error),
     #[error("impossible to read '{file_name}'")]
@@ -32,13 +71,109 @@ pub enum Error {
     #[cfg(feature = "read-url")]
     #[error("impossible to remotely access file")]
     Fetch(#[from] reqwest::Error),
-    #[error("impossible to read csv file '{file_name}'")]
+    #[error(
+        "impossible to read csv file '{file_name}'{}",
+        csv_location(.source, .line_in_error)
+    )]
     CSVError {
         file_name: String,
         #[source]
         source: csv::Error,
         line_in_error: Option<LineError>,
     },
+    #[error("invalid GeoJSON file '{file_name}': {message}")]
+    InvalidGeoJson { file_name: String, message: String },
     #[error(transparent)]
     Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "realtime")]
+    #[error("invalid GTFS-Realtime message: {0}")]
+    InvalidRealtime(String),
+}
+
+impl Error {
+    /// The file that could not be read, if the error concerns a file
+    pub fn file_name(&self) -> Option<&str> {
+        match self {
+            Error::MissingFile(file_name)
+            | Error::NamedFileIO { file_name, .. }
+            | Error::CSVError { file_name, .. }
+            | Error::InvalidGeoJson { file_name, .. } => Some(file_name),
+            _ => None,
+        }
+    }
+
+    /// The line of the csv file that could not be read, if it is known
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            Error::CSVError { source, .. } => source.position().map(|p| p.line()),
+            _ => None,
+        }
+    }
+
+    /// The column of the value that could not be read, if it is known
+    pub fn column(&self) -> Option<&str> {
+        match self {
+            Error::CSVError {
+                line_in_error: Some(line),
+                ..
+            } => line.column.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The value that could not be read, if its column is known
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Error::CSVError {
+                line_in_error: Some(line),
+                ..
+            } => line.value(),
+            _ => None,
+        }
+    }
+}
+
+/// An error that can occur when computing the fare of a journey, see [crate::Gtfs::sum_fares]
+#[derive(Error, Debug)]
+pub enum FareError {
+    #[error("the journey has no legs")]
+    NoLegs,
+    #[error("the trip {trip_id} does not go from {from_stop_id} to {to_stop_id}")]
+    InvalidLeg {
+        trip_id: String,
+        from_stop_id: String,
+        to_stop_id: String,
+    },
+    #[error("no fare applies to the leg on the trip {0}")]
+    NoFare(String),
+    #[error("'{0}' is not a valid amount")]
+    InvalidAmount(String),
+    #[error("cannot add an amount in {found} to an amount in {expected}")]
+    MixedCurrencies { expected: String, found: String },
+    #[error(transparent)]
+    Gtfs(#[from] Error),
+}
+
+/// A problem found while reading a GTFS that did not prevent it from being read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub file_name: String,
+    /// Line of the csv file, if the warning concerns a row
+    pub line: Option<u64>,
+    /// Column of the value in error, if the warning concerns a value
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = &self.column {
+            write!(f, " ({})", column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
 }
@@ -0,0 +1,652 @@
+//! Fare of a journey made of several legs
+
+use crate::objects::*;
+use crate::{FareError, Gtfs, TripStopTimes};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+const SCALE: i64 = 10_000;
+
+/// An amount of money in a currency
+///
+/// The amount is kept in ten-thousandths of the currency unit, so fares add up without rounding errors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Money {
+    /// ISO 4217 code of the currency
+    pub currency: String,
+    ten_thousandths: i64,
+}
+
+impl Money {
+    pub fn zero(currency: &str) -> Self {
+        Money {
+            currency: currency.to_owned(),
+            ten_thousandths: 0,
+        }
+    }
+
+    /// Reads an amount written as in the fare files, such as `1.70`
+    pub fn parse(amount: &str, currency: &str) -> Result<Self, FareError> {
+        let invalid = || FareError::InvalidAmount(amount.to_owned());
+        let trimmed = amount.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, trimmed),
+        };
+        let (units, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if (units.is_empty() && fraction.is_empty())
+            || fraction.len() > 4
+            || !is_number(units)
+            || !is_number(fraction)
+        {
+            return Err(invalid());
+        }
+        let units: i64 = if units.is_empty() {
+            0
+        } else {
+            units.parse().map_err(|_| invalid())?
+        };
+        let fraction: i64 = format!("{:0<4}", fraction).parse().map_err(|_| invalid())?;
+        let value = units
+            .checked_mul(SCALE)
+            .and_then(|v| v.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Money {
+            currency: currency.to_owned(),
+            ten_thousandths: if negative { -value } else { value },
+        })
+    }
+
+    /// The amount in units of the currency
+    pub fn amount(&self) -> f64 {
+        self.ten_thousandths as f64 / SCALE as f64
+    }
+
+    /// Sum of the two amounts, refused if they are not in the same currency
+    pub fn checked_add(&self, other: &Money) -> Result<Money, FareError> {
+        self.combine(other, other.ten_thousandths)
+    }
+
+    fn checked_sub(&self, other: &Money) -> Result<Money, FareError> {
+        self.combine(other, -other.ten_thousandths)
+    }
+
+    /// Tells if the amount is lower than the other one, refused if they are not in the same
+    /// currency
+    fn checked_lt(&self, other: &Money) -> Result<bool, FareError> {
+        self.same_currency(other)?;
+        Ok(self.ten_thousandths < other.ten_thousandths)
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), FareError> {
+        if self.currency != other.currency {
+            return Err(FareError::MixedCurrencies {
+                expected: self.currency.clone(),
+                found: other.currency.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn combine(&self, other: &Money, delta: i64) -> Result<Money, FareError> {
+        self.same_currency(other)?;
+        Ok(Money {
+            currency: self.currency.clone(),
+            ten_thousandths: self.ten_thousandths + delta,
+        })
+    }
+}
+
+impl fmt::Display for Money {
+    /// Writes the amount with at least two decimals, followed by the currency, e.g. `3.40 EUR`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.ten_thousandths < 0 { "-" } else { "" };
+        let value = self.ten_thousandths.unsigned_abs();
+        let mut fraction = format!("{:04}", value % SCALE as u64);
+        while fraction.len() > 2 && fraction.ends_with('0') {
+            fraction.pop();
+        }
+        write!(
+            f,
+            "{}{}.{} {}",
+            sign,
+            value / SCALE as u64,
+            fraction,
+            self.currency
+        )
+    }
+}
+
+/// A leg of a journey: a ride on a trip, from a stop to a later stop of the trip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FareLeg {
+    pub trip_id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+}
+
+impl FareLeg {
+    pub fn new(trip_id: &str, from_stop_id: &str, to_stop_id: &str) -> Self {
+        FareLeg {
+            trip_id: trip_id.to_owned(),
+            from_stop_id: from_stop_id.to_owned(),
+            to_stop_id: to_stop_id.to_owned(),
+        }
+    }
+}
+
+/// A leg with the stop times of its trip
+struct Ride<'a> {
+    trip: &'a Trip,
+    stop_times: TripStopTimes<'a>,
+    from: usize,
+    to: usize,
+}
+
+impl<'a> Ride<'a> {
+    fn first(&self) -> &StopTime {
+        &self.stop_times[self.from]
+    }
+
+    fn last(&self) -> &StopTime {
+        &self.stop_times[self.to]
+    }
+
+    fn departure(&self) -> u32 {
+        let st = self.first();
+        st.departure_time.or(st.arrival_time).unwrap_or_default()
+    }
+
+    fn arrival(&self) -> u32 {
+        let st = self.last();
+        st.arrival_time.or(st.departure_time).unwrap_or_default()
+    }
+}
+
+fn sum(amounts: Vec<Money>) -> Result<Money, FareError> {
+    let mut amounts = amounts.into_iter();
+    let first = amounts.next().ok_or(FareError::NoLegs)?;
+    amounts.try_fold(first, |total, amount| total.checked_add(&amount))
+}
+
+/// A rule without leg group applies to any leg
+fn leg_group_matches(rule_group: &Option<String>, group: Option<&str>) -> bool {
+    rule_group.as_deref().is_none_or(|g| Some(g) == group)
+}
+
+/// The fare data of a leg, see [FareContext::resolve]
+#[derive(Debug, Clone)]
+pub struct ResolvedLeg<'a> {
+    /// The network of the route of the trip
+    pub network_id: Option<&'a str>,
+    /// The areas of the departure stop, or of its parent station
+    pub from_area_ids: Vec<&'a str>,
+    /// The areas of the arrival stop, or of its parent station
+    pub to_area_ids: Vec<&'a str>,
+    /// The fare leg rules applying to the leg, all of the same priority
+    pub rules: Vec<&'a FareLegRule>,
+}
+
+/// Resolution of the fare leg rules of the legs: leg → network → area pair → fare leg rules
+///
+/// The network of a leg is the one of the route of its trip (routes.txt or route_networks.txt),
+/// its areas are the ones of its stops or of their parent stations (stop_areas.txt), and together
+/// with the time of the leg (timeframes.txt) they select the rules of fare_leg_rules.txt.
+/// The areas of the stops are indexed once, so that many legs can be resolved.
+///
+/// ```
+/// let gtfs = gtfs_structures::Gtfs::from_path("fixtures/fares_v2").unwrap();
+/// let context = gtfs_structures::FareContext::new(&gtfs);
+/// let leg = gtfs_structures::FareLeg::new("r71_1200", "central_a", "harbour");
+/// let resolved = context.resolve(&leg).unwrap();
+/// assert_eq!(vec!["port"], resolved.to_area_ids);
+/// assert_eq!("single", resolved.rules[0].fare_product_id);
+/// ```
+pub struct FareContext<'a> {
+    gtfs: &'a Gtfs,
+    /// Ids of the areas of each stop id
+    areas_by_stop: HashMap<&'a str, Vec<&'a str>>,
+    /// Whether the rules have a rule_priority, which changes the meaning of their empty fields
+    uses_priority: bool,
+}
+
+impl<'a> FareContext<'a> {
+    pub fn new(gtfs: &'a Gtfs) -> Self {
+        let mut areas_by_stop: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (area_id, stop_ids) in &gtfs.stop_areas {
+            for stop_id in stop_ids {
+                areas_by_stop
+                    .entry(stop_id.as_str())
+                    .or_default()
+                    .push(area_id.as_str());
+            }
+        }
+        FareContext {
+            gtfs,
+            areas_by_stop,
+            uses_priority: gtfs
+                .fare_leg_rules
+                .iter()
+                .any(|rule| rule.rule_priority.is_some()),
+        }
+    }
+
+    /// Ids of the areas of the stop, or of its parent station
+    pub fn area_ids(&self, stop: &Stop) -> Vec<&'a str> {
+        let mut area_ids: Vec<&str> = std::iter::once(&stop.id)
+            .chain(stop.parent_station.as_ref())
+            .filter_map(|id| self.areas_by_stop.get(id.as_str()))
+            .flatten()
+            .copied()
+            .collect();
+        area_ids.sort_unstable();
+        area_ids.dedup();
+        area_ids
+    }
+
+    /// The network, areas and fare leg rules of a leg
+    ///
+    /// When the rules have a rule_priority, an empty field of a rule matches any value and only
+    /// the matching rules with the highest priority are kept. Otherwise, as required by the
+    /// specification, the fields are filtered in order (network, areas, then timeframes) and an
+    /// empty field only matches when no remaining rule has the value of the leg in this field:
+    /// a rule for the network of the leg replaces the rules without network.
+    pub fn resolve(&self, leg: &FareLeg) -> Result<ResolvedLeg<'a>, FareError> {
+        let ride = self.gtfs.ride(leg)?;
+        Ok(self.resolve_ride(&ride))
+    }
+
+    fn resolve_ride(&self, ride: &Ride) -> ResolvedLeg<'a> {
+        let gtfs = self.gtfs;
+        let network_id = gtfs
+            .routes
+            .get(&ride.trip.route_id)
+            .and_then(|route| route.network_id.as_deref());
+        let from_area_ids = self.area_ids(&ride.first().stop);
+        let to_area_ids = self.area_ids(&ride.last().stop);
+        let (departure, arrival) = (ride.departure(), ride.arrival());
+
+        // whether each field of a rule matches the leg, None for the empty fields
+        let mut fields: Vec<(&FareLegRule, [Option<bool>; 5])> = gtfs
+            .fare_leg_rules
+            .iter()
+            .map(|rule| {
+                let matches = [
+                    rule.network_id.as_deref().map(|id| Some(id) == network_id),
+                    rule.from_area_id
+                        .as_deref()
+                        .map(|area| from_area_ids.contains(&area)),
+                    rule.to_area_id
+                        .as_deref()
+                        .map(|area| to_area_ids.contains(&area)),
+                    rule.from_timeframe_group_id
+                        .as_deref()
+                        .map(|group| gtfs.in_timeframe(group, departure)),
+                    rule.to_timeframe_group_id
+                        .as_deref()
+                        .map(|group| gtfs.in_timeframe(group, arrival)),
+                ];
+                (rule, matches)
+            })
+            .collect();
+        // the fields are filtered one after the other: without rule_priority, the rules with an
+        // empty field are only kept when none of the remaining rules has the value of the leg
+        for field in 0..5 {
+            let explicit = !self.uses_priority
+                && fields
+                    .iter()
+                    .any(|(_, matches)| matches[field] == Some(true));
+            fields.retain(|(_, matches)| matches[field].unwrap_or(!explicit));
+        }
+        let matching: Vec<&FareLegRule> = fields.into_iter().map(|(rule, _)| rule).collect();
+        let priority = matching
+            .iter()
+            .map(|rule| rule.rule_priority.unwrap_or(0))
+            .max();
+        ResolvedLeg {
+            network_id,
+            from_area_ids,
+            to_area_ids,
+            rules: matching
+                .into_iter()
+                .filter(|rule| Some(rule.rule_priority.unwrap_or(0)) == priority)
+                .collect(),
+        }
+    }
+}
+
+impl Gtfs {
+    /// Total fare of a journey made of several legs, taken in this order
+    ///
+    /// The fares v2 tables are used if the feed has fare_leg_rules.txt, with the rules of
+    /// fare_transfer_rules.txt between consecutive legs. Otherwise the fares of fare_attributes.txt
+    /// are used, a leg being free when the ticket of the previous legs allows a transfer to it.
+    /// When several products apply, the cheapest one for the default rider category is used.
+    ///
+    /// The legs priced in different currencies are not added, and the fares or products of a
+    /// leg in different currencies are not compared: [FareError::MixedCurrencies] is returned.
+    pub fn sum_fares(&self, legs: &[FareLeg]) -> Result<Money, FareError> {
+        let rides = legs
+            .iter()
+            .map(|leg| self.ride(leg))
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.fare_leg_rules.is_empty() {
+            self.sum_fares_v1(&rides)
+        } else {
+            self.sum_fares_v2(&rides)
+        }
+    }
+
+    fn ride(&self, leg: &FareLeg) -> Result<Ride<'_>, FareError> {
+        let trip = self.get_trip(&leg.trip_id)?;
+        let stop_times = self.trip_stop_times(&leg.trip_id)?;
+        let invalid = || FareError::InvalidLeg {
+            trip_id: leg.trip_id.clone(),
+            from_stop_id: leg.from_stop_id.clone(),
+            to_stop_id: leg.to_stop_id.clone(),
+        };
+        let from = stop_times
+            .iter()
+            .position(|st| st.stop.id == leg.from_stop_id)
+            .ok_or_else(invalid)?;
+        let to = stop_times[from + 1..]
+            .iter()
+            .position(|st| st.stop.id == leg.to_stop_id)
+            .map(|i| from + 1 + i)
+            .ok_or_else(invalid)?;
+        Ok(Ride {
+            trip,
+            stop_times,
+            from,
+            to,
+        })
+    }
+
+    /// The cheapest line of the fare product for the default rider category
+    ///
+    /// The lines in different currencies cannot be compared: [FareError::MixedCurrencies] is
+    /// returned.
+    fn cheapest_product(&self, product_id: &str) -> Result<Option<Money>, FareError> {
+        let default_category = self.default_rider_category().map(|c| c.id.as_str());
+        let prices = self
+            .fare_products
+            .get(product_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|p| {
+                p.rider_category_id.is_none() || p.rider_category_id.as_deref() == default_category
+            })
+            .map(|p| Money::parse(&p.amount, &p.currency))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut cheapest: Option<Money> = None;
+        for price in prices {
+            if cheapest
+                .as_ref()
+                .map_or(Ok(true), |c| price.checked_lt(c))?
+            {
+                cheapest = Some(price);
+            }
+        }
+        Ok(cheapest)
+    }
+
+    fn in_timeframe(&self, timeframe_group_id: &str, time: u32) -> bool {
+        let time = time % 86400;
+        self.timeframes
+            .get(timeframe_group_id)
+            .is_some_and(|timeframes| {
+                timeframes.iter().any(|t| {
+                    t.start_time.unwrap_or(0) <= time && time < t.end_time.unwrap_or(86400)
+                })
+            })
+    }
+
+    /// Price of the leg with the fares v2 tables, and the leg group of the rule used
+    ///
+    /// The cheapest product of the rules given by [FareContext::resolve] is used.
+    fn leg_fare_v2<'a>(
+        &'a self,
+        context: &FareContext<'a>,
+        ride: &Ride,
+    ) -> Result<(Money, Option<&'a str>), FareError> {
+        let mut best: Option<(Money, Option<&str>)> = None;
+        for rule in context.resolve_ride(ride).rules {
+            if let Some(price) = self.cheapest_product(&rule.fare_product_id)? {
+                if best
+                    .as_ref()
+                    .map_or(Ok(true), |(b, _)| price.checked_lt(b))?
+                {
+                    best = Some((price, rule.leg_group_id.as_deref()));
+                }
+            }
+        }
+        best.ok_or_else(|| FareError::NoFare(ride.trip.id.clone()))
+    }
+
+    /// The transfer rule applying between two legs, when `transfers` transfers were already made
+    fn transfer_rule(
+        &self,
+        from: (&Ride, Option<&str>),
+        to: (&Ride, Option<&str>),
+        transfers: i32,
+    ) -> Option<&FareTransferRule> {
+        self.fare_transfer_rules.iter().find(|rule| {
+            let duration = match rule.duration_limit_type {
+                Some(DurationLimitType::DepartureToDeparture) => {
+                    to.0.departure().saturating_sub(from.0.departure())
+                }
+                Some(DurationLimitType::ArrivalToDeparture) => {
+                    to.0.departure().saturating_sub(from.0.arrival())
+                }
+                Some(DurationLimitType::ArrivalToArrival) => {
+                    to.0.arrival().saturating_sub(from.0.arrival())
+                }
+                Some(DurationLimitType::DepartureToArrival) | None => {
+                    to.0.arrival().saturating_sub(from.0.departure())
+                }
+            };
+            leg_group_matches(&rule.from_leg_group_id, from.1)
+                && leg_group_matches(&rule.to_leg_group_id, to.1)
+                // without transfer_count, the rule allows a single transfer
+                && match rule.transfer_count {
+                    Some(-1) => true,
+                    Some(count) => transfers < count,
+                    None => transfers < 1,
+                }
+                && rule.duration_limit.is_none_or(|limit| duration <= limit)
+        })
+    }
+
+    fn sum_fares_v2(&self, rides: &[Ride]) -> Result<Money, FareError> {
+        let context = FareContext::new(self);
+        let mut charged = Vec::new();
+        let mut previous: Option<(&Ride, Money, Option<&str>)> = None;
+        let mut transfers = 0;
+        for ride in rides {
+            let (price, group) = self.leg_fare_v2(&context, ride)?;
+            let rule = previous.as_ref().and_then(|(prev_ride, _, prev_group)| {
+                self.transfer_rule((prev_ride, *prev_group), (ride, group), transfers)
+            });
+            match (rule, &previous) {
+                (Some(rule), Some((_, prev_price, _))) => {
+                    transfers += 1;
+                    let transfer = match &rule.fare_product_id {
+                        Some(id) => self
+                            .cheapest_product(id)?
+                            .ok_or_else(|| FareError::NoFare(ride.trip.id.clone()))?,
+                        None => Money::zero(&price.currency),
+                    };
+                    charged.push(match rule.fare_transfer_type {
+                        FareTransferType::FirstLegPlusTransfer => transfer,
+                        FareTransferType::AllLegsPlusTransfer => transfer.checked_add(&price)?,
+                        // the transfer product replaces the fare of the previous leg
+                        FareTransferType::TransferOnly => transfer.checked_sub(prev_price)?,
+                    });
+                }
+                _ => {
+                    transfers = 0;
+                    charged.push(price.clone());
+                }
+            }
+            previous = Some((ride, price, group));
+        }
+        sum(charged)
+    }
+
+    /// The fares of fare_attributes.txt applying to the leg
+    ///
+    /// A fare applies if one of its rules without contains_id matches the route and zones of the leg,
+    /// or if the zones of its matching rules with a contains_id are exactly the zones crossed by the leg.
+    /// A fare without rules applies to every leg.
+    fn fares_v1(&self, ride: &Ride) -> Vec<&FareAttribute> {
+        let origin = ride.first().zone_id();
+        let destination = ride.last().zone_id();
+        let zones: BTreeSet<&str> = ride.stop_times[ride.from..=ride.to]
+            .iter()
+            .filter_map(StopTime::zone_id)
+            .collect();
+        self.fare_attributes
+            .values()
+            .filter(|fare| {
+                let matching: Vec<&FareRule> = fare
+                    .rules
+                    .iter()
+                    .filter(|rule| {
+                        rule.route_id
+                            .as_ref()
+                            .is_none_or(|id| *id == ride.trip.route_id)
+                            && rule.origin_id.as_deref().is_none_or(|z| Some(z) == origin)
+                            && rule
+                                .destination_id
+                                .as_deref()
+                                .is_none_or(|z| Some(z) == destination)
+                    })
+                    .collect();
+                let contains: BTreeSet<&str> = matching
+                    .iter()
+                    .filter_map(|rule| rule.contains_id.as_deref())
+                    .collect();
+                fare.rules.is_empty()
+                    || matching.iter().any(|rule| rule.contains_id.is_none())
+                    || (!contains.is_empty() && contains == zones)
+            })
+            .collect()
+    }
+
+    fn sum_fares_v1(&self, rides: &[Ride]) -> Result<Money, FareError> {
+        let mut charged = Vec::new();
+        // fare of the current ticket, with its first departure and the number of transfers made
+        let mut ticket: Option<(&FareAttribute, u32, u16)> = None;
+        for ride in rides {
+            let fares = self.fares_v1(ride);
+            if let Some((fare, start, transfers)) = &mut ticket {
+                let allowed = match fare.transfers {
+                    Transfers::Unlimited => true,
+                    Transfers::NoTransfer => false,
+                    Transfers::UniqueTransfer => *transfers < 1,
+                    Transfers::TwoTransfers => *transfers < 2,
+                    Transfers::Other(count) => *transfers < count,
+                };
+                let in_time = fare
+                    .transfer_duration
+                    .is_none_or(|d| ride.departure().saturating_sub(*start) as usize <= d);
+                if allowed && in_time && fares.iter().any(|f| f.id == fare.id) {
+                    *transfers += 1;
+                    continue;
+                }
+            }
+            let mut best: Option<(Money, &FareAttribute)> = None;
+            for fare in fares {
+                let price = Money::parse(&fare.price, &fare.currency)?;
+                if best
+                    .as_ref()
+                    .map_or(Ok(true), |(b, _)| price.checked_lt(b))?
+                {
+                    best = Some((price, fare));
+                }
+            }
+            let (price, fare) = best.ok_or_else(|| FareError::NoFare(ride.trip.id.clone()))?;
+            charged.push(price);
+            ticket = Some((fare, ride.departure(), 0));
+        }
+        sum(charged)
+    }
+}
+
+/// The ticketing zones of a stop and the fares applying there, see [Stop::fare_info]
+#[derive(Debug, Clone)]
+pub struct StopFareInfo<'a> {
+    /// The zone_id of the stop, or of its parent station (fare_rules.txt)
+    pub zone_id: Option<&'a str>,
+    /// The areas of the stop or of its parent station (stop_areas.txt), ordered by id
+    pub areas: Vec<&'a Area>,
+    /// The fares of fare_attributes.txt applying at the stop, ordered by id
+    pub fares: Vec<&'a FareAttribute>,
+    /// The fare products of the fare leg rules applying at the stop, ordered by id, one for
+    /// each fare media
+    pub fare_products: Vec<&'a FareProduct>,
+}
+
+impl Stop {
+    /// The zones and the areas of the stop, with the fares that can be used there
+    ///
+    /// A fare of fare_attributes.txt applies when one of its rules has the zone of the stop as
+    /// origin, destination or contained zone, or has no zone, or when the fare has no rule. A
+    /// fare product applies when one of its fare leg rules starts or ends in an area of the
+    /// stop, or has no area. The routes, networks and timeframes of the rules are not checked,
+    /// so the fares are the ones to show on a station page, not the price of a given journey.
+    pub fn fare_info<'a>(&'a self, gtfs: &'a Gtfs) -> StopFareInfo<'a> {
+        let context = FareContext::new(gtfs);
+        let area_ids = context.area_ids(self);
+        let parent = self
+            .parent_station
+            .as_ref()
+            .and_then(|id| gtfs.stops.get(id));
+        let zone_id = self
+            .zone_id
+            .as_deref()
+            .or_else(|| parent.and_then(|parent| parent.zone_id.as_deref()));
+
+        let zone_matches = |rule: &FareRule| {
+            let zones = [&rule.origin_id, &rule.destination_id, &rule.contains_id];
+            zones.iter().all(|zone| zone.is_none())
+                || zone_id.is_some() && zones.iter().any(|zone| zone.as_deref() == zone_id)
+        };
+        let fares = gtfs
+            .fare_attributes
+            .values()
+            .filter(|fare| fare.rules.is_empty() || fare.rules.iter().any(zone_matches))
+            .collect();
+
+        let product_ids: BTreeSet<&str> = gtfs
+            .fare_leg_rules
+            .iter()
+            .filter(|rule| {
+                let areas = [&rule.from_area_id, &rule.to_area_id];
+                areas.iter().all(|area| area.is_none())
+                    || areas
+                        .iter()
+                        .flat_map(|area| area.as_deref())
+                        .any(|area| area_ids.contains(&area))
+            })
+            .map(|rule| rule.fare_product_id.as_str())
+            .collect();
+        let fare_products = product_ids
+            .into_iter()
+            .flat_map(|id| gtfs.fare_products.get(id))
+            .flatten()
+            .collect();
+
+        StopFareInfo {
+            zone_id,
+            areas: area_ids
+                .iter()
+                .filter_map(|id| gtfs.areas.get(*id))
+                .collect(),
+            fares,
+            fare_products,
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Downloading a GTFS again only when it changed
+
+use crate::raw_gtfs::spawn_parsing;
+use crate::{Error, Gtfs, RawGtfs};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
+
+/// Headers of the response of a download, to download the feed again only if it changed
+///
+/// They are given by [Gtfs::from_url_if_modified], and can be stored between the runs of a
+/// program fetching a feed regularly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheValidators {
+    /// The ETag header
+    pub etag: Option<String>,
+    /// The Last-Modified header
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// Headers of a request asking for the feed only if it changed
+    fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let values = [
+            (IF_NONE_MATCH, &self.etag),
+            (IF_MODIFIED_SINCE, &self.last_modified),
+        ];
+        for (name, value) in values.iter() {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        headers
+    }
+}
+
+/// Result of a download made only if the feed changed, see [Gtfs::from_url_if_modified]
+#[derive(Debug)]
+pub enum Fetched<T> {
+    /// The feed did not change since the response giving the validators
+    NotModified,
+    Modified {
+        feed: T,
+        /// The validators of this new version of the feed
+        validators: CacheValidators,
+    },
+}
+
+impl<T> Fetched<T> {
+    fn and_then<U, F>(self, f: F) -> Result<Fetched<U>, Error>
+    where
+        F: FnOnce(T) -> Result<U, Error>,
+    {
+        Ok(match self {
+            Fetched::NotModified => Fetched::NotModified,
+            Fetched::Modified { feed, validators } => Fetched::Modified {
+                feed: f(feed)?,
+                validators,
+            },
+        })
+    }
+}
+
+fn download_if_modified<U: reqwest::IntoUrl>(
+    url: U,
+    validators: &CacheValidators,
+) -> Result<Fetched<Vec<u8>>, Error> {
+    let response = reqwest::blocking::Client::new()
+        .get(url)
+        .headers(validators.conditional_headers())
+        .send()?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let mut response = response.error_for_status()?;
+    let validators = CacheValidators::from_headers(response.headers());
+    let mut feed = Vec::new();
+    response.read_to_end(&mut feed)?;
+    Ok(Fetched::Modified { feed, validators })
+}
+
+async fn download_if_modified_async<U: reqwest::IntoUrl>(
+    url: U,
+    validators: &CacheValidators,
+) -> Result<Fetched<Vec<u8>>, Error> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .headers(validators.conditional_headers())
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let mut response = response.error_for_status()?;
+    let validators = CacheValidators::from_headers(response.headers());
+    let mut feed = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        feed.extend_from_slice(&chunk);
+    }
+    Ok(Fetched::Modified { feed, validators })
+}
+
+impl RawGtfs {
+    /// Reads the raw GTFS from a remote url, unless it did not change since a previous download
+    ///
+    /// See [Gtfs::from_url_if_modified].
+    pub fn from_url_if_modified<U: reqwest::IntoUrl>(
+        url: U,
+        validators: &CacheValidators,
+    ) -> Result<Fetched<RawGtfs>, Error> {
+        download_if_modified(url, validators)?
+            .and_then(|feed| RawGtfs::from_reader(Cursor::new(feed)))
+    }
+
+    /// Non-blocking version of [RawGtfs::from_url_if_modified]
+    ///
+    /// The files are parsed on the blocking thread pool of tokio, so it must be called from a
+    /// tokio runtime.
+    pub async fn from_url_if_modified_async<U: reqwest::IntoUrl>(
+        url: U,
+        validators: &CacheValidators,
+    ) -> Result<Fetched<RawGtfs>, Error> {
+        match download_if_modified_async(url, validators).await? {
+            Fetched::NotModified => Ok(Fetched::NotModified),
+            modified => {
+                spawn_parsing(move || {
+                    modified.and_then(|feed| RawGtfs::from_reader(Cursor::new(feed)))
+                })
+                .await
+            }
+        }
+    }
+}
+
+impl Gtfs {
+    /// Reads the GTFS from a remote url, unless it did not change since a previous download
+    ///
+    /// The request is conditional (with the If-None-Match and If-Modified-Since headers) when the
+    /// validators of the previous download are given, otherwise use
+    /// [CacheValidators::default()]. When the server answers that the feed did not change,
+    /// nothing is downloaded nor parsed.
+    ///
+    /// ```no_run
+    /// use gtfs_structures::{CacheValidators, Fetched, Gtfs};
+    ///
+    /// let mut validators = CacheValidators::default();
+    /// loop {
+    ///     match Gtfs::from_url_if_modified("https://example.com/gtfs.zip", &validators) {
+    ///         Ok(Fetched::Modified { feed, validators: new_validators }) => {
+    ///             feed.print_stats();
+    ///             validators = new_validators;
+    ///         }
+    ///         Ok(Fetched::NotModified) => println!("The feed did not change"),
+    ///         Err(e) => println!("Error: {}", e),
+    ///     }
+    ///     std::thread::sleep(std::time::Duration::from_secs(3600));
+    /// }
+    /// ```
+    pub fn from_url_if_modified<U: reqwest::IntoUrl>(
+        url: U,
+        validators: &CacheValidators,
+    ) -> Result<Fetched<Gtfs>, Error> {
+        RawGtfs::from_url_if_modified(url, validators)?.and_then(Gtfs::try_from)
+    }
+
+    /// Non-blocking version of [Gtfs::from_url_if_modified]
+    ///
+    /// The files are parsed on the blocking thread pool of tokio, so it must be called from a
+    /// tokio runtime.
+    pub async fn from_url_if_modified_async<U: reqwest::IntoUrl>(
+        url: U,
+        validators: &CacheValidators,
+    ) -> Result<Fetched<Gtfs>, Error> {
+        match download_if_modified_async(url, validators).await? {
+            Fetched::NotModified => Ok(Fetched::NotModified),
+            modified => {
+                spawn_parsing(move || {
+                    modified.and_then(|feed| {
+                        RawGtfs::from_reader(Cursor::new(feed)).and_then(Gtfs::try_from)
+                    })
+                })
+                .await
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::json::{self, Json};
+use crate::{Error, Id};
+
+const FILE_NAME: &str = "locations.geojson";
+
+/// A ring of a polygon, as (longitude, latitude) points. The last point is the same as the first one.
+pub type Ring = Vec<(f64, f64)>;
+
+/// A zone of locations.geojson, where a GTFS-Flex service picks up or drops off riders
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub id: String,
+    /// The stop_name property of the feature
+    pub name: Option<String>,
+    /// The stop_desc property of the feature
+    pub description: Option<String>,
+    /// Polygons of the zone. The first ring of a polygon is its outline, the next ones are holes.
+    pub polygons: Vec<Vec<Ring>>,
+}
+
+impl Location {
+    /// Tells if a point is inside the zone
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        self.polygons
+            .iter()
+            .any(|rings| polygon_contains(rings, lon, lat))
+    }
+}
+
+impl Id for Location {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Tells if a point is inside a polygon: inside its first ring, and outside the next ones
+pub(crate) fn polygon_contains(rings: &[Ring], lon: f64, lat: f64) -> bool {
+    match rings.split_first() {
+        Some((outline, holes)) => {
+            ring_contains(outline, lon, lat) && !holes.iter().any(|h| ring_contains(h, lon, lat))
+        }
+        None => false,
+    }
+}
+
+/// Even-odd rule: a point is inside when a ray starting from it crosses the ring an odd number of times
+fn ring_contains(ring: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for (i, &(x1, y1)) in ring.iter().enumerate() {
+        let (x2, y2) = ring[(i + 1) % ring.len()];
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidGeoJson {
+        file_name: FILE_NAME.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+fn parse_ring(ring: &Json) -> Result<Ring, Error> {
+    ring.as_array()
+        .ok_or_else(|| invalid("a ring is not an array"))?
+        .iter()
+        .map(|point| match point.as_array() {
+            Some([lon, lat, ..]) => lon.as_f64().zip(lat.as_f64()),
+            _ => None,
+        })
+        .map(|point| point.ok_or_else(|| invalid("a point is not an array of two numbers")))
+        .collect()
+}
+
+fn parse_polygon(polygon: &Json) -> Result<Vec<Ring>, Error> {
+    polygon
+        .as_array()
+        .ok_or_else(|| invalid("a polygon is not an array"))?
+        .iter()
+        .map(parse_ring)
+        .collect()
+}
+
+fn parse_location(feature: &Json) -> Result<Location, Error> {
+    let id = feature
+        .get("id")
+        .and_then(Json::as_str)
+        .ok_or_else(|| invalid("a feature has no id"))?;
+    let property = |name: &str| {
+        feature
+            .get("properties")
+            .and_then(|p| p.get(name))
+            .and_then(Json::as_str)
+            .map(str::to_owned)
+    };
+    let geometry = feature
+        .get("geometry")
+        .ok_or_else(|| invalid(&format!("the feature {} has no geometry", id)))?;
+    let coordinates = geometry
+        .get("coordinates")
+        .ok_or_else(|| invalid(&format!("the geometry of {} has no coordinates", id)))?;
+    let polygons = match geometry.get("type").and_then(Json::as_str) {
+        Some("Polygon") => vec![parse_polygon(coordinates)?],
+        Some("MultiPolygon") => coordinates
+            .as_array()
+            .ok_or_else(|| invalid("a multipolygon is not an array"))?
+            .iter()
+            .map(parse_polygon)
+            .collect::<Result<_, _>>()?,
+        _ => {
+            return Err(invalid(&format!(
+                "the geometry of {} is neither a Polygon nor a MultiPolygon",
+                id
+            )))
+        }
+    };
+    Ok(Location {
+        id: id.to_owned(),
+        name: property("stop_name"),
+        description: property("stop_desc"),
+        polygons,
+    })
+}
+
+/// Reads the features of locations.geojson
+pub(crate) fn parse_locations(content: &str) -> Result<Vec<Location>, Error> {
+    let collection = json::parse(content).map_err(|message| invalid(&message))?;
+    if collection.get("type").and_then(Json::as_str) != Some("FeatureCollection") {
+        return Err(invalid("the file is not a FeatureCollection"));
+    }
+    collection
+        .get("features")
+        .and_then(Json::as_array)
+        .ok_or_else(|| invalid("the FeatureCollection has no features"))?
+        .iter()
+        .map(parse_location)
+        .collect()
+}
+
+fn polygon_to_json(rings: &[Ring]) -> Json {
+    Json::Array(
+        rings
+            .iter()
+            .map(|ring| {
+                Json::Array(
+                    ring.iter()
+                        .map(|&(lon, lat)| Json::Array(vec![Json::Number(lon), Json::Number(lat)]))
+                        .collect(),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn location_to_json(location: &Location) -> Json {
+    let mut properties = Vec::new();
+    if let Some(name) = &location.name {
+        properties.push(("stop_name".to_owned(), Json::String(name.clone())));
+    }
+    if let Some(description) = &location.description {
+        properties.push(("stop_desc".to_owned(), Json::String(description.clone())));
+    }
+    let geometry = match location.polygons.as_slice() {
+        [polygon] => vec![
+            ("type".to_owned(), Json::String("Polygon".to_owned())),
+            ("coordinates".to_owned(), polygon_to_json(polygon)),
+        ],
+        polygons => vec![
+            ("type".to_owned(), Json::String("MultiPolygon".to_owned())),
+            (
+                "coordinates".to_owned(),
+                Json::Array(polygons.iter().map(|p| polygon_to_json(p)).collect()),
+            ),
+        ],
+    };
+    Json::Object(vec![
+        ("type".to_owned(), Json::String("Feature".to_owned())),
+        ("id".to_owned(), Json::String(location.id.clone())),
+        ("properties".to_owned(), Json::Object(properties)),
+        ("geometry".to_owned(), Json::Object(geometry)),
+    ])
+}
+
+/// Writes the zones as the content of locations.geojson
+pub(crate) fn write_locations<'a>(locations: impl IntoIterator<Item = &'a Location>) -> String {
+    Json::Object(vec![
+        (
+            "type".to_owned(),
+            Json::String("FeatureCollection".to_owned()),
+        ),
+        (
+            "features".to_owned(),
+            Json::Array(locations.into_iter().map(location_to_json).collect()),
+        ),
+    ])
+    .to_string()
+}
@@ -0,0 +1,257 @@
+//! GeoJSON of the stops, shapes and routes, as displayed by web maps
+
+use crate::json::Json;
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use rgb::RGB8;
+use std::collections::BTreeMap;
+
+/// A line, as (longitude, latitude) points
+type Line = Vec<(f64, f64)>;
+
+fn string(s: &str) -> Json {
+    Json::String(s.to_owned())
+}
+
+fn color(color: Option<RGB8>) -> Json {
+    match color {
+        Some(c) => Json::String(format!("#{:02X}{:02X}{:02X}", c.r, c.g, c.b)),
+        None => Json::Null,
+    }
+}
+
+fn optional_string(s: Option<&str>) -> Json {
+    s.map_or(Json::Null, string)
+}
+
+fn position((lon, lat): (f64, f64)) -> Json {
+    Json::Array(vec![Json::Number(lon), Json::Number(lat)])
+}
+
+fn line_coordinates(line: Line) -> Json {
+    Json::Array(line.into_iter().map(position).collect())
+}
+
+fn feature(properties: Vec<(&str, Json)>, geometry_type: &str, coordinates: Json) -> Json {
+    Json::Object(vec![
+        ("type".to_owned(), string("Feature")),
+        (
+            "properties".to_owned(),
+            Json::Object(
+                properties
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), value))
+                    .collect(),
+            ),
+        ),
+        (
+            "geometry".to_owned(),
+            Json::Object(vec![
+                ("type".to_owned(), string(geometry_type)),
+                ("coordinates".to_owned(), coordinates),
+            ]),
+        ),
+    ])
+}
+
+fn feature_collection(features: Vec<Json>) -> String {
+    Json::Object(vec![
+        ("type".to_owned(), string("FeatureCollection")),
+        ("features".to_owned(), Json::Array(features)),
+    ])
+    .to_string()
+}
+
+fn direction_key(direction: Option<DirectionType>) -> u8 {
+    match direction {
+        Some(DirectionType::Outbound) => 0,
+        Some(DirectionType::Inbound) => 1,
+        None => 2,
+    }
+}
+
+/// Tells if all the points of `line` are found, in the same order and without a gap, in `other`
+fn is_part_of(line: &[(f64, f64)], other: &[(f64, f64)]) -> bool {
+    line.len() <= other.len() && other.windows(line.len()).any(|w| w == line)
+}
+
+/// Removes the lines that are the same as, or a part of, another line of the list
+fn merge_lines(mut lines: Vec<Line>) -> Vec<Line> {
+    // the longest lines first, so that a line can only be a part of a line before it
+    lines.sort_by_key(|l| std::cmp::Reverse(l.len()));
+    let mut merged: Vec<Line> = Vec::new();
+    for line in lines {
+        if !merged.iter().any(|m| is_part_of(&line, m)) {
+            merged.push(line);
+        }
+    }
+    merged
+}
+
+impl Gtfs {
+    /// Geometry followed by a trip: its shape, or the line between its stops when it has none
+    fn trip_line(&self, trip: &Trip) -> Result<Line, Error> {
+        if let Some(shape) = trip.shape_id.as_ref().and_then(|id| self.shapes.get(id)) {
+            if !shape.is_empty() {
+                return Ok(shape.iter().map(|s| (s.longitude, s.latitude)).collect());
+            }
+        }
+        Ok(self
+            .trip_stop_times(&trip.id)?
+            .iter()
+            .filter_map(|st| Some((st.stop.longitude?, st.stop.latitude?)))
+            .collect())
+    }
+
+    /// GeoJSON FeatureCollection of the geometry of a route, with a feature for each direction
+    ///
+    /// The geometry of a direction is a MultiLineString with a line for each distinct path
+    /// followed by its trips: the shapes (or the stops for the trips without shape) used by
+    /// several trips are only given once, and the paths that are a part of another one (like
+    /// the trips of a pattern not going to the terminus) are left out.
+    ///
+    /// The properties of the features are route_id, direction_id (0, 1 or null),
+    /// route_short_name, route_long_name, route_color and route_text_color (like "#FF0000").
+    pub fn route_to_geojson(&self, route_id: &str) -> Result<String, Error> {
+        let route = self.get_route(route_id)?;
+        let mut keys = BTreeMap::new();
+        for trip in self.trips.values().filter(|t| t.route_id == route.id) {
+            // the trips with the same shape, or the same stops, follow the same path
+            let key = match &trip.shape_id {
+                Some(shape_id) if self.shapes.contains_key(shape_id) => shape_id.clone(),
+                _ => self
+                    .trip_stop_times(&trip.id)?
+                    .iter()
+                    .map(|st| st.stop.id.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            keys.entry(direction_key(trip.direction_id))
+                .or_insert_with(|| (trip.direction_id, BTreeMap::new()))
+                .1
+                .entry(key)
+                .or_insert(trip);
+        }
+        let mut features = Vec::new();
+        for (direction, trips) in keys.into_values() {
+            let lines = trips
+                .into_values()
+                .map(|trip| self.trip_line(trip))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|line| line.len() >= 2)
+                .collect();
+            let direction_id = match direction {
+                Some(DirectionType::Outbound) => Json::Number(0.),
+                Some(DirectionType::Inbound) => Json::Number(1.),
+                None => Json::Null,
+            };
+            let coordinates = merge_lines(lines)
+                .into_iter()
+                .map(line_coordinates)
+                .collect();
+            features.push(feature(
+                vec![
+                    ("route_id", string(&route.id)),
+                    ("direction_id", direction_id),
+                    ("route_short_name", string(&route.short_name)),
+                    ("route_long_name", string(&route.long_name)),
+                    ("route_color", color(route.route_color)),
+                    ("route_text_color", color(route.route_text_color)),
+                ],
+                "MultiLineString",
+                Json::Array(coordinates),
+            ));
+        }
+        Ok(feature_collection(features))
+    }
+
+    /// GeoJSON FeatureCollection of the stops, with a Point feature for each stop with
+    /// coordinates, ordered by id
+    ///
+    /// The properties of the features are stop_id, stop_code, stop_name, location_type (0 to
+    /// 4), parent_station and platform_code, null when they are absent.
+    pub fn stops_to_geojson(&self) -> String {
+        let features = self
+            .stops
+            .values()
+            .filter_map(|stop| {
+                let coordinates = position((stop.longitude?, stop.latitude?));
+                Some(feature(
+                    vec![
+                        ("stop_id", string(&stop.id)),
+                        ("stop_code", optional_string(stop.code.as_deref())),
+                        ("stop_name", string(&stop.name)),
+                        (
+                            "location_type",
+                            Json::Number(stop.location_type as u8 as f64),
+                        ),
+                        (
+                            "parent_station",
+                            optional_string(stop.parent_station.as_deref()),
+                        ),
+                        (
+                            "platform_code",
+                            optional_string(stop.platform_code.as_deref()),
+                        ),
+                    ],
+                    "Point",
+                    coordinates,
+                ))
+            })
+            .collect();
+        feature_collection(features)
+    }
+
+    /// GeoJSON FeatureCollection of the shapes, with a LineString feature for each shape,
+    /// ordered by id
+    ///
+    /// The properties of the features are shape_id and route_ids, the ids of the routes of the
+    /// trips following the shape. When those trips all belong to one route, route_color and
+    /// route_text_color give its colors (like "#FF0000"), otherwise they are null.
+    pub fn shapes_to_geojson(&self) -> String {
+        let features = self
+            .shapes
+            .iter()
+            .filter(|(_, points)| points.len() >= 2)
+            .map(|(shape_id, points)| {
+                let mut route_ids: Vec<&str> = self
+                    .trips_for_shape(shape_id)
+                    .into_iter()
+                    .map(|trip| trip.route_id.as_str())
+                    .collect();
+                route_ids.sort_unstable();
+                route_ids.dedup();
+                let route = match route_ids.as_slice() {
+                    [route_id] => self.routes.get(*route_id),
+                    _ => None,
+                };
+                let line = points.iter().map(|p| (p.longitude, p.latitude)).collect();
+                feature(
+                    vec![
+                        ("shape_id", string(shape_id)),
+                        (
+                            "route_ids",
+                            Json::Array(route_ids.into_iter().map(string).collect()),
+                        ),
+                        ("route_color", color(route.and_then(|r| r.route_color))),
+                        (
+                            "route_text_color",
+                            color(route.and_then(|r| r.route_text_color)),
+                        ),
+                    ],
+                    "LineString",
+                    line_coordinates(line),
+                )
+            })
+            .collect();
+        feature_collection(features)
+    }
+}
+
+impl Route {
+    /// GeoJSON FeatureCollection of the geometry of the route, see [Gtfs::route_to_geojson]
+    pub fn to_geojson(&self, gtfs: &Gtfs) -> Result<String, Error> {
+        gtfs.route_to_geojson(&self.id)
+    }
+}
@@ -0,0 +1,194 @@
+//! Geometry of the stops, of the shapes and of the trips following them
+//!
+//! The points are given as (longitude, latitude), the (x, y) order of GeoJSON and of the
+//! geo ecosystem: `geo_types::Point::from(point)` and `geo_types::LineString::from(line)`
+//! convert them without copying the coordinates one by one.
+
+use crate::objects::*;
+use crate::spatial::distance;
+use crate::{Error, Gtfs};
+
+/// A line, as (longitude, latitude) points
+type Line = Vec<(f64, f64)>;
+
+fn shape_line(points: &[Shape]) -> Line {
+    points.iter().map(Shape::point).collect()
+}
+
+/// Line through the stops with coordinates
+fn stops_line(stop_times: &[StopTime]) -> Line {
+    stop_times.iter().filter_map(|st| st.stop.point()).collect()
+}
+
+/// Distance in meters from the first point to each point of the line
+fn cumulative_lengths(line: &[(f64, f64)]) -> Vec<f64> {
+    let mut length = 0.;
+    let mut lengths = Vec::with_capacity(line.len());
+    for (i, (lon, lat)) in line.iter().enumerate() {
+        if i > 0 {
+            let (previous_lon, previous_lat) = line[i - 1];
+            length += distance(previous_lat, previous_lon, *lat, *lon);
+        }
+        lengths.push(length);
+    }
+    lengths
+}
+
+/// Position along the line of the point of the line closest to the given point, with
+/// `along` the position of each point of the line
+fn locate(line: &[(f64, f64)], along: &[f64], lon: f64, lat: f64) -> Option<f64> {
+    // the points are projected on a plane tangent to the earth at the given point
+    let cos_lat = lat.to_radians().cos();
+    let to_xy = |(point_lon, point_lat): (f64, f64)| {
+        (
+            (point_lon - lon).to_radians() * cos_lat,
+            (point_lat - lat).to_radians(),
+        )
+    };
+    if line.len() == 1 {
+        return along.first().copied();
+    }
+    let mut best: Option<(f64, f64)> = None;
+    for i in 0..line.len().saturating_sub(1) {
+        let (ax, ay) = to_xy(line[i]);
+        let (bx, by) = to_xy(line[i + 1]);
+        let (dx, dy) = (bx - ax, by - ay);
+        let length2 = dx * dx + dy * dy;
+        let t = if length2 > 0. {
+            (-(ax * dx + ay * dy) / length2).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let (px, py) = (ax + t * dx, ay + t * dy);
+        let distance2 = px * px + py * py;
+        if best.is_none_or(|(best_distance2, _)| distance2 < best_distance2) {
+            best = Some((distance2, along[i] + t * (along[i + 1] - along[i])));
+        }
+    }
+    best.map(|(_, position)| position)
+}
+
+/// Part of a line between two positions along it, the points at both ends being interpolated
+fn cut(line: &[(f64, f64)], along: &[f64], start: f64, end: f64) -> Line {
+    let at = |position: f64| {
+        let i = along.partition_point(|a| *a < position);
+        if i == 0 {
+            return line[0];
+        }
+        if i == line.len() {
+            return line[line.len() - 1];
+        }
+        let (a, b) = (along[i - 1], along[i]);
+        let t = if b > a { (position - a) / (b - a) } else { 0. };
+        let ((lon1, lat1), (lon2, lat2)) = (line[i - 1], line[i]);
+        (lon1 + t * (lon2 - lon1), lat1 + t * (lat2 - lat1))
+    };
+    if line.is_empty() {
+        return Vec::new();
+    }
+    let mut part = vec![at(start)];
+    part.extend(
+        line.iter()
+            .zip(along)
+            .filter(|(_, a)| **a > start && **a < end)
+            .map(|(point, _)| *point),
+    );
+    part.push(at(end));
+    part
+}
+
+impl Gtfs {
+    /// The points of a shape, ordered by shape_pt_sequence
+    pub fn shape_points(&self, shape_id: &str) -> Result<&[Shape], Error> {
+        self.get_shape(shape_id).map(|points| points.as_slice())
+    }
+
+    /// The points of a shape, as (longitude, latitude) points
+    pub fn shape_line(&self, shape_id: &str) -> Result<Vec<(f64, f64)>, Error> {
+        self.shape_points(shape_id).map(shape_line)
+    }
+
+    /// Length of a shape in meters, following its points
+    pub fn shape_length(&self, shape_id: &str) -> Result<f64, Error> {
+        let line = shape_line(self.shape_points(shape_id)?);
+        Ok(cumulative_lengths(&line).last().copied().unwrap_or(0.))
+    }
+}
+
+impl Stop {
+    /// The position of the stop as (longitude, latitude), None without coordinates
+    pub fn point(&self) -> Option<(f64, f64)> {
+        Some((self.longitude?, self.latitude?))
+    }
+
+    /// Great-circle distance in meters to another stop, None if one of them has no
+    /// coordinates
+    pub fn distance_to(&self, other: &Stop) -> Option<f64> {
+        let ((lon1, lat1), (lon2, lat2)) = (self.point()?, other.point()?);
+        Some(distance(lat1, lon1, lat2, lon2))
+    }
+}
+
+impl Shape {
+    /// The position of the point as (longitude, latitude)
+    pub fn point(&self) -> (f64, f64) {
+        (self.longitude, self.latitude)
+    }
+}
+
+impl Trip {
+    /// Line followed by the trip, as (longitude, latitude) points
+    ///
+    /// These are the points of its shape, or the stops with coordinates when it has no shape.
+    /// An [Error::ReferenceError] is returned if its shape_id is not in shapes.txt.
+    pub fn geometry(&self, gtfs: &Gtfs) -> Result<Vec<(f64, f64)>, Error> {
+        match &self.shape_id {
+            Some(shape_id) => Ok(shape_line(gtfs.shape_points(shape_id)?)),
+            None => Ok(stops_line(&gtfs.trip_stop_times(&self.id)?)),
+        }
+    }
+
+    /// Part of the [Trip::geometry] between two stop times, given by their stop_sequence
+    ///
+    /// The shape is cut at the shape_dist_traveled of the stop times when they and the points
+    /// of the shape have one, otherwise at the points of the shape closest to the stops. The
+    /// line starts and ends at those cuts, not at the stops. Like for [Trip::segment], an
+    /// [Error::InvalidStopSequence] is returned if a stop_sequence is not in the trip or if
+    /// `to_seq` comes first.
+    pub fn geometry_between(
+        &self,
+        gtfs: &Gtfs,
+        from_seq: u16,
+        to_seq: u16,
+    ) -> Result<Vec<(f64, f64)>, Error> {
+        let stop_times = gtfs.trip_stop_times(&self.id)?;
+        let segment = segment(&self.id, &stop_times, from_seq, to_seq)?;
+        let points = match &self.shape_id {
+            Some(shape_id) => gtfs.shape_points(shape_id)?,
+            None => return Ok(stops_line(segment)),
+        };
+        let line = shape_line(points);
+        let distances: Option<Vec<f64>> = points
+            .iter()
+            .map(|point| point.dist_traveled.map(f64::from))
+            .collect();
+        let along = distances
+            .clone()
+            .unwrap_or_else(|| cumulative_lengths(&line));
+        let position = |stop_time: &StopTime, default: Option<f64>| {
+            stop_time
+                .shape_dist_traveled
+                .filter(|_| distances.is_some())
+                .map(f64::from)
+                .or_else(|| {
+                    let stop = &stop_time.stop;
+                    locate(&line, &along, stop.longitude?, stop.latitude?)
+                })
+                .or(default)
+                .unwrap_or(0.)
+        };
+        let start = position(&segment[0], along.first().copied());
+        let end = position(&segment[segment.len() - 1], along.last().copied());
+        Ok(cut(&line, &along, start, end.max(start)))
+    }
+}
@@ -1,58 +1,294 @@
-use crate::{objects::*, Error, RawGtfs};
-use chrono::prelude::NaiveDate;
+use crate::{
+    objects::*, ChangeEvent, ConnectionTable, Error, GtfsReaderOptions, GtfsTime, IdGenerator,
+    Location, OperatingDays, ParseWarning, RawGtfs, SearchIndex, StopIndex, StopPatterns,
+    StopTimeStore, StringInterner, TripStopTimes, UnknownStopPolicy,
+};
+use chrono::prelude::{NaiveDate, NaiveDateTime, TimeZone, Timelike};
 use chrono::Duration;
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
+use std::ops::Range;
 use std::sync::Arc;
 
+/// The instant at which the clocks of the timezone show a local time
+///
+/// An ambiguous time gives its first occurrence. A time skipped when the clocks are set
+/// forward is read with the offset before the change.
+pub(crate) fn local_instant<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> chrono::DateTime<Tz> {
+    match tz.from_local_datetime(&local) {
+        chrono::LocalResult::Single(instant) => instant,
+        chrono::LocalResult::Ambiguous(first, _) => first,
+        chrono::LocalResult::None => {
+            let offset = tz.offset_from_utc_datetime(&(local - Duration::days(1)));
+            tz.from_utc_datetime(&(local - chrono::Offset::fix(&offset)))
+        }
+    }
+}
+
 /// Data structure with all the GTFS objects
 ///
 /// This structure is easier to use than the [RawGtfs] structure.
+///
+/// The objects are indexed by their id in [BTreeMap]s, so iterating over them
+/// always follows the order of their ids.
 #[derive(Default)]
 pub struct Gtfs {
     pub read_duration: i64,
-    pub calendar: HashMap<String, Calendar>,
-    pub calendar_dates: HashMap<String, Vec<CalendarDate>>,
-    pub stops: HashMap<String, Arc<Stop>>,
-    pub routes: HashMap<String, Route>,
-    pub trips: HashMap<String, Trip>,
+    pub calendar: BTreeMap<String, Arc<Calendar>>,
+    pub calendar_dates: BTreeMap<String, Vec<CalendarDate>>,
+    pub stops: BTreeMap<String, Arc<Stop>>,
+    pub routes: BTreeMap<String, Arc<Route>>,
+    pub trips: BTreeMap<String, Trip>,
     pub agencies: Vec<Agency>,
-    pub shapes: HashMap<String, Vec<Shape>>,
-    pub fare_attributes: HashMap<String, FareAttribute>,
+    pub shapes: BTreeMap<String, Vec<Shape>>,
+    pub fare_attributes: BTreeMap<String, FareAttribute>,
+    /// Fare media of the fares v2 model (fare_media.txt)
+    pub fare_media: BTreeMap<String, FareMedia>,
+    /// Groups of riders with their own fares (rider_categories.txt)
+    pub rider_categories: BTreeMap<String, RiderCategory>,
+    /// Fare containers of the earlier drafts of the fares extension (fare_containers.txt)
+    pub fare_containers: BTreeMap<String, FareContainer>,
+    /// Fare products by id, with one product for each fare media
+    pub fare_products: BTreeMap<String, Vec<FareProduct>>,
+    pub fare_leg_rules: Vec<FareLegRule>,
+    pub fare_transfer_rules: Vec<FareTransferRule>,
+    pub areas: BTreeMap<String, Area>,
+    /// Stop ids of each area, by area_id
+    pub stop_areas: BTreeMap<String, Vec<String>>,
+    /// Networks of routes of the fare leg rules, see [Route::network_id]
+    pub networks: BTreeMap<String, Network>,
+    /// Timeframes by timeframe_group_id
+    pub timeframes: BTreeMap<String, Vec<Timeframe>>,
     pub feed_info: Vec<FeedInfo>,
+    pub attributions: Vec<Attribution>,
+    /// Transfers indexed by their from_stop_id, see [Gtfs::transfers_from]
+    pub transfers: BTreeMap<String, Vec<Transfer>>,
+    pub pathways: BTreeMap<String, Pathway>,
+    pub levels: BTreeMap<String, Arc<Level>>,
+    pub booking_rules: BTreeMap<String, BookingRule>,
+    pub location_groups: BTreeMap<String, LocationGroup>,
+    /// Stop ids of each location group, by location_group_id
+    pub location_group_stops: BTreeMap<String, Vec<String>>,
+    /// The GTFS-Flex zones of locations.geojson
+    pub locations: BTreeMap<String, Location>,
     pub translations_by_id: HashMap<TranslationByIdKey, String>,
     pub translations_by_value: HashMap<TranslationByValueKey, String>,
+    /// Problems that did not prevent the GTFS from being read
+    pub warnings: Vec<ParseWarning>,
+    /// Days of operation of the services, see [Gtfs::update_operating_days]
+    pub operating_days: OperatingDays,
+    /// Ids of the trips using each shape, see [Gtfs::trips_for_shape]
+    pub shape_index: BTreeMap<String, BTreeSet<String>>,
+    /// Ids of the trips of each block, see [Gtfs::trips_of_block]
+    pub block_index: BTreeMap<String, BTreeSet<String>>,
+    /// Ids of the routes calling at each stop, see [Gtfs::routes_at_stop]
+    pub routes_by_stop: BTreeMap<String, BTreeSet<String>>,
+    /// Ids of the stops where each route calls, see [Gtfs::stops_on_route]
+    pub stops_by_route: BTreeMap<String, BTreeSet<String>>,
+    /// The stop times written to the disk, see [GtfsReaderOptions::spill_stop_times]
+    pub stop_time_store: Option<StopTimeStore>,
+    /// The stop times shared by several trips, see [GtfsReaderOptions::share_stop_patterns]
+    pub stop_patterns: Option<StopPatterns>,
+    /// Changes made by the mutation methods, when recorded, see [Gtfs::record_changes]
+    pub change_log: Option<Vec<ChangeEvent>>,
+    /// Index of the names of the stops, routes and trips, see [Gtfs::build_search_index]
+    pub search_index: Option<SearchIndex>,
+    /// Index of the positions of the stops, see [Gtfs::build_stop_index]
+    pub stop_index: Option<StopIndex>,
+    /// Connections of the trips ordered by departure time, see [Gtfs::build_connection_table]
+    pub connection_table: Option<ConnectionTable>,
+    /// Gives the ids of the objects created by the mutation methods, see [Gtfs::new_id]
+    pub id_generator: Option<Box<dyn IdGenerator>>,
 }
 
 impl TryFrom<RawGtfs> for Gtfs {
     type Error = Error;
     fn try_from(raw: RawGtfs) -> Result<Gtfs, Error> {
-        let stops = to_stop_map(raw.stops?);
-        let trips = create_trips(raw.trips?, raw.stop_times?, &stops)?;
+        Gtfs::from_raw(raw, &GtfsReaderOptions::default())
+    }
+}
+
+impl Gtfs {
+    /// Builds the [Gtfs], handling the stop times referencing unknown stops or trips as set by
+    /// the options
+    pub(crate) fn from_raw(raw: RawGtfs, options: &GtfsReaderOptions) -> Result<Gtfs, Error> {
+        let unknown_stops = options.unknown_stops;
+        let mut warnings = raw.warnings;
+        let levels: BTreeMap<String, Arc<Level>> = raw
+            .levels
+            .unwrap_or_else(|| Ok(Vec::new()))?
+            .into_iter()
+            .map(|level| (level.id.clone(), Arc::new(level)))
+            .collect();
+        let mut stops = to_stop_map(raw.stops?, &levels);
+        let trips = create_trips(
+            raw.trips?,
+            raw.stop_times?,
+            raw.frequencies.unwrap_or_else(|| Ok(Vec::new()))?,
+            &mut stops,
+            options,
+            &mut warnings,
+        )?;
+        let mut stop_time_store = raw.stop_time_store;
+        if let Some(store) = &mut stop_time_store {
+            if options.lenient {
+                for trip_id in store.trip_ids().filter(|id| !trips.contains_key(*id)) {
+                    warnings.push(unknown_trip_warning("stop_times.txt", None, trip_id));
+                }
+            }
+            for stop_id in store.check_references(&trips, &stops, options.lenient)? {
+                match unknown_stops {
+                    UnknownStopPolicy::Error => return Err(Error::ReferenceError(stop_id)),
+                    UnknownStopPolicy::Skip => warnings.push(ParseWarning {
+                        file_name: "stop_times.txt".to_owned(),
+                        line: None,
+                        column: None,
+                        message: format!(
+                            "the stop times referencing the unknown stop '{}' are skipped",
+                            stop_id
+                        ),
+                    }),
+                    UnknownStopPolicy::Placeholder => {
+                        stops.insert(stop_id.clone(), placeholder_stop(stop_id));
+                    }
+                }
+            }
+            store.skip_unknown_stops = unknown_stops == UnknownStopPolicy::Skip;
+        }
+        let transfers = to_transfer_map(raw.transfers.unwrap_or_else(|| Ok(Vec::new()))?, &stops)?;
+        let pathways = to_map(raw.pathways.unwrap_or_else(|| Ok(Vec::new()))?);
+        if let Some(stop_id) = pathways
+            .values()
+            .flat_map(|p| vec![&p.from_stop_id, &p.to_stop_id])
+            .find(|id| !stops.contains_key(*id))
+        {
+            return Err(Error::ReferenceError(stop_id.to_string()));
+        }
         let (translations_by_id, translations_by_value) = create_translations(
             raw.translations.unwrap_or(Ok(vec!()))?
         )?;
 
-        Ok(Gtfs {
+        let mut routes = to_map(raw.routes?);
+        for route_network in raw.route_networks.unwrap_or_else(|| Ok(Vec::new()))? {
+            let route = routes
+                .get_mut(&route_network.route_id)
+                .ok_or_else(|| Error::ReferenceError(route_network.route_id.clone()))?;
+            route.network_id = Some(route_network.network_id);
+        }
+        let routes = routes
+            .into_iter()
+            .map(|(id, route)| (id, Arc::new(route)))
+            .collect();
+
+        let mut gtfs = Gtfs {
             stops,
-            routes: to_map(raw.routes?),
+            routes,
             trips,
             agencies: raw.agencies?,
             shapes: to_shape_map(raw.shapes.unwrap_or_else(|| Ok(Vec::new()))?),
-            fare_attributes: to_map(raw.fare_attributes.unwrap_or_else(|| Ok(Vec::new()))?),
+            fare_attributes: to_fare_map(
+                raw.fare_attributes.unwrap_or_else(|| Ok(Vec::new()))?,
+                raw.fare_rules.unwrap_or_else(|| Ok(Vec::new()))?,
+            )?,
+            fare_media: to_map(raw.fare_media.unwrap_or_else(|| Ok(Vec::new()))?),
+            rider_categories: to_map(raw.rider_categories.unwrap_or_else(|| Ok(Vec::new()))?),
+            fare_containers: to_map(raw.fare_containers.unwrap_or_else(|| Ok(Vec::new()))?),
+            fare_products: to_groups(raw.fare_products.unwrap_or_else(|| Ok(Vec::new()))?, |p| {
+                p.id.clone()
+            }),
+            fare_leg_rules: raw.fare_leg_rules.unwrap_or_else(|| Ok(Vec::new()))?,
+            fare_transfer_rules: raw.fare_transfer_rules.unwrap_or_else(|| Ok(Vec::new()))?,
+            areas: to_map(raw.areas.unwrap_or_else(|| Ok(Vec::new()))?),
+            stop_areas: to_groups(raw.stop_areas.unwrap_or_else(|| Ok(Vec::new()))?, |a| {
+                a.area_id.clone()
+            })
+            .into_iter()
+            .map(|(area_id, stops)| (area_id, stops.into_iter().map(|s| s.stop_id).collect()))
+            .collect(),
+            networks: to_map(raw.networks.unwrap_or_else(|| Ok(Vec::new()))?),
+            timeframes: to_groups(raw.timeframes.unwrap_or_else(|| Ok(Vec::new()))?, |t| {
+                t.timeframe_group_id.clone()
+            }),
             feed_info: raw.feed_info.unwrap_or_else(|| Ok(Vec::new()))?,
-            calendar: to_map(raw.calendar.unwrap_or_else(|| Ok(Vec::new()))?),
+            attributions: raw.attributions.unwrap_or_else(|| Ok(Vec::new()))?,
+            transfers,
+            pathways,
+            levels,
+            booking_rules: to_map(raw.booking_rules.unwrap_or_else(|| Ok(Vec::new()))?),
+            location_groups: to_map(raw.location_groups.unwrap_or_else(|| Ok(Vec::new()))?),
+            location_group_stops: to_groups(
+                raw.location_group_stops.unwrap_or_else(|| Ok(Vec::new()))?,
+                |s| s.location_group_id.clone(),
+            )
+            .into_iter()
+            .map(|(group_id, stops)| (group_id, stops.into_iter().map(|s| s.stop_id).collect()))
+            .collect(),
+            locations: to_map(raw.locations.unwrap_or_else(|| Ok(Vec::new()))?),
+            calendar: to_map(raw.calendar.unwrap_or_else(|| Ok(Vec::new()))?)
+                .into_iter()
+                .map(|(id, calendar)| (id, Arc::new(calendar)))
+                .collect(),
             calendar_dates: to_calendar_dates(
                 raw.calendar_dates.unwrap_or_else(|| Ok(Vec::new()))?,
             ),
             translations_by_id,
             translations_by_value,
             read_duration: raw.read_duration,
-        })
+            warnings,
+            operating_days: OperatingDays::default(),
+            shape_index: BTreeMap::new(),
+            block_index: BTreeMap::new(),
+            routes_by_stop: BTreeMap::new(),
+            stops_by_route: BTreeMap::new(),
+            stop_time_store,
+            stop_patterns: None,
+            change_log: None,
+            search_index: None,
+            stop_index: None,
+            connection_table: None,
+            id_generator: None,
+        };
+        gtfs.link_single_agency();
+        gtfs.link_trips();
+        gtfs.update_operating_days();
+        gtfs.update_shape_index();
+        gtfs.update_block_index();
+        gtfs.update_route_stop_index();
+        Ok(gtfs)
     }
 }
 
 impl Gtfs {
+    /// When the feed has a single agency without agency_id (which is allowed by the specification),
+    /// gives it an id derived from its name, and links the routes and fares without agency_id to it
+    fn link_single_agency(&mut self) {
+        let agency = match self.agencies.as_mut_slice() {
+            [agency] if agency.id.is_none() => agency,
+            _ => return,
+        };
+        let id = single_agency_id(&agency.name);
+        agency.id = Some(id.clone());
+        for route in self.routes.values_mut().filter(|r| r.agency_id.is_none()) {
+            Arc::make_mut(route).agency_id = Some(id.clone());
+        }
+        for fare in self.fare_attributes.values_mut().filter(|f| f.agency_id.is_none()) {
+            fare.agency_id = Some(id.clone());
+        }
+    }
+
+    /// Sets the [Trip::route] and [Trip::calendar] of every trip from its route_id and
+    /// service_id
+    ///
+    /// This is done when the feed is read and by the mutation methods, and is needed after
+    /// `routes`, `calendar` or the ids of a trip are modified directly.
+    pub fn link_trips(&mut self) {
+        for trip in self.trips.values_mut() {
+            trip.route = self.routes.get(&trip.route_id).cloned();
+            trip.calendar = self.calendar.get(&trip.service_id).cloned();
+        }
+    }
+
     pub fn print_stats(&self) {
         println!("GTFS data:");
         println!("  Read in {} ms", self.read_duration);
@@ -63,6 +299,14 @@ impl Gtfs {
         println!("  Shapes: {}", self.shapes.len());
         println!("  Fare attributes: {}", self.fare_attributes.len());
         println!("  Feed info: {}", self.feed_info.len());
+        println!("  Attributions: {}", self.attributions.len());
+        println!("  Booking rules: {}", self.booking_rules.len());
+        println!("  Location groups: {}", self.location_groups.len());
+        println!("  Locations: {}", self.locations.len());
+        println!(
+            "  Transfers: {}",
+            self.transfers.values().map(Vec::len).sum::<usize>()
+        );
     }
 
     /// Reads from an url (if starts with http), or a local path (either a directory or zipped file)
@@ -80,6 +324,17 @@ impl Gtfs {
         RawGtfs::from_path(path).and_then(Gtfs::try_from)
     }
 
+    /// Reads the GTFS from a local zip archive or local directory, with custom options
+    pub fn from_path_with_options<P>(path: P, options: GtfsReaderOptions) -> Result<Gtfs, Error>
+    where
+        P: AsRef<std::path::Path> + std::fmt::Display,
+    {
+        let share_stop_patterns = options.share_stop_patterns;
+        let raw = RawGtfs::from_path_with_options(path, options.clone())?;
+        let gtfs = Gtfs::from_raw(raw, &options)?;
+        Ok(gtfs.with_shared_stop_patterns(share_stop_patterns))
+    }
+
     /// Reads the GTFS from a remote url
     /// The library must be built with the read-url feature
     #[cfg(feature = "read-url")]
@@ -89,15 +344,46 @@ impl Gtfs {
 
     /// Asynchronously reads the GTFS from a remote url
     /// The library must be built with the read-url feature
+    ///
+    /// The download does not block the async runtime, and the files are parsed on the blocking
+    /// thread pool of tokio, so it must be called from a tokio runtime.
     #[cfg(feature = "read-url")]
     pub async fn from_url_async<U: reqwest::IntoUrl>(url: U) -> Result<Gtfs, Error> {
-        RawGtfs::from_url_async(url).await.and_then(Gtfs::try_from)
+        let body = crate::raw_gtfs::download(url).await?;
+        crate::raw_gtfs::spawn_parsing(move || {
+            RawGtfs::from_reader(std::io::Cursor::new(body)).and_then(Gtfs::try_from)
+        })
+        .await
     }
 
+    /// Reads the GTFS from a zip archive in any reader, such as a file or a buffer in memory
     pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: T) -> Result<Gtfs, Error> {
         RawGtfs::from_reader(reader).and_then(Gtfs::try_from)
     }
 
+    /// Reads the GTFS from the bytes of a zip archive, e.g. downloaded with another http client
+    pub fn from_bytes(bytes: &[u8]) -> Result<Gtfs, Error> {
+        RawGtfs::from_bytes(bytes).and_then(Gtfs::try_from)
+    }
+
+    /// Reads the GTFS from a zip archive, with custom options
+    pub fn from_reader_with_options<T: std::io::Read + std::io::Seek>(
+        reader: T,
+        options: GtfsReaderOptions,
+    ) -> Result<Gtfs, Error> {
+        let share_stop_patterns = options.share_stop_patterns;
+        let raw = RawGtfs::from_reader_with_options(reader, options.clone())?;
+        let gtfs = Gtfs::from_raw(raw, &options)?;
+        Ok(gtfs.with_shared_stop_patterns(share_stop_patterns))
+    }
+
+    fn with_shared_stop_patterns(mut self, share: bool) -> Self {
+        if share {
+            self.share_stop_patterns();
+        }
+        self
+    }
+
     pub fn trip_days(&self, service_id: &str, start_date: NaiveDate) -> Vec<u16> {
         let mut result = Vec::new();
 
@@ -140,6 +426,264 @@ impl Gtfs {
         result
     }
 
+    /// Lists the trips of a route running on the given date, sorted by their departure time
+    /// at their first stop
+    ///
+    /// If a direction is given, only the trips going in that direction are kept.
+    /// Trips without any departure time at their first stop are ignored. An error is returned
+    /// if the stop times of a trip cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn route_departures(
+        &self,
+        route_id: &str,
+        direction: Option<DirectionType>,
+        date: NaiveDate,
+    ) -> Result<Vec<RouteDeparture<'_>>, Error> {
+        let mut departures = Vec::new();
+        for trip in self
+            .trips
+            .values()
+            .filter(|trip| trip.route_id == route_id)
+            .filter(|trip| direction.is_none() || trip.direction_id == direction)
+            .filter(|trip| self.operating_days.is_active(&trip.service_id, date))
+        {
+            let stop_times = self.trip_stop_times(&trip.id)?;
+            let first = match stop_times.first() {
+                Some(first) => first,
+                None => continue,
+            };
+            if let Some(departure_time) = first.departure_time.or(first.arrival_time) {
+                departures.push(RouteDeparture {
+                    trip,
+                    stop: Arc::clone(&first.stop),
+                    departure_time,
+                });
+            }
+        }
+        departures.sort_by(|a, b| {
+            a.departure_time
+                .cmp(&b.departure_time)
+                .then_with(|| a.trip.id.cmp(&b.trip.id))
+        });
+        Ok(departures)
+    }
+
+    /// Tells if the stop is the station, or one of its platforms, entrances, generic nodes or
+    /// boarding areas
+    pub(crate) fn is_in_station(&self, stop: &Stop, station_id: &str) -> bool {
+        let mut stop_id = Some(stop.id.as_str());
+        // the boarding areas are in a platform, which is in the station
+        for _ in 0..3 {
+            match stop_id {
+                Some(id) if id == station_id => return true,
+                Some(id) => {
+                    stop_id = self
+                        .stops
+                        .get(id)
+                        .and_then(|stop| stop.parent_station.as_deref())
+                }
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Lists the departures from a station on the given date, for a station departure board
+    ///
+    /// The departures are from the station itself and from all its child stops (platforms,
+    /// boarding areas), between the start (included) and the end (excluded) of the window, in
+    /// seconds since midnight of the date. This includes the trips of the previous service day
+    /// leaving after midnight, and every departure of the trips repeated by frequencies.
+    ///
+    /// The calls where passengers cannot board are not departures. The last call of a trip is
+    /// never a departure: when the vehicle continues as the next trip of its block, that trip
+    /// is listed instead, so the vehicle appears once. An error is returned if the stop times
+    /// of a trip cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn origin_departures(
+        &self,
+        station_id: &str,
+        date: NaiveDate,
+        window: Range<u32>,
+    ) -> Result<Vec<StationDeparture<'_>>, Error> {
+        const DAY: i64 = 24 * 3600;
+        let stop_ids: HashSet<&str> = self
+            .stops
+            .values()
+            .filter(|stop| self.is_in_station(stop, station_id))
+            .map(|stop| stop.id.as_str())
+            .collect();
+
+        let mut departures = Vec::new();
+        // the times can be after 24:00:00, the trips of the previous days can leave on the date
+        for days in -1..=(window.end as i64 / DAY) {
+            let service_date = date + Duration::days(days);
+            for trip in self.trips.values() {
+                if !self.operating_days.is_active(&trip.service_id, service_date) {
+                    continue;
+                }
+                let stop_times = self.trip_stop_times(&trip.id)?;
+                let first_departure = stop_times
+                    .first()
+                    .and_then(|st| st.departure_time.or(st.arrival_time));
+                let shifts: Vec<i64> = match first_departure {
+                    Some(first) if !trip.frequencies.is_empty() => trip
+                        .frequencies
+                        .iter()
+                        .flat_map(|f| {
+                            (f.start_time..f.end_time).step_by(f.headway_secs.max(1) as usize)
+                        })
+                        .map(|departure| departure as i64 - first as i64)
+                        .collect(),
+                    _ => vec![0],
+                };
+                let calls = stop_times
+                    .split_last()
+                    .map(|(_, calls)| calls)
+                    .unwrap_or_default();
+                for stop_time in calls {
+                    if !stop_ids.contains(stop_time.stop.id.as_str())
+                        || stop_time.pickup_type == Some(PickupDropOffType::NotAvailable)
+                    {
+                        continue;
+                    }
+                    let time = match stop_time.departure_time.or(stop_time.arrival_time) {
+                        Some(time) => time as i64 + days * DAY,
+                        None => continue,
+                    };
+                    for shift in &shifts {
+                        let departure_time = time + shift;
+                        if departure_time >= window.start as i64
+                            && departure_time < window.end as i64
+                        {
+                            departures.push(StationDeparture {
+                                trip,
+                                stop: Arc::clone(&stop_time.stop),
+                                service_date,
+                                departure_time: departure_time as u32,
+                                headsign: stop_time
+                                    .stop_headsign
+                                    .as_ref()
+                                    .or(trip.trip_headsign.as_ref())
+                                    .cloned(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        departures.sort_by(|a, b| {
+            a.departure_time
+                .cmp(&b.departure_time)
+                .then_with(|| a.trip.id.cmp(&b.trip.id))
+        });
+        Ok(departures)
+    }
+
+    /// Lists the departures from a stop during the given time after a date and time, for a
+    /// departure board
+    ///
+    /// The services are resolved with the calendar and the calendar dates, and the trips
+    /// defined by frequencies give a departure for each vehicle. When the stop is a station,
+    /// the departures from all its child stops are listed. See [Gtfs::origin_departures] for
+    /// the calls that are not departures.
+    pub fn departures_from(
+        &self,
+        stop_id: &str,
+        datetime: NaiveDateTime,
+        horizon: Duration,
+    ) -> Result<Vec<Departure<'_>>, Error> {
+        self.get_stop(stop_id)?;
+        let date = datetime.date();
+        let start = datetime.time().num_seconds_from_midnight();
+        let end = (i64::from(start) + horizon.num_seconds().max(0)).min(u32::MAX as i64) as u32;
+        Ok(self
+            .origin_departures(stop_id, date, start..end)?
+            .into_iter()
+            .filter_map(|departure| {
+                Some(Departure {
+                    route: self.routes.get(&departure.trip.route_id)?,
+                    trip: departure.trip,
+                    stop: departure.stop,
+                    service_date: departure.service_date,
+                    departure: date.and_hms_opt(0, 0, 0)?
+                        + Duration::seconds(departure.departure_time.into()),
+                    headsign: departure.headsign,
+                })
+            })
+            .collect())
+    }
+
+    /// Time elapsed between the departure from a stop of a trip and the arrival at another one,
+    /// when the trip runs on the service date
+    ///
+    /// The times of stop_times.txt are counted from “noon minus 12h” of the service date in
+    /// the timezone `tz`, see [GtfsTime::on], so the elapsed time is the difference of the
+    /// times, even when the clocks are changed during the trip. `tz` is usually the timezone
+    /// of the agency, given by a crate like `chrono-tz`.
+    ///
+    /// The stop times are given by their stop_sequence, an [Error::InvalidStopSequence] is
+    /// returned if the trip has no stop time with such a sequence, or if it has no time.
+    pub fn elapsed_between<Tz: TimeZone>(
+        &self,
+        trip_id: &str,
+        from_sequence: u16,
+        to_sequence: u16,
+        date: NaiveDate,
+        tz: &Tz,
+    ) -> Result<Duration, Error> {
+        let stop_times = self.trip_stop_times(trip_id)?;
+        let time = |sequence: u16, arrival: bool| {
+            let stop_time = stop_times
+                .iter()
+                .find(|st| st.stop_sequence == sequence)
+                .ok_or_else(|| {
+                    Error::InvalidStopSequence(format!(
+                        "the trip {} has no stop time with the stop sequence {}",
+                        trip_id, sequence
+                    ))
+                })?;
+            let time = match arrival {
+                true => stop_time.arrival_time.or(stop_time.departure_time),
+                false => stop_time.departure_time.or(stop_time.arrival_time),
+            };
+            time.and_then(|time| GtfsTime::from_seconds(time).on(date, tz))
+                .ok_or_else(|| {
+                    Error::InvalidStopSequence(format!(
+                        "the stop time {} of the trip {} has no time",
+                        sequence, trip_id
+                    ))
+                })
+        };
+        let departure = time(from_sequence, false)?;
+        Ok(time(to_sequence, true)?.signed_duration_since(departure))
+    }
+
+    /// Language of the texts of the feed
+    ///
+    /// This is the feed_lang of feed_info.txt, or the agency_lang of the first agency defining one.
+    /// It is "mul" when the texts are in several languages.
+    pub fn feed_lang(&self) -> Option<&str> {
+        self.feed_info
+            .iter()
+            .map(|feed_info| feed_info.lang.trim())
+            .chain(self.agencies.iter().filter_map(|a| a.lang.as_deref()))
+            .find(|lang| !lang.is_empty())
+    }
+
+    /// Language to use when the language of the rider is not known
+    ///
+    /// This is the default_lang of feed_info.txt, or the language of the feed if it is not "mul".
+    pub fn default_lang(&self) -> Option<&str> {
+        self.feed_info
+            .iter()
+            .filter_map(|feed_info| feed_info.default_lang.as_deref())
+            .find(|lang| !lang.trim().is_empty())
+            .or_else(|| self.feed_lang().filter(|lang| !lang.eq_ignore_ascii_case("mul")))
+    }
+
+    /// Translates a field in the language
+    ///
+    /// Without translation in the language, the translation in the [Gtfs::default_lang] is used
+    /// for multilingual feeds (whose [Gtfs::feed_lang] is "mul"), then the value itself.
     pub fn translate(
         &self,
         table_name: &str,
@@ -147,30 +691,100 @@ impl Gtfs {
         language: &str,
         record_id: &str,
         record_sub_id: Option<&str>,
-        field_value: &String
+        field_value: &str
     ) -> String {
-        if let Some(ret) = self.translations_by_id.get(&TranslationByIdKey{
-            table_name: table_name.to_string(),
-            field_name: field_name.to_string(),
-            language: language.to_string(),
-            record_id: record_id.to_string(),
-            record_sub_id: record_sub_id.map(|x| x.to_string()),
-        }) {
-            return ret.to_string();
-        }
+        for language in self.translation_languages(language) {
+            if let Some(ret) = self.translations_by_id.get(&TranslationByIdKey{
+                table_name: table_name.to_string(),
+                field_name: field_name.to_string(),
+                language: language.to_string(),
+                record_id: record_id.to_string(),
+                record_sub_id: record_sub_id.map(|x| x.to_string()),
+            }) {
+                return ret.to_string();
+            }
 
-        if let Some(ret) = self.translations_by_value.get(&TranslationByValueKey{
-            table_name: table_name.to_string(),
-            field_name: field_name.to_string(),
-            language: language.to_string(),
-            field_value: field_value.to_string(),
-        }) {
-            return ret.to_string();
+            if let Some(ret) = self.translations_by_value.get(&TranslationByValueKey{
+                table_name: table_name.to_string(),
+                field_name: field_name.to_string(),
+                language: language.to_string(),
+                field_value: field_value.to_string(),
+            }) {
+                return ret.to_string();
+            }
         }
 
         field_value.to_string()
     }
 
+    /// Translates a batch of stops in the language, like [Translatable::translate]
+    ///
+    /// The translations are found in one pass over the translations, which is faster than
+    /// translating the stops one by one for long lists.
+    pub fn translate_many(&self, stops: &[&Stop], language: &str) -> Vec<Stop> {
+        let batch = self.translation_batch("stops", stops.iter().map(|s| s.id.as_str()), language);
+        stops
+            .iter()
+            .map(|stop| stop.translate_fields(|field, value| batch.get(&stop.id, field, value)))
+            .collect()
+    }
+
+    /// Translates a batch of routes in the language, see [Gtfs::translate_many]
+    pub fn translate_many_routes(&self, routes: &[&Route], language: &str) -> Vec<Route> {
+        let batch =
+            self.translation_batch("routes", routes.iter().map(|r| r.id.as_str()), language);
+        routes
+            .iter()
+            .map(|route| route.translate_fields(|field, value| batch.get(&route.id, field, value)))
+            .collect()
+    }
+
+    /// Languages in which the translations are searched, by order of preference
+    fn translation_languages<'a>(&'a self, language: &'a str) -> Vec<&'a str> {
+        let mut languages = vec![language];
+        if self.feed_lang().is_some_and(|lang| lang.eq_ignore_ascii_case("mul")) {
+            languages.extend(self.default_lang().filter(|lang| *lang != language));
+        }
+        languages
+    }
+
+    fn translation_batch<'a>(
+        &'a self,
+        table_name: &str,
+        record_ids: impl Iterator<Item = &'a str>,
+        language: &str,
+    ) -> TranslationBatch<'a> {
+        let record_ids: HashSet<&str> = record_ids.collect();
+        let languages = self.translation_languages(language);
+        let mut batch = TranslationBatch {
+            by_id: vec![HashMap::new(); languages.len()],
+            by_value: vec![HashMap::new(); languages.len()],
+        };
+        for (key, translation) in &self.translations_by_id {
+            if key.table_name != table_name || key.record_sub_id.is_some() {
+                continue;
+            }
+            if let Some(i) = languages.iter().position(|l| *l == key.language) {
+                if let Some(record_id) = record_ids.get(key.record_id.as_str()) {
+                    let field_name = key.field_name.as_str();
+                    batch.by_id[i].insert((*record_id, field_name), translation.as_str());
+                }
+            }
+        }
+        for (key, translation) in &self.translations_by_value {
+            if key.table_name != table_name {
+                continue;
+            }
+            if let Some(i) = languages.iter().position(|l| *l == key.language) {
+                batch.by_value[i].insert(
+                    (key.field_name.as_str(), key.field_value.as_str()),
+                    translation.as_str(),
+                );
+            }
+        }
+        batch
+    }
+
     pub fn get_stop<'a>(&'a self, id: &str) -> Result<&'a Stop, Error> {
         match self.stops.get(id) {
             Some(stop) => Ok(stop),
@@ -203,6 +817,54 @@ impl Gtfs {
         Ok(trip.to_owned().translate(self, language))
     }
 
+    /// Stop times of a trip, sorted by stop_sequence
+    ///
+    /// They are borrowed from the trip when it has its own [Trip::stop_times]. Otherwise they
+    /// are read from the disk if the stop times are spilled, see
+    /// [GtfsReaderOptions::spill_stop_times], or built from their pattern when it is shared
+    /// with other trips, see [GtfsReaderOptions::share_stop_patterns].
+    pub fn trip_stop_times(&self, trip_id: &str) -> Result<TripStopTimes<'_>, Error> {
+        let trip = self.get_trip(trip_id)?;
+        if !trip.stop_times.is_empty() {
+            return Ok(TripStopTimes::Borrowed(&trip.stop_times));
+        }
+        if let Some(store) = &self.stop_time_store {
+            return store.load(trip_id, &self.stops).map(TripStopTimes::Loaded);
+        }
+        let shared = self
+            .stop_patterns
+            .as_ref()
+            .and_then(|patterns| patterns.stop_times(trip_id));
+        Ok(match shared {
+            Some(stop_times) => TripStopTimes::Loaded(Arc::new(stop_times)),
+            None => TripStopTimes::Borrowed(&trip.stop_times),
+        })
+    }
+
+    /// A trip with its stop times, to use the methods of [Trip] reading them, like
+    /// [Trip::segment] or [Trip::crosses_midnight]
+    ///
+    /// The trip is borrowed when it has its own [Trip::stop_times], otherwise it is copied
+    /// with the stop times given by [Gtfs::trip_stop_times].
+    ///
+    /// ```
+    /// let options = gtfs_structures::GtfsReaderOptions::new().share_stop_patterns(true);
+    /// let gtfs = gtfs_structures::Gtfs::from_path_with_options("fixtures/timetable", options)
+    ///     .unwrap();
+    /// let trip = gtfs.trip_with_stop_times("r71_0700").unwrap();
+    /// assert_eq!(3, trip.segment(2, 4).unwrap().len());
+    /// ```
+    pub fn trip_with_stop_times(&self, trip_id: &str) -> Result<Cow<'_, Trip>, Error> {
+        let trip = self.get_trip(trip_id)?;
+        Ok(match self.trip_stop_times(trip_id)? {
+            TripStopTimes::Borrowed(_) => Cow::Borrowed(trip),
+            TripStopTimes::Loaded(stop_times) => Cow::Owned(Trip {
+                stop_times: stop_times.to_vec(),
+                ..trip.clone()
+            }),
+        })
+    }
+
     pub fn get_route<'a>(&'a self, id: &str) -> Result<&'a Route, Error> {
         match self.routes.get(id) {
             Some(route) => Ok(route),
@@ -240,43 +902,436 @@ impl Gtfs {
         }
     }
 
+    /// Trips using the shape, ordered by id
+    pub fn trips_for_shape(&self, shape_id: &str) -> Vec<&Trip> {
+        self.shape_index
+            .get(shape_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|trip_id| self.trips.get(trip_id))
+            .collect()
+    }
+
+    /// Builds again the [Gtfs::shape_index], needed after the trips are modified
+    /// without the mutation methods
+    pub fn update_shape_index(&mut self) {
+        self.shape_index.clear();
+        for trip in self.trips.values() {
+            if let Some(shape_id) = &trip.shape_id {
+                self.shape_index
+                    .entry(shape_id.clone())
+                    .or_default()
+                    .insert(trip.id.clone());
+            }
+        }
+    }
+
+    /// Builds again the [Gtfs::block_index], needed after the trips are modified
+    /// without the mutation methods
+    pub fn update_block_index(&mut self) {
+        self.block_index.clear();
+        for trip in self.trips.values() {
+            if let Some(block_id) = &trip.block_id {
+                self.block_index
+                    .entry(block_id.clone())
+                    .or_default()
+                    .insert(trip.id.clone());
+            }
+        }
+    }
+
+    /// Adds a new trip to the [Gtfs::shape_index] and the [Gtfs::block_index]
+    pub(crate) fn index_trip(&mut self, trip: &Trip) {
+        for (index, key) in [
+            (&mut self.shape_index, &trip.shape_id),
+            (&mut self.block_index, &trip.block_id),
+        ] {
+            if let Some(key) = key {
+                index
+                    .entry(key.clone())
+                    .or_default()
+                    .insert(trip.id.clone());
+            }
+        }
+    }
+
+    /// Removes a trip from the [Gtfs::shape_index] and the [Gtfs::block_index]
+    pub(crate) fn unindex_trip(&mut self, trip: &Trip) {
+        for (index, key) in [
+            (&mut self.shape_index, &trip.shape_id),
+            (&mut self.block_index, &trip.block_id),
+        ] {
+            let key = match key {
+                Some(key) => key,
+                None => continue,
+            };
+            if let Some(trip_ids) = index.get_mut(key) {
+                trip_ids.remove(&trip.id);
+                if trip_ids.is_empty() {
+                    index.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Trips of the block, ordered by id
+    pub fn trips_of_block(&self, block_id: &str) -> Vec<&Trip> {
+        self.block_index
+            .get(block_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|trip_id| self.trips.get(trip_id))
+            .collect()
+    }
+
+    /// The trips made by the vehicle of a trip on the same day, in the order it makes them
+    ///
+    /// These are the trips of its block whose service runs on a day of the service of the
+    /// trip, ordered by their first departure, so the passengers can stay on board from one
+    /// trip to the next. A trip without block_id is alone in its chain.
+    pub fn block_chain(&self, trip_id: &str) -> Result<Vec<&Trip>, Error> {
+        let trip = self.get_trip(trip_id)?;
+        let block_id = match &trip.block_id {
+            Some(block_id) => block_id,
+            None => return Ok(vec![trip]),
+        };
+        let mut chain: Vec<(Option<u32>, &Trip)> = self
+            .trips_of_block(block_id)
+            .into_iter()
+            .filter(|other| {
+                other.id == trip.id
+                    || self
+                        .operating_days
+                        .share_days(&trip.service_id, &other.service_id)
+            })
+            .map(|other| {
+                let first_departure = self.trip_stop_times(&other.id).ok().and_then(|st| {
+                    st.first()
+                        .and_then(|st| st.departure_time.or(st.arrival_time))
+                });
+                (first_departure, other)
+            })
+            .collect();
+        // the trips without times are at the end
+        chain.sort_by_key(|(departure, other)| (departure.is_none(), *departure, &other.id));
+        Ok(chain.into_iter().map(|(_, other)| other).collect())
+    }
+
+    /// Routes with a trip calling at the stop, ordered by id
+    ///
+    /// Only the stop times at the stop itself are considered: the routes calling at the
+    /// platforms of a station are not given for the station.
+    pub fn routes_at_stop(&self, stop_id: &str) -> Vec<&Route> {
+        self.routes_by_stop
+            .get(stop_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|route_id| self.routes.get(route_id))
+            .map(|route| route.as_ref())
+            .collect()
+    }
+
+    /// Stops where a trip of the route calls, ordered by id
+    pub fn stops_on_route(&self, route_id: &str) -> Vec<&Stop> {
+        self.stops_by_route
+            .get(route_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|stop_id| self.stops.get(stop_id))
+            .map(|stop| stop.as_ref())
+            .collect()
+    }
+
+    /// Builds again the [Gtfs::routes_by_stop] and [Gtfs::stops_by_route] indexes, needed
+    /// after the trips or their stop times are modified without the mutation methods
+    pub fn update_route_stop_index(&mut self) {
+        let mut routes_by_stop = BTreeMap::new();
+        let mut stops_by_route = BTreeMap::new();
+        for trip in self.trips.values() {
+            let stop_times = self.trip_stop_times(&trip.id).unwrap_or_default();
+            insert_route_stops(
+                &mut routes_by_stop,
+                &mut stops_by_route,
+                &trip.route_id,
+                &stop_times,
+            );
+        }
+        self.routes_by_stop = routes_by_stop;
+        self.stops_by_route = stops_by_route;
+    }
+
+    /// Adds the stops of the stop times of a trip of the route to the [Gtfs::routes_by_stop]
+    /// and [Gtfs::stops_by_route] indexes
+    pub(crate) fn index_route_stops(&mut self, route_id: &str, stop_times: &[StopTime]) {
+        insert_route_stops(
+            &mut self.routes_by_stop,
+            &mut self.stops_by_route,
+            route_id,
+            stop_times,
+        );
+    }
+
+    pub fn get_level<'a>(&'a self, id: &str) -> Result<&'a Level, Error> {
+        match self.levels.get(id) {
+            Some(level) => Ok(level),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_booking_rule<'a>(&'a self, id: &str) -> Result<&'a BookingRule, Error> {
+        match self.booking_rules.get(id) {
+            Some(booking_rule) => Ok(booking_rule),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_location_group<'a>(&'a self, id: &str) -> Result<&'a LocationGroup, Error> {
+        match self.location_groups.get(id) {
+            Some(location_group) => Ok(location_group),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    /// Stops of a location group, in the order of location_group_stops.txt
+    ///
+    /// The unknown stops are skipped.
+    pub fn stops_in_location_group(&self, group_id: &str) -> Vec<Arc<Stop>> {
+        self.location_group_stops
+            .get(group_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|stop_id| self.stops.get(stop_id).cloned())
+            .collect()
+    }
+
+    pub fn get_location<'a>(&'a self, id: &str) -> Result<&'a Location, Error> {
+        match self.locations.get(id) {
+            Some(location) => Ok(location),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_pathway<'a>(&'a self, id: &str) -> Result<&'a Pathway, Error> {
+        match self.pathways.get(id) {
+            Some(pathway) => Ok(pathway),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    /// Pathways that can be used to leave the stop, including the bidirectional pathways
+    /// leading to it
+    pub fn pathways_from(&self, stop_id: &str) -> Vec<&Pathway> {
+        self.pathways
+            .values()
+            .filter(|p| {
+                p.from_stop_id == stop_id || p.is_bidirectional && p.to_stop_id == stop_id
+            })
+            .collect()
+    }
+
+    /// Attributions to display with the trip: the ones of the whole feed, and the ones of the trip,
+    /// its route or the agency of its route
+    pub fn attributions_for_trip(&self, trip_id: &str) -> Vec<&Attribution> {
+        let trip = self.trips.get(trip_id);
+        let route = trip.and_then(|trip| self.routes.get(&trip.route_id));
+        let agency_id = route.and_then(|route| route.agency_id.as_deref());
+        self.attributions
+            .iter()
+            .filter(|a| match (&a.agency_id, &a.route_id, &a.trip_id) {
+                (None, None, None) => true,
+                (Some(agency), _, _) => Some(agency.as_str()) == agency_id,
+                (_, Some(route_id), _) => route.is_some_and(|r| &r.id == route_id),
+                (_, _, Some(id)) => trip.is_some_and(|t| &t.id == id),
+            })
+            .collect()
+    }
+
+    /// Transfers leaving the stop, in the order of transfers.txt
+    pub fn transfers_from(&self, stop_id: &str) -> &[Transfer] {
+        self.transfers
+            .get(stop_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Fares having a rule for the route, with the matching rules
+    ///
+    /// The rules restricted to zones are included, the zones of the journey must then be checked.
+    pub fn fare_rules_for_route(&self, route_id: &str) -> Vec<(&FareAttribute, Vec<&FareRule>)> {
+        self.fare_attributes
+            .values()
+            .filter_map(|fare| {
+                let rules: Vec<&FareRule> = fare
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.route_id.as_deref() == Some(route_id))
+                    .collect();
+                Some((fare, rules)).filter(|(_, rules)| !rules.is_empty())
+            })
+            .collect()
+    }
+
+    /// The fare products with this id, one for each fare media
+    pub fn get_fare_product<'a>(&'a self, id: &str) -> Result<&'a [FareProduct], Error> {
+        match self.fare_products.get(id) {
+            Some(products) => Ok(products),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_rider_category<'a>(&'a self, id: &str) -> Result<&'a RiderCategory, Error> {
+        match self.rider_categories.get(id) {
+            Some(category) => Ok(category),
+            None => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    /// Rider category to use when the category of the rider is not known
+    pub fn default_rider_category(&self) -> Option<&RiderCategory> {
+        self.rider_categories.values().find(|category| category.is_default)
+    }
+
+    /// Areas containing the stop, ordered by id
+    pub fn areas_of_stop(&self, stop_id: &str) -> Vec<&Area> {
+        self.stop_areas
+            .iter()
+            .filter(|(_, stops)| stops.iter().any(|id| id == stop_id))
+            .filter_map(|(area_id, _)| self.areas.get(area_id))
+            .collect()
+    }
+
     pub fn get_fare_attributes<'a>(&'a self, id: &str) -> Result<&'a FareAttribute, Error> {
         self.fare_attributes
             .get(id)
             .ok_or_else(|| Error::ReferenceError(id.to_owned()))
     }
+
+    /// Interns all the identifiers of the feed (agencies, stops, routes, trips, services and shapes)
+    ///
+    /// The symbols can be used as integer keys in external indexes.
+    /// The ids are sorted within each table, so the numbering only depends on the content of the feed.
+    pub fn string_table(&self) -> StringInterner {
+        let mut interner = StringInterner::new();
+        let mut intern_sorted = |mut ids: Vec<&str>| {
+            ids.sort_unstable();
+            for id in ids {
+                interner.intern(id);
+            }
+        };
+        intern_sorted(self.agencies.iter().map(|a| a.id()).collect());
+        intern_sorted(self.stops.keys().map(|id| id.as_str()).collect());
+        intern_sorted(self.routes.keys().map(|id| id.as_str()).collect());
+        intern_sorted(self.trips.keys().map(|id| id.as_str()).collect());
+        intern_sorted(
+            self.calendar
+                .keys()
+                .chain(self.calendar_dates.keys())
+                .map(|id| id.as_str())
+                .collect(),
+        );
+        intern_sorted(self.shapes.keys().map(|id| id.as_str()).collect());
+        interner
+    }
 }
 
-fn to_map<O: Id>(elements: impl IntoIterator<Item = O>) -> HashMap<String, O> {
+/// Stable id of an agency without agency_id: the words of its name in lowercase,
+/// separated by underscores
+fn single_agency_id(name: &str) -> String {
+    let id = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if id.is_empty() {
+        "agency".to_owned()
+    } else {
+        id
+    }
+}
+
+fn insert_route_stops(
+    routes_by_stop: &mut BTreeMap<String, BTreeSet<String>>,
+    stops_by_route: &mut BTreeMap<String, BTreeSet<String>>,
+    route_id: &str,
+    stop_times: &[StopTime],
+) {
+    // the stop times of the flexible trips can be in a location instead of a stop
+    for stop_time in stop_times.iter().filter(|st| !st.stop.id.is_empty()) {
+        routes_by_stop
+            .entry(stop_time.stop.id.clone())
+            .or_default()
+            .insert(route_id.to_owned());
+        stops_by_route
+            .entry(route_id.to_owned())
+            .or_default()
+            .insert(stop_time.stop.id.clone());
+    }
+}
+
+fn to_map<O: Id>(elements: impl IntoIterator<Item = O>) -> BTreeMap<String, O> {
     elements
         .into_iter()
         .map(|e| (e.id().to_owned(), e))
         .collect()
 }
 
-fn to_stop_map(stops: Vec<Stop>) -> HashMap<String, Arc<Stop>> {
+/// Groups the objects by a key, keeping their order in the file
+fn to_groups<O, F>(objects: Vec<O>, key: F) -> BTreeMap<String, Vec<O>>
+where
+    F: Fn(&O) -> String,
+{
+    let mut res: BTreeMap<String, Vec<O>> = BTreeMap::new();
+    for o in objects {
+        res.entry(key(&o)).or_default().push(o);
+    }
+    res
+}
+
+fn to_fare_map(
+    fare_attributes: Vec<FareAttribute>,
+    fare_rules: Vec<FareRule>,
+) -> Result<BTreeMap<String, FareAttribute>, Error> {
+    let mut fares = to_map(fare_attributes);
+    for rule in fare_rules {
+        fares
+            .get_mut(&rule.fare_id)
+            .ok_or_else(|| Error::ReferenceError(rule.fare_id.to_string()))?
+            .rules
+            .push(rule);
+    }
+    Ok(fares)
+}
+
+fn to_stop_map(
+    stops: Vec<Stop>,
+    levels: &BTreeMap<String, Arc<Level>>,
+) -> BTreeMap<String, Arc<Stop>> {
     stops
         .into_iter()
-        .map(|s| (s.id.clone(), Arc::new(s)))
+        .map(|mut s| {
+            s.level = s.level_id.as_ref().and_then(|id| levels.get(id)).cloned();
+            (s.id.clone(), Arc::new(s))
+        })
         .collect()
 }
 
-fn to_shape_map(shapes: Vec<Shape>) -> HashMap<String, Vec<Shape>> {
-    let mut res = HashMap::default();
+fn to_shape_map(shapes: Vec<Shape>) -> BTreeMap<String, Vec<Shape>> {
+    let mut res = BTreeMap::default();
     for s in shapes {
         let shape = res.entry(s.id.to_owned()).or_insert_with(Vec::new);
         shape.push(s);
     }
     // we sort the shape by it's pt_sequence
-    for (_key, shapes) in &mut res {
+    for shapes in res.values_mut() {
         shapes.sort_by_key(|s| s.sequence);
     }
 
     res
 }
 
-fn to_calendar_dates(cd: Vec<CalendarDate>) -> HashMap<String, Vec<CalendarDate>> {
-    let mut res = HashMap::default();
+fn to_calendar_dates(cd: Vec<CalendarDate>) -> BTreeMap<String, Vec<CalendarDate>> {
+    let mut res = BTreeMap::default();
     for c in cd {
         let cal = res.entry(c.service_id.to_owned()).or_insert_with(Vec::new);
         cal.push(c);
@@ -284,16 +1339,40 @@ fn to_calendar_dates(cd: Vec<CalendarDate>) -> HashMap<String, Vec<CalendarDate>
     res
 }
 
+fn to_transfer_map(
+    transfers: Vec<Transfer>,
+    stops: &BTreeMap<String, Arc<Stop>>,
+) -> Result<BTreeMap<String, Vec<Transfer>>, Error> {
+    let mut res: BTreeMap<String, Vec<Transfer>> = BTreeMap::new();
+    for transfer in transfers {
+        for stop_id in &[&transfer.from_stop_id, &transfer.to_stop_id] {
+            if !stops.contains_key(*stop_id) {
+                return Err(Error::ReferenceError(stop_id.to_string()));
+            }
+        }
+        res.entry(transfer.from_stop_id.to_owned())
+            .or_default()
+            .push(transfer);
+    }
+    Ok(res)
+}
+
 fn create_trips(
     raw_trips: Vec<RawTrip>,
     raw_stop_times: Vec<RawStopTime>,
-    stops: &HashMap<String, Arc<Stop>>,
-) -> Result<HashMap<String, Trip>, Error> {
+    raw_frequencies: Vec<RawFrequency>,
+    stops: &mut BTreeMap<String, Arc<Stop>>,
+    options: &GtfsReaderOptions,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<BTreeMap<String, Trip>, Error> {
     let mut trips = to_map(raw_trips.into_iter().map(|rt| Trip {
         id: rt.id,
         service_id: rt.service_id,
         route_id: rt.route_id,
+        route: None,
+        calendar: None,
         stop_times: vec![],
+        frequencies: vec![],
         shape_id: rt.shape_id,
         trip_headsign: rt.trip_headsign,
         trip_short_name: rt.trip_short_name,
@@ -301,47 +1380,165 @@ fn create_trips(
         block_id: rt.block_id,
         wheelchair_accessible: rt.wheelchair_accessible,
         bikes_allowed: rt.bikes_allowed,
+        #[cfg(feature = "extensions")]
+        vehicle_type: rt.vehicle_type,
+        #[cfg(feature = "extensions")]
+        cars_count: rt.cars_count,
+        #[cfg(feature = "extensions")]
+        capacity: rt.capacity,
     }));
+    let placeholder = Arc::new(Stop::default());
     for s in raw_stop_times {
-        let trip = &mut trips
-            .get_mut(&s.trip_id)
-            .ok_or(Error::ReferenceError(s.trip_id.to_string()))?;
-        let stop = stops
-            .get(&s.stop_id)
-            .ok_or(Error::ReferenceError(s.stop_id.to_string()))?;
-        trip.stop_times.push(StopTime::from(&s, Arc::clone(&stop)));
+        let trip = match trips.get_mut(&s.trip_id) {
+            Some(trip) => trip,
+            None if options.lenient => {
+                let sequence = Some(s.stop_sequence);
+                warnings.push(unknown_trip_warning("stop_times.txt", sequence, &s.trip_id));
+                continue;
+            }
+            None => return Err(Error::ReferenceError(s.trip_id.to_string())),
+        };
+        if !serves_location(&s) && !stops.contains_key(&s.stop_id) {
+            match options.unknown_stops {
+                UnknownStopPolicy::Error => {}
+                UnknownStopPolicy::Skip => {
+                    warnings.push(ParseWarning {
+                        file_name: "stop_times.txt".to_owned(),
+                        line: None,
+                        column: None,
+                        message: format!(
+                            "the stop time {} of the trip '{}' references the unknown stop '{}', \
+                             it is skipped",
+                            s.stop_sequence, s.trip_id, s.stop_id
+                        ),
+                    });
+                    continue;
+                }
+                UnknownStopPolicy::Placeholder => {
+                    stops.insert(s.stop_id.clone(), placeholder_stop(s.stop_id.clone()));
+                }
+            }
+        }
+        let stop = stop_of_stop_time(&s, stops, &placeholder)?;
+        trip.stop_times.push(StopTime::from(&s, stop));
+    }
+
+    for f in raw_frequencies {
+        let trip = match trips.get_mut(&f.trip_id) {
+            Some(trip) => trip,
+            None if options.lenient => {
+                warnings.push(unknown_trip_warning("frequencies.txt", None, &f.trip_id));
+                continue;
+            }
+            None => return Err(Error::ReferenceError(f.trip_id.to_string())),
+        };
+        trip.frequencies.push(Frequency::from(&f));
     }
 
     for trip in &mut trips.values_mut() {
         trip.stop_times
-            .sort_by(|a, b| a.stop_sequence.cmp(&b.stop_sequence));
+            .sort_by_key(|a| a.stop_sequence);
     }
     Ok(trips)
 }
 
-fn create_translations(
-    raw_translations: Vec<Translation>
-) -> Result<(
+/// Warning for a row of a trip that is not in trips.txt, skipped in the lenient mode
+fn unknown_trip_warning(
+    file_name: &str,
+    stop_sequence: Option<u16>,
+    trip_id: &str,
+) -> ParseWarning {
+    let row = match stop_sequence {
+        Some(sequence) => format!("the stop time {}", sequence),
+        None => "a row".to_owned(),
+    };
+    ParseWarning {
+        file_name: file_name.to_owned(),
+        line: None,
+        column: None,
+        message: format!("{} references the unknown trip '{}', it is skipped", row, trip_id),
+    }
+}
+
+/// Tells if a stop time serves a GTFS-Flex location or location group instead of a stop
+pub(crate) fn serves_location(stop_time: &RawStopTime) -> bool {
+    stop_time.stop_id.is_empty()
+        && (stop_time.location_id.is_some() || stop_time.location_group_id.is_some())
+}
+
+/// Stop created for the stop times referencing a stop that is not in stops.txt, see
+/// [UnknownStopPolicy::Placeholder]
+fn placeholder_stop(stop_id: String) -> Arc<Stop> {
+    Arc::new(Stop {
+        id: stop_id,
+        missing: true,
+        ..Stop::default()
+    })
+}
+
+/// Stop of a stop time, the stop times serving a location get the placeholder stop
+pub(crate) fn stop_of_stop_time(
+    stop_time: &RawStopTime,
+    stops: &BTreeMap<String, Arc<Stop>>,
+    placeholder: &Arc<Stop>,
+) -> Result<Arc<Stop>, Error> {
+    if serves_location(stop_time) {
+        return Ok(Arc::clone(placeholder));
+    }
+    stops
+        .get(&stop_time.stop_id)
+        .cloned()
+        .ok_or_else(|| Error::ReferenceError(stop_time.stop_id.to_string()))
+}
+
+/// Translations of a table for a batch of records, see [Gtfs::translate_many]
+///
+/// There is one map for each language, by order of preference.
+struct TranslationBatch<'a> {
+    /// (record_id, field_name) → translation
+    by_id: Vec<HashMap<(&'a str, &'a str), &'a str>>,
+    /// (field_name, field_value) → translation
+    by_value: Vec<HashMap<(&'a str, &'a str), &'a str>>,
+}
+
+impl<'a> TranslationBatch<'a> {
+    fn get(&self, record_id: &str, field_name: &str, field_value: &str) -> String {
+        self.by_id
+            .iter()
+            .zip(&self.by_value)
+            .find_map(|(by_id, by_value)| {
+                by_id
+                    .get(&(record_id, field_name))
+                    .or_else(|| by_value.get(&(field_name, field_value)))
+            })
+            .map_or(field_value, |translation| *translation)
+            .to_owned()
+    }
+}
+
+type TranslationMaps = (
     HashMap<TranslationByIdKey, String>,
-    HashMap<TranslationByValueKey, String>
-), Error> {
+    HashMap<TranslationByValueKey, String>,
+);
+
+fn create_translations(raw_translations: Vec<Translation>) -> Result<TranslationMaps, Error> {
     let mut translations_by_id = HashMap::new();
     let mut translations_by_value = HashMap::new();
 
     for translation_enum in raw_translations {
         match translation_enum {
             Translation::Gtfs(translation) => {
-                if translation.record_id.is_some() {
+                if let Some(record_id) = translation.record_id {
                     // Make sure it is not forbidden
                     if translation.field_value.is_some() ||
-                        translation.table_name == "feed_info".to_string() {
+                        translation.table_name == "feed_info" {
                         return Err(Error::InvalidTranslation(
                                 "record_id was defined when it was forbidden".to_string()
                         ));
                     }
 
                     // Make sure record_sub_id is there if and only if it is required
-                    if translation.table_name == "stop_times".to_string() &&
+                    if translation.table_name == "stop_times" &&
                         translation.record_sub_id.is_none() {
                         return Err(Error::InvalidTranslation(
                                 "record_sub_id was not set when it was required".to_string()
@@ -352,14 +1549,14 @@ fn create_translations(
                         table_name: translation.table_name,
                         field_name: translation.field_name,
                         language: translation.language,
-                        record_id: translation.record_id.unwrap(),
+                        record_id,
                         record_sub_id: translation.record_sub_id,
                     }, translation.translation);
-                } else if translation.field_value.is_some() {
+                } else if let Some(field_value) = translation.field_value {
                     // Make sure it is not forbidden
                     if translation.record_id.is_some() ||
                         translation.record_sub_id.is_some() ||
-                        translation.table_name == "feed_info".to_string() {
+                        translation.table_name == "feed_info" {
                         return Err(Error::InvalidTranslation(
                                 "field_value was defined when it was forbidden".to_string()
                         ));
@@ -369,7 +1566,16 @@ fn create_translations(
                         table_name: translation.table_name,
                         field_name: translation.field_name,
                         language: translation.language,
-                        field_value: translation.field_value.unwrap(),
+                        field_value,
+                    }, translation.translation);
+                } else if translation.table_name == "feed_info" {
+                    // the only row of feed_info.txt is given by an empty record_id
+                    translations_by_id.insert(TranslationByIdKey {
+                        table_name: translation.table_name,
+                        field_name: translation.field_name,
+                        language: translation.language,
+                        record_id: String::new(),
+                        record_sub_id: None,
                     }, translation.translation);
                 }
             }
@@ -390,5 +1596,5 @@ fn create_translations(
         }
     }
 
-    return Ok((translations_by_id, translations_by_value));
+    Ok((translations_by_id, translations_by_value))
 }
@@ -0,0 +1,321 @@
+//! Columns of the csv files and the fields they are read into, to find why a column is ignored
+
+use crate::objects::*;
+use crate::raw_gtfs::skip_bom;
+use crate::{Error, RawGtfs};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use std::fs::File;
+use std::path::Path;
+
+/// Columns read from translations.txt, which are not fields of a structure as their names
+/// depend on the dialect of the file
+const TRANSLATION_COLUMNS: &[&str] = &[
+    "table_name",
+    "field_name",
+    "language",
+    "lang",
+    "translation",
+    "record_id",
+    "record_sub_id",
+    "field_value",
+    "trans_id",
+];
+
+/// How a column of a file is read, see [HeaderMapping]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    /// The header of the column in the file
+    pub header: String,
+    /// The field the column is read into, None when the column is ignored
+    pub field: Option<&'static str>,
+    /// For an ignored column, the field with a close name that is not in the file, as the
+    /// header is probably misspelled
+    pub suggestion: Option<&'static str>,
+}
+
+/// The columns of a file and the fields of the structure they are read into, see
+/// [RawGtfs::header_mapping]
+///
+/// The fields are named like the columns of the specification (for instance stop_id for
+/// [Stop::id]), so a typo or a different case in a header makes the column ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderMapping {
+    pub file_name: String,
+    /// The structure a row is read into, like "Stop", None for a file not read by this crate
+    pub structure: Option<&'static str>,
+    /// The columns, in the order of the file
+    pub columns: Vec<ColumnMapping>,
+    /// The fields without column in the file
+    pub missing: Vec<&'static str>,
+}
+
+impl HeaderMapping {
+    /// Maps the headers of a file to the fields of the structure its rows are read into
+    pub fn new(file_name: &str, headers: &[String]) -> Self {
+        let (structure, fields) = match fields_of_file(file_name) {
+            Some((structure, fields)) => (Some(structure), fields),
+            None => (None, &[][..]),
+        };
+        let missing: Vec<&'static str> = fields
+            .iter()
+            .filter(|field| !headers.iter().any(|h| h == *field))
+            .copied()
+            .collect();
+        let columns = headers
+            .iter()
+            .map(|header| {
+                let field = fields.iter().find(|field| *field == header).copied();
+                ColumnMapping {
+                    header: header.clone(),
+                    field,
+                    suggestion: match field {
+                        Some(_) => None,
+                        None => closest(header, &missing),
+                    },
+                }
+            })
+            .collect();
+        Self {
+            file_name: file_name.to_owned(),
+            structure,
+            columns,
+            missing,
+        }
+    }
+
+    /// The columns that are not read
+    pub fn ignored(&self) -> impl Iterator<Item = &ColumnMapping> {
+        self.columns.iter().filter(|column| column.field.is_none())
+    }
+}
+
+impl std::fmt::Display for HeaderMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.structure {
+            Some(structure) => writeln!(f, "{} is read into {}", self.file_name, structure)?,
+            None => writeln!(f, "{} is not read", self.file_name)?,
+        }
+        for column in &self.columns {
+            match (column.field, column.suggestion) {
+                (Some(field), _) => writeln!(f, "  {:?} → {}", column.header, field)?,
+                (None, Some(suggestion)) => writeln!(
+                    f,
+                    "  {:?} is ignored, did you mean {:?}?",
+                    column.header, suggestion
+                )?,
+                (None, None) => writeln!(f, "  {:?} is ignored", column.header)?,
+            }
+        }
+        if !self.missing.is_empty() {
+            writeln!(f, "  fields without column: {}", self.missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Something done with the structure the rows of a file are read into, see [with_structure]
+pub(crate) trait StructureVisitor {
+    type Output;
+
+    fn visit<T: DeserializeOwned>(self) -> Self::Output;
+}
+
+/// Calls the visitor with the structure read from a file, None for a file not read by this
+/// crate
+pub(crate) fn with_structure<V: StructureVisitor>(file_name: &str, v: V) -> Option<V::Output> {
+    Some(match file_name {
+        "agency.txt" => v.visit::<Agency>(),
+        "stops.txt" => v.visit::<Stop>(),
+        "routes.txt" => v.visit::<Route>(),
+        "trips.txt" => v.visit::<RawTrip>(),
+        "stop_times.txt" => v.visit::<RawStopTime>(),
+        "calendar.txt" => v.visit::<Calendar>(),
+        "calendar_dates.txt" => v.visit::<CalendarDate>(),
+        "frequencies.txt" => v.visit::<RawFrequency>(),
+        "transfers.txt" => v.visit::<Transfer>(),
+        "pathways.txt" => v.visit::<Pathway>(),
+        "levels.txt" => v.visit::<Level>(),
+        "shapes.txt" => v.visit::<Shape>(),
+        "fare_attributes.txt" => v.visit::<FareAttribute>(),
+        "fare_rules.txt" => v.visit::<FareRule>(),
+        "fare_media.txt" => v.visit::<FareMedia>(),
+        "rider_categories.txt" => v.visit::<RiderCategory>(),
+        "fare_containers.txt" => v.visit::<FareContainer>(),
+        "fare_products.txt" => v.visit::<FareProduct>(),
+        "fare_leg_rules.txt" => v.visit::<FareLegRule>(),
+        "fare_transfer_rules.txt" => v.visit::<FareTransferRule>(),
+        "areas.txt" => v.visit::<Area>(),
+        "stop_areas.txt" => v.visit::<StopArea>(),
+        "networks.txt" => v.visit::<Network>(),
+        "route_networks.txt" => v.visit::<RouteNetwork>(),
+        "timeframes.txt" => v.visit::<Timeframe>(),
+        "feed_info.txt" => v.visit::<FeedInfo>(),
+        "translations.txt" => v.visit::<Translation>(),
+        "attributions.txt" => v.visit::<Attribution>(),
+        "booking_rules.txt" => v.visit::<BookingRule>(),
+        "location_groups.txt" => v.visit::<LocationGroup>(),
+        "location_group_stops.txt" => v.visit::<LocationGroupStop>(),
+        _ => return None,
+    })
+}
+
+/// The structure read from a file and its fields
+pub(crate) fn fields_of_file(file_name: &str) -> Option<(&'static str, &'static [&'static str])> {
+    match file_name {
+        "translations.txt" => Some(("Translation", TRANSLATION_COLUMNS)),
+        _ => with_structure(file_name, Fields)?,
+    }
+}
+
+struct Fields;
+
+impl StructureVisitor for Fields {
+    type Output = Option<(&'static str, &'static [&'static str])>;
+
+    fn visit<T: DeserializeOwned>(self) -> Self::Output {
+        fields::<T>()
+    }
+}
+
+/// The name and the fields of a structure deserialized by serde
+///
+/// The deserializer of a structure gives them to [Deserializer::deserialize_struct], so a
+/// deserializer stopping there finds them without reading anything.
+fn fields<T: DeserializeOwned>() -> Option<(&'static str, &'static [&'static str])> {
+    let mut found = None;
+    let _ = T::deserialize(FieldNames { found: &mut found });
+    found
+}
+
+struct FieldNames<'a> {
+    found: &'a mut Option<(&'static str, &'static [&'static str])>,
+}
+
+/// Stops the deserialization once the fields are known
+#[derive(Debug)]
+struct Stopped;
+
+impl std::fmt::Display for Stopped {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "not a structure")
+    }
+}
+
+impl std::error::Error for Stopped {}
+
+impl de::Error for Stopped {
+    fn custom<T: std::fmt::Display>(_: T) -> Self {
+        Stopped
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldNames<'a> {
+    type Error = Stopped;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Stopped> {
+        Err(Stopped)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Stopped> {
+        *self.found = Some((name, fields));
+        Err(Stopped)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// The field the header was probably meant to be: the same name with another case or
+/// spaces around it, or a name differing by at most 2 characters
+fn closest(header: &str, fields: &[&'static str]) -> Option<&'static str> {
+    let normalized = header.trim().to_lowercase();
+    if let Some(field) = fields.iter().find(|field| **field == normalized) {
+        return Some(field);
+    }
+    fields
+        .iter()
+        .map(|field| (edit_distance(&normalized, field), *field))
+        .filter(|(distance, field)| *distance <= 2 && *distance < field.len() / 2)
+        .min()
+        .map(|(_, field)| field)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn read_headers<T: std::io::Read>(reader: T, file_name: &str) -> Result<Vec<String>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(skip_bom(reader, file_name)?);
+    let headers = reader.headers().map_err(|e| Error::CSVError {
+        file_name: file_name.to_owned(),
+        source: e,
+        line_in_error: None,
+    })?;
+    Ok(headers.iter().map(str::to_owned).collect())
+}
+
+impl RawGtfs {
+    /// The headers of a csv file of a local zip archive or local directory, as they are
+    /// written in the file
+    ///
+    /// In a zip archive, the file can be in a subdirectory.
+    pub fn headers<P: AsRef<Path>>(path: P, file_name: &str) -> Result<Vec<String>, Error> {
+        let p = path.as_ref();
+        let missing = || Error::MissingFile(format!("Could not find file: {}", file_name));
+        if p.is_file() {
+            let mut archive = zip::ZipArchive::new(File::open(p)?)?;
+            let index = (0..archive.len())
+                .find(|i| {
+                    archive.by_index(*i).is_ok_and(|file| {
+                        Path::new(file.name()).file_name() == Some(file_name.as_ref())
+                    })
+                })
+                .ok_or_else(missing)?;
+            let headers = read_headers(archive.by_index(index)?, file_name);
+            headers
+        } else if p.is_dir() {
+            let file = File::open(p.join(file_name)).map_err(|_| missing())?;
+            read_headers(file, file_name)
+        } else {
+            Err(Error::NotFileNorDirectory(format!("{}", p.display())))
+        }
+    }
+
+    /// The columns of a csv file of a local zip archive or local directory, with the fields
+    /// they are read into, see [HeaderMapping]
+    ///
+    /// ```
+    /// let mapping = gtfs_structures::RawGtfs::header_mapping("fixtures/basic", "stops.txt")
+    ///     .unwrap();
+    /// assert_eq!(Some("Stop"), mapping.structure);
+    /// assert_eq!(0, mapping.ignored().count());
+    /// ```
+    pub fn header_mapping<P: AsRef<Path>>(
+        path: P,
+        file_name: &str,
+    ) -> Result<HeaderMapping, Error> {
+        let headers = Self::headers(path, file_name)?;
+        Ok(HeaderMapping::new(file_name, &headers))
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+
+/// Kind of holiday of a date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HolidayKind {
+    /// Bank holiday, on which a reduced service is usually expected
+    Public,
+    /// School vacations, often served by a specific variant of the timetable
+    School,
+}
+
+/// Source of the holidays of the area covered by a feed
+///
+/// GTFS has no notion of holidays, so a provider can be given to the helpers that need one,
+/// like [crate::Gtfs::describe_service].
+pub trait HolidayProvider {
+    /// Returns the kind of holiday of a date, or None if it is a normal day
+    fn holiday(&self, date: NaiveDate) -> Option<HolidayKind>;
+}
+
+/// Provider without any holiday
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoHolidays;
+
+impl HolidayProvider for NoHolidays {
+    fn holiday(&self, _date: NaiveDate) -> Option<HolidayKind> {
+        None
+    }
+}
+
+/// Provider with a fixed list of holidays
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use gtfs_structures::{FixedHolidays, HolidayKind, HolidayProvider};
+///
+/// let holidays = FixedHolidays::new()
+///     .yearly(12, 25, HolidayKind::Public)
+///     .period(
+///         NaiveDate::from_ymd_opt(2024, 2, 10).unwrap(),
+///         NaiveDate::from_ymd_opt(2024, 2, 25).unwrap(),
+///         HolidayKind::School,
+///     );
+/// assert_eq!(
+///     Some(HolidayKind::Public),
+///     holidays.holiday(NaiveDate::from_ymd_opt(2023, 12, 25).unwrap())
+/// );
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct FixedHolidays {
+    /// Holidays happening every year, by (month, day)
+    yearly: BTreeMap<(u32, u32), HolidayKind>,
+    /// Holidays of a given year, they take precedence over the yearly ones
+    dates: BTreeMap<NaiveDate, HolidayKind>,
+}
+
+impl FixedHolidays {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a holiday happening every year on the same day
+    pub fn yearly(mut self, month: u32, day: u32, kind: HolidayKind) -> Self {
+        self.yearly.insert((month, day), kind);
+        self
+    }
+
+    /// Adds a holiday on a single date
+    pub fn date(mut self, date: NaiveDate, kind: HolidayKind) -> Self {
+        self.dates.insert(date, kind);
+        self
+    }
+
+    /// Adds a holiday on every date from start to end (included)
+    pub fn period(mut self, start: NaiveDate, end: NaiveDate, kind: HolidayKind) -> Self {
+        for date in start.iter_days().take_while(|date| *date <= end) {
+            self.dates.insert(date, kind);
+        }
+        self
+    }
+}
+
+impl HolidayProvider for FixedHolidays {
+    fn holiday(&self, date: NaiveDate) -> Option<HolidayKind> {
+        self.dates
+            .get(&date)
+            .or_else(|| self.yearly.get(&(date.month(), date.day())))
+            .copied()
+    }
+}
@@ -0,0 +1,211 @@
+//! iCalendar export of the trips and of the departures from a stop, for personal calendars
+
+use crate::gtfs::local_instant;
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use std::ops::RangeInclusive;
+
+/// Properties of a VEVENT, as (name, value) with the value already escaped if it is a text
+type Event = Vec<(&'static str, String)>;
+
+/// Escapes the characters with a special meaning in the text values
+fn text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits a content line in lines of at most 75 bytes, the next lines starting with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+/// Time of a stop time of a service day, in UTC
+fn utc_time<Tz: TimeZone>(tz: &Tz, service_date: NaiveDate, time: i64) -> Option<String> {
+    let local = service_date.and_hms_opt(0, 0, 0)? + Duration::seconds(time);
+    let instant = local_instant(tz, local).naive_utc();
+    Some(instant.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn calendar(events: Vec<Event>) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//gtfs-structures//EN".to_owned(),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.extend(
+            event
+                .into_iter()
+                .map(|(name, value)| fold(&format!("{}:{}", name, value))),
+        );
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+    lines.join("\r\n") + "\r\n"
+}
+
+fn route_name(route: Option<&Route>) -> &str {
+    match route {
+        Some(route) if !route.short_name.is_empty() => &route.short_name,
+        Some(route) => &route.long_name,
+        None => "",
+    }
+}
+
+/// Time of the hours and minutes of a stop time, like 25:10
+fn hours_minutes(time: i64) -> String {
+    format!("{:02}:{:02}", time / 3600, time % 3600 / 60)
+}
+
+impl Gtfs {
+    /// iCalendar file of a trip, with an event from its first departure to its last arrival
+    /// on each day of the range when it runs
+    ///
+    /// A trip repeated by frequencies gives an event for each departure. The times of the
+    /// feed are read in the timezone `tz` (usually the one of the agency, see
+    /// [Gtfs::elapsed_between]) and written in UTC, so the calendar applications show them
+    /// in the timezone of the user. The description lists the stops with their times.
+    pub fn trip_to_ical<Tz: TimeZone>(
+        &self,
+        trip_id: &str,
+        dates: RangeInclusive<NaiveDate>,
+        tz: &Tz,
+    ) -> Result<String, Error> {
+        let trip = self.get_trip(trip_id)?;
+        let stop_times = self.trip_stop_times(trip_id)?;
+        let times: Vec<(i64, &StopTime)> = stop_times
+            .iter()
+            .filter_map(|st| Some((st.departure_time.or(st.arrival_time)? as i64, st)))
+            .collect();
+        let last = stop_times
+            .iter()
+            .rev()
+            .find_map(|st| st.arrival_time.or(st.departure_time));
+        let (first, last) = match (times.first(), last) {
+            (Some((first, _)), Some(last)) => (*first, last as i64),
+            _ => return Ok(calendar(vec![])),
+        };
+        let shifts: Vec<i64> = match trip.frequencies.is_empty() {
+            true => vec![0],
+            false => trip
+                .frequencies
+                .iter()
+                .flat_map(|f| (f.start_time..f.end_time).step_by(f.headway_secs.max(1) as usize))
+                .map(|departure| departure as i64 - first)
+                .collect(),
+        };
+        let route = route_name(trip.route.as_deref());
+        let summary = format!(
+            "{} {}",
+            route,
+            trip.trip_headsign.as_deref().unwrap_or_default()
+        );
+
+        let mut events = Vec::new();
+        let mut date = *dates.start();
+        while date <= *dates.end() {
+            if self.operating_days.is_active(&trip.service_id, date) {
+                for shift in &shifts {
+                    let description: Vec<String> = times
+                        .iter()
+                        .map(|(time, st)| {
+                            format!("{} {}", hours_minutes(time + shift), st.stop.name)
+                        })
+                        .collect();
+                    let (start, end) = match (
+                        utc_time(tz, date, first + shift),
+                        utc_time(tz, date, last + shift),
+                    ) {
+                        (Some(start), Some(end)) => (start, end),
+                        _ => continue,
+                    };
+                    events.push(vec![
+                        (
+                            "UID",
+                            text(&format!(
+                                "{}-{}-{}@gtfs-structures",
+                                trip.id,
+                                date.format("%Y%m%d"),
+                                first + shift
+                            )),
+                        ),
+                        ("DTSTART", start),
+                        ("DTEND", end),
+                        ("SUMMARY", text(summary.trim())),
+                        ("LOCATION", text(&stop_times[0].stop.name)),
+                        ("DESCRIPTION", text(&description.join("\n"))),
+                    ]);
+                }
+            }
+            date += Duration::days(1);
+        }
+        Ok(calendar(events))
+    }
+
+    /// iCalendar file of the departures from a stop on each day of the range, see
+    /// [Gtfs::origin_departures]
+    ///
+    /// Each departure is an event without duration, with the route and the headsign as
+    /// summary, and the stop where the trip is boarded as location. Like for
+    /// [Gtfs::trip_to_ical], the times are read in the timezone `tz`.
+    pub fn stop_departures_to_ical<Tz: TimeZone>(
+        &self,
+        stop_id: &str,
+        dates: RangeInclusive<NaiveDate>,
+        tz: &Tz,
+    ) -> Result<String, Error> {
+        self.get_stop(stop_id)?;
+        let mut events = Vec::new();
+        let mut date = *dates.start();
+        while date <= *dates.end() {
+            for departure in self.origin_departures(stop_id, date, 0..24 * 3600)? {
+                let start = match utc_time(tz, date, departure.departure_time.into()) {
+                    Some(start) => start,
+                    None => continue,
+                };
+                let route = route_name(departure.trip.route.as_deref());
+                let summary = format!("{} {}", route, departure.headsign.unwrap_or_default());
+                let uid = format!(
+                    "{}-{}-{}-{}@gtfs-structures",
+                    departure.trip.id,
+                    departure.service_date.format("%Y%m%d"),
+                    departure.stop.id,
+                    departure.departure_time
+                );
+                events.push(vec![
+                    ("UID", text(&uid)),
+                    ("DTSTART", start),
+                    ("SUMMARY", text(summary.trim())),
+                    ("LOCATION", text(&departure.stop.name)),
+                ]);
+            }
+            date += Duration::days(1);
+        }
+        Ok(calendar(events))
+    }
+}
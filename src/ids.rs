@@ -0,0 +1,181 @@
+//! Generation of the ids of the objects created by the builder and the mutation methods
+
+use crate::objects::*;
+use crate::Gtfs;
+use sha2::digest::Digest;
+use sha2::Sha256;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+
+/// Strategy giving the ids of the new objects, see [Gtfs::id_generator]
+///
+/// The ids can follow the conventions of an organization, like a prefix or UUIDs. The
+/// caller checks that a generated id is not used yet, and asks for another one otherwise,
+/// so a generator must not give the same id forever.
+pub trait IdGenerator: std::fmt::Debug + Send + Sync {
+    /// A new id for an object of the type
+    ///
+    /// `base` is the id of the object the new one is derived from, like the trip split by
+    /// [Gtfs::split_trips_at_midnight]. It is empty for an object created from scratch.
+    fn generate(&mut self, object_type: ObjectType, base: &str) -> String;
+}
+
+/// Ids made of a prefix and a number, counted separately for each type of object
+///
+/// ```
+/// use gtfs_structures::{IdGenerator, ObjectType, SequentialIds};
+///
+/// let mut ids = SequentialIds::new("tag:");
+/// assert_eq!("tag:1", ids.generate(ObjectType::Stop, ""));
+/// assert_eq!("tag:2", ids.generate(ObjectType::Stop, ""));
+/// assert_eq!("tag:1", ids.generate(ObjectType::Trip, "trip_a"));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SequentialIds {
+    prefix: String,
+    counters: HashMap<ObjectType, u64>,
+}
+
+impl SequentialIds {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_owned(),
+            counters: HashMap::new(),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn generate(&mut self, object_type: ObjectType, _base: &str) -> String {
+        let counter = self.counters.entry(object_type).or_insert(0);
+        *counter += 1;
+        format!("{}{}", self.prefix, counter)
+    }
+}
+
+/// Ids made of a prefix and a hash of the id of the original object
+///
+/// The same objects derived in the same order always get the same ids, so the ids stay
+/// stable when a feed is transformed again.
+#[derive(Debug, Default, Clone)]
+pub struct HashIds {
+    prefix: String,
+    /// Number of ids already given for each type and base
+    given: HashMap<(ObjectType, String), u64>,
+}
+
+impl HashIds {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_owned(),
+            given: HashMap::new(),
+        }
+    }
+}
+
+impl IdGenerator for HashIds {
+    fn generate(&mut self, object_type: ObjectType, base: &str) -> String {
+        let count = self
+            .given
+            .entry((object_type, base.to_owned()))
+            .or_insert(0);
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:?}\n{}\n{}", object_type, base, count).as_bytes());
+        *count += 1;
+        let hash = format!("{:x}", hasher.finalize());
+        format!("{}{}", self.prefix, &hash[..16])
+    }
+}
+
+/// Random ids, formatted as version 4 UUIDs
+#[derive(Debug, Clone)]
+pub struct UuidIds {
+    state: u64,
+}
+
+impl Default for UuidIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UuidIds {
+    pub fn new() -> Self {
+        // the hasher of the standard library is seeded with random keys
+        Self {
+            state: RandomState::new().build_hasher().finish(),
+        }
+    }
+
+    /// splitmix64 generator
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl IdGenerator for UuidIds {
+    fn generate(&mut self, _object_type: ObjectType, _base: &str) -> String {
+        let (high, low) = (self.next(), self.next());
+        // version 4 and variant 1 bits
+        let high = (high & !0xf000) | 0x4000;
+        let low = (low & !(0b11 << 62)) | (0b10 << 62);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl Gtfs {
+    /// A new id for an object of the type, not used by any object of the feed
+    ///
+    /// The id is given by the [Gtfs::id_generator]. Without generator, the ids are
+    /// sequential numbers prefixed by the type of the object, like "trip_1".
+    pub fn new_id(&mut self, object_type: ObjectType) -> String {
+        let prefix = format!("{:?}_", object_type).to_lowercase();
+        let mut default = SequentialIds::new(&prefix);
+        self.unused_id(object_type, "", |o, b| default.generate(o, b))
+    }
+
+    /// Id of an object derived from another one, given by the [Gtfs::id_generator], or
+    /// the original id with the suffix without generator
+    pub(crate) fn derived_id(
+        &mut self,
+        object_type: ObjectType,
+        base: &str,
+        suffix: &str,
+    ) -> String {
+        if self.id_generator.is_none() {
+            return format!("{}{}", base, suffix);
+        }
+        self.unused_id(object_type, base, |_, base| format!("{}{}", base, suffix))
+    }
+
+    /// Asks the [Gtfs::id_generator], or the default one, for ids until one is not used
+    fn unused_id<F>(&mut self, object_type: ObjectType, base: &str, mut default: F) -> String
+    where
+        F: FnMut(ObjectType, &str) -> String,
+    {
+        let mut generator = self.id_generator.take();
+        let id = loop {
+            let id = match generator.as_mut() {
+                Some(generator) => generator.generate(object_type, base),
+                None => default(object_type, base),
+            };
+            if !self.has_object(object_type, &id) {
+                break id;
+            }
+        };
+        self.id_generator = generator;
+        id
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Integer handle of a string stored in a [StringInterner]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Position of the symbol in its interner, usable as a dense index in external structures
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Table of unique strings, each mapped to a [Symbol]
+///
+/// Each string is stored once, no matter how many times it has been interned.
+#[derive(Debug, Default, Clone)]
+pub struct StringInterner {
+    strings: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the symbol of the string, adding it to the table if needed
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(s) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        let s: Arc<str> = Arc::from(s);
+        self.strings.push(Arc::clone(&s));
+        self.symbols.insert(s, symbol);
+        symbol
+    }
+
+    /// Returns the symbol of an already interned string
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.symbols.get(s).copied()
+    }
+
+    /// Returns the string behind a symbol
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index()).map(|s| s.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Iterates over all the strings, in the order of their symbols
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (Symbol(i as u32), s.as_ref()))
+    }
+
+    /// Estimation in bytes of the heap memory used by the table
+    pub fn heap_size(&self) -> usize {
+        let strings: usize = self.strings.iter().map(|s| s.len()).sum();
+        strings
+            + self.strings.capacity() * std::mem::size_of::<Arc<str>>()
+            + self.symbols.capacity() * std::mem::size_of::<(Arc<str>, Symbol)>()
+    }
+}
@@ -0,0 +1,520 @@
+//! Reading the files of a GTFS only when they are used
+
+use crate::objects::*;
+use crate::raw_gtfs::{
+    read_file, read_locations_file, read_optional_file, CoordinateRecord, GtfsFile, ReadContext,
+    ZipFiles,
+};
+use crate::{Error, GtfsReaderOptions, Location, ParseWarning, RawGtfs};
+use serde::Deserialize;
+use std::cell::{Cell, OnceCell, Ref, RefCell};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Where the files of a [LazyRawGtfs] are read from
+enum FileSource {
+    Directory(PathBuf),
+    Zip(RefCell<ZipFiles<BufReader<File>>>),
+}
+
+/// A [RawGtfs] whose files are parsed the first time they are accessed
+///
+/// Opening the GTFS only lists its files, so a tool that only looks at the stops does not pay
+/// for the parsing of the stop times. Combined with [GtfsReaderOptions::skip_file] and
+/// [GtfsReaderOptions::only_files], the skipped files are never read. The stop times are never
+/// spilled to the disk, [GtfsReaderOptions::spill_stop_times] is ignored.
+///
+/// ```
+/// let gtfs = gtfs_structures::RawGtfs::lazy("fixtures/basic").unwrap();
+/// // only stops.txt is parsed
+/// println!("{} stops", gtfs.stops().as_ref().unwrap().len());
+/// ```
+pub struct LazyRawGtfs {
+    source: FileSource,
+    options: GtfsReaderOptions,
+    files: Vec<String>,
+    warnings: RefCell<Vec<ParseWarning>>,
+    read_duration: Cell<Duration>,
+    agencies: OnceCell<Result<Vec<Agency>, Error>>,
+    stops: OnceCell<Result<Vec<Stop>, Error>>,
+    routes: OnceCell<Result<Vec<Route>, Error>>,
+    trips: OnceCell<Result<Vec<RawTrip>, Error>>,
+    stop_times: OnceCell<Result<Vec<RawStopTime>, Error>>,
+    calendar: OnceCell<Option<Result<Vec<Calendar>, Error>>>,
+    calendar_dates: OnceCell<Option<Result<Vec<CalendarDate>, Error>>>,
+    shapes: OnceCell<Option<Result<Vec<Shape>, Error>>>,
+    fare_attributes: OnceCell<Option<Result<Vec<FareAttribute>, Error>>>,
+    fare_rules: OnceCell<Option<Result<Vec<FareRule>, Error>>>,
+    fare_media: OnceCell<Option<Result<Vec<FareMedia>, Error>>>,
+    fare_products: OnceCell<Option<Result<Vec<FareProduct>, Error>>>,
+    rider_categories: OnceCell<Option<Result<Vec<RiderCategory>, Error>>>,
+    fare_containers: OnceCell<Option<Result<Vec<FareContainer>, Error>>>,
+    fare_leg_rules: OnceCell<Option<Result<Vec<FareLegRule>, Error>>>,
+    fare_transfer_rules: OnceCell<Option<Result<Vec<FareTransferRule>, Error>>>,
+    areas: OnceCell<Option<Result<Vec<Area>, Error>>>,
+    stop_areas: OnceCell<Option<Result<Vec<StopArea>, Error>>>,
+    networks: OnceCell<Option<Result<Vec<Network>, Error>>>,
+    route_networks: OnceCell<Option<Result<Vec<RouteNetwork>, Error>>>,
+    timeframes: OnceCell<Option<Result<Vec<Timeframe>, Error>>>,
+    feed_info: OnceCell<Option<Result<Vec<FeedInfo>, Error>>>,
+    frequencies: OnceCell<Option<Result<Vec<RawFrequency>, Error>>>,
+    transfers: OnceCell<Option<Result<Vec<Transfer>, Error>>>,
+    pathways: OnceCell<Option<Result<Vec<Pathway>, Error>>>,
+    levels: OnceCell<Option<Result<Vec<Level>, Error>>>,
+    translations: OnceCell<Option<Result<Vec<Translation>, Error>>>,
+    attributions: OnceCell<Option<Result<Vec<Attribution>, Error>>>,
+    booking_rules: OnceCell<Option<Result<Vec<BookingRule>, Error>>>,
+    location_groups: OnceCell<Option<Result<Vec<LocationGroup>, Error>>>,
+    location_group_stops: OnceCell<Option<Result<Vec<LocationGroupStop>, Error>>>,
+    locations: OnceCell<Option<Result<Vec<Location>, Error>>>,
+}
+
+impl RawGtfs {
+    /// Opens a local zip archive or local directory, without reading its files yet,
+    /// see [LazyRawGtfs]
+    pub fn lazy<P>(path: P) -> Result<LazyRawGtfs, Error>
+    where
+        P: AsRef<Path> + std::fmt::Display,
+    {
+        Self::lazy_with_options(path, GtfsReaderOptions::default())
+    }
+
+    /// Opens a local zip archive or local directory whose files are read with custom options,
+    /// see [LazyRawGtfs]
+    pub fn lazy_with_options<P>(path: P, options: GtfsReaderOptions) -> Result<LazyRawGtfs, Error>
+    where
+        P: AsRef<Path> + std::fmt::Display,
+    {
+        let p = path.as_ref();
+        let (source, files) = if p.is_file() {
+            let archive = zip::ZipArchive::new(BufReader::new(File::open(p)?))?;
+            let (zip, files) = ZipFiles::new(archive)?;
+            (FileSource::Zip(RefCell::new(zip)), files)
+        } else if p.is_dir() {
+            let files = std::fs::read_dir(p)?
+                .filter_map(|d| d.ok().and_then(|p| p.path().to_str().map(|s| s.to_owned())))
+                .collect();
+            (FileSource::Directory(p.to_owned()), files)
+        } else {
+            return Err(Error::NotFileNorDirectory(format!("{}", p.display())));
+        };
+        Ok(LazyRawGtfs {
+            source,
+            options,
+            files,
+            warnings: RefCell::new(Vec::new()),
+            read_duration: Cell::new(Duration::ZERO),
+            agencies: OnceCell::new(),
+            stops: OnceCell::new(),
+            routes: OnceCell::new(),
+            trips: OnceCell::new(),
+            stop_times: OnceCell::new(),
+            calendar: OnceCell::new(),
+            calendar_dates: OnceCell::new(),
+            shapes: OnceCell::new(),
+            fare_attributes: OnceCell::new(),
+            fare_rules: OnceCell::new(),
+            fare_media: OnceCell::new(),
+            fare_products: OnceCell::new(),
+            rider_categories: OnceCell::new(),
+            fare_containers: OnceCell::new(),
+            fare_leg_rules: OnceCell::new(),
+            fare_transfer_rules: OnceCell::new(),
+            areas: OnceCell::new(),
+            stop_areas: OnceCell::new(),
+            networks: OnceCell::new(),
+            route_networks: OnceCell::new(),
+            timeframes: OnceCell::new(),
+            feed_info: OnceCell::new(),
+            frequencies: OnceCell::new(),
+            transfers: OnceCell::new(),
+            pathways: OnceCell::new(),
+            levels: OnceCell::new(),
+            translations: OnceCell::new(),
+            attributions: OnceCell::new(),
+            booking_rules: OnceCell::new(),
+            location_groups: OnceCell::new(),
+            location_group_stops: OnceCell::new(),
+            locations: OnceCell::new(),
+        })
+    }
+}
+
+impl LazyRawGtfs {
+    /// Reads a file, keeping its warnings and the time spent
+    fn with_context<T>(&self, read: impl FnOnce(&mut ReadContext) -> T) -> T {
+        let start = Instant::now();
+        let mut ctx = ReadContext::new(&self.options);
+        let result = read(&mut ctx);
+        self.warnings.borrow_mut().extend(ctx.warnings);
+        self.read_duration
+            .set(self.read_duration.get() + start.elapsed());
+        result
+    }
+
+    /// Reads a file of the directory or archive with one of the read_file functions
+    fn read_with<O>(
+        &self,
+        file_name: &str,
+        read: impl FnOnce(Option<Result<GtfsFile<'_>, Error>>, &str, &mut ReadContext) -> O,
+    ) -> O {
+        self.with_context(|ctx| match &self.source {
+            FileSource::Directory(p) => {
+                read(GtfsFile::open(p, file_name, &self.options), file_name, ctx)
+            }
+            FileSource::Zip(zip) => {
+                let mut zip = zip.borrow_mut();
+                read(zip.entry(file_name, &self.options), file_name, ctx)
+            }
+        })
+    }
+
+    fn read<O>(&self, file_name: &str) -> Result<Vec<O>, Error>
+    where
+        for<'de> O: Deserialize<'de> + CoordinateRecord,
+    {
+        self.read_with(file_name, read_file)
+    }
+
+    fn read_optional<O>(&self, file_name: &str) -> Option<Result<Vec<O>, Error>>
+    where
+        for<'de> O: Deserialize<'de> + CoordinateRecord,
+    {
+        self.read_with(file_name, read_optional_file)
+    }
+
+    /// The names of the files of the directory or archive
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Problems found so far in the files that were read
+    pub fn warnings(&self) -> Ref<'_, Vec<ParseWarning>> {
+        self.warnings.borrow()
+    }
+
+    /// The agencies of agency.txt, parsed on the first call
+    pub fn agencies(&self) -> &Result<Vec<Agency>, Error> {
+        self.agencies.get_or_init(|| self.read("agency.txt"))
+    }
+
+    /// The stops of stops.txt, parsed on the first call
+    pub fn stops(&self) -> &Result<Vec<Stop>, Error> {
+        self.stops.get_or_init(|| self.read("stops.txt"))
+    }
+
+    /// The routes of routes.txt, parsed on the first call
+    pub fn routes(&self) -> &Result<Vec<Route>, Error> {
+        self.routes.get_or_init(|| self.read("routes.txt"))
+    }
+
+    /// The trips of trips.txt, parsed on the first call
+    pub fn trips(&self) -> &Result<Vec<RawTrip>, Error> {
+        self.trips.get_or_init(|| self.read("trips.txt"))
+    }
+
+    /// The stop times of stop_times.txt, parsed on the first call
+    pub fn stop_times(&self) -> &Result<Vec<RawStopTime>, Error> {
+        self.stop_times.get_or_init(|| self.read("stop_times.txt"))
+    }
+
+    /// The calendars of calendar.txt, parsed on the first call
+    pub fn calendar(&self) -> &Option<Result<Vec<Calendar>, Error>> {
+        self.calendar
+            .get_or_init(|| self.read_optional("calendar.txt"))
+    }
+
+    /// The exceptions of the calendars of calendar_dates.txt, parsed on the first call
+    pub fn calendar_dates(&self) -> &Option<Result<Vec<CalendarDate>, Error>> {
+        self.calendar_dates
+            .get_or_init(|| self.read_optional("calendar_dates.txt"))
+    }
+
+    /// The points of the shapes of shapes.txt, parsed on the first call
+    pub fn shapes(&self) -> &Option<Result<Vec<Shape>, Error>> {
+        self.shapes.get_or_init(|| self.read_optional("shapes.txt"))
+    }
+
+    /// The fare attributes of fare_attributes.txt, parsed on the first call
+    pub fn fare_attributes(&self) -> &Option<Result<Vec<FareAttribute>, Error>> {
+        self.fare_attributes
+            .get_or_init(|| self.read_optional("fare_attributes.txt"))
+    }
+
+    /// The fare rules of fare_rules.txt, parsed on the first call
+    pub fn fare_rules(&self) -> &Option<Result<Vec<FareRule>, Error>> {
+        self.fare_rules
+            .get_or_init(|| self.read_optional("fare_rules.txt"))
+    }
+
+    /// The fare media of fare_media.txt, parsed on the first call
+    pub fn fare_media(&self) -> &Option<Result<Vec<FareMedia>, Error>> {
+        self.fare_media
+            .get_or_init(|| self.read_optional("fare_media.txt"))
+    }
+
+    /// The fare products of fare_products.txt, parsed on the first call
+    pub fn fare_products(&self) -> &Option<Result<Vec<FareProduct>, Error>> {
+        self.fare_products
+            .get_or_init(|| self.read_optional("fare_products.txt"))
+    }
+
+    /// The rider categories of rider_categories.txt, parsed on the first call
+    pub fn rider_categories(&self) -> &Option<Result<Vec<RiderCategory>, Error>> {
+        self.rider_categories
+            .get_or_init(|| self.read_optional("rider_categories.txt"))
+    }
+
+    /// The fare containers of fare_containers.txt, parsed on the first call
+    pub fn fare_containers(&self) -> &Option<Result<Vec<FareContainer>, Error>> {
+        self.fare_containers
+            .get_or_init(|| self.read_optional("fare_containers.txt"))
+    }
+
+    /// The fare leg rules of fare_leg_rules.txt, parsed on the first call
+    pub fn fare_leg_rules(&self) -> &Option<Result<Vec<FareLegRule>, Error>> {
+        self.fare_leg_rules
+            .get_or_init(|| self.read_optional("fare_leg_rules.txt"))
+    }
+
+    /// The fare transfer rules of fare_transfer_rules.txt, parsed on the first call
+    pub fn fare_transfer_rules(&self) -> &Option<Result<Vec<FareTransferRule>, Error>> {
+        self.fare_transfer_rules
+            .get_or_init(|| self.read_optional("fare_transfer_rules.txt"))
+    }
+
+    /// The areas of areas.txt, parsed on the first call
+    pub fn areas(&self) -> &Option<Result<Vec<Area>, Error>> {
+        self.areas.get_or_init(|| self.read_optional("areas.txt"))
+    }
+
+    /// The stops of the areas of stop_areas.txt, parsed on the first call
+    pub fn stop_areas(&self) -> &Option<Result<Vec<StopArea>, Error>> {
+        self.stop_areas
+            .get_or_init(|| self.read_optional("stop_areas.txt"))
+    }
+
+    /// The networks of networks.txt, parsed on the first call
+    pub fn networks(&self) -> &Option<Result<Vec<Network>, Error>> {
+        self.networks
+            .get_or_init(|| self.read_optional("networks.txt"))
+    }
+
+    /// The routes of the networks of route_networks.txt, parsed on the first call
+    pub fn route_networks(&self) -> &Option<Result<Vec<RouteNetwork>, Error>> {
+        self.route_networks
+            .get_or_init(|| self.read_optional("route_networks.txt"))
+    }
+
+    /// The timeframes of timeframes.txt, parsed on the first call
+    pub fn timeframes(&self) -> &Option<Result<Vec<Timeframe>, Error>> {
+        self.timeframes
+            .get_or_init(|| self.read_optional("timeframes.txt"))
+    }
+
+    /// The feed information of feed_info.txt, parsed on the first call
+    pub fn feed_info(&self) -> &Option<Result<Vec<FeedInfo>, Error>> {
+        self.feed_info
+            .get_or_init(|| self.read_optional("feed_info.txt"))
+    }
+
+    /// The frequencies of frequencies.txt, parsed on the first call
+    pub fn frequencies(&self) -> &Option<Result<Vec<RawFrequency>, Error>> {
+        self.frequencies
+            .get_or_init(|| self.read_optional("frequencies.txt"))
+    }
+
+    /// The transfers of transfers.txt, parsed on the first call
+    pub fn transfers(&self) -> &Option<Result<Vec<Transfer>, Error>> {
+        self.transfers
+            .get_or_init(|| self.read_optional("transfers.txt"))
+    }
+
+    /// The pathways of pathways.txt, parsed on the first call
+    pub fn pathways(&self) -> &Option<Result<Vec<Pathway>, Error>> {
+        self.pathways
+            .get_or_init(|| self.read_optional("pathways.txt"))
+    }
+
+    /// The levels of levels.txt, parsed on the first call
+    pub fn levels(&self) -> &Option<Result<Vec<Level>, Error>> {
+        self.levels.get_or_init(|| self.read_optional("levels.txt"))
+    }
+
+    /// The translations of translations.txt, parsed on the first call
+    pub fn translations(&self) -> &Option<Result<Vec<Translation>, Error>> {
+        self.translations
+            .get_or_init(|| self.read_optional("translations.txt"))
+    }
+
+    /// The attributions of attributions.txt, parsed on the first call
+    pub fn attributions(&self) -> &Option<Result<Vec<Attribution>, Error>> {
+        self.attributions
+            .get_or_init(|| self.read_optional("attributions.txt"))
+    }
+
+    /// The booking rules of booking_rules.txt, parsed on the first call
+    pub fn booking_rules(&self) -> &Option<Result<Vec<BookingRule>, Error>> {
+        self.booking_rules
+            .get_or_init(|| self.read_optional("booking_rules.txt"))
+    }
+
+    /// The location groups of location_groups.txt, parsed on the first call
+    pub fn location_groups(&self) -> &Option<Result<Vec<LocationGroup>, Error>> {
+        self.location_groups
+            .get_or_init(|| self.read_optional("location_groups.txt"))
+    }
+
+    /// The stops of the location groups of location_group_stops.txt, parsed on the first call
+    pub fn location_group_stops(&self) -> &Option<Result<Vec<LocationGroupStop>, Error>> {
+        self.location_group_stops
+            .get_or_init(|| self.read_optional("location_group_stops.txt"))
+    }
+
+    /// The GTFS-Flex zones of locations.geojson, parsed on the first call
+    pub fn locations(&self) -> &Option<Result<Vec<Location>, Error>> {
+        self.locations.get_or_init(|| self.read_locations())
+    }
+
+    fn read_locations(&self) -> Option<Result<Vec<Location>, Error>> {
+        self.read_with("locations.geojson", read_locations_file)
+    }
+}
+
+impl From<LazyRawGtfs> for RawGtfs {
+    /// Reads the files that were not accessed yet
+    fn from(mut lazy: LazyRawGtfs) -> Self {
+        let locations = lazy
+            .locations
+            .take()
+            .unwrap_or_else(|| lazy.read_locations());
+        RawGtfs {
+            agencies: lazy
+                .agencies
+                .take()
+                .unwrap_or_else(|| lazy.read("agency.txt")),
+            stops: lazy.stops.take().unwrap_or_else(|| lazy.read("stops.txt")),
+            routes: lazy
+                .routes
+                .take()
+                .unwrap_or_else(|| lazy.read("routes.txt")),
+            trips: lazy.trips.take().unwrap_or_else(|| lazy.read("trips.txt")),
+            stop_times: lazy
+                .stop_times
+                .take()
+                .unwrap_or_else(|| lazy.read("stop_times.txt")),
+            calendar: lazy
+                .calendar
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("calendar.txt")),
+            calendar_dates: lazy
+                .calendar_dates
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("calendar_dates.txt")),
+            shapes: lazy
+                .shapes
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("shapes.txt")),
+            fare_attributes: lazy
+                .fare_attributes
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_attributes.txt")),
+            fare_rules: lazy
+                .fare_rules
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_rules.txt")),
+            fare_media: lazy
+                .fare_media
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_media.txt")),
+            fare_products: lazy
+                .fare_products
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_products.txt")),
+            rider_categories: lazy
+                .rider_categories
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("rider_categories.txt")),
+            fare_containers: lazy
+                .fare_containers
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_containers.txt")),
+            fare_leg_rules: lazy
+                .fare_leg_rules
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_leg_rules.txt")),
+            fare_transfer_rules: lazy
+                .fare_transfer_rules
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("fare_transfer_rules.txt")),
+            areas: lazy
+                .areas
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("areas.txt")),
+            stop_areas: lazy
+                .stop_areas
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("stop_areas.txt")),
+            networks: lazy
+                .networks
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("networks.txt")),
+            route_networks: lazy
+                .route_networks
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("route_networks.txt")),
+            timeframes: lazy
+                .timeframes
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("timeframes.txt")),
+            feed_info: lazy
+                .feed_info
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("feed_info.txt")),
+            frequencies: lazy
+                .frequencies
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("frequencies.txt")),
+            transfers: lazy
+                .transfers
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("transfers.txt")),
+            pathways: lazy
+                .pathways
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("pathways.txt")),
+            levels: lazy
+                .levels
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("levels.txt")),
+            translations: lazy
+                .translations
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("translations.txt")),
+            attributions: lazy
+                .attributions
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("attributions.txt")),
+            booking_rules: lazy
+                .booking_rules
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("booking_rules.txt")),
+            location_groups: lazy
+                .location_groups
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("location_groups.txt")),
+            location_group_stops: lazy
+                .location_group_stops
+                .take()
+                .unwrap_or_else(|| lazy.read_optional("location_group_stops.txt")),
+            locations,
+            stop_time_store: None,
+            read_duration: lazy.read_duration.get().as_millis() as i64,
+            files: lazy.files,
+            sha256: None,
+            warnings: lazy.warnings.into_inner(),
+        }
+    }
+}
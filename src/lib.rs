@@ -3,15 +3,81 @@ extern crate derivative;
 #[macro_use]
 extern crate serde_derive;
 
+mod analysis;
+mod builder;
+mod change_log;
+mod connections;
 pub mod error;
+mod fares;
+#[cfg(feature = "read-url")]
+mod fetch;
+mod flex;
+#[cfg(feature = "geojson")]
+mod geojson;
+mod geometry;
 mod gtfs;
+mod headers;
+mod holidays;
+mod ical;
+mod ids;
+mod interner;
+mod json;
+mod lazy;
+mod matching;
+mod memory;
+mod mutation;
 pub(crate) mod objects;
+mod operating_days;
+mod options;
+mod parallel;
+mod patterns;
 mod raw_gtfs;
+#[cfg(feature = "realtime")]
+pub mod realtime;
+mod sanitize;
+mod schema;
+mod search;
+mod spatial;
+mod spill;
+mod station;
+mod stream;
+mod time;
+mod validation;
+mod view;
+mod writer;
 
 #[cfg(test)]
 mod tests;
 
-pub use error::Error;
+pub use analysis::{DayAccessibility, FrequencyBand, RouteDestination, ServiceBand};
+pub use builder::GtfsBuilder;
+pub use change_log::ChangeEvent;
+pub use connections::{Connection, ConnectionTable};
+pub use error::{Error, FareError, ParseWarning};
+pub use fares::{FareContext, FareLeg, Money, ResolvedLeg, StopFareInfo};
+#[cfg(feature = "read-url")]
+pub use fetch::{CacheValidators, Fetched};
+pub use flex::{Location, Ring};
 pub use gtfs::Gtfs;
+pub use headers::{ColumnMapping, HeaderMapping};
+pub use holidays::{FixedHolidays, HolidayKind, HolidayProvider, NoHolidays};
+pub use ids::{HashIds, IdGenerator, SequentialIds, UuidIds};
+pub use interner::{StringInterner, Symbol};
+pub use lazy::LazyRawGtfs;
+pub use matching::StopMatch;
+pub use memory::MemoryReport;
 pub use objects::*;
+pub use operating_days::{HolidayVariant, OperatingDays, ServiceDescription};
+pub use options::{GtfsReaderOptions, UnknownStopPolicy};
+pub use patterns::StopPatterns;
 pub use raw_gtfs::RawGtfs;
+pub use sanitize::sanitize_text;
+pub use schema::{schema, FieldSchema, FieldType, TableSchema};
+pub use search::{SearchIndex, SearchResult};
+pub use spatial::{distance, StopIndex};
+pub use spill::{StopTimeStore, TripStopTimes};
+pub use station::{StationEdge, StationGraph, StationNode};
+pub use stream::Records;
+pub use time::GtfsTime;
+pub use validation::{Fix, Issue, IssueKind, Severity, ValidationReport};
+pub use view::GtfsView;
@@ -0,0 +1,96 @@
+//! Matching of the stops of two feeds, to find the interchanges between operators
+
+use crate::objects::*;
+use crate::search::words;
+use crate::{Gtfs, StopIndex};
+use std::collections::HashSet;
+
+/// A stop of a feed and a stop of another feed that are likely the same place, see
+/// [Gtfs::matching_stops]
+#[derive(Debug, Clone)]
+pub struct StopMatch<'a> {
+    /// The stop of the feed
+    pub stop: &'a Stop,
+    /// The stop of the other feed
+    pub other: &'a Stop,
+    /// Distance in meters between the stops
+    pub distance: f64,
+    /// Similarity of the names, from 0 (nothing in common) to 1 (same name)
+    pub name_similarity: f64,
+}
+
+/// Pairs of letters of the words of a name, without accents nor case
+fn bigrams(name: &str) -> HashSet<(char, char)> {
+    let text: Vec<char> = words(name).join(" ").chars().collect();
+    text.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Dice coefficient of the pairs of letters of two names
+///
+/// It is not changed by the case, the accents and the punctuation, so "St-Étienne" and
+/// "st etienne" are the same name, and it stays high when a word is added, like "Gare du
+/// Nord" and "Paris Gare du Nord".
+fn similarity(a: &HashSet<(char, char)>, b: &HashSet<(char, char)>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.;
+    }
+    2. * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+impl Gtfs {
+    /// The stops of another feed that are at most `distance_m` meters away from a stop of this
+    /// feed, with a name similarity of at least `name_similarity` (from 0 to 1)
+    ///
+    /// Only the stop points and the stations are matched, each with the stops of the same
+    /// type. The matches are ordered by the id of the stop of this feed, then by distance. A
+    /// stop can match several stops of the other feed, to choose from when generating the
+    /// transfers between the operators. The [Gtfs::stop_index] of the other feed is used if
+    /// it was built.
+    ///
+    /// ```
+    /// let gtfs = gtfs_structures::Gtfs::from_path("fixtures/timetable").unwrap();
+    /// let other = gtfs_structures::Gtfs::from_path("fixtures/fares_v2").unwrap();
+    /// let matches = gtfs.matching_stops(&other, 50., 0.95);
+    /// assert_eq!("central_a", matches[1].stop.id);
+    /// assert_eq!("central_a", matches[1].other.id);
+    /// ```
+    pub fn matching_stops<'a>(
+        &'a self,
+        other: &'a Gtfs,
+        distance_m: f64,
+        name_similarity: f64,
+    ) -> Vec<StopMatch<'a>> {
+        let built;
+        let index = match &other.stop_index {
+            Some(index) => index,
+            None => {
+                built = StopIndex::new(&other.stops);
+                &built
+            }
+        };
+        let matched_types = [LocationType::StopPoint, LocationType::StopArea];
+        let mut matches = Vec::new();
+        for stop in self.stops.values() {
+            let (lat, lon) = match (stop.latitude, stop.longitude) {
+                (Some(lat), Some(lon)) if matched_types.contains(&stop.location_type) => (lat, lon),
+                _ => continue,
+            };
+            let names = bigrams(&stop.name);
+            for (near, distance) in index.within(lat, lon, distance_m) {
+                let similarity = similarity(&names, &bigrams(&near.name));
+                if near.location_type != stop.location_type || similarity < name_similarity {
+                    continue;
+                }
+                if let Some(near) = other.stops.get(&near.id) {
+                    matches.push(StopMatch {
+                        stop,
+                        other: near,
+                        distance,
+                        name_similarity: similarity,
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
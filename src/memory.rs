@@ -0,0 +1,520 @@
+use crate::objects::*;
+use crate::patterns::Times;
+use crate::search::Document;
+use crate::{
+    Connection, ConnectionTable, Gtfs, Location, SearchIndex, StopIndex, StopPatterns,
+    StopTimeStore,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// Estimation of the memory used by each collection of a [Gtfs], in bytes
+///
+/// The values include the size of the structures themselves and of their heap allocations
+/// (strings, vectors, hash tables). They are estimations: allocator overhead is not counted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub stops: usize,
+    pub routes: usize,
+    /// The trips, without their stop times
+    pub trips: usize,
+    pub stop_times: usize,
+    /// The shapes, and the index of the trips using them
+    pub shapes: usize,
+    /// calendar.txt and calendar_dates.txt
+    pub calendars: usize,
+    /// The fare attributes and the files of the fares v2 model
+    pub fare_attributes: usize,
+    pub translations: usize,
+    /// Agencies, feed info, attributions, transfers, pathways, levels, the booking rules,
+    /// locations and location groups of GTFS-Flex, and the search and stop indexes
+    pub other: usize,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> usize {
+        self.stops
+            + self.routes
+            + self.trips
+            + self.stop_times
+            + self.shapes
+            + self.calendars
+            + self.fare_attributes
+            + self.translations
+            + self.other
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kib = |bytes: usize| bytes as f64 / 1024.;
+        writeln!(f, "  Stops: {:.1} KiB", kib(self.stops))?;
+        writeln!(f, "  Routes: {:.1} KiB", kib(self.routes))?;
+        writeln!(f, "  Trips: {:.1} KiB", kib(self.trips))?;
+        writeln!(f, "  Stop times: {:.1} KiB", kib(self.stop_times))?;
+        writeln!(f, "  Shapes: {:.1} KiB", kib(self.shapes))?;
+        writeln!(f, "  Calendars: {:.1} KiB", kib(self.calendars))?;
+        writeln!(f, "  Fare attributes: {:.1} KiB", kib(self.fare_attributes))?;
+        writeln!(f, "  Translations: {:.1} KiB", kib(self.translations))?;
+        writeln!(f, "  Other: {:.1} KiB", kib(self.other))?;
+        write!(f, "  Total: {:.1} KiB", kib(self.total()))
+    }
+}
+
+/// Memory allocated on the heap by a value
+trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl<T: HeapSize> HeapSize for BTreeSet<T> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<T>() * 3 / 2 + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        // the counters of the Arc are stored next to the value
+        2 * size_of::<usize>() + size_of::<T>() + self.as_ref().heap_size()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for HashMap<K, V> {
+    fn heap_size(&self) -> usize {
+        // hashbrown stores one control byte per bucket
+        self.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        // the nodes are not full, and have some bookkeeping (parent, length, children)
+        self.len() * (size_of::<K>() + size_of::<V>()) * 3 / 2
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl HeapSize for Stop {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.code.heap_size()
+            + self.name.heap_size()
+            + self.description.heap_size()
+            + self.parent_station.heap_size()
+            + self.zone_id.heap_size()
+            + self.url.heap_size()
+            + self.timezone.heap_size()
+            + self.level_id.heap_size()
+            + self.platform_code.heap_size()
+    }
+}
+
+impl HeapSize for Route {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.short_name.heap_size()
+            + self.long_name.heap_size()
+            + self.desc.heap_size()
+            + self.url.heap_size()
+            + self.agency_id.heap_size()
+            + self.network_id.heap_size()
+    }
+}
+
+/// Only the trip itself, the stop times are counted separately
+impl HeapSize for Trip {
+    fn heap_size(&self) -> usize {
+        let size = self.id.heap_size()
+            + self.service_id.heap_size()
+            + self.route_id.heap_size()
+            + self.shape_id.heap_size()
+            + self.trip_headsign.heap_size()
+            + self.trip_short_name.heap_size()
+            + self.block_id.heap_size()
+            + self.frequencies.heap_size();
+        #[cfg(feature = "extensions")]
+        let size = size + self.vehicle_type.heap_size();
+        size
+    }
+}
+
+/// The stop is shared with the stops collection and is not counted
+impl HeapSize for StopTime {
+    fn heap_size(&self) -> usize {
+        let size = self.stop_headsign.heap_size()
+            + self.location_group_id.heap_size()
+            + self.location_id.heap_size()
+            + self.pickup_booking_rule_id.heap_size()
+            + self.drop_off_booking_rule_id.heap_size();
+        #[cfg(feature = "extensions")]
+        let size = size + self.local_zone_id.heap_size();
+        size
+    }
+}
+
+impl HeapSize for StopPatterns {
+    fn heap_size(&self) -> usize {
+        self.patterns.heap_size()
+            + self.trips.capacity() * (size_of::<String>() + size_of::<(usize, Times)>() + 1)
+            + self
+                .trips
+                .iter()
+                .map(|(trip_id, (_, times))| {
+                    trip_id.heap_size() + times.capacity() * size_of::<(Option<u32>, Option<u32>)>()
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Only the index of the trips in the file, the stop times themselves are on the disk
+impl HeapSize for StopTimeStore {
+    fn heap_size(&self) -> usize {
+        self.index.capacity() * (size_of::<String>() + size_of::<(u64, usize)>() + 1)
+            + self.index.keys().map(|id| id.heap_size()).sum::<usize>()
+            + self.stop_ids.capacity() * (size_of::<String>() + 1)
+            + self.stop_ids.iter().map(|id| id.heap_size()).sum::<usize>()
+    }
+}
+
+impl HeapSize for Frequency {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for Shape {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+    }
+}
+
+impl HeapSize for Calendar {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+    }
+}
+
+impl HeapSize for CalendarDate {
+    fn heap_size(&self) -> usize {
+        self.service_id.heap_size()
+    }
+}
+
+impl HeapSize for FareAttribute {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.price.heap_size()
+            + self.currency.heap_size()
+            + self.agency_id.heap_size()
+            + self.rules.heap_size()
+    }
+}
+
+impl HeapSize for FareRule {
+    fn heap_size(&self) -> usize {
+        self.fare_id.heap_size()
+            + self.route_id.heap_size()
+            + self.origin_id.heap_size()
+            + self.destination_id.heap_size()
+            + self.contains_id.heap_size()
+    }
+}
+
+impl HeapSize for FareMedia {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size()
+    }
+}
+
+impl HeapSize for RiderCategory {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size() + self.eligibility_url.heap_size()
+    }
+}
+
+impl HeapSize for FareContainer {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.name.heap_size()
+            + self.amount.heap_size()
+            + self.minimum_initial_purchase.heap_size()
+            + self.currency.heap_size()
+    }
+}
+
+impl HeapSize for FareProduct {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.name.heap_size()
+            + self.rider_category_id.heap_size()
+            + self.fare_media_id.heap_size()
+            + self.amount.heap_size()
+            + self.currency.heap_size()
+    }
+}
+
+impl HeapSize for FareLegRule {
+    fn heap_size(&self) -> usize {
+        self.leg_group_id.heap_size()
+            + self.network_id.heap_size()
+            + self.from_area_id.heap_size()
+            + self.to_area_id.heap_size()
+            + self.from_timeframe_group_id.heap_size()
+            + self.to_timeframe_group_id.heap_size()
+            + self.fare_product_id.heap_size()
+    }
+}
+
+impl HeapSize for FareTransferRule {
+    fn heap_size(&self) -> usize {
+        self.from_leg_group_id.heap_size()
+            + self.to_leg_group_id.heap_size()
+            + self.fare_product_id.heap_size()
+    }
+}
+
+impl HeapSize for Area {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size()
+    }
+}
+
+impl HeapSize for Network {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size()
+    }
+}
+
+impl HeapSize for Timeframe {
+    fn heap_size(&self) -> usize {
+        self.timeframe_group_id.heap_size() + self.service_id.heap_size()
+    }
+}
+
+impl HeapSize for Agency {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.name.heap_size()
+            + self.url.heap_size()
+            + self.timezone.heap_size()
+            + self.lang.heap_size()
+            + self.phone.heap_size()
+            + self.fare_url.heap_size()
+            + self.email.heap_size()
+    }
+}
+
+impl HeapSize for Attribution {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.agency_id.heap_size()
+            + self.route_id.heap_size()
+            + self.trip_id.heap_size()
+            + self.organization_name.heap_size()
+            + self.url.heap_size()
+            + self.email.heap_size()
+            + self.phone.heap_size()
+    }
+}
+
+impl HeapSize for BookingRule {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.prior_notice_service_id.heap_size()
+            + self.message.heap_size()
+            + self.pickup_message.heap_size()
+            + self.drop_off_message.heap_size()
+            + self.phone_number.heap_size()
+            + self.info_url.heap_size()
+            + self.booking_url.heap_size()
+    }
+}
+
+impl HeapSize for LocationGroup {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size()
+    }
+}
+
+/// A point of a [Location]
+impl HeapSize for (f64, f64) {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for Location {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.name.heap_size()
+            + self.description.heap_size()
+            + self.polygons.heap_size()
+    }
+}
+
+impl HeapSize for FeedInfo {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.url.heap_size()
+            + self.lang.heap_size()
+            + self.default_lang.heap_size()
+            + self.version.heap_size()
+            + self.contact_email.heap_size()
+            + self.contact_url.heap_size()
+    }
+}
+
+impl HeapSize for Transfer {
+    fn heap_size(&self) -> usize {
+        self.from_stop_id.heap_size()
+            + self.to_stop_id.heap_size()
+            + self.from_route_id.heap_size()
+            + self.to_route_id.heap_size()
+            + self.from_trip_id.heap_size()
+            + self.to_trip_id.heap_size()
+    }
+}
+
+impl HeapSize for Pathway {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+            + self.from_stop_id.heap_size()
+            + self.to_stop_id.heap_size()
+            + self.signposted_as.heap_size()
+            + self.reversed_signposted_as.heap_size()
+    }
+}
+
+impl HeapSize for Level {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.name.heap_size()
+    }
+}
+
+impl HeapSize for TranslationByIdKey {
+    fn heap_size(&self) -> usize {
+        self.table_name.heap_size()
+            + self.field_name.heap_size()
+            + self.language.heap_size()
+            + self.record_id.heap_size()
+            + self.record_sub_id.heap_size()
+    }
+}
+
+impl HeapSize for TranslationByValueKey {
+    fn heap_size(&self) -> usize {
+        self.table_name.heap_size()
+            + self.field_name.heap_size()
+            + self.language.heap_size()
+            + self.field_value.heap_size()
+    }
+}
+
+impl HeapSize for (usize, u32) {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for Document {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size()
+    }
+}
+
+impl HeapSize for SearchIndex {
+    fn heap_size(&self) -> usize {
+        self.documents.heap_size() + self.words.heap_size()
+    }
+}
+
+impl HeapSize for StopIndex {
+    fn heap_size(&self) -> usize {
+        // the stops themselves are shared with Gtfs::stops
+        self.points.capacity() * size_of::<([f64; 3], Arc<Stop>)>()
+    }
+}
+
+impl HeapSize for ConnectionTable {
+    fn heap_size(&self) -> usize {
+        // the stops and the trip ids are shared between the connections
+        self.connections.capacity() * size_of::<Connection>()
+            + self.services.iter().map(|s| s.len()).sum::<usize>()
+    }
+}
+
+impl Gtfs {
+    /// Estimates the memory used by each collection of the feed
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            stops: self.stops.heap_size(),
+            routes: self.routes.heap_size()
+                + self.routes_by_stop.heap_size()
+                + self.stops_by_route.heap_size(),
+            trips: self.trips.heap_size() + self.block_index.heap_size(),
+            // the stop times are in the trips, in the shared patterns or spilled to the disk
+            stop_times: self
+                .trips
+                .values()
+                .map(|t| t.stop_times.heap_size())
+                .sum::<usize>()
+                + self.stop_patterns.heap_size()
+                + self.stop_time_store.heap_size(),
+            shapes: self.shapes.heap_size() + self.shape_index.heap_size(),
+            calendars: self.calendar.heap_size() + self.calendar_dates.heap_size(),
+            fare_attributes: self.fare_attributes.heap_size()
+                + self.fare_media.heap_size()
+                + self.rider_categories.heap_size()
+                + self.fare_containers.heap_size()
+                + self.fare_products.heap_size()
+                + self.fare_leg_rules.heap_size()
+                + self.fare_transfer_rules.heap_size()
+                + self.areas.heap_size()
+                + self.stop_areas.heap_size()
+                + self.networks.heap_size()
+                + self.timeframes.heap_size(),
+            translations: self.translations_by_id.heap_size()
+                + self.translations_by_value.heap_size(),
+            other: self.agencies.heap_size()
+                + self.feed_info.heap_size()
+                + self.attributions.heap_size()
+                + self.transfers.heap_size()
+                + self.pathways.heap_size()
+                + self.levels.heap_size()
+                + self.booking_rules.heap_size()
+                + self.location_groups.heap_size()
+                + self.location_group_stops.heap_size()
+                + self.locations.heap_size()
+                + self.search_index.heap_size()
+                + self.stop_index.heap_size()
+                + self.connection_table.heap_size(),
+        }
+    }
+}
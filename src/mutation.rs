@@ -0,0 +1,788 @@
+use crate::objects::*;
+use crate::patterns::{same_pattern, Times};
+use crate::{ChangeEvent, Error, Gtfs, TripStopTimes};
+use chrono::Duration;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+
+const DAY: u32 = 24 * 3600;
+
+/// Number of trips from which regular departures are replaced by a frequency, see
+/// [Gtfs::compress_to_frequencies]
+const MIN_FREQUENCY_TRIPS: usize = 3;
+
+/// Identifiers of the objects removed by a pruning operation
+#[derive(Default)]
+struct Removed {
+    stops: HashSet<String>,
+    routes: HashSet<String>,
+    trips: HashSet<String>,
+    /// Shapes of the removed trips, they are removed if no other trip uses them
+    shape_candidates: HashSet<String>,
+}
+
+impl Removed {
+    /// Tells if the object references a removed object
+    fn is_referenced_by(&self, object: &impl References) -> bool {
+        object
+            .references()
+            .into_iter()
+            .any(|(object_type, id)| match object_type {
+                ObjectType::Stop => self.stops.contains(id),
+                ObjectType::Route => self.routes.contains(id),
+                ObjectType::Trip => self.trips.contains(id),
+                _ => false,
+            })
+    }
+}
+
+impl Gtfs {
+    /// Keeps only the trips for which the predicate returns true
+    ///
+    /// The shapes that are no longer used by any trip are removed. The [Trip::stop_times] given
+    /// to the predicate are empty when they are spilled or shared, see [Gtfs::trip_stop_times].
+    pub fn retain_trips<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Trip) -> bool,
+    {
+        let mut removed = Removed::default();
+        self.remove_trips(&mut removed, |trip| !predicate(trip));
+        self.remove_dependents(removed);
+    }
+
+    /// Keeps only the routes for which the predicate returns true
+    ///
+    /// The trips of the removed routes are removed, and so are the shapes
+    /// that are no longer used by any trip.
+    pub fn retain_routes<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Route) -> bool,
+    {
+        let mut removed = Removed {
+            routes: self
+                .routes
+                .values()
+                .filter(|route| !predicate(route))
+                .map(|route| route.id.clone())
+                .collect(),
+            ..Removed::default()
+        };
+        for id in sorted(&removed.routes) {
+            if let Some(route) = self.routes.remove(&id) {
+                self.log_change(ChangeEvent::RouteRemoved(route));
+            }
+        }
+        let removed_routes = std::mem::take(&mut removed.routes);
+        self.remove_trips(&mut removed, |trip| removed_routes.contains(&trip.route_id));
+        removed.routes = removed_routes;
+        self.remove_dependents(removed);
+    }
+
+    /// Keeps only the stops for which the predicate returns true
+    ///
+    /// The children of a removed station are removed with it, and so are the transfers and
+    /// pathways of the removed stops. The stop times calling at a removed stop are dropped,
+    /// and trips left with less than two stop times are removed, along with the shapes no
+    /// longer used by any trip. The stop patterns shared by the trips are built again, see
+    /// [Gtfs::share_stop_patterns], and the stop times spilled to the disk calling at a removed
+    /// stop are moved to their trip. An error is returned if the spilled stop times cannot be
+    /// loaded, see [Gtfs::trip_stop_times].
+    pub fn retain_stops<F>(&mut self, mut predicate: F) -> Result<(), Error>
+    where
+        F: FnMut(&Stop) -> bool,
+    {
+        let shared = self.unshare_stop_patterns();
+        let mut removed = Removed {
+            stops: self
+                .stops
+                .values()
+                .filter(|stop| !predicate(stop))
+                .map(|stop| stop.id.clone())
+                .collect(),
+            ..Removed::default()
+        };
+        // a stop can be the parent of a parent (e.g. boarding area → platform → station)
+        loop {
+            let children: Vec<String> = self
+                .stops
+                .values()
+                .filter(|stop| !removed.stops.contains(&stop.id))
+                .filter(|stop| {
+                    stop.parent_station
+                        .as_ref()
+                        .is_some_and(|parent| removed.stops.contains(parent))
+                })
+                .map(|stop| stop.id.clone())
+                .collect();
+            if children.is_empty() {
+                break;
+            }
+            removed.stops.extend(children);
+        }
+        // the spilled stop times are loaded before their stops are removed
+        self.unspill_stop_times(|_, stop_times| {
+            stop_times
+                .iter()
+                .any(|stop_time| removed.stops.contains(&stop_time.stop.id))
+        })?;
+        for id in sorted(&removed.stops) {
+            if let Some(stop) = self.stops.remove(&id) {
+                self.log_change(ChangeEvent::StopRemoved(stop));
+            }
+        }
+
+        let mut shortened_trips = HashSet::new();
+        let mut changes = Vec::new();
+        for trip in self.trips.values_mut() {
+            if !trip
+                .stop_times
+                .iter()
+                .any(|stop_time| removed.stops.contains(&stop_time.stop.id))
+            {
+                continue;
+            }
+            let before = self.change_log.as_ref().map(|_| trip.stop_times.clone());
+            trip.stop_times
+                .retain(|stop_time| !removed.stops.contains(&stop_time.stop.id));
+            shortened_trips.insert(trip.id.clone());
+            if let Some(before) = before {
+                changes.push(ChangeEvent::StopTimesChanged {
+                    trip_id: trip.id.clone(),
+                    before,
+                    after: trip.stop_times.clone(),
+                });
+            }
+        }
+        for change in changes {
+            self.log_change(change);
+        }
+        self.remove_trips(&mut removed, |trip| {
+            trip.stop_times.len() < 2 && shortened_trips.contains(&trip.id)
+        });
+        self.remove_dependents(removed);
+        if shared {
+            self.share_stop_patterns();
+        }
+        Ok(())
+    }
+
+    /// Keeps a pseudo-random subset of the trips, e.g. to derive a small test feed from a large one
+    ///
+    /// The trips are grouped by route type and service, and `fraction` of the trips of each group
+    /// are kept, rounded up, so every group keeps at least one trip when `fraction` is positive.
+    /// The same seed always keeps the same trips. The routes, services and stops left without
+    /// trips are then removed; the stations of the kept stops are kept with all their children.
+    /// An error is returned if the stop times of a trip cannot be loaded, see
+    /// [Gtfs::trip_stop_times].
+    pub fn sample(&mut self, fraction: f64, seed: u64) -> Result<(), Error> {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let mut groups: HashMap<(RouteType, &str), Vec<&str>> = HashMap::new();
+        for trip in self.trips.values() {
+            if let Some(route) = self.routes.get(&trip.route_id) {
+                groups
+                    .entry((route.route_type, trip.service_id.as_str()))
+                    .or_default()
+                    .push(trip.id.as_str());
+            }
+        }
+        let mut kept = HashSet::new();
+        for trip_ids in groups.values_mut() {
+            let count = (trip_ids.len() as f64 * fraction).ceil() as usize;
+            trip_ids.sort_by_key(|id| (sample_rank(seed, id), *id));
+            kept.extend(trip_ids.iter().take(count).map(|id| id.to_string()));
+        }
+        self.retain_trips(|trip| kept.contains(&trip.id));
+
+        let used_routes: HashSet<String> =
+            self.trips.values().map(|trip| trip.route_id.clone()).collect();
+        self.retain_routes(|route| used_routes.contains(&route.id));
+        self.prune_unused_services();
+
+        let mut kept_stops: HashSet<String> = self
+            .location_group_stops
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        for trip_id in self.trips.keys() {
+            let stop_times = self.trip_stop_times(trip_id)?;
+            kept_stops.extend(stop_times.iter().map(|st| st.stop.id.clone()));
+        }
+        kept_stops.remove("");
+        // the parents of the kept stops, then the children of the kept stations
+        let mut parents: Vec<String> = kept_stops.iter().cloned().collect();
+        while let Some(id) = parents.pop() {
+            let parent = self.stops.get(&id).and_then(|s| s.parent_station.clone());
+            if let Some(parent) = parent {
+                if kept_stops.insert(parent.clone()) {
+                    parents.push(parent);
+                }
+            }
+        }
+        loop {
+            let children: Vec<String> = self
+                .stops
+                .values()
+                .filter(|stop| !kept_stops.contains(&stop.id))
+                .filter(|stop| {
+                    stop.parent_station
+                        .as_ref()
+                        .is_some_and(|parent| kept_stops.contains(parent))
+                })
+                .map(|stop| stop.id.clone())
+                .collect();
+            if children.is_empty() {
+                break;
+            }
+            kept_stops.extend(children);
+        }
+        self.retain_stops(|stop| kept_stops.contains(&stop.id))
+    }
+
+    /// Changes the type of every route with the mapping, e.g. [RouteType::to_basic]
+    ///
+    /// The type read from the feed is kept in [Route::original_route_type].
+    pub fn remap_route_types<F>(&mut self, mut mapping: F)
+    where
+        F: FnMut(RouteType) -> RouteType,
+    {
+        let mut changes = Vec::new();
+        for route in self.routes.values_mut() {
+            let route_type = mapping(route.route_type);
+            if route_type != route.route_type {
+                changes.push(ChangeEvent::RouteTypeChanged {
+                    route_id: route.id.clone(),
+                    before: route.route_type,
+                    after: route_type,
+                });
+                let route = Arc::make_mut(route);
+                route.original_route_type.get_or_insert(route.route_type);
+                route.route_type = route_type;
+            }
+        }
+        if !changes.is_empty() {
+            self.link_trips();
+        }
+        for change in changes {
+            self.log_change(change);
+        }
+    }
+
+    /// Gives times to the untimed stop times of every trip, see [Trip::interpolate_stop_times],
+    /// and returns how many were interpolated
+    ///
+    /// The stop patterns shared by the trips are built again, see [Gtfs::share_stop_patterns],
+    /// and the untimed stop times spilled to the disk are moved to their trip. An error is
+    /// returned if the spilled stop times cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn interpolate_stop_times(&mut self) -> Result<usize, Error> {
+        let shared = self.unshare_stop_patterns();
+        self.unspill_stop_times(|_, stop_times| {
+            stop_times.iter().any(|stop_time| {
+                stop_time.arrival_time.is_none() || stop_time.departure_time.is_none()
+            })
+        })?;
+        let mut count = 0;
+        let mut changes = Vec::new();
+        for trip in self.trips.values_mut() {
+            let before = self.change_log.as_ref().map(|_| trip.stop_times.clone());
+            count += trip.interpolate_stop_times();
+            let changed = |before: &Vec<StopTime>| {
+                before.iter().zip(&trip.stop_times).any(|(a, b)| {
+                    a.arrival_time != b.arrival_time || a.departure_time != b.departure_time
+                })
+            };
+            if let Some(before) = before.filter(changed) {
+                changes.push(ChangeEvent::StopTimesChanged {
+                    trip_id: trip.id.clone(),
+                    before,
+                    after: trip.stop_times.clone(),
+                });
+            }
+        }
+        for change in changes {
+            self.log_change(change);
+        }
+        if shared {
+            self.share_stop_patterns();
+        }
+        Ok(count)
+    }
+
+    /// Removes the services used by no trip, see [Gtfs::unused_services], and returns their ids
+    pub fn prune_unused_services(&mut self) -> Vec<String> {
+        let unused: Vec<String> = self
+            .unused_services()
+            .into_iter()
+            .map(|id| id.to_owned())
+            .collect();
+        for service_id in &unused {
+            if let Some(calendar) = self.calendar.remove(service_id) {
+                self.log_change(ChangeEvent::CalendarRemoved(calendar));
+            }
+            if let Some(dates) = self.calendar_dates.remove(service_id) {
+                self.log_change(ChangeEvent::CalendarDatesRemoved {
+                    service_id: service_id.clone(),
+                    dates,
+                });
+            }
+        }
+        if !unused.is_empty() {
+            self.update_operating_days();
+        }
+        unused
+    }
+
+    /// Keeps only the trips running during a daily time window, e.g. `6 * 3600..10 * 3600` for
+    /// the morning peak, to analyse a smaller feed
+    ///
+    /// The window is in seconds since midnight, and ends after 24:00:00 to go past midnight,
+    /// like `22 * 3600..26 * 3600`. A trip is kept if it runs at a time of the
+    /// window, between its first departure and its last arrival, or between the start and
+    /// the end of its frequencies. The times after midnight are on the clocks of the next
+    /// day, so a trip at 25:00:00 runs during the window `0..2 * 3600`. The trips without
+    /// times are kept, and the stop times of the kept trips are not modified. The shapes and
+    /// services no longer used by any trip are removed.
+    pub fn retain_time_window(&mut self, window: Range<u32>) {
+        let Range { start, end } = window;
+        let mut removed_trips = HashSet::new();
+        for trip in self.trips.values() {
+            let stop_times = match self.trip_stop_times(&trip.id) {
+                Ok(stop_times) => stop_times,
+                Err(_) => continue,
+            };
+            let times: Vec<u32> = stop_times
+                .iter()
+                .flat_map(|st| st.arrival_time.into_iter().chain(st.departure_time))
+                .collect();
+            let (first, last) = match (times.iter().min(), times.iter().max()) {
+                (Some(first), Some(last)) => (*first, *last),
+                _ => continue,
+            };
+            let (first, last) = match trip.frequencies.iter().map(|f| f.start_time).min() {
+                Some(start_time) => {
+                    let end_time = trip.frequencies.iter().map(|f| f.end_time).max();
+                    (start_time, end_time.unwrap_or(start_time) + last - first)
+                }
+                None => (first, last),
+            };
+            // the window of each day, the trip is also shifted by a day for the part of a
+            // window past midnight
+            let overlaps = |shift: u32| {
+                (0..=(last + shift) / DAY)
+                    .any(|day| start + day * DAY <= last + shift && first + shift < end + day * DAY)
+            };
+            if !overlaps(0) && !overlaps(DAY) {
+                removed_trips.insert(trip.id.clone());
+            }
+        }
+        self.retain_trips(|trip| !removed_trips.contains(&trip.id));
+        self.prune_unused_services();
+    }
+
+    /// Moves the stop times after midnight to trips of the next day, for the consumers that do not
+    /// handle times of 24:00:00 or more
+    ///
+    /// A trip crossing midnight is split in two: the stop times after midnight are moved to a new
+    /// trip with the `_next_day` suffix. A trip running entirely after midnight is moved as is.
+    /// They use a new service `<service_id>_next_day`, defined in calendar_dates on the day after
+    /// each day of the original service. The times are shifted by 24 hours.
+    ///
+    /// If a stop time arrives before midnight and leaves after, it stays in the first part with
+    /// its departure set to its arrival. Returns the ids of the trips of the next day. The stop
+    /// patterns shared by the trips are built again, see [Gtfs::share_stop_patterns], and the
+    /// stop times spilled to the disk of the split trips are moved to their trip.
+    ///
+    /// The trips with frequencies are not split, as their stop times are only a template for
+    /// the departures given by the frequencies. An error is returned if the spilled stop times
+    /// cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn split_trips_at_midnight(&mut self) -> Result<Vec<String>, Error> {
+        let shared = self.unshare_stop_patterns();
+        self.unspill_stop_times(|trip, stop_times| {
+            trip.frequencies.is_empty()
+                && stop_times.iter().any(|stop_time| {
+                    stop_time
+                        .arrival_time
+                        .max(stop_time.departure_time)
+                        .is_some_and(|time| time >= DAY)
+                })
+        })?;
+        let after_midnight = |stop_time: &StopTime| {
+            stop_time
+                .arrival_time
+                .or(stop_time.departure_time)
+                .is_some_and(|time| time >= DAY)
+        };
+        let mut next_day_trips = Vec::new();
+        // the services of the split trips, then their services of the next day
+        let mut services = BTreeSet::new();
+        let mut next_day_services = HashMap::new();
+        let recording = self.change_log.is_some();
+        let mut changes = Vec::new();
+        for trip in self
+            .trips
+            .values_mut()
+            .filter(|trip| trip.frequencies.is_empty() && trip.crosses_midnight())
+        {
+            let original = Some(&*trip).filter(|_| recording).cloned();
+            let split = trip
+                .stop_times
+                .iter()
+                .position(after_midnight)
+                .unwrap_or(trip.stop_times.len());
+            let after = trip.stop_times.split_off(split);
+            let mut clamped = false;
+            if let Some(last) = trip.stop_times.last_mut() {
+                if last.departure_time.is_some_and(|time| time >= DAY) {
+                    last.departure_time = last.arrival_time;
+                    clamped = true;
+                }
+            }
+            if let Some(original) = original {
+                if trip.stop_times.is_empty() {
+                    // the trip is replaced by the one of the next day
+                    changes.push(ChangeEvent::TripRemoved(original));
+                } else if clamped || !after.is_empty() {
+                    changes.push(ChangeEvent::StopTimesChanged {
+                        trip_id: trip.id.clone(),
+                        before: original.stop_times,
+                        after: trip.stop_times.clone(),
+                    });
+                }
+            }
+            if after.is_empty() {
+                continue;
+            }
+            let before = std::mem::take(&mut trip.stop_times);
+            let mut next_day = trip.clone();
+            trip.stop_times = before;
+            // the ids of the next day are given once all the trips are split
+            let renamed = !trip.stop_times.is_empty();
+            services.insert(trip.service_id.clone());
+            next_day.stop_times = after
+                .into_iter()
+                .map(|mut stop_time| {
+                    stop_time.arrival_time = stop_time.arrival_time.map(|t| t.saturating_sub(DAY));
+                    stop_time.departure_time =
+                        stop_time.departure_time.map(|t| t.saturating_sub(DAY));
+                    stop_time
+                })
+                .collect();
+            next_day_trips.push((next_day, renamed));
+        }
+
+        for service_id in services {
+            let next_day_service = self.derived_id(ObjectType::Calendar, &service_id, "_next_day");
+            next_day_services.insert(service_id.clone(), next_day_service.clone());
+            let dates: Vec<_> = self
+                .operating_days
+                .active_dates(&service_id)
+                .into_iter()
+                .map(|date| date + Duration::days(1))
+                .collect();
+            let calendar_dates = self
+                .calendar_dates
+                .entry(next_day_service.clone())
+                .or_default();
+            for date in dates {
+                if !calendar_dates.iter().any(|d| d.date == date) {
+                    let calendar_date = CalendarDate {
+                        service_id: next_day_service.clone(),
+                        date,
+                        exception_type: Exception::Added,
+                    };
+                    if recording {
+                        changes.push(ChangeEvent::CalendarDateAdded(calendar_date.clone()));
+                    }
+                    calendar_dates.push(calendar_date);
+                }
+            }
+        }
+        self.update_operating_days();
+
+        let ids = next_day_trips
+            .into_iter()
+            .map(|(mut trip, renamed)| {
+                if renamed {
+                    trip.id = self.derived_id(ObjectType::Trip, &trip.id, "_next_day");
+                }
+                trip.service_id = next_day_services[&trip.service_id].clone();
+                trip.calendar = None;
+                let id = trip.id.clone();
+                if recording {
+                    changes.push(ChangeEvent::TripAdded(trip.clone()));
+                }
+                self.index_trip(&trip);
+                self.index_route_stops(&trip.route_id, &trip.stop_times);
+                self.trips.insert(trip.id.clone(), trip);
+                id
+            })
+            .collect();
+        for change in changes {
+            self.log_change(change);
+        }
+        if shared {
+            self.share_stop_patterns();
+        }
+        Ok(ids)
+    }
+
+    /// Replaces the trips leaving at regular intervals by a trip with frequencies, to shrink
+    /// the generated feeds, and returns the ids of the trips given frequencies
+    ///
+    /// The trips are merged when they are the same (route, service, headsign, block, stop
+    /// times…) except for their id, and their stop times have the same offsets from their
+    /// first departure. At least 3 trips leaving every headway are needed, each one within
+    /// `tolerance` seconds of its regular departure. The first trip is kept, with an
+    /// [ExactTimes::ScheduleBased] frequency ending one headway after the last departure, so
+    /// [Trip::expand_frequencies] gives the merged trips back (with other ids when the times
+    /// were not regular). The other trips are removed like with [Gtfs::retain_trips]. Only the
+    /// trips without frequencies are considered, with the stop times given by
+    /// [Gtfs::trip_stop_times]. An error is returned if the stop times of a trip cannot be
+    /// loaded.
+    pub fn compress_to_frequencies(&mut self, tolerance: u32) -> Result<Vec<String>, Error> {
+        // the trips with the same route, service and offsets, then the groups of the same
+        // trips, with the stop times of their first trip
+        type Group<'a> = (TripStopTimes<'a>, Vec<(u32, &'a Trip)>);
+        let mut candidates: HashMap<_, Vec<Group>> = HashMap::new();
+        for trip in self.trips.values().filter(|trip| trip.frequencies.is_empty()) {
+            let stop_times = self.trip_stop_times(&trip.id)?;
+            let (departure, offsets) = match relative_times(&stop_times) {
+                Some(times) => times,
+                None => continue,
+            };
+            let groups = candidates
+                .entry((trip.route_id.as_str(), trip.service_id.as_str(), offsets))
+                .or_default();
+            let same = |(first, group): &&mut Group| {
+                same_trip(group[0].1, first, trip, &stop_times)
+            };
+            match groups.iter_mut().find(same) {
+                Some((_, group)) => group.push((departure, trip)),
+                None => groups.push((stop_times, vec![(departure, trip)])),
+            }
+        }
+
+        let mut compressed: Vec<(String, Frequency)> = Vec::new();
+        let mut merged = HashSet::new();
+        for (_, mut group) in candidates.into_values().flatten() {
+            group.sort_by(|(a, trip_a), (b, trip_b)| {
+                a.cmp(b).then_with(|| trip_a.id.cmp(&trip_b.id))
+            });
+            let mut first = 0;
+            while first + MIN_FREQUENCY_TRIPS <= group.len() {
+                let start = group[first].0;
+                let headway = group[first + 1].0 - start;
+                let mut last = first + 1;
+                while last + 1 < group.len() && headway > 0 {
+                    let expected = start + (last + 1 - first) as u32 * headway;
+                    if group[last + 1].0.abs_diff(expected) > tolerance {
+                        break;
+                    }
+                    last += 1;
+                }
+                if headway == 0 || last + 1 - first < MIN_FREQUENCY_TRIPS {
+                    first += 1;
+                    continue;
+                }
+                let frequency = Frequency {
+                    start_time: start,
+                    end_time: start + (last + 1 - first) as u32 * headway,
+                    headway_secs: headway,
+                    exact_times: Some(ExactTimes::ScheduleBased),
+                };
+                compressed.push((group[first].1.id.clone(), frequency));
+                merged.extend(group[first + 1..=last].iter().map(|(_, trip)| trip.id.clone()));
+                first = last + 1;
+            }
+        }
+        compressed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let recording = self.change_log.is_some();
+        let mut changes = Vec::new();
+        for (trip_id, frequency) in &compressed {
+            if let Some(trip) = self.trips.get_mut(trip_id) {
+                if recording {
+                    changes.push(ChangeEvent::TripRemoved(trip.clone()));
+                }
+                trip.frequencies.push(frequency.clone());
+                if recording {
+                    changes.push(ChangeEvent::TripAdded(trip.clone()));
+                }
+            }
+        }
+        for change in changes {
+            self.log_change(change);
+        }
+        self.retain_trips(|trip| !merged.contains(&trip.id));
+        Ok(compressed.into_iter().map(|(trip_id, _)| trip_id).collect())
+    }
+
+    fn remove_trips<F>(&mut self, removed: &mut Removed, mut should_remove: F)
+    where
+        F: FnMut(&Trip) -> bool,
+    {
+        let ids: Vec<String> = self
+            .trips
+            .iter()
+            .filter(|(_, trip)| should_remove(trip))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            // the trip is recorded with its stop times, even when they are spilled or shared
+            let recorded = match self.change_log {
+                Some(_) => self.trip_with_stop_times(&id).ok().map(Cow::into_owned),
+                None => None,
+            };
+            if let Some(store) = &mut self.stop_time_store {
+                store.remove(&id);
+            }
+            if let Some(trip) = self.trips.remove(&id) {
+                if let Some(shape_id) = &trip.shape_id {
+                    removed.shape_candidates.insert(shape_id.clone());
+                }
+                self.unindex_trip(&trip);
+                removed.trips.insert(id);
+                self.log_change(ChangeEvent::TripRemoved(recorded.unwrap_or(trip)));
+            }
+        }
+    }
+
+    /// Removes the objects that were only referenced by removed objects:
+    /// shapes no longer used by any trip, translations of removed objects,
+    /// transfers, pathways and attributions referencing a removed object,
+    /// and area or location group memberships of removed stops
+    fn remove_dependents(&mut self, removed: Removed) {
+        for transfers in self.transfers.values_mut() {
+            transfers.retain(|transfer| !removed.is_referenced_by(transfer));
+        }
+        self.transfers.retain(|_, transfers| !transfers.is_empty());
+        self.pathways
+            .retain(|_, pathway| !removed.is_referenced_by(pathway));
+        self.attributions
+            .retain(|attribution| !removed.is_referenced_by(attribution));
+        if !removed.stops.is_empty() {
+            for stop_ids in self
+                .stop_areas
+                .values_mut()
+                .chain(self.location_group_stops.values_mut())
+            {
+                stop_ids.retain(|stop_id| !removed.stops.contains(stop_id));
+            }
+        }
+
+        if !removed.trips.is_empty() || !removed.stops.is_empty() {
+            self.update_route_stop_index();
+        }
+
+        for shape_id in sorted(&removed.shape_candidates) {
+            if self.shape_index.contains_key(&shape_id) {
+                continue;
+            }
+            if let Some(points) = self.shapes.remove(&shape_id) {
+                self.log_change(ChangeEvent::ShapeRemoved { shape_id, points });
+            }
+        }
+
+        let mut keys: Vec<TranslationByIdKey> = self
+            .translations_by_id
+            .keys()
+            .filter(|key| match key.table_name.as_str() {
+                "stops" => removed.stops.contains(&key.record_id),
+                "routes" => removed.routes.contains(&key.record_id),
+                "trips" | "stop_times" => removed.trips.contains(&key.record_id),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        keys.sort_by(|a, b| {
+            (&a.table_name, &a.record_id, &a.field_name, &a.language)
+                .cmp(&(&b.table_name, &b.record_id, &b.field_name, &b.language))
+        });
+        for key in keys {
+            if let Some(value) = self.translations_by_id.remove(&key) {
+                self.log_change(ChangeEvent::TranslationRemoved { key, value });
+            }
+        }
+    }
+}
+
+/// Position of a trip in the sample order, stable across platforms and versions of Rust
+fn sample_rank(seed: u64, id: &str) -> u64 {
+    // FNV-1a hash of the id, mixed with the seed by the splitmix64 finalizer
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let mut z = (hash ^ seed).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// First departure of the stop times of a trip, and their times relative to it
+fn relative_times(stop_times: &[StopTime]) -> Option<(u32, Times)> {
+    let first = stop_times.first()?;
+    let departure = first.departure_time.or(first.arrival_time)?;
+    let offsets = stop_times
+        .iter()
+        .map(|st| {
+            let offset = |time: Option<u32>| time.and_then(|time| time.checked_sub(departure));
+            (offset(st.arrival_time), offset(st.departure_time))
+        })
+        .collect();
+    Some((departure, offsets))
+}
+
+/// Tells if two trips with their stop times are the same, except for their id and the times of
+/// their stop times
+fn same_trip(a: &Trip, a_stop_times: &[StopTime], b: &Trip, b_stop_times: &[StopTime]) -> bool {
+    // destructured, so that a new field of Trip cannot be forgotten here
+    let Trip {
+        id: _,
+        service_id,
+        route_id,
+        route: _,
+        calendar: _,
+        stop_times: _,
+        frequencies,
+        shape_id,
+        trip_headsign,
+        trip_short_name,
+        direction_id,
+        block_id,
+        wheelchair_accessible,
+        bikes_allowed,
+        #[cfg(feature = "extensions")]
+        vehicle_type,
+        #[cfg(feature = "extensions")]
+        cars_count,
+        #[cfg(feature = "extensions")]
+        capacity,
+    } = a;
+    #[cfg(feature = "extensions")]
+    let same_vehicle = vehicle_type == &b.vehicle_type
+        && cars_count == &b.cars_count
+        && capacity == &b.capacity;
+    #[cfg(not(feature = "extensions"))]
+    let same_vehicle = true;
+    service_id == &b.service_id
+        && route_id == &b.route_id
+        && same_pattern(a_stop_times, b_stop_times)
+        && frequencies == &b.frequencies
+        && shape_id == &b.shape_id
+        && trip_headsign == &b.trip_headsign
+        && trip_short_name == &b.trip_short_name
+        && direction_id == &b.direction_id
+        && block_id == &b.block_id
+        && wheelchair_accessible == &b.wheelchair_accessible
+        && bikes_allowed == &b.bikes_allowed
+        && same_vehicle
+}
+
+/// Ids in a stable order, so that the changes are always recorded in the same order
+fn sorted(ids: &HashSet<String>) -> Vec<String> {
+    let mut ids: Vec<String> = ids.iter().cloned().collect();
+    ids.sort();
+    ids
+}
@@ -1,11 +1,12 @@
 use core::fmt::Formatter;
 use serde::de::MapAccess;
 use serde::de::Visitor;
-use crate::Gtfs;
-use chrono::{Datelike, NaiveDate, Weekday};
+use crate::{Error, Gtfs};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
 use rgb::RGB8;
 use serde::de::{self, Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 
@@ -17,6 +18,20 @@ pub trait Type {
     fn object_type(&self) -> ObjectType;
 }
 
+/// Objects referencing other objects of the feed by their id
+pub trait References {
+    /// The type and id of the objects referenced by this one, like the route of a trip
+    fn references(&self) -> Vec<(ObjectType, &str)>;
+}
+
+/// The references that are given
+fn given(references: Vec<(ObjectType, Option<&str>)>) -> Vec<(ObjectType, &str)> {
+    references
+        .into_iter()
+        .filter_map(|(object_type, id)| Some((object_type, id?)))
+        .collect()
+}
+
 pub trait Translatable {
     fn translate(&self, gtfs: &Gtfs, language: &str) -> Self;
 }
@@ -49,27 +64,85 @@ pub enum Translation {
     Nmbs(NmbsTranslation),
 }
 
+/// Format of a translations.txt file, detected from its columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TranslationDialect {
+    /// The format of the specification: table_name, field_name, language, translation,
+    /// and record_id, record_sub_id or field_value
+    Gtfs,
+    /// trans_id, lang and translation, where trans_id is the translated text,
+    /// used by the NMBS and the first feeds with translations
+    Nmbs,
+    /// The per-field format of the specification, with a lang column instead of language,
+    /// found in older feeds
+    LegacyLang,
+}
+
+impl TranslationDialect {
+    /// Detects the dialect from the column names of translations.txt
+    ///
+    /// Returns None if the columns match no dialect.
+    pub fn detect<'a>(columns: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let columns: Vec<&str> = columns.into_iter().collect();
+        let has = |column: &str| columns.contains(&column);
+        if has("trans_id") && has("lang") && has("translation") {
+            Some(TranslationDialect::Nmbs)
+        } else if has("table_name") && has("field_name") && has("translation") {
+            if has("language") {
+                Some(TranslationDialect::Gtfs)
+            } else if has("lang") {
+                Some(TranslationDialect::LegacyLang)
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Builds a translation from the values of a row, by column name
+    ///
+    /// The required columns must be present with a value, the other columns can be absent
+    /// or empty.
+    fn translation<E: de::Error>(
+        self,
+        mut row: HashMap<String, Option<String>>,
+    ) -> Result<Translation, E> {
+        let mut required = |column: &'static str| match row.remove(column) {
+            Some(Some(value)) => Ok(value),
+            Some(None) => Err(E::custom(format!("the required column {} is empty", column))),
+            None => Err(E::missing_field(column)),
+        };
+        Ok(match self {
+            TranslationDialect::Nmbs => Translation::Nmbs(NmbsTranslation {
+                trans_id: required("trans_id")?,
+                lang: required("lang")?,
+                translation: required("translation")?,
+            }),
+            TranslationDialect::Gtfs | TranslationDialect::LegacyLang => {
+                let language = match self {
+                    TranslationDialect::Gtfs => "language",
+                    _ => "lang",
+                };
+                Translation::Gtfs(GtfsTranslation {
+                    table_name: required("table_name")?,
+                    field_name: required("field_name")?,
+                    language: required(language)?,
+                    translation: required("translation")?,
+                    record_id: row.remove("record_id").flatten(),
+                    record_sub_id: row.remove("record_sub_id").flatten(),
+                    field_value: row.remove("field_value").flatten(),
+                })
+            }
+        })
+    }
+}
+
 impl<'de> Deserialize<'de> for Translation {
     fn deserialize<D>(deserializer: D) -> Result<Translation, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "snake_case")]
-        enum Field {
-            // GTFS/shared values
-            TableName,
-            FieldName,
-            Language,
-            Translation,
-            RecordId,
-            RecordSubId,
-            FieldValue,
-            // NMBS values
-            TransId,
-            Lang,
-        }
-
         struct TranslationVisitor;
 
         impl<'de> Visitor<'de> for TranslationVisitor {
@@ -79,77 +152,45 @@ impl<'de> Deserialize<'de> for Translation {
             where
                 V: MapAccess<'de>,
             {
-                let mut maybe_table_name = None;
-                let mut maybe_field_name = None;
-                let mut maybe_language = None;
-                let mut maybe_translation = None;
-                let mut maybe_record_id = None;
-                let mut maybe_record_sub_id = None;
-                let mut maybe_field_value = None;
-                let mut maybe_trans_id = None;
-                let mut maybe_lang = None;
-
-                while let Some(field) = map.next_key()? {
-                    match field {
-                        Field::TableName => {
-                            maybe_table_name = Some(map.next_value()?);
-                        }
-                        Field::FieldName => {
-                            maybe_field_name = Some(map.next_value()?);
-                        }
-                        Field::Language => {
-                            maybe_language = Some(map.next_value()?);
-                        }
-                        Field::Translation => {
-                            maybe_translation = Some(map.next_value()?);
-                        }
-                        Field::RecordId => {
-                            maybe_record_id = Some(map.next_value()?);
-                        }
-                        Field::RecordSubId => {
-                            maybe_record_sub_id = Some(map.next_value()?);
-                        }
-                        Field::FieldValue => {
-                            maybe_field_value = Some(map.next_value()?);
-                        }
-                        Field::TransId => {
-                            maybe_trans_id = Some(map.next_value()?);
-                        }
-                        Field::Lang => {
-                            maybe_lang = Some(map.next_value()?);
-                        }
-                    }
-                }
-
-                if maybe_lang.is_some() && maybe_trans_id.is_some() && maybe_translation.is_some() {
-                    Ok(Translation::Nmbs(NmbsTranslation {
-                        lang: maybe_lang.unwrap(),
-                        trans_id: maybe_trans_id.unwrap(),
-                        translation: maybe_translation.unwrap()
-                    }))
-                } else {
-                    Ok(Translation::Gtfs(GtfsTranslation {
-                        table_name: maybe_table_name.unwrap(),
-                        field_name: maybe_field_name.unwrap(),
-                        language: maybe_language.unwrap(),
-                        translation: maybe_translation.unwrap(),
-                        record_id: maybe_record_id.unwrap(),
-                        record_sub_id: maybe_record_sub_id.unwrap(),
-                        field_value: maybe_field_value.unwrap(),
-                    }))
+                // every row of a csv file has all the columns, so this is the header of the file
+                let mut row: HashMap<String, Option<String>> = HashMap::new();
+                while let Some(column) = map.next_key::<String>()? {
+                    let value = map.next_value()?;
+                    row.insert(column, value);
                 }
+                let dialect = TranslationDialect::detect(row.keys().map(String::as_str))
+                    .ok_or_else(|| {
+                        let mut columns: Vec<&str> = row.keys().map(String::as_str).collect();
+                        columns.sort_unstable();
+                        de::Error::custom(format!(
+                            "unknown format of translations, with the columns {}",
+                            columns.join(", ")
+                        ))
+                    })?;
+                dialect.translation(row)
             }
+
             fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
                 formatter.write_str("struct Translation")
             }
         }
 
-        const FIELDS: &'static [&'static str] = &["table_name", "field_name", "language", "translation", "record_id", "record_sub_id", "field_value", "trans_id", "lang"];
-        deserializer.deserialize_struct("Duration", FIELDS, TranslationVisitor)
+        const FIELDS: &[&str] = &[
+            "table_name",
+            "field_name",
+            "language",
+            "translation",
+            "record_id",
+            "record_sub_id",
+            "field_value",
+            "trans_id",
+            "lang",
+        ];
+        deserializer.deserialize_struct("Translation", FIELDS, TranslationVisitor)
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct TranslationByIdKey {
     pub table_name: String,
     pub field_name: String,
@@ -166,7 +207,7 @@ pub struct TranslationByValueKey {
     pub field_value: String,
 }
 
-#[derive(Debug, Serialize, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Serialize, Eq, PartialEq, Hash, Clone, Copy)]
 pub enum ObjectType {
     Agency,
     Stop,
@@ -177,10 +218,13 @@ pub enum ObjectType {
     Fare,
     StopTime,
     FeedInfo,
+    Level,
+    Pathway,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub enum LocationType {
+    #[default]
     StopPoint = 0,
     StopArea = 1,
     StationEntrance = 2,
@@ -204,17 +248,21 @@ impl<'de> Deserialize<'de> for LocationType {
     }
 }
 
-impl Default for LocationType {
-    fn default() -> LocationType {
-        LocationType::StopPoint
+impl Serialize for LocationType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(*self as u8)
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 pub enum RouteType {
     Tramway,
     Subway,
     Rail,
+    #[default]
     Bus,
     Ferry,
     CableCar,
@@ -227,9 +275,22 @@ pub enum RouteType {
     Other(u16),
 }
 
-impl Default for RouteType {
-    fn default() -> RouteType {
-        RouteType::Bus
+impl RouteType {
+    /// Closest basic route type (0 to 7) of an extended route type
+    ///
+    /// Air transport and the unknown types have no basic equivalent and are kept as is.
+    pub fn to_basic(self) -> RouteType {
+        match self {
+            RouteType::Coach | RouteType::Taxi => RouteType::Bus,
+            // trolleybus
+            RouteType::Other(11) => RouteType::Bus,
+            // monorail and suburban railway
+            RouteType::Other(12) => RouteType::Rail,
+            RouteType::Other(i) if i / 100 == 3 => RouteType::Rail,
+            // metro and underground
+            RouteType::Other(i) if i / 100 == 5 || i / 100 == 6 => RouteType::Subway,
+            route_type => route_type,
+        }
     }
 }
 
@@ -238,10 +299,15 @@ impl<'de> Deserialize<'de> for RouteType {
     where
         D: Deserializer<'de>,
     {
-        let i = u16::deserialize(deserializer)?;
+        Ok(RouteType::from(u16::deserialize(deserializer)?))
+    }
+}
 
+impl From<u16> for RouteType {
+    /// The route type of a route_type code, basic or extended
+    fn from(i: u16) -> RouteType {
         let hundreds = i / 100;
-        Ok(match (i, hundreds) {
+        match (i, hundreds) {
             (0, _) | (_, 9) => RouteType::Tramway,
             (1, _) | (_, 4) => RouteType::Subway,
             (2, _) | (_, 1) => RouteType::Rail,
@@ -254,7 +320,7 @@ impl<'de> Deserialize<'de> for RouteType {
             (_, 11) => RouteType::Air,
             (_, 15) => RouteType::Taxi,
             _ => RouteType::Other(i),
-        })
+        }
     }
 }
 
@@ -311,7 +377,7 @@ pub enum ContinuousPickupDropOff {
     CoordinateWithDriver,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Calendar {
     #[serde(rename = "service_id")]
     pub id: String,
@@ -374,6 +440,12 @@ impl Id for Calendar {
     }
 }
 
+impl References for Calendar {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
 impl fmt::Display for Calendar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}—{}", self.start_date, self.end_date)
@@ -414,7 +486,7 @@ pub enum Exception {
     Deleted,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CalendarDate {
     pub service_id: String,
     #[serde(
@@ -425,6 +497,12 @@ pub struct CalendarDate {
     pub exception_type: Exception,
 }
 
+impl References for CalendarDate {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Stop {
     #[serde(rename = "stop_id")]
@@ -453,6 +531,16 @@ pub struct Stop {
     pub wheelchair_boarding: Availability,
     pub level_id: Option<String>,
     pub platform_code: Option<String>,
+    /// The level referenced by level_id, set when the [Gtfs] is built from levels.txt
+    #[serde(skip)]
+    pub level: Option<Arc<Level>>,
+    /// The coordinates as written in stops.txt, see [CoordinateText]
+    #[serde(skip)]
+    pub coordinate_text: Option<Box<CoordinateText>>,
+    /// The stop is not in stops.txt, it was created for the stop times referencing it, see
+    /// [crate::UnknownStopPolicy::Placeholder]
+    #[serde(skip)]
+    pub missing: bool,
 }
 
 impl Type for Stop {
@@ -467,64 +555,47 @@ impl Id for Stop {
     }
 }
 
+impl References for Stop {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Stop, self.parent_station.as_deref()),
+            (ObjectType::Level, self.level_id.as_deref()),
+        ])
+    }
+}
+
 impl Translatable for Stop {
     fn translate(&self, gtfs: &Gtfs, language: &str) -> Self {
+        self.translate_fields(|field, value| {
+            gtfs.translate("stops", field, language, &self.id, None, value)
+        })
+    }
+}
+
+impl Stop {
+    /// Copy of the stop with the translatable fields given by `translate(field_name, value)`
+    pub(crate) fn translate_fields<F: Fn(&str, &str) -> String>(&self, translate: F) -> Self {
         Stop {
             id: self.id.clone(),
-            code: self.code.as_ref().map(|code| 
-                gtfs.translate(
-                    "stops",
-                    "stop_code",
-                    language,
-                    &self.id,
-                    None,
-                    &code
-                )
-            ),
-            name: gtfs.translate(
-                "stops",
-                "stop_name",
-                language,
-                &self.id,
-                None,
-                &self.name
-            ),
-            description: gtfs.translate(
-                "stops",
-                "stop_desc",
-                language,
-                &self.id,
-                None,
-                &self.description
-            ),
+            code: self.code.as_ref().map(|code| translate("stop_code", code)),
+            name: translate("stop_name", &self.name),
+            description: translate("stop_desc", &self.description),
             location_type: self.location_type,
             parent_station: self.parent_station.clone(),
             zone_id: self.zone_id.clone(),
-            url: self.code.as_ref().map(|url|
-                gtfs.translate(
-                    "stops",
-                    "stop_url",
-                    language,
-                    &self.id,
-                    None,
-                    &url
-                )
-            ),
+            url: self.url.as_ref().map(|url| translate("stop_url", url)),
             longitude: self.longitude,
             latitude: self.latitude,
             timezone: self.timezone.clone(),
             wheelchair_boarding: self.wheelchair_boarding,
             level_id: self.level_id.clone(),
-            platform_code: self.code.as_ref().map(|platform_code|
-                gtfs.translate(
-                    "stops",
-                    "platform_code",
-                    language,
-                    &self.id,
-                    None,
-                    &platform_code
-                )
-            ),
+            level: self.level.clone(),
+            coordinate_text: self.coordinate_text.clone(),
+            missing: self.missing,
+            platform_code: self
+                .platform_code
+                .as_ref()
+                .map(|platform_code| translate("platform_code", platform_code)),
         }
     }
 }
@@ -540,7 +611,7 @@ pub struct RawStopTime {
     pub trip_id: String,
     /// Arrival time of the stop time.
     /// It's an option since the intermediate stops can have have no arrival
-    /// and this arrival needs to be interpolated
+    /// and this arrival needs to be interpolated, see [Trip::interpolate_stop_times]
     #[serde(
         deserialize_with = "deserialize_optional_time",
         serialize_with = "serialize_optional_time"
@@ -548,12 +619,14 @@ pub struct RawStopTime {
     pub arrival_time: Option<u32>,
     /// Departure time of the stop time.
     /// It's an option since the intermediate stops can have have no departure
-    /// and this departure needs to be interpolated
+    /// and this departure needs to be interpolated, see [Trip::interpolate_stop_times]
     #[serde(
         deserialize_with = "deserialize_optional_time",
         serialize_with = "serialize_optional_time"
     )]
     pub departure_time: Option<u32>,
+    /// Empty for the GTFS-Flex stop times serving a location or a location group
+    #[serde(default)]
     pub stop_id: String,
     pub stop_sequence: u16,
     pub stop_headsign: Option<String>,
@@ -568,11 +641,45 @@ pub struct RawStopTime {
         default = "bool_default_true"
     )]
     pub timepoint: bool,
+    /// GTFS-Flex location group served instead of a stop
+    pub location_group_id: Option<String>,
+    /// GTFS-Flex zone of locations.geojson served instead of a stop
+    pub location_id: Option<String>,
+    /// Start of the GTFS-Flex on demand service at the location, in seconds since midnight
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub start_pickup_drop_off_window: Option<u32>,
+    /// End of the GTFS-Flex on demand service at the location, in seconds since midnight
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub end_pickup_drop_off_window: Option<u32>,
+    pub pickup_booking_rule_id: Option<String>,
+    pub drop_off_booking_rule_id: Option<String>,
+    /// Fare zone of the stop for this stop time, overriding the zone_id of the stop (extension)
+    #[cfg(feature = "extensions")]
+    pub local_zone_id: Option<String>,
+}
+
+impl References for RawStopTime {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        vec![
+            (ObjectType::Trip, self.trip_id.as_str()),
+            (ObjectType::Stop, self.stop_id.as_str()),
+        ]
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct StopTime {
     pub arrival_time: Option<u32>,
+    /// The GTFS-Flex stop times serving a location or a location group instead of a stop
+    /// have a placeholder stop with an empty id
     pub stop: Arc<Stop>,
     pub departure_time: Option<u32>,
     pub pickup_type: Option<PickupDropOffType>,
@@ -583,23 +690,55 @@ pub struct StopTime {
     pub continuous_drop_off: Option<ContinuousPickupDropOff>,
     pub shape_dist_traveled: Option<f32>,
     pub timepoint: bool,
+    /// The arrival and departure times were computed by the crate instead of read from the feed
+    pub interpolated: bool,
+    /// GTFS-Flex location group served instead of a stop
+    pub location_group_id: Option<String>,
+    /// GTFS-Flex zone of locations.geojson served instead of a stop, see [Gtfs::get_location]
+    pub location_id: Option<String>,
+    /// Start of the GTFS-Flex on demand service at the location, in seconds since midnight
+    pub start_pickup_drop_off_window: Option<u32>,
+    /// End of the GTFS-Flex on demand service at the location, in seconds since midnight
+    pub end_pickup_drop_off_window: Option<u32>,
+    /// Booking rule to be picked up, see [Gtfs::get_booking_rule]
+    pub pickup_booking_rule_id: Option<String>,
+    /// Booking rule to be dropped off, see [Gtfs::get_booking_rule]
+    pub drop_off_booking_rule_id: Option<String>,
+    /// Fare zone of the stop for this stop time, overriding the zone_id of the stop (extension)
+    #[cfg(feature = "extensions")]
+    pub local_zone_id: Option<String>,
+}
+
+impl References for StopTime {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        vec![(ObjectType::Stop, self.stop.id.as_str())]
+    }
 }
 
 impl Translatable for StopTime {
     fn translate(&self, gtfs: &Gtfs, language: &str) -> Self {
         StopTime {
-            arrival_time: self.arrival_time.clone(),
+            arrival_time: self.arrival_time,
             stop: Arc::new(self.stop.translate(gtfs, language)),
-            departure_time: self.departure_time.clone(),
-            pickup_type: self.pickup_type.clone(),
-            drop_off_type: self.drop_off_type.clone(),
+            departure_time: self.departure_time,
+            pickup_type: self.pickup_type,
+            drop_off_type: self.drop_off_type,
             stop_sequence: self.stop_sequence,
-            // Headsign can't be translated as we do not have a reference to this StopTime's Trip
+            // the headsign is translated by trip, see Trip::translated_stop_headsigns
             stop_headsign: self.stop_headsign.clone(),
-            continuous_pickup: self.continuous_pickup.clone(),
-            continuous_drop_off: self.continuous_drop_off.clone(),
+            continuous_pickup: self.continuous_pickup,
+            continuous_drop_off: self.continuous_drop_off,
             shape_dist_traveled: self.shape_dist_traveled,
-            timepoint: self.timepoint
+            timepoint: self.timepoint,
+            interpolated: self.interpolated,
+            location_group_id: self.location_group_id.clone(),
+            location_id: self.location_id.clone(),
+            start_pickup_drop_off_window: self.start_pickup_drop_off_window,
+            end_pickup_drop_off_window: self.end_pickup_drop_off_window,
+            pickup_booking_rule_id: self.pickup_booking_rule_id.clone(),
+            drop_off_booking_rule_id: self.drop_off_booking_rule_id.clone(),
+            #[cfg(feature = "extensions")]
+            local_zone_id: self.local_zone_id.clone(),
         }
     }
 }
@@ -618,11 +757,49 @@ impl StopTime {
             continuous_drop_off: stop_time_gtfs.continuous_drop_off,
             shape_dist_traveled: stop_time_gtfs.shape_dist_traveled,
             timepoint: stop_time_gtfs.timepoint,
+            interpolated: false,
+            location_group_id: stop_time_gtfs.location_group_id.clone(),
+            location_id: stop_time_gtfs.location_id.clone(),
+            start_pickup_drop_off_window: stop_time_gtfs.start_pickup_drop_off_window,
+            end_pickup_drop_off_window: stop_time_gtfs.end_pickup_drop_off_window,
+            pickup_booking_rule_id: stop_time_gtfs.pickup_booking_rule_id.clone(),
+            drop_off_booking_rule_id: stop_time_gtfs.drop_off_booking_rule_id.clone(),
+            #[cfg(feature = "extensions")]
+            local_zone_id: stop_time_gtfs.local_zone_id.clone(),
         }
     }
+
+    /// Tells if the times are exact: the stop time is a timepoint and its times come from the feed
+    ///
+    /// The other times are approximate, such as the times left empty in the feed or interpolated by the crate.
+    pub fn is_exact(&self) -> bool {
+        self.timepoint
+            && !self.interpolated
+            && (self.arrival_time.is_some() || self.departure_time.is_some())
+    }
+
+    /// The arrival time as a [crate::GtfsTime]
+    pub fn arrival(&self) -> Option<crate::GtfsTime> {
+        self.arrival_time.map(crate::GtfsTime::from_seconds)
+    }
+
+    /// The departure time as a [crate::GtfsTime]
+    pub fn departure(&self) -> Option<crate::GtfsTime> {
+        self.departure_time.map(crate::GtfsTime::from_seconds)
+    }
+
+    /// Fare zone of the stop time: its local_zone_id with the feature 'extensions',
+    /// or the zone_id of its stop
+    pub fn zone_id(&self) -> Option<&str> {
+        #[cfg(feature = "extensions")]
+        if let Some(zone_id) = &self.local_zone_id {
+            return Some(zone_id);
+        }
+        self.stop.zone_id.as_deref()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Route {
     #[serde(rename = "route_id")]
     pub id: String,
@@ -652,6 +829,13 @@ pub struct Route {
     pub route_text_color: Option<RGB8>,
     pub continuous_pickup: Option<ContinuousPickupDropOff>,
     pub continuous_drop_off: Option<ContinuousPickupDropOff>,
+    /// The [Network] of the route, for the fare leg rules
+    ///
+    /// When the feed uses route_networks.txt, it is set from this file by [Gtfs]
+    pub network_id: Option<String>,
+    /// Route type read from the feed, if it was changed by [Gtfs::remap_route_types]
+    #[serde(skip)]
+    pub original_route_type: Option<RouteType>,
 }
 
 impl Type for Route {
@@ -666,49 +850,51 @@ impl Id for Route {
     }
 }
 
+impl References for Route {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![(ObjectType::Agency, self.agency_id.as_deref())])
+    }
+}
+
 impl Translatable for Route {
     fn translate(&self, gtfs: &Gtfs, language: &str) -> Route {
+        self.translate_fields(|field, value| {
+            gtfs.translate("routes", field, language, &self.id, None, value)
+        })
+    }
+}
+
+impl Route {
+    /// Name to show to riders: the route_short_name, or the route_long_name if there is no
+    /// short name
+    ///
+    /// The specification advises against showing both names when one repeats the other, the
+    /// long name can be shown in addition when it gives more information.
+    pub fn display_name(&self) -> &str {
+        if self.short_name.trim().is_empty() {
+            &self.long_name
+        } else {
+            &self.short_name
+        }
+    }
+
+    /// Copy of the route with the translatable fields given by `translate(field_name, value)`
+    pub(crate) fn translate_fields<F: Fn(&str, &str) -> String>(&self, translate: F) -> Route {
         Route {
             id: self.id.clone(),
-            short_name: gtfs.translate(
-                "routes",
-                "route_short_name",
-                language,
-                &self.id,
-                None,
-                &self.short_name
-            ),
-            long_name: gtfs.translate(
-                "routes",
-                "route_long_name",
-                language,
-                &self.id,
-                None,
-                &self.long_name
-            ),
-            desc: self.desc.as_ref().map(|desc| gtfs.translate(
-                    "routes",
-                    "route_desc",
-                    language,
-                    &self.id,
-                    None,
-                    &desc
-            )),
-            route_type: self.route_type.clone(),
-            url: self.url.as_ref().map(|url| gtfs.translate(
-                    "routes",
-                    "route_url",
-                    language,
-                    &self.id,
-                    None,
-                    &url
-            )),
+            short_name: translate("route_short_name", &self.short_name),
+            long_name: translate("route_long_name", &self.long_name),
+            desc: self.desc.as_ref().map(|desc| translate("route_desc", desc)),
+            route_type: self.route_type,
+            url: self.url.as_ref().map(|url| translate("route_url", url)),
             agency_id: self.agency_id.clone(),
-            route_order: self.route_order.clone(),
-            route_color: self.route_color.clone(),
-            route_text_color: self.route_text_color.clone(),
-            continuous_pickup: self.continuous_pickup.clone(),
-            continuous_drop_off: self.continuous_drop_off.clone(),
+            route_order: self.route_order,
+            route_color: self.route_color,
+            route_text_color: self.route_text_color,
+            continuous_pickup: self.continuous_pickup,
+            continuous_drop_off: self.continuous_drop_off,
+            network_id: self.network_id.clone(),
+            original_route_type: self.original_route_type,
         }
     }
 }
@@ -723,7 +909,7 @@ impl fmt::Display for Route {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum DirectionType {
     #[serde(rename = "0")]
     Outbound,
@@ -764,6 +950,15 @@ pub struct RawTrip {
     pub block_id: Option<String>,
     pub wheelchair_accessible: Option<WheelChairAccessibleType>,
     pub bikes_allowed: Option<BikesAllowedType>,
+    /// Type of vehicle operating the trip, as named by the producer (extension)
+    #[cfg(feature = "extensions")]
+    pub vehicle_type: Option<String>,
+    /// Number of cars of the vehicle (extension)
+    #[cfg(feature = "extensions")]
+    pub cars_count: Option<u16>,
+    /// Number of passengers the vehicle can carry (extension)
+    #[cfg(feature = "extensions")]
+    pub capacity: Option<u32>,
 }
 
 impl Type for RawTrip {
@@ -778,6 +973,16 @@ impl Id for RawTrip {
     }
 }
 
+impl References for RawTrip {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Route, Some(self.route_id.as_str())),
+            (ObjectType::Calendar, Some(self.service_id.as_str())),
+            (ObjectType::Shape, self.shape_id.as_deref()),
+        ])
+    }
+}
+
 impl fmt::Display for RawTrip {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -788,12 +993,22 @@ impl fmt::Display for RawTrip {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Trip {
     pub id: String,
     pub service_id: String,
     pub route_id: String,
+    /// The route of the trip, None if its route_id is not a route of the feed, see
+    /// [Gtfs::link_trips]
+    pub route: Option<Arc<Route>>,
+    /// The calendar of the service of the trip, None if the service is only defined in
+    /// calendar_dates.txt, see [Gtfs::link_trips]
+    pub calendar: Option<Arc<Calendar>>,
+    /// Empty when the stop times are spilled to the disk or shared in a pattern, see
+    /// [Gtfs::trip_stop_times] and [Gtfs::trip_with_stop_times]
     pub stop_times: Vec<StopTime>,
+    /// Periods during which the trip is repeated, from frequencies.txt
+    pub frequencies: Vec<Frequency>,
     pub shape_id: Option<String>,
     pub trip_headsign: Option<String>,
     pub trip_short_name: Option<String>,
@@ -801,6 +1016,15 @@ pub struct Trip {
     pub block_id: Option<String>,
     pub wheelchair_accessible: Option<WheelChairAccessibleType>,
     pub bikes_allowed: Option<BikesAllowedType>,
+    /// Type of vehicle operating the trip, as named by the producer (extension)
+    #[cfg(feature = "extensions")]
+    pub vehicle_type: Option<String>,
+    /// Number of cars of the vehicle (extension)
+    #[cfg(feature = "extensions")]
+    pub cars_count: Option<u16>,
+    /// Number of passengers the vehicle can carry (extension)
+    #[cfg(feature = "extensions")]
+    pub capacity: Option<u32>,
 }
 
 impl Type for Trip {
@@ -815,13 +1039,29 @@ impl Id for Trip {
     }
 }
 
+impl References for Trip {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Route, Some(self.route_id.as_str())),
+            (ObjectType::Calendar, Some(self.service_id.as_str())),
+            (ObjectType::Shape, self.shape_id.as_deref()),
+        ])
+    }
+}
+
 impl Translatable for Trip {
     fn translate(&self, gtfs: &Gtfs, language: &str) -> Self {
         Trip {
             id: self.id.clone(),
             service_id: self.service_id.clone(),
             route_id: self.route_id.clone(),
-            stop_times: self.stop_times.iter().map(|stop_time| stop_time.translate(gtfs, language)).collect(),
+            route: self.route.clone(),
+            calendar: self.calendar.clone(),
+            stop_times: self.stop_times.iter().map(|stop_time| StopTime {
+                stop_headsign: self.translate_stop_headsign(gtfs, language, stop_time),
+                ..stop_time.translate(gtfs, language)
+            }).collect(),
+            frequencies: self.frequencies.clone(),
             shape_id: self.shape_id.clone(),
             trip_headsign: self.trip_headsign.as_ref().map(|headsign| gtfs.translate(
                 "trips",
@@ -829,7 +1069,7 @@ impl Translatable for Trip {
                 language,
                 &self.id,
                 None,
-                &headsign
+                headsign
             )),
             trip_short_name: self.trip_short_name.as_ref().map(|short_name| gtfs.translate(
                 "trips",
@@ -837,12 +1077,18 @@ impl Translatable for Trip {
                 language,
                 &self.id,
                 None,
-                &short_name
+                short_name
             )),
-            direction_id: self.direction_id.clone(),
+            direction_id: self.direction_id,
             block_id: self.block_id.clone(),
-            wheelchair_accessible: self.wheelchair_accessible.clone(),
-            bikes_allowed: self.bikes_allowed.clone(),
+            wheelchair_accessible: self.wheelchair_accessible,
+            bikes_allowed: self.bikes_allowed,
+            #[cfg(feature = "extensions")]
+            vehicle_type: self.vehicle_type.clone(),
+            #[cfg(feature = "extensions")]
+            cars_count: self.cars_count,
+            #[cfg(feature = "extensions")]
+            capacity: self.capacity,
         }
     }
 }
@@ -857,6 +1103,224 @@ impl fmt::Display for Trip {
     }
 }
 
+impl Trip {
+    /// The stop_headsign of a stop time of the trip in the language
+    fn translate_stop_headsign(
+        &self,
+        gtfs: &Gtfs,
+        language: &str,
+        stop_time: &StopTime,
+    ) -> Option<String> {
+        let stop_sequence = stop_time.stop_sequence.to_string();
+        stop_time.stop_headsign.as_ref().map(|headsign| {
+            let sub_id = Some(stop_sequence.as_str());
+            gtfs.translate("stop_times", "stop_headsign", language, &self.id, sub_id, headsign)
+        })
+    }
+
+    /// The stop_headsign of each stop time of [Gtfs::trip_stop_times] in the language, None
+    /// for the stop times without one
+    ///
+    /// The translations of stop_times are given by trip_id (their record_id) and
+    /// stop_sequence (their record_sub_id), or by value.
+    pub fn translated_stop_headsigns(
+        &self,
+        gtfs: &Gtfs,
+        language: &str,
+    ) -> Result<Vec<Option<String>>, Error> {
+        Ok(gtfs
+            .trip_stop_times(&self.id)?
+            .iter()
+            .map(|stop_time| self.translate_stop_headsign(gtfs, language, stop_time))
+            .collect())
+    }
+
+    /// Trips implied by the frequencies of the trip, or the trip itself if it has none
+    ///
+    /// A trip is created for each departure from start_time, every headway_secs, until end_time
+    /// (excluded). Its stop times keep the same offsets from the first departure as in the trip,
+    /// and its id is `<trip_id>_<HHMMSS>` after the time of its first departure. The
+    /// [Trip::stop_times] are used, see [Gtfs::trip_with_stop_times] for a trip whose stop
+    /// times are spilled or shared.
+    pub fn expand_frequencies(&self) -> Vec<Trip> {
+        let first_departure = self
+            .stop_times
+            .first()
+            .and_then(|stop_time| stop_time.departure_time.or(stop_time.arrival_time));
+        let first_departure = match first_departure {
+            Some(time) if !self.frequencies.is_empty() => time,
+            _ => return vec![self.clone()],
+        };
+        let mut trips = Vec::new();
+        for frequency in &self.frequencies {
+            let headway = frequency.headway_secs.max(1) as usize;
+            for departure in (frequency.start_time..frequency.end_time).step_by(headway) {
+                let shift = |time: u32| time + departure - first_departure;
+                trips.push(Trip {
+                    id: format!(
+                        "{}_{:02}{:02}{:02}",
+                        self.id,
+                        departure / 3600,
+                        departure % 3600 / 60,
+                        departure % 60
+                    ),
+                    stop_times: self
+                        .stop_times
+                        .iter()
+                        .map(|stop_time| StopTime {
+                            arrival_time: stop_time.arrival_time.map(shift),
+                            departure_time: stop_time.departure_time.map(shift),
+                            ..stop_time.clone()
+                        })
+                        .collect(),
+                    frequencies: vec![],
+                    ..self.clone()
+                });
+            }
+        }
+        trips
+    }
+
+    /// Tells if the trip has times after midnight of its service day (times of 24:00:00 or more)
+    ///
+    /// The [Trip::stop_times] are used, see [Gtfs::trip_with_stop_times].
+    pub fn crosses_midnight(&self) -> bool {
+        self.stop_times.iter().any(|stop_time| {
+            stop_time
+                .arrival_time
+                .max(stop_time.departure_time)
+                .is_some_and(|time| time >= 24 * 3600)
+        })
+    }
+
+    /// Gives times to the stop times without arrival nor departure, between the previous and
+    /// the next stop times with a time, and returns how many were interpolated
+    ///
+    /// When the shape_dist_traveled of the stop times is known, the time is proportional to the
+    /// distance traveled, otherwise the untimed stop times are evenly spaced. They get the same
+    /// arrival and departure, and are marked as [StopTime::interpolated]. A stop time with only
+    /// an arrival or a departure gets the same time for the other one. The untimed stop times
+    /// before the first or after the last time of the trip are left without times.
+    ///
+    /// Only the [Trip::stop_times] are modified, see [Gtfs::interpolate_stop_times] for the
+    /// stop times spilled or shared.
+    pub fn interpolate_stop_times(&mut self) -> usize {
+        for stop_time in &mut self.stop_times {
+            stop_time.arrival_time = stop_time.arrival_time.or(stop_time.departure_time);
+            stop_time.departure_time = stop_time.departure_time.or(stop_time.arrival_time);
+        }
+        let timed: Vec<usize> = (0..self.stop_times.len())
+            .filter(|i| self.stop_times[*i].departure_time.is_some())
+            .collect();
+        let mut count = 0;
+        for bounds in timed.windows(2) {
+            let (start, end) = (bounds[0], bounds[1]);
+            if end == start + 1 {
+                continue;
+            }
+            let from = self.stop_times[start].departure_time.unwrap_or_default();
+            let to = self.stop_times[end].arrival_time.unwrap_or_default();
+            let distances: Option<Vec<f32>> = self.stop_times[start..=end]
+                .iter()
+                .map(|stop_time| stop_time.shape_dist_traveled)
+                .collect();
+            // the distances must increase to be used
+            let distances =
+                distances.filter(|d| d.windows(2).all(|w| w[0] <= w[1]) && d[0] < d[d.len() - 1]);
+            for i in start + 1..end {
+                let fraction = match &distances {
+                    Some(d) => ((d[i - start] - d[0]) / (d[end - start] - d[0])) as f64,
+                    None => (i - start) as f64 / (end - start) as f64,
+                };
+                let time = from as f64 + to.saturating_sub(from) as f64 * fraction;
+                let stop_time = &mut self.stop_times[i];
+                stop_time.arrival_time = Some(time.round() as u32);
+                stop_time.departure_time = stop_time.arrival_time;
+                stop_time.interpolated = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Stop times from the stop_sequence `from_seq` to the stop_sequence `to_seq`, both included
+    ///
+    /// Returns an error if one of the sequences is not in the trip, or if `to_seq` comes before
+    /// `from_seq` in the stop times. The [Trip::stop_times] are used, see
+    /// [Gtfs::trip_with_stop_times].
+    pub fn segment(&self, from_seq: u16, to_seq: u16) -> Result<&[StopTime], Error> {
+        segment(&self.id, &self.stop_times, from_seq, to_seq)
+    }
+}
+
+/// Stop times of a trip from the stop_sequence `from_seq` to `to_seq`, see [Trip::segment]
+pub(crate) fn segment<'a>(
+    trip_id: &str,
+    stop_times: &'a [StopTime],
+    from_seq: u16,
+    to_seq: u16,
+) -> Result<&'a [StopTime], Error> {
+    let position = |seq: u16| {
+        stop_times
+            .iter()
+            .position(|stop_time| stop_time.stop_sequence == seq)
+            .ok_or_else(|| {
+                Error::InvalidStopSequence(format!(
+                    "trip {} has no stop_sequence {}",
+                    trip_id, seq
+                ))
+            })
+    };
+    let (from, to) = (position(from_seq)?, position(to_seq)?);
+    if from > to {
+        return Err(Error::InvalidStopSequence(format!(
+            "stop_sequence {} comes after {} in trip {}",
+            from_seq, to_seq, trip_id
+        )));
+    }
+    Ok(&stop_times[from..=to])
+}
+
+/// A trip leaving its first stop, as listed by [Gtfs::route_departures]
+#[derive(Debug)]
+pub struct RouteDeparture<'a> {
+    pub trip: &'a Trip,
+    /// The first stop of the trip
+    pub stop: Arc<Stop>,
+    /// Departure time at the first stop, in seconds since midnight
+    pub departure_time: u32,
+}
+
+/// A trip leaving a station, as listed by [Gtfs::origin_departures]
+#[derive(Debug)]
+pub struct StationDeparture<'a> {
+    pub trip: &'a Trip,
+    /// The stop of the station where the trip is boarded, usually a platform
+    pub stop: Arc<Stop>,
+    /// Day of service of the trip, the day before the requested date for the trips after midnight
+    pub service_date: NaiveDate,
+    /// Departure time from the stop, in seconds since midnight of the requested date
+    pub departure_time: u32,
+    /// The stop_headsign of the stop time, or the trip_headsign
+    pub headsign: Option<String>,
+}
+
+/// A departure from a stop, as listed by [Gtfs::departures_from]
+#[derive(Debug)]
+pub struct Departure<'a> {
+    pub trip: &'a Trip,
+    pub route: &'a Route,
+    /// The stop where the trip is boarded, a child stop when the departures of a station are
+    /// listed
+    pub stop: Arc<Stop>,
+    /// Day of service of the trip, the day before the departure for the trips after midnight
+    pub service_date: NaiveDate,
+    /// Date and time of the departure, in the timezone of the feed
+    pub departure: NaiveDateTime,
+    /// The stop_headsign of the stop time, or the trip_headsign
+    pub headsign: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Agency {
     #[serde(rename = "agency_id")]
@@ -877,6 +1341,25 @@ pub struct Agency {
     pub email: Option<String>,
 }
 
+impl Translatable for Agency {
+    fn translate(&self, gtfs: &Gtfs, language: &str) -> Self {
+        let id = self.id.as_deref().unwrap_or_default();
+        let translate = |field: &str, value: &str| {
+            gtfs.translate("agency", field, language, id, None, value)
+        };
+        Agency {
+            id: self.id.clone(),
+            name: translate("agency_name", &self.name),
+            url: translate("agency_url", &self.url),
+            timezone: self.timezone.clone(),
+            lang: self.lang.clone(),
+            phone: self.phone.as_ref().map(|phone| translate("agency_phone", phone)),
+            fare_url: self.fare_url.as_ref().map(|url| translate("agency_fare_url", url)),
+            email: self.email.as_ref().map(|email| translate("agency_email", email)),
+        }
+    }
+}
+
 impl Type for Agency {
     fn object_type(&self) -> ObjectType {
         ObjectType::Agency
@@ -892,13 +1375,42 @@ impl Id for Agency {
     }
 }
 
+impl References for Agency {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
 impl fmt::Display for Agency {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.name)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Latitude and longitude as written in the feed, kept with
+/// [crate::GtfsReaderOptions::keep_coordinate_text]
+///
+/// Parsing a coordinate as a f64 loses how it was written (like trailing zeros or the
+/// exponent), so a feed written back would not be byte for byte the same. The text is written
+/// instead of the value as long as it still gives the same value.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CoordinateText {
+    pub latitude: String,
+    pub longitude: String,
+}
+
+impl CoordinateText {
+    /// Tells if the text gives these coordinates, an empty text giving no coordinate
+    pub fn matches(&self, latitude: Option<f64>, longitude: Option<f64>) -> bool {
+        let parse = |text: &str| match text.trim() {
+            "" => None,
+            text => text.parse::<f64>().ok(),
+        };
+        parse(&self.latitude) == latitude && parse(&self.longitude) == longitude
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Shape {
     #[serde(rename = "shape_id")]
     pub id: String,
@@ -910,6 +1422,9 @@ pub struct Shape {
     pub sequence: usize,
     #[serde(rename = "shape_dist_traveled")]
     pub dist_traveled: Option<f32>,
+    /// The coordinates as written in shapes.txt, see [CoordinateText]
+    #[serde(skip)]
+    pub coordinate_text: Option<Box<CoordinateText>>,
 }
 
 impl Type for Shape {
@@ -924,6 +1439,489 @@ impl Id for Shape {
     }
 }
 
+impl References for Shape {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
+/// Tells if the trips of a frequency follow a fixed schedule
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum ExactTimes {
+    /// The trips run every headway_secs, without a precise schedule
+    #[derivative(Default)]
+    #[serde(rename = "0")]
+    FrequencyBased,
+    /// The trips leave exactly at start_time + n × headway_secs
+    #[serde(rename = "1")]
+    ScheduleBased,
+}
+
+/// A line of frequencies.txt, see [Frequency] for the version attached to a [Trip]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RawFrequency {
+    pub trip_id: String,
+    #[serde(deserialize_with = "deserialize_time", serialize_with = "serialize_time")]
+    pub start_time: u32,
+    #[serde(deserialize_with = "deserialize_time", serialize_with = "serialize_time")]
+    pub end_time: u32,
+    pub headway_secs: u32,
+    pub exact_times: Option<ExactTimes>,
+}
+
+impl References for RawFrequency {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        vec![(ObjectType::Trip, self.trip_id.as_str())]
+    }
+}
+
+/// A period during which a trip is repeated at a fixed interval
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frequency {
+    /// First departure of the period from the first stop, in seconds since midnight
+    pub start_time: u32,
+    /// End of the period (exclusive), in seconds since midnight
+    pub end_time: u32,
+    /// Interval between two departures, in seconds
+    pub headway_secs: u32,
+    pub exact_times: Option<ExactTimes>,
+}
+
+impl Frequency {
+    /// The start_time as a [crate::GtfsTime]
+    pub fn start(&self) -> crate::GtfsTime {
+        crate::GtfsTime::from_seconds(self.start_time)
+    }
+
+    /// The end_time as a [crate::GtfsTime]
+    pub fn end(&self) -> crate::GtfsTime {
+        crate::GtfsTime::from_seconds(self.end_time)
+    }
+}
+
+impl From<&RawFrequency> for Frequency {
+    fn from(frequency: &RawFrequency) -> Self {
+        Self {
+            start_time: frequency.start_time,
+            end_time: frequency.end_time,
+            headway_secs: frequency.headway_secs,
+            exact_times: frequency.exact_times,
+        }
+    }
+}
+
+/// A level of a station, from levels.txt
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Level {
+    #[serde(rename = "level_id")]
+    pub id: String,
+    /// Position of the level relative to the ground level (0), negative when underground
+    #[serde(rename = "level_index")]
+    pub index: f64,
+    #[serde(rename = "level_name")]
+    pub name: Option<String>,
+}
+
+impl Id for Level {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Type for Level {
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Level
+    }
+}
+
+impl References for Level {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
+/// Kind of a [Pathway]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum PathwayMode {
+    #[default]
+    #[serde(rename = "1")]
+    Walkway,
+    #[serde(rename = "2")]
+    Stairs,
+    /// Moving sidewalk or travelator
+    #[serde(rename = "3")]
+    MovingSidewalk,
+    #[serde(rename = "4")]
+    Escalator,
+    #[serde(rename = "5")]
+    Elevator,
+    /// Pathway crossing into an area where a proof of payment is required
+    #[serde(rename = "6")]
+    FareGate,
+    /// Pathway leaving an area where a proof of payment is required
+    #[serde(rename = "7")]
+    ExitGate,
+}
+
+/// A link between two locations of a station, from pathways.txt
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Pathway {
+    #[serde(rename = "pathway_id")]
+    pub id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub pathway_mode: PathwayMode,
+    /// If false, the pathway can only be used from from_stop_id to to_stop_id
+    #[serde(
+        deserialize_with = "deserialize_bool",
+        serialize_with = "serialize_bool"
+    )]
+    pub is_bidirectional: bool,
+    /// Horizontal length in meters
+    pub length: Option<f64>,
+    /// Average time in seconds needed to walk through the pathway
+    pub traversal_time: Option<u32>,
+    /// Number of stairs, negative when going down from from_stop_id
+    pub stair_count: Option<i32>,
+    /// Slope ratio, negative when going down from from_stop_id
+    pub max_slope: Option<f64>,
+    /// Minimum width in meters
+    pub min_width: Option<f64>,
+    pub signposted_as: Option<String>,
+    pub reversed_signposted_as: Option<String>,
+}
+
+impl Id for Pathway {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Type for Pathway {
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Pathway
+    }
+}
+
+impl References for Pathway {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        vec![
+            (ObjectType::Stop, self.from_stop_id.as_str()),
+            (ObjectType::Stop, self.to_stop_id.as_str()),
+        ]
+    }
+}
+
+/// How a transfer can be made between two stops, see [Transfer]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TransferType {
+    /// Recommended transfer point between routes
+    #[default]
+    Recommended,
+    /// The departing vehicle waits for the arriving one
+    Timed,
+    /// The transfer needs at least min_transfer_time seconds
+    MinTime,
+    /// No transfer is possible at this location
+    Impossible,
+    /// The passengers can stay in the vehicle, which continues as another trip
+    StaySeated,
+    /// The vehicle continues as another trip, but the passengers have to get off and board again
+    InSeatNotAllowed,
+    /// A value not defined by the specification
+    Other(u16),
+}
+
+impl<'de> Deserialize<'de> for TransferType {
+    fn deserialize<D>(deserializer: D) -> Result<TransferType, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let i = Option::<u16>::deserialize(deserializer)?;
+        Ok(match i {
+            Some(0) | None => TransferType::Recommended,
+            Some(1) => TransferType::Timed,
+            Some(2) => TransferType::MinTime,
+            Some(3) => TransferType::Impossible,
+            Some(4) => TransferType::StaySeated,
+            Some(5) => TransferType::InSeatNotAllowed,
+            Some(a) => TransferType::Other(a),
+        })
+    }
+}
+
+impl Serialize for TransferType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u16(match self {
+            TransferType::Recommended => 0,
+            TransferType::Timed => 1,
+            TransferType::MinTime => 2,
+            TransferType::Impossible => 3,
+            TransferType::StaySeated => 4,
+            TransferType::InSeatNotAllowed => 5,
+            TransferType::Other(a) => *a,
+        })
+    }
+}
+
+/// A line of transfers.txt, describing a connection between two stops
+///
+/// The transfer can be restricted to some routes or trips.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Transfer {
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub from_route_id: Option<String>,
+    pub to_route_id: Option<String>,
+    pub from_trip_id: Option<String>,
+    pub to_trip_id: Option<String>,
+    #[serde(default)]
+    pub transfer_type: TransferType,
+    /// Time in seconds needed to make the transfer
+    pub min_transfer_time: Option<u32>,
+}
+
+impl References for Transfer {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Stop, Some(self.from_stop_id.as_str())),
+            (ObjectType::Stop, Some(self.to_stop_id.as_str())),
+            (ObjectType::Route, self.from_route_id.as_deref()),
+            (ObjectType::Route, self.to_route_id.as_deref()),
+            (ObjectType::Trip, self.from_trip_id.as_deref()),
+            (ObjectType::Trip, self.to_trip_id.as_deref()),
+        ])
+    }
+}
+
+/// Kind of a [FareMedia]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FareMediaType {
+    /// No fare media is needed, e.g. cash paid to the driver
+    #[default]
+    #[serde(rename = "0")]
+    None,
+    #[serde(rename = "1")]
+    PaperTicket,
+    #[serde(rename = "2")]
+    TransitCard,
+    /// Contactless bank card or device
+    #[serde(rename = "3")]
+    Cemv,
+    #[serde(rename = "4")]
+    MobileApp,
+}
+
+/// A line of fare_media.txt, what a rider uses to hold a fare product
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareMedia {
+    #[serde(rename = "fare_media_id")]
+    pub id: String,
+    #[serde(rename = "fare_media_name")]
+    pub name: Option<String>,
+    #[serde(rename = "fare_media_type")]
+    pub media_type: FareMediaType,
+}
+
+impl Id for FareMedia {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of rider_categories.txt, a group of riders with their own fares, such as seniors
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct RiderCategory {
+    #[serde(rename = "rider_category_id")]
+    pub id: String,
+    #[serde(rename = "rider_category_name")]
+    pub name: String,
+    /// The category used when the rider does not tell which category they belong to
+    #[serde(
+        rename = "is_default_fare_category",
+        deserialize_with = "deserialize_optional_bool",
+        serialize_with = "serialize_bool",
+        default
+    )]
+    pub is_default: bool,
+    /// Minimum age of the riders, a column of the earlier drafts of the fares extension
+    pub min_age: Option<u32>,
+    /// Maximum age of the riders, a column of the earlier drafts of the fares extension
+    pub max_age: Option<u32>,
+    /// Page explaining who belongs to the category
+    pub eligibility_url: Option<String>,
+}
+
+impl Id for RiderCategory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of fare_containers.txt, from the earlier drafts of the fares extension:
+/// a card or an account holding fare products
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareContainer {
+    #[serde(rename = "fare_container_id")]
+    pub id: String,
+    #[serde(rename = "fare_container_name")]
+    pub name: Option<String>,
+    /// Cost of the container, as written in the file
+    pub amount: Option<String>,
+    /// Minimum amount to load on the container when buying it
+    pub minimum_initial_purchase: Option<String>,
+    pub currency: Option<String>,
+}
+
+impl Id for FareContainer {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of fare_products.txt, a fare product can have one line for each fare media
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareProduct {
+    #[serde(rename = "fare_product_id")]
+    pub id: String,
+    #[serde(rename = "fare_product_name")]
+    pub name: Option<String>,
+    pub rider_category_id: Option<String>,
+    pub fare_media_id: Option<String>,
+    pub amount: String,
+    pub currency: String,
+}
+
+/// A line of fare_leg_rules.txt, giving the fare product of a leg
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareLegRule {
+    pub leg_group_id: Option<String>,
+    pub network_id: Option<String>,
+    pub from_area_id: Option<String>,
+    pub to_area_id: Option<String>,
+    pub from_timeframe_group_id: Option<String>,
+    pub to_timeframe_group_id: Option<String>,
+    pub fare_product_id: String,
+    /// When several rules match, only the ones with the highest priority apply
+    pub rule_priority: Option<u32>,
+}
+
+/// How the duration_limit of a [FareTransferRule] is measured
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DurationLimitType {
+    /// From the departure of the current leg to the arrival of the next leg
+    #[serde(rename = "0")]
+    DepartureToArrival,
+    /// From the departure of the current leg to the departure of the next leg
+    #[serde(rename = "1")]
+    DepartureToDeparture,
+    /// From the arrival of the current leg to the departure of the next leg
+    #[serde(rename = "2")]
+    ArrivalToDeparture,
+    /// From the arrival of the current leg to the arrival of the next leg
+    #[serde(rename = "3")]
+    ArrivalToArrival,
+}
+
+/// How the cost of a transfer is computed, see [FareTransferRule]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FareTransferType {
+    /// The fare of the first leg plus the transfer fare
+    #[default]
+    #[serde(rename = "0")]
+    FirstLegPlusTransfer,
+    /// The fare of the first leg plus the transfer fare plus the fare of the second leg
+    #[serde(rename = "1")]
+    AllLegsPlusTransfer,
+    /// The transfer fare only
+    #[serde(rename = "2")]
+    TransferOnly,
+}
+
+/// A line of fare_transfer_rules.txt, giving the cost of a transfer between two legs
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareTransferRule {
+    pub from_leg_group_id: Option<String>,
+    pub to_leg_group_id: Option<String>,
+    /// Number of consecutive transfers allowed, -1 if unlimited
+    pub transfer_count: Option<i32>,
+    /// Duration in seconds during which the transfer is allowed
+    pub duration_limit: Option<u32>,
+    pub duration_limit_type: Option<DurationLimitType>,
+    pub fare_transfer_type: FareTransferType,
+    pub fare_product_id: Option<String>,
+}
+
+/// A line of areas.txt, a group of stops used by the fare rules
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Area {
+    #[serde(rename = "area_id")]
+    pub id: String,
+    #[serde(rename = "area_name")]
+    pub name: Option<String>,
+}
+
+impl Id for Area {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of stop_areas.txt, assigning a stop to an [Area]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct StopArea {
+    pub area_id: String,
+    pub stop_id: String,
+}
+
+/// A line of networks.txt, a group of routes used by the fare leg rules
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    #[serde(rename = "network_id")]
+    pub id: String,
+    #[serde(rename = "network_name")]
+    pub name: Option<String>,
+}
+
+impl Id for Network {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of route_networks.txt, assigning a route to a [Network]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct RouteNetwork {
+    pub network_id: String,
+    pub route_id: String,
+}
+
+/// A line of timeframes.txt, a period of the day used by the fare leg rules
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Timeframe {
+    pub timeframe_group_id: String,
+    /// Start of the period in seconds since midnight, the whole day if absent
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub start_time: Option<u32>,
+    /// End of the period (exclusive) in seconds since midnight
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub end_time: Option<u32>,
+    pub service_id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FareAttribute {
     #[serde(rename = "fare_id")]
@@ -935,6 +1933,9 @@ pub struct FareAttribute {
     pub transfers: Transfers,
     pub agency_id: Option<String>,
     pub transfer_duration: Option<usize>,
+    /// Rules of fare_rules.txt using this fare, set when the [Gtfs] is built
+    #[serde(skip)]
+    pub rules: Vec<FareRule>,
 }
 
 impl Id for FareAttribute {
@@ -949,6 +1950,36 @@ impl Type for FareAttribute {
     }
 }
 
+impl References for FareAttribute {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![(ObjectType::Agency, self.agency_id.as_deref())])
+    }
+}
+
+/// A line of fare_rules.txt, telling when a [FareAttribute] applies
+///
+/// A fare applies to a trip if one of its rules matches: the fields that are set must all match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct FareRule {
+    pub fare_id: String,
+    pub route_id: Option<String>,
+    /// Zone of the first stop, see [Stop::zone_id]
+    pub origin_id: Option<String>,
+    /// Zone of the last stop
+    pub destination_id: Option<String>,
+    /// Zone that must be crossed, a fare has one rule per zone
+    pub contains_id: Option<String>,
+}
+
+impl References for FareRule {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Fare, Some(self.fare_id.as_str())),
+            (ObjectType::Route, self.route_id.as_deref()),
+        ])
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq)]
 pub enum PaymentMethod {
     #[serde(rename = "0")]
@@ -957,8 +1988,9 @@ pub enum PaymentMethod {
     PreBoarding,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub enum Transfers {
+    #[default]
     Unlimited,
     NoTransfer,
     UniqueTransfer,
@@ -997,12 +2029,134 @@ impl Serialize for Transfers {
     }
 }
 
-impl Default for Transfers {
-    fn default() -> Transfers {
-        Transfers::Unlimited
+/// A line of attributions.txt, an organization to credit for the data
+///
+/// Without agency_id, route_id and trip_id, the attribution applies to the whole feed.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Attribution {
+    #[serde(rename = "attribution_id")]
+    pub id: Option<String>,
+    pub agency_id: Option<String>,
+    pub route_id: Option<String>,
+    pub trip_id: Option<String>,
+    pub organization_name: String,
+    #[serde(
+        deserialize_with = "deserialize_optional_bool",
+        serialize_with = "serialize_bool",
+        default
+    )]
+    pub is_producer: bool,
+    #[serde(
+        deserialize_with = "deserialize_optional_bool",
+        serialize_with = "serialize_bool",
+        default
+    )]
+    pub is_operator: bool,
+    #[serde(
+        deserialize_with = "deserialize_optional_bool",
+        serialize_with = "serialize_bool",
+        default
+    )]
+    pub is_authority: bool,
+    #[serde(rename = "attribution_url")]
+    pub url: Option<String>,
+    #[serde(rename = "attribution_email")]
+    pub email: Option<String>,
+    #[serde(rename = "attribution_phone")]
+    pub phone: Option<String>,
+}
+
+impl References for Attribution {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        given(vec![
+            (ObjectType::Agency, self.agency_id.as_deref()),
+            (ObjectType::Route, self.route_id.as_deref()),
+            (ObjectType::Trip, self.trip_id.as_deref()),
+        ])
     }
 }
 
+/// How long in advance a [BookingRule] requires the trip to be booked
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum BookingType {
+    /// Up to the time of the trip, with the notice given by prior_notice_duration_min
+    #[default]
+    #[serde(rename = "0")]
+    RealTime,
+    /// On the day of the trip, with a prior notice
+    #[serde(rename = "1")]
+    SameDay,
+    /// Until prior_notice_last_day days before the trip
+    #[serde(rename = "2")]
+    PriorDays,
+}
+
+/// A line of booking_rules.txt, how to book a GTFS-Flex service
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct BookingRule {
+    #[serde(rename = "booking_rule_id")]
+    pub id: String,
+    pub booking_type: BookingType,
+    /// Minimum number of minutes before the trip to book it
+    pub prior_notice_duration_min: Option<u32>,
+    /// Maximum number of minutes before the trip to book it
+    pub prior_notice_duration_max: Option<u32>,
+    /// Last day before the trip to book it
+    pub prior_notice_last_day: Option<u32>,
+    /// Last time of prior_notice_last_day to book the trip, in seconds since midnight
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub prior_notice_last_time: Option<u32>,
+    /// Earliest day before the trip to book it
+    pub prior_notice_start_day: Option<u32>,
+    /// Earliest time of prior_notice_start_day to book the trip, in seconds since midnight
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub prior_notice_start_time: Option<u32>,
+    /// Service giving the days counted by prior_notice_last_day and prior_notice_start_day
+    pub prior_notice_service_id: Option<String>,
+    pub message: Option<String>,
+    pub pickup_message: Option<String>,
+    pub drop_off_message: Option<String>,
+    pub phone_number: Option<String>,
+    pub info_url: Option<String>,
+    pub booking_url: Option<String>,
+}
+
+impl Id for BookingRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of location_groups.txt, a set of stops that a GTFS-Flex stop time can serve
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct LocationGroup {
+    #[serde(rename = "location_group_id")]
+    pub id: String,
+    #[serde(rename = "location_group_name")]
+    pub name: Option<String>,
+}
+
+impl Id for LocationGroup {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of location_group_stops.txt, assigning a stop to a [LocationGroup]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct LocationGroupStop {
+    pub location_group_id: String,
+    pub stop_id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FeedInfo {
     #[serde(rename = "feed_publisher_name")]
@@ -1034,6 +2188,36 @@ pub struct FeedInfo {
     pub contact_url: Option<String>,
 }
 
+impl Translatable for FeedInfo {
+    /// The translations of feed_info have neither record_id nor field_value, as the file has
+    /// only one row
+    fn translate(&self, gtfs: &Gtfs, language: &str) -> Self {
+        let translate = |field: &str, value: &str| {
+            gtfs.translate("feed_info", field, language, "", None, value)
+        };
+        FeedInfo {
+            name: translate("feed_publisher_name", &self.name),
+            url: translate("feed_publisher_url", &self.url),
+            lang: self.lang.clone(),
+            default_lang: self.default_lang.clone(),
+            start_date: self.start_date,
+            end_date: self.end_date,
+            version: self.version.as_ref().map(|version| translate("feed_version", version)),
+            contact_email: self
+                .contact_email
+                .as_ref()
+                .map(|email| translate("feed_contact_email", email)),
+            contact_url: self.contact_url.as_ref().map(|url| translate("feed_contact_url", url)),
+        }
+    }
+}
+
+impl References for FeedInfo {
+    fn references(&self) -> Vec<(ObjectType, &str)> {
+        Vec::new()
+    }
+}
+
 impl fmt::Display for FeedInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.name)
@@ -1048,11 +2232,11 @@ where
     NaiveDate::parse_from_str(&s, "%Y%m%d").map_err(serde::de::Error::custom)
 }
 
-fn serialize_date<'ser, S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_date<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    serializer.serialize_str(format!("{}{}{}", date.year(), date.month(), date.day()).as_str())
+    serializer.serialize_str(&date.format("%Y%m%d").to_string())
 }
 
 fn deserialize_option_date<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
@@ -1074,14 +2258,12 @@ where
 {
     match date {
         None => serializer.serialize_none(),
-        Some(d) => {
-            serializer.serialize_str(format!("{}{}{}", d.year(), d.month(), d.day()).as_str())
-        }
+        Some(d) => serialize_date(d, serializer),
     }
 }
 
 fn parse_time_impl(v: Vec<&str>) -> Result<u32, std::num::ParseIntError> {
-    Ok(&v[0].parse()? * 3600u32 + &v[1].parse()? * 60u32 + &v[2].parse()?)
+    Ok(v[0].parse::<u32>()? * 3600 + v[1].parse::<u32>()? * 60 + v[2].parse::<u32>()?)
 }
 
 pub fn parse_time(s: &str) -> Result<u32, crate::Error> {
@@ -1093,6 +2275,21 @@ pub fn parse_time(s: &str) -> Result<u32, crate::Error> {
     }
 }
 
+fn deserialize_time<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_time(&s).map_err(de::Error::custom)
+}
+
+fn serialize_time<S>(time: &u32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&crate::GtfsTime::from_seconds(*time).to_string())
+}
+
 fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
 where
     D: Deserializer<'de>,
@@ -1111,7 +2308,7 @@ where
 {
     match time {
         None => serializer.serialize_none(),
-        Some(t) => serializer.serialize_str(format!("{}", t).as_str()),
+        Some(t) => serialize_time(t, serializer),
     }
 }
 
@@ -1121,7 +2318,7 @@ where
 {
     String::deserialize(de).and_then(|s| {
         let s = s.trim();
-        if s == "" {
+        if s.is_empty() {
             Ok(None)
         } else {
             s.parse().map(Some).map_err(de::Error::custom)
@@ -1130,7 +2327,7 @@ where
 }
 
 pub fn parse_color(s: &str) -> Result<RGB8, crate::Error> {
-    if s.len() != 6 {
+    if s.len() != 6 || !s.is_ascii() {
         return Err(crate::Error::InvalidColor(s.to_owned()));
     }
     let r =
@@ -1142,13 +2339,26 @@ pub fn parse_color(s: &str) -> Result<RGB8, crate::Error> {
     Ok(RGB8::new(r, g, b))
 }
 
+/// Parses a color like [parse_color], also accepting a leading `#` and the three digits shorthand
+///
+/// Those formats are not allowed by the specification, but appear in some feeds, see
+/// [crate::GtfsReaderOptions::lenient_colors].
+pub fn parse_color_lenient(s: &str) -> Result<RGB8, crate::Error> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() == 3 {
+        let expanded: String = hex.chars().flat_map(|c| vec![c, c]).collect();
+        return parse_color(&expanded).map_err(|_| crate::Error::InvalidColor(s.to_owned()));
+    }
+    parse_color(hex).map_err(|_| crate::Error::InvalidColor(s.to_owned()))
+}
+
 fn de_with_optional_color<'de, D>(de: D) -> Result<Option<RGB8>, D::Error>
 where
     D: Deserializer<'de>,
 {
     String::deserialize(de).and_then(|s| {
         let s = s.trim();
-        if s == "" {
+        if s.is_empty() {
             Ok(None)
         } else {
             parse_color(s).map(Some).map_err(de::Error::custom)
@@ -1168,10 +2378,10 @@ where
     }
 }
 
-pub fn de_with_empty_default<'de, T: Default, D>(de: D) -> Result<T, D::Error>
+pub fn de_with_empty_default<'de, T, D>(de: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
-    T: Deserialize<'de>,
+    T: Deserialize<'de> + Default,
 {
     Option::<T>::deserialize(de).map(|opt| opt.unwrap_or_else(Default::default))
 }
@@ -1195,11 +2405,27 @@ where
     }
 }
 
+/// An empty value is false
+fn deserialize_optional_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    match s.as_deref().map(str::trim) {
+        None | Some("") | Some("0") => Ok(false),
+        Some("1") => Ok(true),
+        Some(s) => Err(serde::de::Error::custom(format!(
+            "Invalid value `{}`, expected 0 or 1",
+            s
+        ))),
+    }
+}
+
 fn bool_default_true() -> bool {
     true
 }
 
-fn serialize_bool<'ser, S>(value: &bool, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_bool<S>(value: &bool, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
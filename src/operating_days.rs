@@ -0,0 +1,313 @@
+use crate::objects::*;
+use crate::{Error, Gtfs, HolidayKind, HolidayProvider};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Days of operation of each service, precomputed over the date range of the feed
+///
+/// Each service is stored as a bitmap with one bit per day, so testing if a service
+/// runs on a given date does not need to evaluate calendar.txt and calendar_dates.txt again.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OperatingDays {
+    start_date: Option<NaiveDate>,
+    nb_days: usize,
+    services: HashMap<String, Vec<u64>>,
+}
+
+/// How a service relates to the school holidays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HolidayVariant {
+    /// The service runs both during and outside of the school holidays
+    Regular,
+    /// The service does not run during the school holidays falling on its weekdays
+    SchoolDaysOnly,
+    /// The service only runs during holidays
+    HolidaysOnly,
+}
+
+/// Human oriented summary of when a service runs, see [Gtfs::describe_service]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDescription {
+    pub service_id: String,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    /// Days of the week on which the service usually runs
+    pub weekdays: Vec<Weekday>,
+    /// Number of days on which the service runs
+    pub nb_days: usize,
+    pub variant: HolidayVariant,
+    /// Public holidays on which the service runs, which is often an omission in calendar_dates.txt
+    pub public_holidays: Vec<NaiveDate>,
+}
+
+impl OperatingDays {
+    /// Computes the bitmaps from calendar.txt and calendar_dates.txt
+    pub fn new(
+        calendar: &BTreeMap<String, Arc<Calendar>>,
+        calendar_dates: &BTreeMap<String, Vec<CalendarDate>>,
+    ) -> Self {
+        let dates = calendar
+            .values()
+            .flat_map(|c| vec![c.start_date, c.end_date])
+            .chain(calendar_dates.values().flatten().map(|d| d.date));
+        let (start_date, end_date) = match (dates.clone().min(), dates.max()) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return Self::default(),
+        };
+        let nb_days = (end_date - start_date).num_days() as usize + 1;
+        let mut days = Self {
+            start_date: Some(start_date),
+            nb_days,
+            services: HashMap::new(),
+        };
+
+        for calendar in calendar.values() {
+            let bits = days.bitmap(&calendar.id);
+            let mut date = calendar.start_date;
+            while date <= calendar.end_date {
+                if calendar.valid_weekday(date) {
+                    let i = (date - start_date).num_days() as usize;
+                    bits[i / 64] |= 1 << (i % 64);
+                }
+                date += Duration::days(1);
+            }
+        }
+        for (service_id, calendar_dates) in calendar_dates {
+            let bits = days.bitmap(service_id);
+            for calendar_date in calendar_dates {
+                let i = (calendar_date.date - start_date).num_days() as usize;
+                match calendar_date.exception_type {
+                    Exception::Added => bits[i / 64] |= 1 << (i % 64),
+                    Exception::Deleted => bits[i / 64] &= !(1 << (i % 64)),
+                }
+            }
+        }
+        days
+    }
+
+    fn bitmap(&mut self, service_id: &str) -> &mut Vec<u64> {
+        let len = self.nb_days.div_ceil(64);
+        self.services
+            .entry(service_id.to_owned())
+            .or_insert_with(|| vec![0; len])
+    }
+
+    /// First day on which a service of the feed can run
+    pub fn start_date(&self) -> Option<NaiveDate> {
+        self.start_date
+    }
+
+    /// Last day on which a service of the feed can run
+    pub fn end_date(&self) -> Option<NaiveDate> {
+        self.start_date
+            .map(|start| start + Duration::days(self.nb_days as i64 - 1))
+    }
+
+    /// Tells if the service runs on the date, dates outside of the feed range are never active
+    pub fn is_active(&self, service_id: &str, date: NaiveDate) -> bool {
+        let start = match self.start_date {
+            Some(start) if date >= start => start,
+            _ => return false,
+        };
+        let i = (date - start).num_days() as usize;
+        i < self.nb_days
+            && self
+                .services
+                .get(service_id)
+                .is_some_and(|bits| bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Tells if two services run on at least one common day
+    pub fn share_days(&self, service_id: &str, other_service_id: &str) -> bool {
+        match (
+            self.services.get(service_id),
+            self.services.get(other_service_id),
+        ) {
+            (Some(bits), Some(other_bits)) => bits.iter().zip(other_bits).any(|(a, b)| a & b != 0),
+            _ => false,
+        }
+    }
+
+    /// All the dates on which the service runs, in chronological order
+    pub fn active_dates(&self, service_id: &str) -> Vec<NaiveDate> {
+        let (start, bits) = match (self.start_date, self.services.get(service_id)) {
+            (Some(start), Some(bits)) => (start, bits),
+            _ => return vec![],
+        };
+        (0..self.nb_days)
+            .filter(|i| bits[i / 64] & (1 << (i % 64)) != 0)
+            .map(|i| start + Duration::days(i as i64))
+            .collect()
+    }
+}
+
+impl Gtfs {
+    /// Summarizes when a service runs, using the holidays to find its variant
+    ///
+    /// Use [crate::NoHolidays] if the holidays are not known. Returns None if the service is unknown.
+    pub fn describe_service(
+        &self,
+        service_id: &str,
+        holidays: &dyn HolidayProvider,
+    ) -> Option<ServiceDescription> {
+        if !self.calendar.contains_key(service_id) && !self.calendar_dates.contains_key(service_id)
+        {
+            return None;
+        }
+        let dates = self.operating_days.active_dates(service_id);
+
+        let mut weekdays: Vec<Weekday> = match self.calendar.get(service_id) {
+            Some(calendar) => (0..7)
+                .map(|i| calendar.start_date + Duration::days(i))
+                .filter(|date| calendar.valid_weekday(*date))
+                .map(|date| date.weekday())
+                .collect(),
+            None => dates.iter().map(|date| date.weekday()).collect(),
+        };
+        weekdays.sort_by_key(|d| d.num_days_from_monday());
+        weekdays.dedup();
+
+        let during_holidays = dates
+            .iter()
+            .filter(|date| holidays.holiday(**date).is_some())
+            .count();
+        // the calendar can start before the first date on which the service runs, on a holiday
+        let span = match self.calendar.get(service_id) {
+            Some(calendar) => Some((calendar.start_date, calendar.end_date)),
+            None => dates.first().copied().zip(dates.last().copied()),
+        };
+        let school_holidays_missed = span.is_some_and(|(first, last)| {
+            first
+                .iter_days()
+                .take_while(|date| *date <= last)
+                .filter(|date| weekdays.contains(&date.weekday()))
+                .filter(|date| holidays.holiday(*date) == Some(HolidayKind::School))
+                .any(|date| !self.operating_days.is_active(service_id, date))
+        });
+        let variant = if !dates.is_empty() && during_holidays == dates.len() {
+            HolidayVariant::HolidaysOnly
+        } else if school_holidays_missed
+            && !dates
+                .iter()
+                .any(|date| holidays.holiday(*date) == Some(HolidayKind::School))
+        {
+            HolidayVariant::SchoolDaysOnly
+        } else {
+            HolidayVariant::Regular
+        };
+
+        Some(ServiceDescription {
+            service_id: service_id.to_owned(),
+            start_date: dates.first().copied(),
+            end_date: dates.last().copied(),
+            weekdays,
+            nb_days: dates.len(),
+            variant,
+            public_holidays: dates
+                .iter()
+                .copied()
+                .filter(|date| holidays.holiday(*date) == Some(HolidayKind::Public))
+                .collect(),
+        })
+    }
+
+    /// Computes again the [OperatingDays], needed after `calendar` or `calendar_dates` are modified
+    pub fn update_operating_days(&mut self) {
+        self.operating_days = OperatingDays::new(&self.calendar, &self.calendar_dates);
+    }
+
+    /// Tells if the service runs on the date, see [OperatingDays::is_active]
+    ///
+    /// The exceptions of calendar_dates.txt win over the weekdays of calendar.txt. After the
+    /// calendars are modified, [Gtfs::update_operating_days] must be called first.
+    pub fn service_active(&self, service_id: &str, date: NaiveDate) -> bool {
+        self.operating_days.is_active(service_id, date)
+    }
+
+    /// First and last dates on which the feed can be used, None if it has no service
+    ///
+    /// The range goes from the first start_date to the last end_date of calendar.txt,
+    /// extended to the dates added in calendar_dates.txt. The feed_start_date and
+    /// feed_end_date of feed_info.txt restrict this range, or give it when the feed has no
+    /// calendar. Once the end of the range is past, the feed is stale.
+    pub fn service_date_range(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let dates = self
+            .calendar
+            .values()
+            .flat_map(|c| vec![c.start_date, c.end_date])
+            .chain(
+                self.calendar_dates
+                    .values()
+                    .flatten()
+                    .filter(|d| d.exception_type == Exception::Added)
+                    .map(|d| d.date),
+            );
+        let feed_start = self.feed_info.iter().filter_map(|f| f.start_date).max();
+        let feed_end = self.feed_info.iter().filter_map(|f| f.end_date).min();
+        let (start, end) = match (dates.clone().min(), dates.max()) {
+            (Some(start), Some(end)) => (
+                feed_start.map_or(start, |f| f.max(start)),
+                feed_end.map_or(end, |f| f.min(end)),
+            ),
+            _ => (feed_start?, feed_end?),
+        };
+        Some((start, end)).filter(|(start, end)| start <= end)
+    }
+
+    /// Trips running on the date, ordered by id, see [Gtfs::service_active]
+    pub fn trips_on_date(&self, date: NaiveDate) -> impl Iterator<Item = &Trip> {
+        self.trips
+            .values()
+            .filter(move |trip| self.service_active(&trip.service_id, date))
+    }
+
+    /// Trips running on the date, ordered by id, collected from [Gtfs::trips_on_date]
+    pub fn trips_on(&self, date: NaiveDate) -> Vec<&Trip> {
+        self.trips_on_date(date).collect()
+    }
+
+    /// Routes with at least one trip running on the date, ordered by id
+    pub fn routes_on(&self, date: NaiveDate) -> Vec<&Route> {
+        let mut route_ids: Vec<&str> = self
+            .trips_on(date)
+            .into_iter()
+            .map(|trip| trip.route_id.as_str())
+            .collect();
+        route_ids.sort_unstable();
+        route_ids.dedup();
+        route_ids
+            .into_iter()
+            .filter_map(|id| self.routes.get(id))
+            .map(|route| route.as_ref())
+            .collect()
+    }
+
+    /// Number of trips of the route running on each day of the [Gtfs::service_date_range],
+    /// to chart the level of service over time
+    ///
+    /// The days without trip are given with 0. A trip repeated by frequencies counts once.
+    pub fn route_service_heatmap(&self, route_id: &str) -> Result<Vec<(NaiveDate, u32)>, Error> {
+        self.get_route(route_id)?;
+        let mut trips_by_service: HashMap<&str, u32> = HashMap::new();
+        for trip in self.trips.values().filter(|t| t.route_id == route_id) {
+            *trips_by_service.entry(&trip.service_id).or_insert(0) += 1;
+        }
+        let (start, end) = match self.service_date_range() {
+            Some(range) => range,
+            None => return Ok(vec![]),
+        };
+        Ok(start
+            .iter_days()
+            .take_while(|date| *date <= end)
+            .map(|date| {
+                let nb_trips = trips_by_service
+                    .iter()
+                    .filter(|(service_id, _)| self.operating_days.is_active(service_id, date))
+                    .map(|(_, nb_trips)| nb_trips)
+                    .sum();
+                (date, nb_trips)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+
+/// What to do with the stop times referencing a stop that is not in stops.txt, see
+/// [GtfsReaderOptions::unknown_stops]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownStopPolicy {
+    /// Fails with a [crate::Error::ReferenceError]
+    #[default]
+    Error,
+    /// Drops the stop time, reporting it as a [crate::ParseWarning]
+    Skip,
+    /// Adds a stop with only the id, flagged by [crate::Stop::missing], and keeps the stop time
+    Placeholder,
+}
+
+/// Options controlling how a GTFS is read
+///
+/// ```
+/// let options = gtfs_structures::GtfsReaderOptions::new().deduplicate_rows(true);
+/// let gtfs = gtfs_structures::Gtfs::from_path_with_options("fixtures/basic", options);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GtfsReaderOptions {
+    pub(crate) deduplicate_rows: bool,
+    /// Default values by file name, then by column name
+    pub(crate) column_defaults: HashMap<String, HashMap<String, String>>,
+    /// Number of trips kept in memory when the stop times are spilled to the disk
+    pub(crate) spill_stop_times: Option<usize>,
+    pub(crate) share_stop_patterns: bool,
+    pub(crate) skipped_files: HashSet<String>,
+    /// The only files to read, all the files are read if None
+    pub(crate) only_files: Option<HashSet<String>>,
+    pub(crate) lenient_colors: bool,
+    pub(crate) keep_coordinate_text: bool,
+    pub(crate) unknown_stops: UnknownStopPolicy,
+    pub(crate) lenient: bool,
+    pub(crate) sanitize_texts: bool,
+    pub(crate) report_defaults: bool,
+    pub(crate) sequential: bool,
+}
+
+impl GtfsReaderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops the rows that are exact duplicates of a previous row of the same file
+    ///
+    /// This often happens when several exports are concatenated. Each dropped row is reported
+    /// as a [crate::ParseWarning]. Disabled by default.
+    pub fn deduplicate_rows(mut self, deduplicate: bool) -> Self {
+        self.deduplicate_rows = deduplicate;
+        self
+    }
+
+    /// Uses a value for a column when it is empty or absent from the file
+    ///
+    /// The value is used as if it was written in the csv file, and thus takes precedence over the
+    /// default values of the crate. For example, to consider that the stop times are not
+    /// timepoints unless specified otherwise:
+    ///
+    /// ```
+    /// let options = gtfs_structures::GtfsReaderOptions::new()
+    ///     .column_default("stop_times.txt", "timepoint", "0");
+    /// ```
+    pub fn column_default(mut self, file_name: &str, column: &str, value: &str) -> Self {
+        self.column_defaults
+            .entry(file_name.to_owned())
+            .or_default()
+            .insert(column.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Accepts the colors of routes.txt written `#RRGGBB` or with three digits (`F00` or `#F00`)
+    ///
+    /// Each color read this way is reported as a [crate::ParseWarning]. When disabled (by
+    /// default), those colors are errors, as the specification only allows six hexadecimal digits.
+    pub fn lenient_colors(mut self, lenient: bool) -> Self {
+        self.lenient_colors = lenient;
+        self
+    }
+
+    /// Cleans the names, descriptions and headsigns with [crate::sanitize_text]: the control
+    /// characters are removed and the whitespace is collapsed
+    ///
+    /// Each modified value is reported as a [crate::ParseWarning], with its line and column.
+    /// The texts that are not valid UTF-8 are always errors. Disabled by default.
+    pub fn sanitize_texts(mut self, sanitize: bool) -> Self {
+        self.sanitize_texts = sanitize;
+        self
+    }
+
+    /// Reports the values read as a default value by the crate, to tell them apart from the
+    /// values written in the feed
+    ///
+    /// A [crate::ParseWarning] with the line and the column is given for each empty
+    /// location_type, wheelchair_boarding of stops.txt and transfer_type, and for each
+    /// location_type not defined by the specification, all read as 0. The columns absent from
+    /// a file are not reported, nor the values set by [GtfsReaderOptions::column_default].
+    /// Disabled by default.
+    pub fn report_defaults(mut self, report: bool) -> Self {
+        self.report_defaults = report;
+        self
+    }
+
+    /// Keeps the coordinates of stops.txt and shapes.txt as they are written, in
+    /// [crate::Stop::coordinate_text] and [crate::Shape::coordinate_text]
+    ///
+    /// The coordinates are then written back the same way by [crate::Gtfs::write_to_dir], as
+    /// needed for the submissions of feeds checked by checksum. Disabled by default, as it uses
+    /// memory for every stop and shape point.
+    pub fn keep_coordinate_text(mut self, keep: bool) -> Self {
+        self.keep_coordinate_text = keep;
+        self
+    }
+
+    /// Skips the rows that cannot be read instead of failing on the first one
+    ///
+    /// Each skipped row is reported as a [crate::ParseWarning] with its line, the column of the
+    /// value in error and the reason. When building a [crate::Gtfs], the stop times and the
+    /// frequencies of the trips that are not in trips.txt (for instance because their row was
+    /// skipped) are also skipped and reported, the stop times of unknown stops are handled by
+    /// [GtfsReaderOptions::unknown_stops]. Disabled by default.
+    ///
+    /// ```
+    /// let options = gtfs_structures::GtfsReaderOptions::new().lenient(true);
+    /// let gtfs = gtfs_structures::Gtfs::from_path_with_options("fixtures/basic", options).unwrap();
+    /// assert!(gtfs.warnings.is_empty());
+    /// ```
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// How the stop times referencing a stop that is not in stops.txt are handled when
+    /// building a [crate::Gtfs]
+    ///
+    /// By default they are errors. The trips calling at placeholder stops are reported by
+    /// [crate::Gtfs::validate].
+    ///
+    /// ```
+    /// use gtfs_structures::{GtfsReaderOptions, UnknownStopPolicy};
+    ///
+    /// let options = GtfsReaderOptions::new().unknown_stops(UnknownStopPolicy::Skip);
+    /// ```
+    pub fn unknown_stops(mut self, policy: UnknownStopPolicy) -> Self {
+        self.unknown_stops = policy;
+        self
+    }
+
+    /// Keeps the stop times in a temporary file instead of in memory
+    ///
+    /// The stop times are usually the largest part of a feed. With this option they are written
+    /// to the temporary directory, grouped by trip, and [crate::Gtfs::trip_stop_times] reads
+    /// them when needed, keeping the stop times of the last `cached_trips` trips in memory.
+    /// The `stop_times` of the trips are then empty. The rest of the feed stays in memory.
+    pub fn spill_stop_times(mut self, cached_trips: usize) -> Self {
+        self.spill_stop_times = Some(cached_trips);
+        self
+    }
+
+    /// Stores once the stop times of the trips calling at the same stops, with the same
+    /// stop sequences, headsigns, pickup and drop off types…
+    ///
+    /// For each of those trips only the arrival and departure times are kept, which uses much
+    /// less memory for the feeds with many trips following a few patterns, like commuter
+    /// rail. The `stop_times` of those trips are then empty, and [crate::Gtfs::trip_stop_times]
+    /// builds them from their pattern. Ignored when the stop times are spilled to the disk, the
+    /// [crate::RawGtfs] is not changed. Disabled by default.
+    pub fn share_stop_patterns(mut self, share: bool) -> Self {
+        self.share_stop_patterns = share;
+        self
+    }
+
+    /// Does not read a file of the GTFS, as if it was absent
+    ///
+    /// The skipped required files (like stop_times.txt) are read as empty files. The files whose
+    /// references are checked when building a [crate::Gtfs] are not read when the files they
+    /// reference are not: stop_times.txt needs trips.txt and stops.txt, frequencies.txt needs
+    /// trips.txt, transfers.txt and pathways.txt need stops.txt, fare_rules.txt needs
+    /// fare_attributes.txt, and route_networks.txt needs routes.txt.
+    ///
+    /// ```
+    /// let options = gtfs_structures::GtfsReaderOptions::new()
+    ///     .skip_file("stop_times.txt")
+    ///     .skip_file("shapes.txt");
+    /// let gtfs = gtfs_structures::Gtfs::from_path_with_options("fixtures/basic", options).unwrap();
+    /// assert!(gtfs.shapes.is_empty());
+    /// ```
+    pub fn skip_file(mut self, file_name: &str) -> Self {
+        self.skipped_files.insert(file_name.to_owned());
+        self
+    }
+
+    /// Reads only the given files of the GTFS, the other files are skipped like with
+    /// [GtfsReaderOptions::skip_file]
+    ///
+    /// ```
+    /// let options = gtfs_structures::GtfsReaderOptions::new()
+    ///     .only_files(&["stops.txt", "routes.txt"]);
+    /// let gtfs = gtfs_structures::Gtfs::from_path_with_options("fixtures/basic", options).unwrap();
+    /// assert!(gtfs.trips.is_empty());
+    /// ```
+    pub fn only_files(mut self, file_names: &[&str]) -> Self {
+        self.only_files
+            .get_or_insert_with(HashSet::new)
+            .extend(file_names.iter().map(|f| f.to_string()));
+        self
+    }
+
+    /// Reads the files one after the other, in the current thread, even with the parallel
+    /// feature
+    ///
+    /// The files of a zip archive are then decompressed while they are parsed, instead of being
+    /// decompressed in memory to be parsed in their own thread. Disabled by default.
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.sequential = sequential;
+        self
+    }
+
+    /// Whether the file must be read, see [GtfsReaderOptions::skip_file]
+    pub(crate) fn reads(&self, file_name: &str) -> bool {
+        let selected = |file_name: &str| {
+            !self.skipped_files.contains(file_name)
+                && self
+                    .only_files
+                    .as_ref()
+                    .is_none_or(|only| only.contains(file_name))
+        };
+        let referenced: &[&str] = match file_name {
+            "stop_times.txt" => &["trips.txt", "stops.txt"],
+            "frequencies.txt" => &["trips.txt"],
+            "transfers.txt" | "pathways.txt" => &["stops.txt"],
+            "fare_rules.txt" => &["fare_attributes.txt"],
+            "route_networks.txt" => &["routes.txt"],
+            _ => &[],
+        };
+        selected(file_name) && referenced.iter().all(|f| selected(f))
+    }
+}
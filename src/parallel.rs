@@ -0,0 +1,117 @@
+//! Scans of the trips split between threads, with the parallel feature
+
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use std::ops::Bound;
+
+/// Number of threads used by the scans, 1 without the parallel feature
+fn threads() -> usize {
+    if cfg!(feature = "parallel") {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        1
+    }
+}
+
+type TripRange<'a> = (Bound<&'a str>, Bound<&'a str>);
+
+impl Gtfs {
+    /// Ranges of trip ids splitting the trips in about the same number of trips, one for each
+    /// thread
+    fn trip_ranges(&self) -> Vec<TripRange<'_>> {
+        let size = self.trips.len().div_ceil(threads()).max(1);
+        let starts: Vec<&str> = self
+            .trips
+            .keys()
+            .step_by(size)
+            .map(|id| id.as_str())
+            .collect();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, start)| {
+                let end = starts
+                    .get(i + 1)
+                    .map_or(Bound::Unbounded, |end| Bound::Excluded(*end));
+                (Bound::Included(*start), end)
+            })
+            .collect()
+    }
+
+    /// Applies `f` to each range of trips in its own thread, and gives the results in the
+    /// order of the ranges
+    fn scan_trips<R, F>(&self, f: F) -> Vec<R>
+    where
+        R: Send,
+        F: Fn(TripRange<'_>) -> R + Sync,
+    {
+        let ranges = self.trip_ranges();
+        if ranges.len() <= 1 {
+            return ranges.into_iter().map(f).collect();
+        }
+        std::thread::scope(|scope| {
+            let f = &f;
+            let handles: Vec<_> = ranges
+                .into_iter()
+                .map(|range| scope.spawn(move || f(range)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        })
+    }
+
+    /// Results of `f` for each trip, ordered by trip id
+    ///
+    /// With the parallel feature, the trips are split between as many threads as the machine
+    /// has cores, without copying them. Otherwise they are scanned one after the other.
+    ///
+    /// ```
+    /// let gtfs = gtfs_structures::Gtfs::from_path("fixtures/basic").unwrap();
+    /// let counts = gtfs.par_map_trips(|trip| trip.stop_times.len());
+    /// assert_eq!(gtfs.trips.len(), counts.len());
+    /// ```
+    pub fn par_map_trips<R, F>(&self, f: F) -> Vec<R>
+    where
+        R: Send,
+        F: Fn(&Trip) -> R + Sync,
+    {
+        self.scan_trips(|range| {
+            self.trips
+                .range::<str, _>(range)
+                .map(|(_, trip)| f(trip))
+                .collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Results of `f` for each trip and its stop times, ordered by trip id, see
+    /// [Gtfs::par_map_trips]
+    ///
+    /// The stop times are given by [Gtfs::trip_stop_times], so the ones spilled to the disk or
+    /// shared by patterns are also scanned.
+    pub fn par_map_stop_times<R, F>(&self, f: F) -> Result<Vec<R>, Error>
+    where
+        R: Send,
+        F: Fn(&Trip, &[StopTime]) -> R + Sync,
+    {
+        let results = self.scan_trips(|range| {
+            self.trips
+                .range::<str, _>(range)
+                .map(|(trip_id, trip)| Ok(f(trip, &self.trip_stop_times(trip_id)?)))
+                .collect::<Result<Vec<_>, Error>>()
+        });
+        let mut all = Vec::with_capacity(self.trips.len());
+        for result in results {
+            all.extend(result?);
+        }
+        Ok(all)
+    }
+}
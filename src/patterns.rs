@@ -0,0 +1,202 @@
+//! Stop times of the trips calling at the same stops, stored once
+
+use crate::objects::*;
+use crate::Gtfs;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Arrival and departure times of the stop times of a trip
+pub(crate) type Times = Vec<(Option<u32>, Option<u32>)>;
+
+/// Stop times shared by the trips with the same stop pattern, see
+/// [crate::GtfsReaderOptions::share_stop_patterns]
+///
+/// A pattern is the list of the stop times of a trip without their arrival and departure
+/// times: the stops, stop sequences, headsigns, pickup and drop off types… Most trips of a
+/// feed follow a pattern used by many other trips, so only the times are stored for each trip.
+#[derive(Debug, Default)]
+pub struct StopPatterns {
+    /// The stop times of each pattern, without times
+    pub(crate) patterns: Vec<Vec<StopTime>>,
+    /// Index of the pattern of each trip, and its arrival and departure times
+    pub(crate) trips: HashMap<String, (usize, Times)>,
+}
+
+/// Tells if two stop times are the same, except for their arrival and departure times
+fn same_call(a: &StopTime, b: &StopTime) -> bool {
+    // destructured, so that a new field of StopTime cannot be forgotten here
+    let StopTime {
+        arrival_time: _,
+        departure_time: _,
+        stop,
+        pickup_type,
+        drop_off_type,
+        stop_sequence,
+        stop_headsign,
+        continuous_pickup,
+        continuous_drop_off,
+        shape_dist_traveled,
+        timepoint,
+        interpolated,
+        location_group_id,
+        location_id,
+        start_pickup_drop_off_window,
+        end_pickup_drop_off_window,
+        pickup_booking_rule_id,
+        drop_off_booking_rule_id,
+        #[cfg(feature = "extensions")]
+        local_zone_id,
+    } = a;
+    #[cfg(feature = "extensions")]
+    let same_zone = local_zone_id == &b.local_zone_id;
+    #[cfg(not(feature = "extensions"))]
+    let same_zone = true;
+    Arc::ptr_eq(stop, &b.stop)
+        && pickup_type == &b.pickup_type
+        && drop_off_type == &b.drop_off_type
+        && stop_sequence == &b.stop_sequence
+        && stop_headsign == &b.stop_headsign
+        && continuous_pickup == &b.continuous_pickup
+        && continuous_drop_off == &b.continuous_drop_off
+        && shape_dist_traveled == &b.shape_dist_traveled
+        && timepoint == &b.timepoint
+        && interpolated == &b.interpolated
+        && location_group_id == &b.location_group_id
+        && location_id == &b.location_id
+        && start_pickup_drop_off_window == &b.start_pickup_drop_off_window
+        && end_pickup_drop_off_window == &b.end_pickup_drop_off_window
+        && pickup_booking_rule_id == &b.pickup_booking_rule_id
+        && drop_off_booking_rule_id == &b.drop_off_booking_rule_id
+        && same_zone
+}
+
+pub(crate) fn same_pattern(a: &[StopTime], b: &[StopTime]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_call(a, b))
+}
+
+impl StopPatterns {
+    /// Moves the stop times of the trips sharing their pattern with another trip to the
+    /// patterns, the stop times of the other trips are left in the trips
+    pub(crate) fn new(trips: &mut BTreeMap<String, Trip>) -> Self {
+        // the trips by stop ids, then the distinct patterns among them and their trips
+        let mut candidates: HashMap<Vec<&str>, Vec<(&Trip, Vec<&str>)>> = HashMap::new();
+        for trip in trips.values().filter(|t| !t.stop_times.is_empty()) {
+            let stops = trip
+                .stop_times
+                .iter()
+                .map(|st| st.stop.id.as_str())
+                .collect();
+            let patterns = candidates.entry(stops).or_default();
+            match patterns
+                .iter_mut()
+                .find(|(first, _)| same_pattern(&first.stop_times, &trip.stop_times))
+            {
+                Some((_, trip_ids)) => trip_ids.push(&trip.id),
+                None => patterns.push((trip, vec![trip.id.as_str()])),
+            }
+        }
+        let mut shared: Vec<Vec<String>> = candidates
+            .into_values()
+            .flatten()
+            .filter(|(_, trip_ids)| trip_ids.len() > 1)
+            .map(|(_, trip_ids)| trip_ids.into_iter().map(str::to_owned).collect())
+            .collect();
+        // the patterns are numbered in a stable order
+        shared.sort();
+
+        let mut res = Self::default();
+        for trip_ids in shared {
+            let index = res.patterns.len();
+            for trip_id in trip_ids {
+                let trip = match trips.get_mut(&trip_id) {
+                    Some(trip) => trip,
+                    None => continue,
+                };
+                let stop_times = std::mem::take(&mut trip.stop_times);
+                let times = stop_times
+                    .iter()
+                    .map(|st| (st.arrival_time, st.departure_time))
+                    .collect();
+                if res.patterns.len() == index {
+                    let mut pattern = stop_times;
+                    for stop_time in &mut pattern {
+                        stop_time.arrival_time = None;
+                        stop_time.departure_time = None;
+                    }
+                    pattern.shrink_to_fit();
+                    res.patterns.push(pattern);
+                }
+                res.trips.insert(trip_id, (index, times));
+            }
+        }
+        res
+    }
+
+    /// Number of distinct patterns
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Number of trips whose stop times are in a pattern
+    pub fn nb_trips(&self) -> usize {
+        self.trips.len()
+    }
+
+    /// The stop times of a trip, None if they are not in a pattern
+    pub fn stop_times(&self, trip_id: &str) -> Option<Vec<StopTime>> {
+        let (pattern, times) = self.trips.get(trip_id)?;
+        Some(
+            self.patterns[*pattern]
+                .iter()
+                .zip(times)
+                .map(|(stop_time, &(arrival_time, departure_time))| StopTime {
+                    arrival_time,
+                    departure_time,
+                    ..stop_time.clone()
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Gtfs {
+    /// Stores once the stop times shared by the trips with the same stop pattern, see
+    /// [crate::GtfsReaderOptions::share_stop_patterns]
+    ///
+    /// Nothing is done if the stop times are spilled to the disk. The stop times of the
+    /// trips whose pattern is shared are moved to [Gtfs::stop_patterns], and can be read with
+    /// [Gtfs::trip_stop_times].
+    pub fn share_stop_patterns(&mut self) {
+        if self.stop_time_store.is_some() {
+            return;
+        }
+        // the trips of the previous patterns get their stop times back
+        self.unshare_stop_patterns();
+        self.stop_patterns = Some(StopPatterns::new(&mut self.trips));
+    }
+
+    /// Gives their stop times back to the trips whose pattern is shared, and removes
+    /// [Gtfs::stop_patterns]
+    ///
+    /// Returns true if the stop patterns were shared.
+    pub(crate) fn unshare_stop_patterns(&mut self) -> bool {
+        let patterns = match self.stop_patterns.take() {
+            Some(patterns) => patterns,
+            None => return false,
+        };
+        for (trip_id, trip) in self
+            .trips
+            .iter_mut()
+            .filter(|(_, t)| t.stop_times.is_empty())
+        {
+            if let Some(stop_times) = patterns.stop_times(trip_id) {
+                trip.stop_times = stop_times;
+            }
+        }
+        true
+    }
+}
@@ -1,11 +1,14 @@
+use crate::flex::{parse_locations, Location};
 use crate::objects::Translation;
 use crate::objects::*;
-use crate::Error;
+use crate::sanitize::sanitize_record;
+use crate::{Error, GtfsReaderOptions, ParseWarning, StopTimeStore};
 use chrono::Utc;
 use serde::Deserialize;
 use sha2::digest::Digest;
 use sha2::Sha256;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -22,18 +25,264 @@ pub struct RawGtfs {
     pub agencies: Result<Vec<Agency>, Error>,
     pub shapes: Option<Result<Vec<Shape>, Error>>,
     pub fare_attributes: Option<Result<Vec<FareAttribute>, Error>>,
+    pub fare_rules: Option<Result<Vec<FareRule>, Error>>,
+    pub fare_media: Option<Result<Vec<FareMedia>, Error>>,
+    pub fare_products: Option<Result<Vec<FareProduct>, Error>>,
+    pub rider_categories: Option<Result<Vec<RiderCategory>, Error>>,
+    pub fare_containers: Option<Result<Vec<FareContainer>, Error>>,
+    pub fare_leg_rules: Option<Result<Vec<FareLegRule>, Error>>,
+    pub fare_transfer_rules: Option<Result<Vec<FareTransferRule>, Error>>,
+    pub areas: Option<Result<Vec<Area>, Error>>,
+    pub stop_areas: Option<Result<Vec<StopArea>, Error>>,
+    pub networks: Option<Result<Vec<Network>, Error>>,
+    pub route_networks: Option<Result<Vec<RouteNetwork>, Error>>,
+    pub timeframes: Option<Result<Vec<Timeframe>, Error>>,
     pub feed_info: Option<Result<Vec<FeedInfo>, Error>>,
+    /// Empty when the stop times are spilled to the disk, see [GtfsReaderOptions::spill_stop_times]
     pub stop_times: Result<Vec<RawStopTime>, Error>,
+    /// The stop times written to the disk, see [GtfsReaderOptions::spill_stop_times]
+    pub stop_time_store: Option<StopTimeStore>,
+    pub frequencies: Option<Result<Vec<RawFrequency>, Error>>,
+    pub transfers: Option<Result<Vec<Transfer>, Error>>,
+    pub pathways: Option<Result<Vec<Pathway>, Error>>,
+    pub levels: Option<Result<Vec<Level>, Error>>,
     pub files: Vec<String>,
     pub sha256: Option<String>,
     pub translations: Option<Result<Vec<Translation>, Error>>,
+    pub attributions: Option<Result<Vec<Attribution>, Error>>,
+    pub booking_rules: Option<Result<Vec<BookingRule>, Error>>,
+    pub location_groups: Option<Result<Vec<LocationGroup>, Error>>,
+    pub location_group_stops: Option<Result<Vec<LocationGroupStop>, Error>>,
+    /// The GTFS-Flex zones of locations.geojson
+    pub locations: Option<Result<Vec<Location>, Error>>,
+    /// Problems that did not prevent the files from being read
+    pub warnings: Vec<ParseWarning>,
 }
 
-fn read_objs<T, O>(mut reader: T, file_name: &str) -> Result<Vec<O>, Error>
+/// Downloads the body of the response chunk by chunk, without blocking the async runtime
+#[cfg(feature = "read-url")]
+pub(crate) async fn download<U: reqwest::IntoUrl>(url: U) -> Result<Vec<u8>, Error> {
+    let mut response = reqwest::get(url).await?;
+    let mut body = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Runs the parsing of a GTFS on the blocking thread pool of tokio
+#[cfg(feature = "read-url")]
+pub(crate) async fn spawn_parsing<T, F>(parse: F) -> Result<T, Error>
 where
-    for<'de> O: Deserialize<'de>,
-    T: std::io::Read,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
 {
+    tokio::task::spawn_blocking(parse)
+        .await
+        .map_err(|e| Error::IO(std::io::Error::other(e)))?
+}
+
+/// State shared by the reading of the files of a GTFS
+pub(crate) struct ReadContext<'a> {
+    options: &'a GtfsReaderOptions,
+    pub(crate) warnings: Vec<ParseWarning>,
+}
+
+impl<'a> ReadContext<'a> {
+    pub(crate) fn new(options: &'a GtfsReaderOptions) -> Self {
+        Self {
+            options,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Reports a row that cannot be read and skips it in the lenient mode, see
+    /// [GtfsReaderOptions::lenient]
+    ///
+    /// The other errors, and all the errors outside the lenient mode, are returned.
+    pub(crate) fn skip_row(
+        &mut self,
+        error: Error,
+        headers: &csv::StringRecord,
+    ) -> Result<(), Error> {
+        let (file_name, source, line_in_error) = match error {
+            Error::CSVError {
+                file_name,
+                source,
+                line_in_error,
+            } if self.options.lenient => (file_name, source, line_in_error),
+            error => return Err(error),
+        };
+        let column = match source.kind() {
+            csv::ErrorKind::Deserialize { .. } => line_in_error.and_then(|line| line.column),
+            csv::ErrorKind::Utf8 { err, .. } => headers.get(err.field()).map(str::to_owned),
+            _ => {
+                return Err(Error::CSVError {
+                    file_name,
+                    source,
+                    line_in_error,
+                })
+            }
+        };
+        let message = match source.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+            _ => "invalid UTF-8".to_owned(),
+        };
+        self.warnings.push(ParseWarning {
+            file_name,
+            line: source.position().map(|p| p.line()),
+            column,
+            message: format!("{}, the row is skipped", message),
+        });
+        Ok(())
+    }
+}
+
+/// Reads the files of a GTFS, each in its own thread with the parallel feature
+///
+/// Each file has its own [ReadContext]. The warnings are gathered in the order the readings are
+/// joined, so they do not depend on the scheduling of the threads.
+struct Tasks<'scope, 'env> {
+    scope: &'scope std::thread::Scope<'scope, 'env>,
+    options: &'env GtfsReaderOptions,
+}
+
+/// Reading of a file, see [Tasks]
+enum Task<'scope, T> {
+    Done(T, Vec<ParseWarning>),
+    Running(std::thread::ScopedJoinHandle<'scope, (T, Vec<ParseWarning>)>),
+}
+
+impl<'scope> Tasks<'scope, '_> {
+    /// Reads a file of a directory with one of the read_file functions
+    fn read_dir<O, F>(&self, dir_path: &'scope Path, file_name: &'static str, read: F) -> Task<'scope, O>
+    where
+        O: Send + 'scope,
+        F: FnOnce(Option<Result<GtfsFile<'_>, Error>>, &str, &mut ReadContext) -> O + Send + 'scope,
+    {
+        let options = self.options;
+        self.read(move |ctx| read(GtfsFile::open(dir_path, file_name, options), file_name, ctx))
+    }
+
+    /// Tells if the files are read in their own threads
+    fn parallel(&self) -> bool {
+        cfg!(feature = "parallel") && !self.options.sequential
+    }
+
+    /// Reads a file in the current thread
+    fn run<T>(&self, read: impl FnOnce(&mut ReadContext) -> T) -> Task<'scope, T> {
+        let mut ctx = ReadContext::new(self.options);
+        let result = read(&mut ctx);
+        Task::Done(result, ctx.warnings)
+    }
+
+    fn read<T, F>(&self, read: F) -> Task<'scope, T>
+    where
+        T: Send + 'scope,
+        F: FnOnce(&mut ReadContext) -> T + Send + 'scope,
+    {
+        if !self.parallel() {
+            return self.run(read);
+        }
+        let options = self.options;
+        Task::Running(self.scope.spawn(move || {
+            let mut ctx = ReadContext::new(options);
+            let result = read(&mut ctx);
+            (result, ctx.warnings)
+        }))
+    }
+}
+
+impl<T> Task<'_, T> {
+    /// Waits for the end of the reading, and adds its warnings to the warnings of the GTFS
+    fn join(self, warnings: &mut Vec<ParseWarning>) -> T {
+        let (result, file_warnings) = match self {
+            Task::Done(result, file_warnings) => (result, file_warnings),
+            Task::Running(handle) => handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+        };
+        warnings.extend(file_warnings);
+        result
+    }
+}
+
+/// Values to use for the empty or missing columns of a file
+#[derive(Default)]
+struct ColumnDefaults {
+    /// Number of columns of the file, the missing columns are added after them in the headers
+    nb_columns: usize,
+    /// Default value by column index
+    values: Vec<Option<String>>,
+}
+
+impl ColumnDefaults {
+    fn new(headers: &mut csv::StringRecord, defaults: &HashMap<String, String>) -> Self {
+        let nb_columns = headers.len();
+        let mut values: Vec<Option<String>> =
+            headers.iter().map(|h| defaults.get(h).cloned()).collect();
+        let mut missing: Vec<(&String, &String)> = defaults
+            .iter()
+            .filter(|(column, _)| !headers.iter().any(|h| h == column.as_str()))
+            .collect();
+        missing.sort();
+        for (column, value) in missing {
+            headers.push_field(column);
+            values.push(Some(value.clone()));
+        }
+        Self { nb_columns, values }
+    }
+
+    /// Fills the empty and missing columns of the record
+    ///
+    /// The headers of the returned record are only given if they differ from the headers of the
+    /// file, when the record is shorter than them: the missing fields without default must stay
+    /// absent, and not become empty.
+    fn apply(
+        &self,
+        headers: &csv::StringRecord,
+        record: csv::StringRecord,
+    ) -> (Option<csv::StringRecord>, csv::StringRecord) {
+        if self.values.iter().all(Option::is_none) {
+            return (None, record);
+        }
+        let is_short = record.len() < self.nb_columns;
+        let mut row_headers = csv::StringRecord::new();
+        let mut with_defaults = csv::StringRecord::new();
+        for (i, default) in self.values.iter().enumerate() {
+            let value = record.get(i).filter(|_| i < self.nb_columns);
+            let value = match (value, default) {
+                (Some(value), Some(default)) if value.trim().is_empty() => default.as_str(),
+                (Some(value), _) => value,
+                (None, Some(default)) => default.as_str(),
+                (None, None) => continue,
+            };
+            with_defaults.push_field(value);
+            if is_short {
+                row_headers.push_field(&headers[i]);
+            }
+        }
+        with_defaults.set_position(record.position().cloned());
+        (Some(row_headers).filter(|_| is_short), with_defaults)
+    }
+}
+
+/// Key identifying the exact content of a row, the length prefix keeps the fields boundaries
+fn row_key(record: &csv::StringRecord) -> String {
+    let mut key = String::with_capacity(record.as_slice().len() + 2 * record.len());
+    for field in record {
+        key.push_str(&field.len().to_string());
+        key.push(':');
+        key.push_str(field);
+    }
+    key
+}
+
+/// Skips the byte order mark at the beginning of a file, if there is one
+pub(crate) fn skip_bom<T: Read>(
+    mut reader: T,
+    file_name: &str,
+) -> Result<std::io::Chain<std::io::Cursor<Vec<u8>>, T>, Error> {
     let mut bom = [0; 3];
     reader
         .read_exact(&mut bom)
@@ -41,18 +290,116 @@ where
             file_name: file_name.to_owned(),
             source: e,
         })?;
-
-    let chained = if bom != [0xefu8, 0xbbu8, 0xbfu8] {
-        bom.chain(reader)
+    let start = if bom != [0xefu8, 0xbbu8, 0xbfu8] {
+        bom.to_vec()
     } else {
-        [].chain(reader)
+        Vec::new()
     };
+    Ok(std::io::Cursor::new(start).chain(reader))
+}
+
+/// Writes the colors of the record accepted by [parse_color_lenient] in the format of the
+/// specification, see [GtfsReaderOptions::lenient_colors]
+fn fix_colors(
+    headers: &csv::StringRecord,
+    record: csv::StringRecord,
+    file_name: &str,
+    warnings: &mut Vec<ParseWarning>,
+) -> csv::StringRecord {
+    let mut fixed = csv::StringRecord::new();
+    for (i, value) in record.iter().enumerate() {
+        let column = headers.get(i).unwrap_or_default();
+        let color = value.trim();
+        let is_malformed_color = (column == "route_color" || column == "route_text_color")
+            && !color.is_empty()
+            && parse_color(color).is_err();
+        match Some(color)
+            .filter(|_| is_malformed_color)
+            .and_then(|color| parse_color_lenient(color).ok())
+        {
+            Some(parsed) => {
+                let six_digits = format!("{:02X}{:02X}{:02X}", parsed.r, parsed.g, parsed.b);
+                warnings.push(ParseWarning {
+                    file_name: file_name.to_owned(),
+                    line: record.position().map(|p| p.line()),
+                    column: Some(column.to_owned()),
+                    message: format!(
+                        "invalid color \"{}\" of {}, read as {}",
+                        color, column, six_digits
+                    ),
+                });
+                fixed.push_field(&six_digits);
+            }
+            None => fixed.push_field(value),
+        }
+    }
+    fixed.set_position(record.position().cloned());
+    fixed
+}
+
+/// Columns whose empty values, or values not defined by the specification, are read as a
+/// default value: (file name, column, values of the specification, default value)
+const DEFAULTED_COLUMNS: &[(&str, &str, &[&str], &str)] = &[
+    ("stops.txt", "location_type", &["0", "1", "2", "3", "4"], "0"),
+    ("stops.txt", "wheelchair_boarding", &["0", "1", "2"], "0"),
+    ("transfers.txt", "transfer_type", &["0", "1", "2", "3", "4", "5"], "0"),
+];
 
+/// Reports the values of the record read as a default value, see
+/// [GtfsReaderOptions::report_defaults]
+///
+/// Only [LocationType] accepts any value: the values of the other columns not defined by the
+/// specification are errors, or are kept like [TransferType::Other].
+fn report_defaults(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+    file_name: &str,
+    warnings: &mut Vec<ParseWarning>,
+) {
+    let columns = DEFAULTED_COLUMNS.iter().filter(|(file, ..)| *file == file_name);
+    for (_, column, values, default) in columns {
+        let value = match headers.iter().position(|h| h == *column) {
+            Some(i) => record.get(i).unwrap_or_default().trim(),
+            None => continue,
+        };
+        let message = if value.is_empty() {
+            format!("empty {}, read as {}", column, default)
+        } else if *column == "location_type" && !values.contains(&value) {
+            format!("unknown {} \"{}\", read as {}", column, value, default)
+        } else {
+            continue;
+        };
+        warnings.push(ParseWarning {
+            file_name: file_name.to_owned(),
+            line: record.position().map(|p| p.line()),
+            column: Some((*column).to_owned()),
+            message,
+        });
+    }
+}
+
+/// Reads the csv records of a file, with the column defaults applied and the duplicates removed
+///
+/// The callback is given the headers of each record: they are the headers of the file,
+/// unless the record is shorter than them and some column defaults are set.
+///
+/// In the lenient mode, the records that cannot be read or whose callback fails to deserialize
+/// them are skipped, see [ReadContext::skip_row].
+pub(crate) fn read_records<T, F>(
+    reader: T,
+    file_name: &str,
+    ctx: &mut ReadContext,
+    mut on_record: F,
+) -> Result<(), Error>
+where
+    T: std::io::Read,
+    F: FnMut(&csv::StringRecord, csv::StringRecord) -> Result<(), Error>,
+{
     let mut reader = csv::ReaderBuilder::new()
         .flexible(true)
-        .from_reader(chained);
+        .from_reader(skip_bom(reader, file_name)?);
     // We store the headers to be able to return them in case of errors
-    let headers = reader
+    let mut headers = reader
         .headers()
         .map_err(|e| Error::CSVError {
             file_name: file_name.to_owned(),
@@ -60,95 +407,518 @@ where
             line_in_error: None,
         })?
         .clone();
+    let defaults = ctx
+        .options
+        .column_defaults
+        .get(file_name)
+        .map(|defaults| ColumnDefaults::new(&mut headers, defaults))
+        .unwrap_or_default();
 
-    let mut res = Vec::new();
+    // Line of the first occurrence of each row, when deduplicating
+    let mut seen_rows: HashMap<String, u64> = HashMap::new();
     for rec in reader.records() {
-        let r = rec.map_err(|e| Error::CSVError {
-            file_name: file_name.to_owned(),
-            source: e,
-            line_in_error: None,
-        })?;
-        let o = r.deserialize(Some(&headers)).map_err(|e| Error::CSVError {
+        let r = match rec {
+            Ok(r) => r,
+            Err(e) => {
+                let error = Error::CSVError {
+                    file_name: file_name.to_owned(),
+                    source: e,
+                    line_in_error: None,
+                };
+                ctx.skip_row(error, &headers)?;
+                continue;
+            }
+        };
+        if ctx.options.deduplicate_rows {
+            let line = r.position().map_or(0, |p| p.line());
+            match seen_rows.entry(row_key(&r)) {
+                Entry::Occupied(first) => {
+                    ctx.warnings.push(ParseWarning {
+                        file_name: file_name.to_owned(),
+                        line: Some(line),
+                        column: None,
+                        message: format!("duplicate of line {}, the row is ignored", first.get()),
+                    });
+                    continue;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(line);
+                }
+            }
+        }
+        let (row_headers, r) = defaults.apply(&headers, r);
+        let row_headers = row_headers.as_ref().unwrap_or(&headers);
+        let r = if ctx.options.lenient_colors && file_name == "routes.txt" {
+            fix_colors(row_headers, r, file_name, &mut ctx.warnings)
+        } else {
+            r
+        };
+        let r = if ctx.options.sanitize_texts {
+            sanitize_record(row_headers, r, file_name, &mut ctx.warnings)
+        } else {
+            r
+        };
+        if ctx.options.report_defaults {
+            report_defaults(row_headers, &r, file_name, &mut ctx.warnings);
+        }
+        if let Err(error) = on_record(row_headers, r) {
+            ctx.skip_row(error, row_headers)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn deserialize_record<O>(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+    file_name: &str,
+) -> Result<O, Error>
+where
+    for<'de> O: Deserialize<'de>,
+{
+    FAILED_COLUMN.with(|c| c.set(None));
+    record
+        .deserialize::<Columns<O>>(Some(headers))
+        .map(|columns| columns.0)
+        .map_err(|e| Error::CSVError {
             file_name: file_name.to_owned(),
-            source: e,
             line_in_error: Some(crate::error::LineError {
                 headers: headers.into_iter().map(|s| s.to_owned()).collect(),
-                values: r.into_iter().map(|s| s.to_owned()).collect(),
+                values: record.into_iter().map(|s| s.to_owned()).collect(),
+                column: error_column(&e)
+                    .and_then(|i| headers.get(i))
+                    .map(str::to_owned),
             }),
-        })?;
-        res.push(o);
+            source: e,
+        })
+}
+
+/// Index of the column of the value that could not be deserialized
+///
+/// The line is given by [csv::Error::position]. csv only knows the column of the values it
+/// parses itself, the errors of the deserializers of the crate get the column tracked by
+/// [Columns].
+fn error_column(error: &csv::Error) -> Option<usize> {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err
+            .field()
+            .map(|field| field as usize)
+            .or_else(|| FAILED_COLUMN.with(|c| c.take())),
+        _ => None,
     }
+}
 
-    Ok(res)
+thread_local! {
+    /// Column of the value that failed to deserialize in the last record of the thread
+    static FAILED_COLUMN: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
 }
 
-fn read_objs_from_path<O>(path: std::path::PathBuf) -> Result<Vec<O>, Error>
-where
-    for<'de> O: Deserialize<'de>,
-{
-    let file_name = path
-        .file_name()
-        .and_then(|f| f.to_str())
-        .unwrap_or_else(|| "invalid_file_name")
-        .to_string();
-    File::open(path)
-        .map_err(|e| Error::MissingFile(format!("Could not find file: {}", e)))
-        .and_then(|r| read_objs(r, &file_name))
-}
-
-fn read_objs_from_optional_path<O>(
-    dir_path: &std::path::Path,
-    file_name: &str,
-) -> Option<Result<Vec<O>, Error>>
+/// A record deserialized while counting its columns, to know the column of a value that fails
+/// to deserialize
+struct Columns<O>(O);
+
+impl<'de, O: Deserialize<'de>> Deserialize<'de> for Columns<O> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        O::deserialize(ColumnDeserializer(deserializer)).map(Columns)
+    }
+}
+
+struct ColumnDeserializer<D>(D);
+
+impl<'de, D: serde::Deserializer<'de>> serde::Deserializer<'de> for ColumnDeserializer<D> {
+    type Error = D::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(ColumnVisitor(visitor))
+    }
+
+    fn deserialize_map<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0.deserialize_map(ColumnVisitor(visitor))
+    }
+
+    fn deserialize_struct<V: serde::de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.0.deserialize_struct(name, fields, ColumnVisitor(visitor))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+struct ColumnVisitor<V>(V);
+
+impl<'de, V: serde::de::Visitor<'de>> serde::de::Visitor<'de> for ColumnVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.expecting(formatter)
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(ColumnMap { map, column: 0 })
+    }
+}
+
+/// The values of a record, read in the order of the columns
+struct ColumnMap<A> {
+    map: A,
+    column: usize,
+}
+
+impl<'de, A: serde::de::MapAccess<'de>> serde::de::MapAccess<'de> for ColumnMap<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error> {
+        self.map.next_key_seed(seed)
+    }
+
+    fn next_value_seed<S: serde::de::DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, A::Error> {
+        let value = self.map.next_value_seed(seed);
+        if value.is_err() {
+            FAILED_COLUMN.with(|c| c.set(Some(self.column)));
+        }
+        self.column += 1;
+        value
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.map.size_hint()
+    }
+}
+
+/// A record read from a csv file, that can keep its coordinates as they are written, see
+/// [GtfsReaderOptions::keep_coordinate_text]
+pub(crate) trait CoordinateRecord {
+    /// Stores the coordinates of a stop or a shape point, the other records having none
+    fn set_coordinate_text(&mut self, _headers: &csv::StringRecord, _record: &csv::StringRecord) {}
+}
+
+/// The coordinates of the columns `latitude` and `longitude` as they are written in the record
+fn coordinate_text(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+    latitude: &str,
+    longitude: &str,
+) -> Option<Box<CoordinateText>> {
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .and_then(|i| record.get(i))
+            .unwrap_or_default()
+            .to_owned()
+    };
+    Some(Box::new(CoordinateText {
+        latitude: column(latitude),
+        longitude: column(longitude),
+    }))
+}
+
+impl CoordinateRecord for Stop {
+    fn set_coordinate_text(&mut self, headers: &csv::StringRecord, record: &csv::StringRecord) {
+        self.coordinate_text = coordinate_text(headers, record, "stop_lat", "stop_lon");
+    }
+}
+
+impl CoordinateRecord for Shape {
+    fn set_coordinate_text(&mut self, headers: &csv::StringRecord, record: &csv::StringRecord) {
+        self.coordinate_text = coordinate_text(headers, record, "shape_pt_lat", "shape_pt_lon");
+    }
+}
+
+impl CoordinateRecord for Agency {}
+impl CoordinateRecord for Calendar {}
+impl CoordinateRecord for CalendarDate {}
+impl CoordinateRecord for Route {}
+impl CoordinateRecord for RawTrip {}
+impl CoordinateRecord for RawStopTime {}
+impl CoordinateRecord for FareAttribute {}
+impl CoordinateRecord for FareRule {}
+impl CoordinateRecord for FareMedia {}
+impl CoordinateRecord for FareProduct {}
+impl CoordinateRecord for RiderCategory {}
+impl CoordinateRecord for FareContainer {}
+impl CoordinateRecord for FareLegRule {}
+impl CoordinateRecord for FareTransferRule {}
+impl CoordinateRecord for Area {}
+impl CoordinateRecord for StopArea {}
+impl CoordinateRecord for Network {}
+impl CoordinateRecord for RouteNetwork {}
+impl CoordinateRecord for Timeframe {}
+impl CoordinateRecord for FeedInfo {}
+impl CoordinateRecord for RawFrequency {}
+impl CoordinateRecord for Transfer {}
+impl CoordinateRecord for Pathway {}
+impl CoordinateRecord for Level {}
+impl CoordinateRecord for Translation {}
+impl CoordinateRecord for Attribution {}
+impl CoordinateRecord for BookingRule {}
+impl CoordinateRecord for LocationGroup {}
+impl CoordinateRecord for LocationGroupStop {}
+
+fn read_objs<T, O>(reader: T, file_name: &str, ctx: &mut ReadContext) -> Result<Vec<O>, Error>
 where
-    for<'de> O: Deserialize<'de>,
+    for<'de> O: Deserialize<'de> + CoordinateRecord,
+    T: std::io::Read,
 {
-    File::open(dir_path.join(file_name))
-        .ok()
-        .map(|r| read_objs(r, file_name))
+    let mut res = Vec::new();
+    let keep_text = ctx.options.keep_coordinate_text;
+    read_records(reader, file_name, ctx, |headers, record| {
+        let mut obj: O = deserialize_record(headers, &record, file_name)?;
+        if keep_text {
+            obj.set_coordinate_text(headers, &record);
+        }
+        res.push(obj);
+        Ok(())
+    })?;
+    Ok(res)
+}
+
+/// A file of a GTFS, opened in a directory or in a zip archive
+///
+/// A file of a zip archive is decompressed while it is parsed, the [zip] crate checking its
+/// CRC32 at the end. It is only decompressed beforehand when it is parsed in another thread.
+pub(crate) enum GtfsFile<'a> {
+    File(File),
+    Zip(Box<zip::read::ZipFile<'a>>),
+    Decompressed(std::io::Cursor<Vec<u8>>),
+}
+
+impl GtfsFile<'_> {
+    /// Opens a file of a directory, None if the file is absent or skipped
+    pub(crate) fn open(dir_path: &Path, file_name: &str, options: &GtfsReaderOptions) -> Option<Result<Self, Error>> {
+        if !options.reads(file_name) {
+            return None;
+        }
+        match File::open(dir_path.join(file_name)) {
+            Ok(file) => Some(Ok(GtfsFile::File(file))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(source) => Some(Err(Error::NamedFileIO {
+                file_name: file_name.to_owned(),
+                source,
+            })),
+        }
+    }
 }
 
-fn read_file<O, T>(
-    file_mapping: &HashMap<&&str, usize>,
-    archive: &mut zip::ZipArchive<T>,
+impl Read for GtfsFile<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            GtfsFile::File(file) => file.read(buf),
+            GtfsFile::Zip(file) => file.read(buf),
+            GtfsFile::Decompressed(content) => content.read(buf),
+        }
+    }
+}
+
+/// The files of a GTFS that are read
+pub(crate) const GTFS_FILES: &[&str] = &[
+    "agency.txt",
+    "calendar.txt",
+    "calendar_dates.txt",
+    "routes.txt",
+    "stops.txt",
+    "stop_times.txt",
+    "frequencies.txt",
+    "transfers.txt",
+    "pathways.txt",
+    "levels.txt",
+    "trips.txt",
+    "fare_attributes.txt",
+    "fare_rules.txt",
+    "fare_media.txt",
+    "rider_categories.txt",
+    "fare_containers.txt",
+    "fare_products.txt",
+    "fare_leg_rules.txt",
+    "fare_transfer_rules.txt",
+    "areas.txt",
+    "stop_areas.txt",
+    "networks.txt",
+    "route_networks.txt",
+    "timeframes.txt",
+    "feed_info.txt",
+    "shapes.txt",
+    "translations.txt",
+    "attributions.txt",
+    "booking_rules.txt",
+    "location_groups.txt",
+    "location_group_stops.txt",
+    "locations.geojson",
+];
+
+/// The files of a zip archive that are part of a GTFS, by file name
+pub(crate) struct ZipFiles<T> {
+    archive: zip::ZipArchive<T>,
+    indexes: HashMap<&'static str, usize>,
+}
+
+impl<T: std::io::Read + std::io::Seek> ZipFiles<T> {
+    /// Finds the files of the GTFS in the archive, also returns the names of all its files
+    pub(crate) fn new(mut archive: zip::ZipArchive<T>) -> Result<(Self, Vec<String>), Error> {
+        let mut indexes = HashMap::new();
+        let mut files = Vec::new();
+        for i in 0..archive.len() {
+            let archive_file = archive.by_index(i)?;
+            files.push(archive_file.name().to_owned());
+            let path = std::path::Path::new(archive_file.name());
+            if let Some(gtfs_file) = GTFS_FILES
+                .iter()
+                .find(|f| path.file_name() == Some(std::ffi::OsStr::new(f)))
+            {
+                indexes.insert(*gtfs_file, i);
+            }
+        }
+        Ok((Self { archive, indexes }, files))
+    }
+
+    /// A file of the archive, None if the file is absent or skipped
+    pub(crate) fn entry(
+        &mut self,
+        file_name: &str,
+        options: &GtfsReaderOptions,
+    ) -> Option<Result<GtfsFile<'_>, Error>> {
+        if !options.reads(file_name) {
+            return None;
+        }
+        let index = *self.indexes.get(file_name)?;
+        Some(
+            self.archive
+                .by_index(index)
+                .map(|file| GtfsFile::Zip(Box::new(file)))
+                .map_err(|_| Error::MissingFile(format!("Could not find file: {}", file_name))),
+        )
+    }
+
+    /// Decompressed content of a file, to parse it in another thread
+    fn content(&mut self, file_name: &str, options: &GtfsReaderOptions) -> Option<Result<Vec<u8>, Error>> {
+        self.entry(file_name, options).map(|entry| {
+            let mut data = Vec::new();
+            entry?.read_to_end(&mut data).map_err(|source| Error::NamedFileIO {
+                file_name: file_name.to_owned(),
+                source,
+            })?;
+            Ok(data)
+        })
+    }
+
+    /// Reads a file with one of the read_file functions, see [Tasks::read]
+    ///
+    /// The file is only decompressed in memory when it is parsed in another thread.
+    fn read<'scope, O, F>(&mut self, tasks: &Tasks<'scope, '_>, file_name: &'static str, read: F) -> Task<'scope, O>
+    where
+        O: Send + 'scope,
+        F: FnOnce(Option<Result<GtfsFile<'_>, Error>>, &str, &mut ReadContext) -> O + Send + 'scope,
+    {
+        if tasks.parallel() {
+            let content = self.content(file_name, tasks.options);
+            tasks.read(move |ctx| {
+                let file = content.map(|data| Ok(GtfsFile::Decompressed(std::io::Cursor::new(data?))));
+                read(file, file_name, ctx)
+            })
+        } else {
+            tasks.run(|ctx| read(self.entry(file_name, tasks.options), file_name, ctx))
+        }
+    }
+}
+
+/// Reads a required file, opened by [GtfsFile::open] or [ZipFiles::entry]
+pub(crate) fn read_file<O>(
+    file: Option<Result<GtfsFile<'_>, Error>>,
     file_name: &str,
+    ctx: &mut ReadContext,
 ) -> Result<Vec<O>, Error>
 where
-    for<'de> O: Deserialize<'de>,
-    T: std::io::Read + std::io::Seek,
+    for<'de> O: Deserialize<'de> + CoordinateRecord,
 {
-    file_mapping
-        .get(&file_name)
-        .map(|i| {
-            read_objs(
-                archive.by_index(*i).map_err(|_| {
-                    Error::MissingFile(format!("Could not find file: {}", file_name.clone()))
-                })?,
-                file_name,
-            )
-        })
-        .unwrap_or_else(|| Err(Error::MissingFile(file_name.to_owned())))
+    if !ctx.options.reads(file_name) {
+        return Ok(Vec::new());
+    }
+    let file = file.unwrap_or_else(|| Err(Error::MissingFile(file_name.to_owned())))?;
+    read_objs(file, file_name, ctx)
 }
 
-fn read_optional_file<O, T>(
-    file_mapping: &HashMap<&&str, usize>,
-    archive: &mut zip::ZipArchive<T>,
+/// Reads an optional file, None if it is absent or skipped
+pub(crate) fn read_optional_file<O>(
+    file: Option<Result<GtfsFile<'_>, Error>>,
     file_name: &str,
+    ctx: &mut ReadContext,
 ) -> Option<Result<Vec<O>, Error>>
 where
-    for<'de> O: Deserialize<'de>,
-    T: std::io::Read + std::io::Seek,
+    for<'de> O: Deserialize<'de> + CoordinateRecord,
 {
-    file_mapping.get(&file_name).map(|i| {
-        read_objs(
-            archive.by_index(*i).map_err(|_| {
-                Error::MissingFile(format!("Could not find file: {}", file_name.clone()))
-            })?,
-            file_name,
-        )
+    file.map(|file| read_objs(file?, file_name, ctx))
+}
+
+/// Reads the GTFS-Flex zones of locations.geojson, None if it is absent or skipped
+pub(crate) fn read_locations_file(
+    file: Option<Result<GtfsFile<'_>, Error>>,
+    file_name: &str,
+    _: &mut ReadContext,
+) -> Option<Result<Vec<Location>, Error>> {
+    file.map(|file| {
+        let mut content = String::new();
+        file?
+            .read_to_string(&mut content)
+            .map_err(|source| Error::NamedFileIO {
+                file_name: file_name.to_owned(),
+                source,
+            })?;
+        parse_locations(&content)
     })
 }
 
+/// Reads stop_times.txt, or writes it to a [StopTimeStore] if the stop times are spilled to the disk
+fn read_stop_times_file(
+    file: Option<Result<GtfsFile<'_>, Error>>,
+    file_name: &str,
+    ctx: &mut ReadContext,
+) -> (Result<Vec<RawStopTime>, Error>, Option<StopTimeStore>) {
+    let cached_trips = match ctx.options.spill_stop_times {
+        Some(cached_trips) if ctx.options.reads(file_name) => cached_trips,
+        _ => return (read_file(file, file_name, ctx), None),
+    };
+    let store = file
+        .unwrap_or_else(|| Err(Error::MissingFile(file_name.to_owned())))
+        .and_then(|file| StopTimeStore::new(file, ctx, cached_trips));
+    match store {
+        Ok(store) => (Ok(Vec::new()), Some(store)),
+        Err(e) => (Err(e), None),
+    }
+}
+
+/// Counts the records of a csv file, without deserializing them
+fn count_records<T: std::io::Read>(reader: T, file_name: &str) -> Result<usize, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(reader);
+    let mut record = csv::ByteRecord::new();
+    let mut count = 0;
+    while reader
+        .read_byte_record(&mut record)
+        .map_err(|e| Error::CSVError {
+            file_name: file_name.to_owned(),
+            source: e,
+            line_in_error: None,
+        })?
+    {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Tells if the file is a csv file of the GTFS, ignoring hidden files like macOS resource forks
+fn is_gtfs_csv_file(file_name: &str) -> bool {
+    file_name.ends_with(".txt") && !file_name.starts_with('.')
+}
+
 fn mandatory_file_summary<T>(objs: &Result<Vec<T>, Error>) -> String {
     match objs {
         Ok(vec) => format!("{} objects", vec.len()),
@@ -173,9 +943,33 @@ impl RawGtfs {
         println!("  Trips: {}", mandatory_file_summary(&self.trips));
         println!("  Agencies: {}", mandatory_file_summary(&self.agencies));
         println!("  Stop times: {}", mandatory_file_summary(&self.stop_times));
+        println!("  Frequencies: {}", optional_file_summary(&self.frequencies));
+        println!("  Transfers: {}", optional_file_summary(&self.transfers));
+        println!("  Pathways: {}", optional_file_summary(&self.pathways));
+        println!("  Levels: {}", optional_file_summary(&self.levels));
         println!("  Shapes: {}", optional_file_summary(&self.shapes));
         println!("  Fares: {}", optional_file_summary(&self.fare_attributes));
+        println!("  Fare rules: {}", optional_file_summary(&self.fare_rules));
+        println!("  Fare media: {}", optional_file_summary(&self.fare_media));
+        println!("  Rider categories: {}", optional_file_summary(&self.rider_categories));
+        println!("  Fare containers: {}", optional_file_summary(&self.fare_containers));
+        println!("  Fare products: {}", optional_file_summary(&self.fare_products));
+        println!("  Fare leg rules: {}", optional_file_summary(&self.fare_leg_rules));
+        println!("  Fare transfer rules: {}", optional_file_summary(&self.fare_transfer_rules));
+        println!("  Areas: {}", optional_file_summary(&self.areas));
+        println!("  Stop areas: {}", optional_file_summary(&self.stop_areas));
+        println!("  Networks: {}", optional_file_summary(&self.networks));
+        println!("  Route networks: {}", optional_file_summary(&self.route_networks));
+        println!("  Timeframes: {}", optional_file_summary(&self.timeframes));
         println!("  Feed info: {}", optional_file_summary(&self.feed_info));
+        println!("  Attributions: {}", optional_file_summary(&self.attributions));
+        println!("  Booking rules: {}", optional_file_summary(&self.booking_rules));
+        println!("  Location groups: {}", optional_file_summary(&self.location_groups));
+        println!(
+            "  Location group stops: {}",
+            optional_file_summary(&self.location_group_stops)
+        );
+        println!("  Locations: {}", optional_file_summary(&self.locations));
     }
 
     /// Reads from an url (if starts with http), or a local path (either a directory or zipped file)
@@ -195,23 +989,73 @@ impl RawGtfs {
         Self::from_path(gtfs_source)
     }
 
+    /// Counts the records of each csv file of a local zip archive or local directory
+    ///
+    /// The rows are only split, not deserialized: this is much faster than reading the GTFS
+    /// and gives an idea of the size of a feed before loading it.
+    /// The keys are the file names, without the directory they are in.
+    pub fn record_counts<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, usize>, Error> {
+        let p = path.as_ref();
+        let mut counts = BTreeMap::new();
+        if p.is_file() {
+            let mut archive = zip::ZipArchive::new(File::open(p)?)?;
+            for i in 0..archive.len() {
+                let archive_file = archive.by_index(i)?;
+                let file_name = match Path::new(archive_file.name())
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                {
+                    Some(f) if is_gtfs_csv_file(f) => f.to_owned(),
+                    _ => continue,
+                };
+                let count = count_records(archive_file, &file_name)?;
+                counts.insert(file_name, count);
+            }
+        } else if p.is_dir() {
+            for entry in std::fs::read_dir(p)? {
+                let path = entry?.path();
+                let file_name = match path.file_name().and_then(|f| f.to_str()) {
+                    Some(f) if is_gtfs_csv_file(f) => f.to_owned(),
+                    _ => continue,
+                };
+                let file = File::open(&path).map_err(|e| Error::NamedFileIO {
+                    file_name: file_name.clone(),
+                    source: e,
+                })?;
+                let count = count_records(file, &file_name)?;
+                counts.insert(file_name, count);
+            }
+        } else {
+            return Err(Error::NotFileNorDirectory(format!("{}", p.display())));
+        }
+        Ok(counts)
+    }
+
     /// Reads the raw GTFS from a local zip archive or local directory
     pub fn from_path<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path> + std::fmt::Display,
+    {
+        Self::from_path_with_options(path, GtfsReaderOptions::default())
+    }
+
+    /// Reads the raw GTFS from a local zip archive or local directory, with custom options
+    pub fn from_path_with_options<P>(path: P, options: GtfsReaderOptions) -> Result<Self, Error>
     where
         P: AsRef<Path> + std::fmt::Display,
     {
         let p = path.as_ref();
         if p.is_file() {
             let reader = File::open(p)?;
-            Self::from_reader(reader)
+            Self::from_reader_with_options(reader, options)
         } else if p.is_dir() {
-            Self::from_directory(p)
+            Self::from_directory(p, &options)
         } else {
             Err(Error::NotFileNorDirectory(format!("{}", p.display())))
         }
     }
 
-    fn from_directory(p: &std::path::Path) -> Result<Self, Error> {
+    fn from_directory(p: &std::path::Path, options: &GtfsReaderOptions) -> Result<Self, Error> {
         let now = Utc::now();
         // Thoses files are not mandatory
         // We use None if they don’t exist, not an Error
@@ -219,21 +1063,87 @@ impl RawGtfs {
             .filter_map(|d| d.ok().and_then(|p| p.path().to_str().map(|s| s.to_owned())))
             .collect();
 
-        Ok(Self {
-            trips: read_objs_from_path(p.join("trips.txt")),
-            calendar: read_objs_from_optional_path(&p, "calendar.txt"),
-            calendar_dates: read_objs_from_optional_path(&p, "calendar_dates.txt"),
-            stops: read_objs_from_path(p.join("stops.txt")),
-            routes: read_objs_from_path(p.join("routes.txt")),
-            stop_times: read_objs_from_path(p.join("stop_times.txt")),
-            agencies: read_objs_from_path(p.join("agency.txt")),
-            shapes: read_objs_from_optional_path(&p, "shapes.txt"),
-            fare_attributes: read_objs_from_optional_path(&p, "fare_attributes.txt"),
-            feed_info: read_objs_from_optional_path(&p, "feed_info.txt"),
-            translations: read_objs_from_optional_path(&p, "translations.txt"),
-            read_duration: Utc::now().signed_duration_since(now).num_milliseconds(),
-            files,
-            sha256: None,
+        std::thread::scope(|scope| {
+            let tasks = Tasks { scope, options };
+            let trips = tasks.read_dir(p, "trips.txt", read_file);
+            let stops = tasks.read_dir(p, "stops.txt", read_file);
+            let routes = tasks.read_dir(p, "routes.txt", read_file);
+            let agencies = tasks.read_dir(p, "agency.txt", read_file);
+            let stop_times = tasks.read_dir(p, "stop_times.txt", read_stop_times_file);
+            let calendar = tasks.read_dir(p, "calendar.txt", read_optional_file);
+            let calendar_dates = tasks.read_dir(p, "calendar_dates.txt", read_optional_file);
+            let frequencies = tasks.read_dir(p, "frequencies.txt", read_optional_file);
+            let transfers = tasks.read_dir(p, "transfers.txt", read_optional_file);
+            let pathways = tasks.read_dir(p, "pathways.txt", read_optional_file);
+            let levels = tasks.read_dir(p, "levels.txt", read_optional_file);
+            let shapes = tasks.read_dir(p, "shapes.txt", read_optional_file);
+            let fare_attributes = tasks.read_dir(p, "fare_attributes.txt", read_optional_file);
+            let fare_rules = tasks.read_dir(p, "fare_rules.txt", read_optional_file);
+            let fare_media = tasks.read_dir(p, "fare_media.txt", read_optional_file);
+            let rider_categories = tasks.read_dir(p, "rider_categories.txt", read_optional_file);
+            let fare_containers = tasks.read_dir(p, "fare_containers.txt", read_optional_file);
+            let fare_products = tasks.read_dir(p, "fare_products.txt", read_optional_file);
+            let fare_leg_rules = tasks.read_dir(p, "fare_leg_rules.txt", read_optional_file);
+            let fare_transfer_rules = tasks.read_dir(p, "fare_transfer_rules.txt", read_optional_file);
+            let areas = tasks.read_dir(p, "areas.txt", read_optional_file);
+            let stop_areas = tasks.read_dir(p, "stop_areas.txt", read_optional_file);
+            let networks = tasks.read_dir(p, "networks.txt", read_optional_file);
+            let route_networks = tasks.read_dir(p, "route_networks.txt", read_optional_file);
+            let timeframes = tasks.read_dir(p, "timeframes.txt", read_optional_file);
+            let feed_info = tasks.read_dir(p, "feed_info.txt", read_optional_file);
+            let translations = tasks.read_dir(p, "translations.txt", read_optional_file);
+            let attributions = tasks.read_dir(p, "attributions.txt", read_optional_file);
+            let booking_rules = tasks.read_dir(p, "booking_rules.txt", read_optional_file);
+            let location_groups = tasks.read_dir(p, "location_groups.txt", read_optional_file);
+            let location_group_stops = tasks.read_dir(p, "location_group_stops.txt", read_optional_file);
+            let locations = tasks.read_dir(p, "locations.geojson", read_locations_file);
+
+            let mut warnings = Vec::new();
+            let trips = trips.join(&mut warnings);
+            let calendar = calendar.join(&mut warnings);
+            let calendar_dates = calendar_dates.join(&mut warnings);
+            let stops = stops.join(&mut warnings);
+            let routes = routes.join(&mut warnings);
+            let (stop_times, stop_time_store) = stop_times.join(&mut warnings);
+            Ok(Self {
+                trips,
+                calendar,
+                calendar_dates,
+                stops,
+                routes,
+                stop_times,
+                stop_time_store,
+                frequencies: frequencies.join(&mut warnings),
+                transfers: transfers.join(&mut warnings),
+                pathways: pathways.join(&mut warnings),
+                levels: levels.join(&mut warnings),
+                agencies: agencies.join(&mut warnings),
+                shapes: shapes.join(&mut warnings),
+                fare_attributes: fare_attributes.join(&mut warnings),
+                fare_rules: fare_rules.join(&mut warnings),
+                fare_media: fare_media.join(&mut warnings),
+                rider_categories: rider_categories.join(&mut warnings),
+                fare_containers: fare_containers.join(&mut warnings),
+                fare_products: fare_products.join(&mut warnings),
+                fare_leg_rules: fare_leg_rules.join(&mut warnings),
+                fare_transfer_rules: fare_transfer_rules.join(&mut warnings),
+                areas: areas.join(&mut warnings),
+                stop_areas: stop_areas.join(&mut warnings),
+                networks: networks.join(&mut warnings),
+                route_networks: route_networks.join(&mut warnings),
+                timeframes: timeframes.join(&mut warnings),
+                feed_info: feed_info.join(&mut warnings),
+                translations: translations.join(&mut warnings),
+                attributions: attributions.join(&mut warnings),
+                booking_rules: booking_rules.join(&mut warnings),
+                location_groups: location_groups.join(&mut warnings),
+                location_group_stops: location_group_stops.join(&mut warnings),
+                locations: locations.join(&mut warnings),
+                read_duration: Utc::now().signed_duration_since(now).num_milliseconds(),
+                files,
+                sha256: None,
+                warnings,
+            })
         })
     }
 
@@ -250,64 +1160,147 @@ impl RawGtfs {
 
     /// Non-blocking read the raw GTFS from a remote url
     /// The library must be built with the read-url feature
+    ///
+    /// The files are parsed on the blocking thread pool of tokio, so it must be called from a tokio runtime.
     #[cfg(feature = "read-url")]
     pub async fn from_url_async<U: reqwest::IntoUrl>(url: U) -> Result<Self, Error> {
-        let res = reqwest::get(url).await?.bytes().await?;
-
-        let reader = std::io::Cursor::new(res);
-        Self::from_reader(reader)
+        let body = download(url).await?;
+        spawn_parsing(move || Self::from_reader(std::io::Cursor::new(body))).await
     }
 
+    /// Reads the raw GTFS from a zip archive in any reader, such as a file or a buffer in memory
     pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: T) -> Result<Self, Error> {
+        Self::from_reader_with_options(reader, GtfsReaderOptions::default())
+    }
+
+    /// Reads the raw GTFS from the bytes of a zip archive
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_reader(std::io::Cursor::new(bytes))
+    }
+
+    /// Reads the raw GTFS from a zip archive, with custom options
+    pub fn from_reader_with_options<T: std::io::Read + std::io::Seek>(
+        reader: T,
+        options: GtfsReaderOptions,
+    ) -> Result<Self, Error> {
         let now = Utc::now();
         let mut hasher = Sha256::new();
         let mut buf_reader = std::io::BufReader::new(reader);
         let _n = std::io::copy(&mut buf_reader, &mut hasher)?;
         let hash = hasher.finalize();
-        let mut archive = zip::ZipArchive::new(buf_reader)?;
-        let mut file_mapping = HashMap::new();
-        let mut files = Vec::new();
+        let archive = zip::ZipArchive::new(buf_reader)?;
+        let (mut zip, files) = ZipFiles::new(archive)?;
 
-        for i in 0..archive.len() {
-            let archive_file = archive.by_index(i)?;
-            files.push(archive_file.name().to_owned());
-
-            for gtfs_file in &[
-                "agency.txt",
-                "calendar.txt",
-                "calendar_dates.txt",
-                "routes.txt",
-                "stops.txt",
-                "stop_times.txt",
-                "trips.txt",
-                "fare_attributes.txt",
-                "feed_info.txt",
-                "shapes.txt",
-                "translations.txt",
-            ] {
-                let path = std::path::Path::new(archive_file.name());
-                if path.file_name() == Some(std::ffi::OsStr::new(gtfs_file)) {
-                    file_mapping.insert(gtfs_file, i);
-                    break;
-                }
-            }
-        }
+        std::thread::scope(|scope| {
+            let tasks = Tasks {
+                scope,
+                options: &options,
+            };
+            let agencies = zip.read(&tasks, "agency.txt", read_file);
+            let calendar = zip.read(&tasks, "calendar.txt", read_optional_file);
+            let calendar_dates = zip.read(&tasks, "calendar_dates.txt", read_optional_file);
+            let routes = zip.read(&tasks, "routes.txt", read_file);
+            let stops = zip.read(&tasks, "stops.txt", read_file);
+            let stop_times = zip.read(&tasks, "stop_times.txt", read_stop_times_file);
+            let frequencies = zip.read(&tasks, "frequencies.txt", read_optional_file);
+            let transfers = zip.read(&tasks, "transfers.txt", read_optional_file);
+            let pathways = zip.read(&tasks, "pathways.txt", read_optional_file);
+            let levels = zip.read(&tasks, "levels.txt", read_optional_file);
+            let trips = zip.read(&tasks, "trips.txt", read_file);
+            let fare_attributes = zip.read(&tasks, "fare_attributes.txt", read_optional_file);
+            let fare_rules = zip.read(&tasks, "fare_rules.txt", read_optional_file);
+            let fare_media = zip.read(&tasks, "fare_media.txt", read_optional_file);
+            let rider_categories = zip.read(&tasks, "rider_categories.txt", read_optional_file);
+            let fare_containers = zip.read(&tasks, "fare_containers.txt", read_optional_file);
+            let fare_products = zip.read(&tasks, "fare_products.txt", read_optional_file);
+            let fare_leg_rules = zip.read(&tasks, "fare_leg_rules.txt", read_optional_file);
+            let fare_transfer_rules = zip.read(&tasks, "fare_transfer_rules.txt", read_optional_file);
+            let areas = zip.read(&tasks, "areas.txt", read_optional_file);
+            let stop_areas = zip.read(&tasks, "stop_areas.txt", read_optional_file);
+            let networks = zip.read(&tasks, "networks.txt", read_optional_file);
+            let route_networks = zip.read(&tasks, "route_networks.txt", read_optional_file);
+            let timeframes = zip.read(&tasks, "timeframes.txt", read_optional_file);
+            let feed_info = zip.read(&tasks, "feed_info.txt", read_optional_file);
+            let shapes = zip.read(&tasks, "shapes.txt", read_optional_file);
+            let translations = zip.read(&tasks, "translations.txt", read_optional_file);
+            let attributions = zip.read(&tasks, "attributions.txt", read_optional_file);
+            let booking_rules = zip.read(&tasks, "booking_rules.txt", read_optional_file);
+            let location_groups = zip.read(&tasks, "location_groups.txt", read_optional_file);
+            let location_group_stops = zip.read(&tasks, "location_group_stops.txt", read_optional_file);
+            let locations = zip.read(&tasks, "locations.geojson", read_locations_file);
 
-        Ok(Self {
-            agencies: read_file(&file_mapping, &mut archive, "agency.txt"),
-            calendar: read_optional_file(&file_mapping, &mut archive, "calendar.txt"),
-            calendar_dates: read_optional_file(&file_mapping, &mut archive, "calendar_dates.txt"),
-            routes: read_file(&file_mapping, &mut archive, "routes.txt"),
-            stops: read_file(&file_mapping, &mut archive, "stops.txt"),
-            stop_times: read_file(&file_mapping, &mut archive, "stop_times.txt"),
-            trips: read_file(&file_mapping, &mut archive, "trips.txt"),
-            fare_attributes: read_optional_file(&file_mapping, &mut archive, "fare_attributes.txt"),
-            feed_info: read_optional_file(&file_mapping, &mut archive, "feed_info.txt"),
-            shapes: read_optional_file(&file_mapping, &mut archive, "shapes.txt"),
-            translations: read_optional_file(&file_mapping, &mut archive, "translations.txt"),
-            read_duration: Utc::now().signed_duration_since(now).num_milliseconds(),
-            files,
-            sha256: Some(format!("{:x}", hash)),
+            let mut warnings = Vec::new();
+            let agencies = agencies.join(&mut warnings);
+            let calendar = calendar.join(&mut warnings);
+            let calendar_dates = calendar_dates.join(&mut warnings);
+            let routes = routes.join(&mut warnings);
+            let stops = stops.join(&mut warnings);
+            let (stop_times, stop_time_store) = stop_times.join(&mut warnings);
+            let frequencies = frequencies.join(&mut warnings);
+            let transfers = transfers.join(&mut warnings);
+            let pathways = pathways.join(&mut warnings);
+            let levels = levels.join(&mut warnings);
+            let trips = trips.join(&mut warnings);
+            let fare_attributes = fare_attributes.join(&mut warnings);
+            let fare_rules = fare_rules.join(&mut warnings);
+            let fare_media = fare_media.join(&mut warnings);
+            let rider_categories = rider_categories.join(&mut warnings);
+            let fare_containers = fare_containers.join(&mut warnings);
+            let fare_products = fare_products.join(&mut warnings);
+            let fare_leg_rules = fare_leg_rules.join(&mut warnings);
+            let fare_transfer_rules = fare_transfer_rules.join(&mut warnings);
+            let areas = areas.join(&mut warnings);
+            let stop_areas = stop_areas.join(&mut warnings);
+            let networks = networks.join(&mut warnings);
+            let route_networks = route_networks.join(&mut warnings);
+            let timeframes = timeframes.join(&mut warnings);
+            let feed_info = feed_info.join(&mut warnings);
+            let shapes = shapes.join(&mut warnings);
+            let translations = translations.join(&mut warnings);
+            let attributions = attributions.join(&mut warnings);
+            let booking_rules = booking_rules.join(&mut warnings);
+            let location_groups = location_groups.join(&mut warnings);
+            let location_group_stops = location_group_stops.join(&mut warnings);
+            let locations = locations.join(&mut warnings);
+            Ok(Self {
+                agencies,
+                calendar,
+                calendar_dates,
+                routes,
+                stops,
+                stop_times,
+                stop_time_store,
+                frequencies,
+                transfers,
+                pathways,
+                levels,
+                trips,
+                fare_attributes,
+                fare_rules,
+                fare_media,
+                rider_categories,
+                fare_containers,
+                fare_products,
+                fare_leg_rules,
+                fare_transfer_rules,
+                areas,
+                stop_areas,
+                networks,
+                route_networks,
+                timeframes,
+                feed_info,
+                shapes,
+                translations,
+                attributions,
+                booking_rules,
+                location_groups,
+                location_group_stops,
+                locations,
+                read_duration: Utc::now().signed_duration_since(now).num_milliseconds(),
+                files,
+                sha256: Some(format!("{:x}", hash)),
+                warnings,
+            })
         })
     }
 }
@@ -0,0 +1,1098 @@
+//! Reading GTFS-Realtime messages, and applying them to the static GTFS
+//!
+//! The messages are decoded by a minimal protocol buffers reader, only the fields used by the
+//! crate are kept. Their names are the ones of the
+//! [gtfs-realtime.proto](https://gtfs.org/realtime/reference/) file.
+
+use crate::gtfs::local_instant;
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use chrono::{NaiveDate, TimeZone};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// A value of a protocol buffers field, by wire type
+enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidRealtime(message.to_owned())
+}
+
+impl<'a> Value<'a> {
+    fn varint(&self) -> Result<u64, Error> {
+        match self {
+            Value::Varint(v) => Ok(*v),
+            _ => Err(invalid("expected a varint")),
+        }
+    }
+
+    fn int32(&self) -> Result<i32, Error> {
+        // negative values are sign extended to 64 bits
+        Ok(self.varint()? as i32)
+    }
+
+    fn float(&self) -> Result<f32, Error> {
+        match self {
+            Value::Fixed32(v) => Ok(f32::from_bits(*v)),
+            _ => Err(invalid("expected a float")),
+        }
+    }
+
+    fn double(&self) -> Result<f64, Error> {
+        match self {
+            Value::Fixed64(v) => Ok(f64::from_bits(*v)),
+            _ => Err(invalid("expected a double")),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8], Error> {
+        match self {
+            Value::Bytes(b) => Ok(b),
+            _ => Err(invalid("expected a length delimited value")),
+        }
+    }
+
+    fn string(&self) -> Result<String, Error> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid("invalid utf-8 string"))
+    }
+
+    fn message<T: Message>(&self) -> Result<T, Error> {
+        decode(self.bytes()?)
+    }
+}
+
+/// Iterator over the fields of an encoded message
+struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = self
+                .data
+                .split_first()
+                .ok_or_else(|| invalid("truncated varint"))?;
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < len {
+            return Err(invalid("truncated field"));
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> Result<(u64, Value<'a>), Error> {
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                let bytes = self.take(8)?;
+                Value::Fixed64(u64::from_le_bytes(<[u8; 8]>::try_from(bytes).unwrap()))
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                let bytes = self.take(4)?;
+                Value::Fixed32(u32::from_le_bytes(<[u8; 4]>::try_from(bytes).unwrap()))
+            }
+            _ => return Err(invalid("unsupported wire type")),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(self.field())
+        }
+    }
+}
+
+/// A message that can be decoded field by field, the unknown fields are ignored
+trait Message: Default {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error>;
+}
+
+fn decode<T: Message>(data: &[u8]) -> Result<T, Error> {
+    let mut message = T::default();
+    for field in (Fields { data }) {
+        let (number, value) = field?;
+        message.merge(number, value)?;
+    }
+    Ok(message)
+}
+
+/// A GTFS-Realtime feed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedMessage {
+    pub header: FeedHeader,
+    pub entity: Vec<FeedEntity>,
+}
+
+impl FeedMessage {
+    /// Decodes a feed from its protocol buffers encoding
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        decode(data)
+    }
+}
+
+impl Message for FeedMessage {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.header = value.message()?,
+            2 => self.entity.push(value.message()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedHeader {
+    pub gtfs_realtime_version: String,
+    /// Creation time of the feed, in seconds since the epoch
+    pub timestamp: Option<u64>,
+}
+
+impl Message for FeedHeader {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.gtfs_realtime_version = value.string()?,
+            3 => self.timestamp = Some(value.varint()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedEntity {
+    pub id: String,
+    pub is_deleted: bool,
+    pub trip_update: Option<TripUpdate>,
+    pub vehicle: Option<VehiclePosition>,
+    pub alert: Option<Alert>,
+}
+
+impl Message for FeedEntity {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.id = value.string()?,
+            2 => self.is_deleted = value.varint()? != 0,
+            3 => self.trip_update = Some(value.message()?),
+            4 => self.vehicle = Some(value.message()?),
+            5 => self.alert = Some(value.message()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Realtime progress of a trip, see [Gtfs::apply_trip_updates]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TripUpdate {
+    pub trip: TripDescriptor,
+    /// The updates of the stops of the trip, ordered by stop sequence
+    pub stop_time_update: Vec<StopTimeUpdate>,
+    pub timestamp: Option<u64>,
+    /// Delay of the trip in seconds, for the stops without update
+    pub delay: Option<i32>,
+}
+
+impl Message for TripUpdate {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.trip = value.message()?,
+            2 => self.stop_time_update.push(value.message()?),
+            4 => self.timestamp = Some(value.varint()?),
+            5 => self.delay = Some(value.int32()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TripScheduleRelationship {
+    #[default]
+    Scheduled,
+    /// A trip that is not in the static GTFS
+    Added,
+    /// A trip running without schedule, such as trips defined by frequencies
+    Unscheduled,
+    Canceled,
+    Duplicated,
+    Deleted,
+}
+
+impl TripScheduleRelationship {
+    fn from_value(value: u64) -> Self {
+        match value {
+            1 => Self::Added,
+            2 => Self::Unscheduled,
+            3 => Self::Canceled,
+            6 => Self::Duplicated,
+            7 => Self::Deleted,
+            _ => Self::Scheduled,
+        }
+    }
+}
+
+/// The trip of the static GTFS an update refers to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TripDescriptor {
+    pub trip_id: Option<String>,
+    pub route_id: Option<String>,
+    pub direction_id: Option<u32>,
+    /// Start time of the trip, needed for the trips defined by frequencies, as "HH:MM:SS"
+    pub start_time: Option<String>,
+    /// Service date of the trip, as "YYYYMMDD"
+    pub start_date: Option<String>,
+    pub schedule_relationship: TripScheduleRelationship,
+}
+
+impl Message for TripDescriptor {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.trip_id = Some(value.string()?),
+            2 => self.start_time = Some(value.string()?),
+            3 => self.start_date = Some(value.string()?),
+            4 => self.schedule_relationship = TripScheduleRelationship::from_value(value.varint()?),
+            5 => self.route_id = Some(value.string()?),
+            6 => self.direction_id = Some(value.varint()? as u32),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopTimeScheduleRelationship {
+    #[default]
+    Scheduled,
+    /// The vehicle does not stop at the stop
+    Skipped,
+    /// No realtime information for the stop, the schedule applies
+    NoData,
+    Unscheduled,
+}
+
+impl StopTimeScheduleRelationship {
+    fn from_value(value: u64) -> Self {
+        match value {
+            1 => Self::Skipped,
+            2 => Self::NoData,
+            3 => Self::Unscheduled,
+            _ => Self::Scheduled,
+        }
+    }
+}
+
+/// Realtime update of a stop of a trip, identified by its stop sequence or stop id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StopTimeUpdate {
+    pub stop_sequence: Option<u32>,
+    pub stop_id: Option<String>,
+    pub arrival: Option<StopTimeEvent>,
+    pub departure: Option<StopTimeEvent>,
+    pub schedule_relationship: StopTimeScheduleRelationship,
+}
+
+impl Message for StopTimeUpdate {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.stop_sequence = Some(value.varint()? as u32),
+            2 => self.arrival = Some(value.message()?),
+            3 => self.departure = Some(value.message()?),
+            4 => self.stop_id = Some(value.string()?),
+            5 => {
+                self.schedule_relationship =
+                    StopTimeScheduleRelationship::from_value(value.varint()?)
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Predicted arrival or departure, as a delay or an absolute time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StopTimeEvent {
+    /// Delay in seconds
+    pub delay: Option<i32>,
+    /// Time in seconds since the epoch
+    pub time: Option<i64>,
+}
+
+impl Message for StopTimeEvent {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.delay = Some(value.int32()?),
+            2 => self.time = Some(value.varint()? as i64),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Position of a vehicle, see [Gtfs::resolve_vehicle_position]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehiclePosition {
+    pub trip: Option<TripDescriptor>,
+    pub vehicle: Option<VehicleDescriptor>,
+    pub position: Option<Position>,
+    /// The stop sequence of the stop the status refers to
+    pub current_stop_sequence: Option<u32>,
+    /// The stop the status refers to
+    pub stop_id: Option<String>,
+    pub current_status: VehicleStopStatus,
+    pub timestamp: Option<u64>,
+}
+
+impl Message for VehiclePosition {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.trip = Some(value.message()?),
+            2 => self.position = Some(value.message()?),
+            3 => self.current_stop_sequence = Some(value.varint()? as u32),
+            4 => self.current_status = VehicleStopStatus::from_value(value.varint()?),
+            5 => self.timestamp = Some(value.varint()?),
+            7 => self.stop_id = Some(value.string()?),
+            8 => self.vehicle = Some(value.message()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Status of a vehicle relative to the stop of its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VehicleStopStatus {
+    /// The vehicle is about to arrive at the stop
+    IncomingAt,
+    /// The vehicle is at the stop
+    StoppedAt,
+    /// The vehicle has left the previous stop and goes to the stop
+    #[default]
+    InTransitTo,
+}
+
+impl VehicleStopStatus {
+    fn from_value(value: u64) -> Self {
+        match value {
+            0 => Self::IncomingAt,
+            1 => Self::StoppedAt,
+            _ => Self::InTransitTo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleDescriptor {
+    pub id: Option<String>,
+    pub label: Option<String>,
+    pub license_plate: Option<String>,
+}
+
+impl Message for VehicleDescriptor {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.id = Some(value.string()?),
+            2 => self.label = Some(value.string()?),
+            3 => self.license_plate = Some(value.string()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    pub latitude: f32,
+    pub longitude: f32,
+    /// Direction of the vehicle in degrees, clockwise from the north
+    pub bearing: Option<f32>,
+    /// Distance traveled by the vehicle in meters
+    pub odometer: Option<f64>,
+    /// Speed in meters per second
+    pub speed: Option<f32>,
+}
+
+impl Message for Position {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.latitude = value.float()?,
+            2 => self.longitude = value.float()?,
+            3 => self.bearing = Some(value.float()?),
+            4 => self.odometer = Some(value.double()?),
+            5 => self.speed = Some(value.float()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// A service alert, see [Gtfs::alert_entities]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Alert {
+    /// When the alert is shown, always if empty
+    pub active_period: Vec<TimeRange>,
+    /// The objects affected by the alert
+    pub informed_entity: Vec<EntitySelector>,
+    pub header_text: Option<TranslatedString>,
+    pub description_text: Option<TranslatedString>,
+}
+
+impl Message for Alert {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.active_period.push(value.message()?),
+            5 => self.informed_entity.push(value.message()?),
+            10 => self.header_text = Some(value.message()?),
+            11 => self.description_text = Some(value.message()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// An interval of time, in seconds since the epoch. A missing bound means no limit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl Message for TimeRange {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.start = Some(value.varint()?),
+            2 => self.end = Some(value.varint()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// A text in several languages
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranslatedString {
+    pub translation: Vec<Translation>,
+}
+
+impl TranslatedString {
+    /// The text in a language, or the text without language if there is no translation for it
+    pub fn text(&self, language: &str) -> Option<&str> {
+        self.translation
+            .iter()
+            .find(|t| t.language.as_deref() == Some(language))
+            .or_else(|| self.translation.iter().find(|t| t.language.is_none()))
+            .map(|t| t.text.as_str())
+    }
+}
+
+impl Message for TranslatedString {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        if number == 1 {
+            self.translation.push(value.message()?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Translation {
+    pub text: String,
+    /// BCP-47 code of the language
+    pub language: Option<String>,
+}
+
+impl Message for Translation {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.text = value.string()?,
+            2 => self.language = Some(value.string()?),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Selects the objects affected by an alert, all the given fields must match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntitySelector {
+    pub agency_id: Option<String>,
+    pub route_id: Option<String>,
+    /// A route_type code of routes.txt
+    pub route_type: Option<i32>,
+    pub trip: Option<TripDescriptor>,
+    pub stop_id: Option<String>,
+    /// Direction of the trips of the route, only used with route_id
+    pub direction_id: Option<u32>,
+}
+
+impl Message for EntitySelector {
+    fn merge(&mut self, number: u64, value: Value) -> Result<(), Error> {
+        match number {
+            1 => self.agency_id = Some(value.string()?),
+            2 => self.route_id = Some(value.string()?),
+            3 => self.route_type = Some(value.int32()?),
+            4 => self.trip = Some(value.message()?),
+            5 => self.stop_id = Some(value.string()?),
+            6 => self.direction_id = Some(value.varint()? as u32),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// A trip with its realtime stop times, see [Gtfs::apply_trip_updates]
+#[derive(Debug, Clone)]
+pub struct RealtimeTrip {
+    pub trip_id: String,
+    pub route_id: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub schedule_relationship: TripScheduleRelationship,
+    pub stop_times: Vec<RealtimeStopTime>,
+}
+
+/// A stop time with its predicted arrival and departure
+#[derive(Debug, Clone)]
+pub struct RealtimeStopTime {
+    /// The stop time of the static GTFS, with the predicted arrival and departure times
+    pub stop_time: StopTime,
+    /// Delay of the arrival in seconds, None when unknown
+    pub arrival_delay: Option<i32>,
+    /// Delay of the departure in seconds, None when unknown
+    pub departure_delay: Option<i32>,
+    /// The vehicle does not stop there
+    pub skipped: bool,
+}
+
+/// Timestamp of the start of a service day in a timezone: noon minus 12h, which is midnight
+/// except on the days of the daylight saving time changes
+fn service_day_start<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> Option<i64> {
+    let noon = local_instant(tz, date.and_hms_opt(12, 0, 0)?);
+    Some(noon.timestamp() - 12 * 3600)
+}
+
+/// Predicted time (in seconds since the start of the service day) and delay of an event
+fn predict(
+    event: Option<&StopTimeEvent>,
+    scheduled: Option<i64>,
+    day_start: Option<i64>,
+) -> (Option<i64>, Option<i64>) {
+    let event = match event {
+        Some(event) => event,
+        None => return (None, None),
+    };
+    let time = event.time.zip(day_start).map(|(time, start)| time - start);
+    match (event.delay, time, scheduled) {
+        (Some(delay), _, Some(scheduled)) => {
+            (Some(scheduled + i64::from(delay)), Some(i64::from(delay)))
+        }
+        (_, Some(time), Some(scheduled)) => (Some(time), Some(time - scheduled)),
+        (_, time, _) => (time, None),
+    }
+}
+
+fn to_time(seconds: Option<i64>) -> Option<u32> {
+    seconds.and_then(|s| u32::try_from(s).ok())
+}
+
+fn to_delay(seconds: Option<i64>) -> Option<i32> {
+    seconds.and_then(|s| i32::try_from(s).ok())
+}
+
+/// The stop times of a trip of the static GTFS, updated by the realtime
+fn updated_stop_times(
+    trip: &Trip,
+    stop_times: &[StopTime],
+    update: &TripUpdate,
+    day_start: Option<i64>,
+) -> Vec<RealtimeStopTime> {
+    let relationship = update.trip.schedule_relationship;
+    // the trips defined by frequencies start at the start time of the update
+    let shift = match (stop_times.first(), &update.trip.start_time) {
+        (Some(first), Some(start_time)) if !trip.frequencies.is_empty() => {
+            let first_departure = first.departure_time.or(first.arrival_time);
+            parse_time(start_time)
+                .ok()
+                .zip(first_departure)
+                .map(|(start, first)| i64::from(start) - i64::from(first))
+                .unwrap_or(0)
+        }
+        _ => 0,
+    };
+
+    // the update of each stop time, the updates are ordered like the stop times
+    let mut updates = vec![None; stop_times.len()];
+    let mut next = 0;
+    for stop_time_update in &update.stop_time_update {
+        let found = stop_times[next..].iter().position(|stop_time| {
+            match (stop_time_update.stop_sequence, &stop_time_update.stop_id) {
+                (Some(sequence), _) => u32::from(stop_time.stop_sequence) == sequence,
+                (None, Some(stop_id)) => &stop_time.stop.id == stop_id,
+                (None, None) => false,
+            }
+        });
+        if let Some(position) = found {
+            updates[next + position] = Some(stop_time_update);
+            next += position + 1;
+        }
+    }
+
+    // a delay applies to the next stops until another update
+    let mut propagated = update.delay.map(i64::from);
+    stop_times
+        .iter()
+        .zip(updates)
+        .map(|(stop_time, stop_time_update)| {
+            let scheduled_arrival = stop_time.arrival_time.map(|t| i64::from(t) + shift);
+            let scheduled_departure = stop_time.departure_time.map(|t| i64::from(t) + shift);
+            let mut skipped = relationship == TripScheduleRelationship::Canceled;
+            let (mut arrival, mut arrival_delay) = (scheduled_arrival, None);
+            let (mut departure, mut departure_delay) = (scheduled_departure, None);
+            let apply_delay = |scheduled: Option<i64>, delay: Option<i64>| {
+                (
+                    scheduled.zip(delay).map(|(s, d)| s + d).or(scheduled),
+                    delay,
+                )
+            };
+            match stop_time_update.map(|u| (u, u.schedule_relationship)) {
+                _ if skipped => (),
+                Some((_, StopTimeScheduleRelationship::Skipped)) => skipped = true,
+                Some((_, StopTimeScheduleRelationship::NoData)) => propagated = None,
+                Some((stop_time_update, _)) => {
+                    let (a, a_delay) = predict(
+                        stop_time_update.arrival.as_ref(),
+                        scheduled_arrival,
+                        day_start,
+                    );
+                    let (d, d_delay) = predict(
+                        stop_time_update.departure.as_ref(),
+                        scheduled_departure,
+                        day_start,
+                    );
+                    // without one of the events, the other one gives the delay
+                    let (a, a_delay) = match (a, a_delay) {
+                        (None, None) => apply_delay(scheduled_arrival, d_delay),
+                        predicted => predicted,
+                    };
+                    let (d, d_delay) = match (d, d_delay) {
+                        (None, None) => apply_delay(scheduled_departure, a_delay),
+                        predicted => predicted,
+                    };
+                    arrival = a.or(scheduled_arrival);
+                    arrival_delay = a_delay;
+                    // the vehicle cannot leave before it arrives
+                    departure = d.or(scheduled_departure).max(arrival);
+                    departure_delay = d_delay;
+                    propagated = d_delay.or(a_delay).or(propagated);
+                }
+                None => {
+                    let (a, a_delay) = apply_delay(scheduled_arrival, propagated);
+                    let (d, d_delay) = apply_delay(scheduled_departure, propagated);
+                    arrival = a;
+                    arrival_delay = a_delay;
+                    departure = d;
+                    departure_delay = d_delay;
+                }
+            }
+            RealtimeStopTime {
+                stop_time: StopTime {
+                    arrival_time: to_time(arrival),
+                    departure_time: to_time(departure),
+                    ..stop_time.clone()
+                },
+                arrival_delay: to_delay(arrival_delay),
+                departure_delay: to_delay(departure_delay),
+                skipped,
+            }
+        })
+        .collect()
+}
+
+impl Gtfs {
+    /// The trips of the trip updates of a GTFS-Realtime feed, with their predicted stop times
+    ///
+    /// The delay of a stop is applied to the next stops until another update, as required by
+    /// the specification. The skipped stops and all the stops of the canceled trips are
+    /// flagged as skipped. The added trips, that are not in the static GTFS, get the stop
+    /// times of their updates whose stop is known.
+    ///
+    /// The absolute times of the updates are converted in the timezone of the agency of each
+    /// trip, at its service date: the start date of the update, or the date of the feed in that
+    /// timezone. `timezone` gives the timezone of a name of the tz database, like for
+    /// [Gtfs::departure_instant], an [Error::UnknownTimezone] is returned if it does not know
+    /// a name. The added trips of an unknown route use the timezone of the first agency.
+    ///
+    /// Like in stop_times.txt, the times are counted from the start of the service day in the
+    /// timezone of the agency, even at the stops with a stop_timezone: [Gtfs::departure_instant]
+    /// gives the instant in the timezone of the stop. The updates of unknown trips are ignored.
+    /// An error is also returned if the stop times of a trip cannot be loaded, see
+    /// [Gtfs::trip_stop_times].
+    pub fn apply_trip_updates<Tz, F>(
+        &self,
+        feed: &FeedMessage,
+        timezone: F,
+    ) -> Result<Vec<RealtimeTrip>, Error>
+    where
+        Tz: TimeZone,
+        F: Fn(&str) -> Option<Tz>,
+    {
+        feed.entity
+            .iter()
+            .filter(|entity| !entity.is_deleted)
+            .filter_map(|entity| entity.trip_update.as_ref())
+            .map(|update| self.apply_trip_update(update, feed.header.timestamp, &timezone))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    fn apply_trip_update<Tz, F>(
+        &self,
+        update: &TripUpdate,
+        feed_timestamp: Option<u64>,
+        timezone: &F,
+    ) -> Result<Option<RealtimeTrip>, Error>
+    where
+        Tz: TimeZone,
+        F: Fn(&str) -> Option<Tz>,
+    {
+        let descriptor = &update.trip;
+        let added = descriptor.schedule_relationship == TripScheduleRelationship::Added;
+        let trip = descriptor
+            .trip_id
+            .as_deref()
+            .and_then(|id| self.trips.get(id))
+            .filter(|_| !added);
+        let name = match (trip, &descriptor.route_id) {
+            (None, _) if !added => return Ok(None),
+            (Some(trip), _) => self.trip_timezone(&trip.id)?,
+            (None, Some(route_id)) if self.routes.contains_key(route_id) => {
+                self.route_timezone(route_id)?
+            }
+            (None, _) => match self.agencies.first() {
+                Some(agency) => &agency.timezone,
+                None => return Ok(None),
+            },
+        };
+        let tz = timezone(name.trim()).ok_or_else(|| Error::UnknownTimezone(name.to_owned()))?;
+
+        let feed_date = feed_timestamp
+            .and_then(|t| tz.timestamp_opt(t as i64, 0).single())
+            .map(|t| t.date_naive());
+        let start_date = descriptor
+            .start_date
+            .as_deref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+            .or(feed_date);
+        let day_start = start_date.and_then(|date| service_day_start(date, &tz));
+        let (route_id, stop_times) = match trip {
+            Some(trip) => {
+                let stop_times = self.trip_stop_times(&trip.id)?;
+                (
+                    Some(trip.route_id.clone()),
+                    updated_stop_times(trip, &stop_times, update, day_start),
+                )
+            }
+            None => (
+                descriptor.route_id.clone(),
+                self.added_stop_times(update, day_start),
+            ),
+        };
+        Ok(Some(RealtimeTrip {
+            trip_id: descriptor.trip_id.clone().unwrap_or_default(),
+            route_id,
+            start_date,
+            schedule_relationship: descriptor.schedule_relationship,
+            stop_times,
+        }))
+    }
+
+    /// The stop times of a trip that is not in the static GTFS
+    fn added_stop_times(
+        &self,
+        update: &TripUpdate,
+        day_start: Option<i64>,
+    ) -> Vec<RealtimeStopTime> {
+        update
+            .stop_time_update
+            .iter()
+            .enumerate()
+            .filter_map(|(index, stop_time_update)| {
+                let stop = self.stops.get(stop_time_update.stop_id.as_deref()?)?;
+                let (arrival, _) = predict(stop_time_update.arrival.as_ref(), None, day_start);
+                let (departure, _) = predict(stop_time_update.departure.as_ref(), None, day_start);
+                let sequence = stop_time_update.stop_sequence.unwrap_or(index as u32);
+                Some(RealtimeStopTime {
+                    stop_time: StopTime {
+                        arrival_time: to_time(arrival.or(departure)),
+                        departure_time: to_time(departure.or(arrival)),
+                        stop: stop.clone(),
+                        stop_sequence: u16::try_from(sequence).unwrap_or(u16::MAX),
+                        ..StopTime::default()
+                    },
+                    arrival_delay: None,
+                    departure_delay: None,
+                    skipped: stop_time_update.schedule_relationship
+                        == StopTimeScheduleRelationship::Skipped,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A vehicle with the objects of the static GTFS it refers to, see
+/// [Gtfs::resolve_vehicle_position]
+#[derive(Debug, Clone)]
+pub struct ResolvedVehicle<'a> {
+    pub vehicle: &'a VehiclePosition,
+    pub trip: Option<&'a Trip>,
+    pub route: Option<&'a Route>,
+    /// The stop where the vehicle is, when it is stopped
+    pub current_stop: Option<Arc<Stop>>,
+    /// The next stop of the vehicle
+    pub next_stop: Option<Arc<Stop>>,
+    /// The scheduled stop time of the trip at the next stop
+    pub next_stop_time: Option<StopTime>,
+}
+
+impl Gtfs {
+    /// The trip, route and stops of the static GTFS a vehicle position refers to
+    ///
+    /// The stop of the position is found by its stop sequence in the trip, or by its stop id.
+    /// When the vehicle is stopped at it, the next stop is the following stop of the trip.
+    /// The objects that are not in the static GTFS are None.
+    pub fn resolve_vehicle_position<'a>(
+        &'a self,
+        vehicle: &'a VehiclePosition,
+    ) -> ResolvedVehicle<'a> {
+        let descriptor = vehicle.trip.as_ref();
+        let trip = descriptor
+            .and_then(|d| d.trip_id.as_deref())
+            .and_then(|id| self.trips.get(id));
+        let route = trip
+            .map(|trip| trip.route_id.as_str())
+            .or_else(|| descriptor.and_then(|d| d.route_id.as_deref()))
+            .and_then(|id| self.routes.get(id))
+            .map(|route| route.as_ref());
+        let stopped = vehicle.current_status == VehicleStopStatus::StoppedAt;
+
+        let stop_times = trip.and_then(|trip| self.trip_stop_times(&trip.id).ok());
+        let position = stop_times.as_ref().and_then(|stop_times| {
+            stop_times.iter().position(|stop_time| {
+                match (vehicle.current_stop_sequence, &vehicle.stop_id) {
+                    (Some(sequence), _) => u32::from(stop_time.stop_sequence) == sequence,
+                    (None, Some(stop_id)) => &stop_time.stop.id == stop_id,
+                    (None, None) => false,
+                }
+            })
+        });
+        let (current_stop, next_stop, next_stop_time) = match (&stop_times, position) {
+            (Some(stop_times), Some(position)) => {
+                let next = if stopped { position + 1 } else { position };
+                let next_stop_time = stop_times.get(next).cloned();
+                (
+                    Some(stop_times[position].stop.clone()).filter(|_| stopped),
+                    next_stop_time.as_ref().map(|st| st.stop.clone()),
+                    next_stop_time,
+                )
+            }
+            // without the trip, only the stop of the position is known
+            _ => {
+                let stop = vehicle
+                    .stop_id
+                    .as_deref()
+                    .and_then(|id| self.stops.get(id))
+                    .cloned();
+                if stopped {
+                    (stop, None, None)
+                } else {
+                    (None, stop, None)
+                }
+            }
+        };
+        ResolvedVehicle {
+            vehicle,
+            trip,
+            route,
+            current_stop,
+            next_stop,
+            next_stop_time,
+        }
+    }
+
+    /// The vehicles of a GTFS-Realtime feed, see [Gtfs::resolve_vehicle_position]
+    pub fn resolve_vehicle_positions<'a>(
+        &'a self,
+        feed: &'a FeedMessage,
+    ) -> Vec<ResolvedVehicle<'a>> {
+        feed.entity
+            .iter()
+            .filter(|entity| !entity.is_deleted)
+            .filter_map(|entity| entity.vehicle.as_ref())
+            .map(|vehicle| self.resolve_vehicle_position(vehicle))
+            .collect()
+    }
+}
+
+/// The objects of the static GTFS affected by an alert, see [Gtfs::alert_entities]
+///
+/// Each list is ordered by id, without duplicates.
+#[derive(Debug, Clone, Default)]
+pub struct AffectedEntities<'a> {
+    pub routes: Vec<&'a Route>,
+    pub stops: Vec<Arc<Stop>>,
+    pub trips: Vec<&'a Trip>,
+}
+
+fn direction_matches(direction: Option<DirectionType>, direction_id: Option<u32>) -> bool {
+    match direction_id {
+        None => true,
+        Some(0) => direction == Some(DirectionType::Outbound),
+        Some(1) => direction == Some(DirectionType::Inbound),
+        Some(_) => false,
+    }
+}
+
+impl Gtfs {
+    /// Tells if a route matches the agency_id, route_id and route_type of a selector
+    fn route_matches(&self, route: &Route, selector: &EntitySelector) -> bool {
+        let agency = match (&selector.agency_id, &route.agency_id) {
+            (None, _) => true,
+            (Some(id), Some(agency_id)) => id == agency_id,
+            // without agency_id, the route belongs to the only agency of the feed
+            (Some(_), None) => self.agencies.len() == 1,
+        };
+        agency
+            && selector.route_id.as_ref().is_none_or(|id| id == &route.id)
+            && selector.route_type.is_none_or(|route_type| {
+                u16::try_from(route_type).map(RouteType::from) == Ok(route.route_type)
+            })
+    }
+
+    /// Tells if a trip matches a trip descriptor of a selector: by trip_id, or else by
+    /// route_id and direction_id
+    fn trip_matches(trip: &Trip, descriptor: &TripDescriptor) -> bool {
+        match &descriptor.trip_id {
+            Some(trip_id) => trip_id == &trip.id,
+            None => {
+                descriptor.route_id.is_some()
+                    && descriptor.route_id.as_ref() == Some(&trip.route_id)
+                    && direction_matches(trip.direction_id, descriptor.direction_id)
+            }
+        }
+    }
+
+    fn stops_at(&self, trip: &Trip, stop_ids: &[&str]) -> bool {
+        self.trip_stop_times(&trip.id)
+            .map(|stop_times| {
+                stop_times
+                    .iter()
+                    .any(|st| stop_ids.contains(&st.stop.id.as_str()))
+            })
+            .unwrap_or(false)
+    }
+
+    /// The routes, stops and trips of the static GTFS affected by an alert
+    ///
+    /// For each informed entity:
+    /// * a stop_id selects the stop, and the stops of the station when it is one. With other
+    ///   fields, only the trips (and their routes) stopping at it are selected.
+    /// * agency_id, route_id and route_type select all the matching routes and their trips
+    ///   (only the ones in the direction_id if given). An agency or a route_type alone selects
+    ///   the whole network of the agency or all the routes of this type.
+    /// * a trip selects the trip, and its route, by its trip_id, or else all the trips of its
+    ///   route_id and direction_id. The start_date and start_time are not checked.
+    ///
+    /// The unknown ids select nothing.
+    pub fn alert_entities(&self, alert: &Alert) -> AffectedEntities<'_> {
+        let mut routes = BTreeMap::new();
+        let mut stops = BTreeMap::new();
+        let mut trips = BTreeMap::new();
+        for selector in &alert.informed_entity {
+            let mut stop_ids = Vec::new();
+            if let Some(stop_id) = &selector.stop_id {
+                if let Some(stop) = self.stops.get(stop_id) {
+                    stop_ids.push(stop.id.as_str());
+                    stops.insert(stop.id.as_str(), stop.clone());
+                    for child in self
+                        .stops
+                        .values()
+                        .filter(|s| s.parent_station.as_ref() == Some(stop_id))
+                    {
+                        stop_ids.push(child.id.as_str());
+                        stops.insert(child.id.as_str(), child.clone());
+                    }
+                } else {
+                    continue;
+                }
+            }
+            let by_route = selector.agency_id.is_some()
+                || selector.route_id.is_some()
+                || selector.route_type.is_some();
+            if !by_route && selector.trip.is_none() {
+                continue;
+            }
+            for trip in self.trips.values() {
+                let route = match trip.route.as_deref() {
+                    Some(route) => route,
+                    None => continue,
+                };
+                let matches = self.route_matches(route, selector)
+                    && direction_matches(trip.direction_id, selector.direction_id)
+                    && selector
+                        .trip
+                        .as_ref()
+                        .is_none_or(|descriptor| Self::trip_matches(trip, descriptor))
+                    && (stop_ids.is_empty() || self.stops_at(trip, &stop_ids));
+                if matches {
+                    trips.insert(trip.id.as_str(), trip);
+                    routes.insert(route.id.as_str(), route);
+                }
+            }
+            // the routes of an agency or a route type without trips are affected too
+            if by_route && selector.trip.is_none() && stop_ids.is_empty() {
+                for route in self
+                    .routes
+                    .values()
+                    .map(|route| route.as_ref())
+                    .filter(|r| self.route_matches(r, selector))
+                {
+                    routes.insert(route.id.as_str(), route);
+                }
+            }
+        }
+        AffectedEntities {
+            routes: routes.into_values().collect(),
+            stops: stops.into_values().collect(),
+            trips: trips.into_values().collect(),
+        }
+    }
+}
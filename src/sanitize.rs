@@ -0,0 +1,104 @@
+//! Cleaning of the names and headsigns, see [GtfsReaderOptions::sanitize_texts]
+//!
+//! [GtfsReaderOptions::sanitize_texts]: crate::GtfsReaderOptions::sanitize_texts
+
+use crate::ParseWarning;
+use std::borrow::Cow;
+
+/// Columns of texts shown to the riders
+const TEXT_COLUMNS: &[&str] = &[
+    "agency_name",
+    "stop_name",
+    "tts_stop_name",
+    "stop_desc",
+    "route_short_name",
+    "route_long_name",
+    "route_desc",
+    "trip_headsign",
+    "trip_short_name",
+    "stop_headsign",
+    "level_name",
+    "area_name",
+    "network_name",
+    "fare_media_name",
+    "fare_product_name",
+    "rider_category_name",
+    "location_group_name",
+    "feed_publisher_name",
+    "translation",
+];
+
+/// The text without control characters, with the runs of whitespace (including tabs and line
+/// breaks) replaced by a space and without whitespace at the start and at the end
+///
+/// With the feature 'normalize-unicode', the text is also normalized to the composed form
+/// (NFC), so that an accented letter is always written with one character.
+///
+/// ```
+/// assert_eq!("Gare du Nord", gtfs_structures::sanitize_text(" Gare\u{7}  du\tNord\r\n"));
+/// ```
+pub fn sanitize_text(text: &str) -> Cow<'_, str> {
+    let text = normalize(text);
+    let mut sanitized = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.trim().chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else if !c.is_control() {
+            if space {
+                sanitized.push(' ');
+                space = false;
+            }
+            sanitized.push(c);
+        }
+    }
+    if sanitized == text {
+        text
+    } else {
+        Cow::Owned(sanitized)
+    }
+}
+
+#[cfg(feature = "normalize-unicode")]
+fn normalize(text: &str) -> Cow<'_, str> {
+    icu_normalizer::ComposingNormalizerBorrowed::new_nfc().normalize(text)
+}
+
+#[cfg(not(feature = "normalize-unicode"))]
+fn normalize(text: &str) -> Cow<'_, str> {
+    Cow::Borrowed(text)
+}
+
+/// Sanitizes the texts of the record with [sanitize_text], reporting each modified value
+pub(crate) fn sanitize_record(
+    headers: &csv::StringRecord,
+    record: csv::StringRecord,
+    file_name: &str,
+    warnings: &mut Vec<ParseWarning>,
+) -> csv::StringRecord {
+    let is_text = |i: usize| headers.get(i).is_some_and(|h| TEXT_COLUMNS.contains(&h));
+    let dirty =
+        |(i, value): (usize, &str)| is_text(i) && matches!(sanitize_text(value), Cow::Owned(_));
+    if !record.iter().enumerate().any(dirty) {
+        return record;
+    }
+    let mut sanitized = csv::StringRecord::new();
+    for (i, value) in record.iter().enumerate() {
+        if !is_text(i) {
+            sanitized.push_field(value);
+            continue;
+        }
+        let text = sanitize_text(value);
+        if text != value {
+            warnings.push(ParseWarning {
+                file_name: file_name.to_owned(),
+                line: record.position().map(|p| p.line()),
+                column: headers.get(i).map(str::to_owned),
+                message: format!("the text {:?} is sanitized to {:?}", value, text),
+            });
+        }
+        sanitized.push_field(&text);
+    }
+    sanitized.set_position(record.position().cloned());
+    sanitized
+}
@@ -0,0 +1,380 @@
+//! Description of the files read by the crate, found by deserializing probe values
+
+use crate::headers::{fields_of_file, with_structure, StructureVisitor};
+use crate::raw_gtfs::GTFS_FILES;
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, MapAccess, Visitor};
+
+/// Files without which a feed cannot be read
+const REQUIRED_FILES: &[&str] = &[
+    "agency.txt",
+    "stops.txt",
+    "routes.txt",
+    "trips.txt",
+    "stop_times.txt",
+];
+
+/// Columns that are not part of the specification: the ones of the feature 'extensions', and
+/// the column of the translations of the NMBS
+const EXTENSION_FIELDS: &[&str] = &[
+    "local_zone_id",
+    "vehicle_type",
+    "cars_count",
+    "capacity",
+    "trans_id",
+];
+
+/// Values given to the text fields, in the order they are tried
+///
+/// The values a field accepts tell its type: a date accepts 20240101 but not 0, a text
+/// accepts everything.
+const SAMPLES: &[&str] = &["x", "0", "1", "0.5", "00:00:00", "20240101", "FFFFFF"];
+
+/// Type of the values of a column, see [schema]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FieldType {
+    Text,
+    Integer,
+    Float,
+    /// 0 or 1
+    Boolean,
+    /// HH:MM:SS, possibly past 24:00:00
+    Time,
+    /// YYYYMMDD
+    Date,
+    /// Hexadecimal RGB color, like FFFFFF
+    Color,
+    /// One of the values
+    Enum(&'static [&'static str]),
+}
+
+/// A column of a file, see [schema]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldSchema {
+    /// The name of the column
+    pub name: &'static str,
+    pub field_type: FieldType,
+    /// The rows cannot be read without this column
+    pub required: bool,
+    /// The column is not part of the GTFS specification
+    pub extension: bool,
+}
+
+/// A file read by the crate and its columns, see [schema]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableSchema {
+    pub file_name: &'static str,
+    /// The structure a row is read into, like "Stop"
+    pub structure: &'static str,
+    /// The feed cannot be read without this file
+    pub required: bool,
+    pub fields: Vec<FieldSchema>,
+}
+
+/// The files read by the crate, with the type of their columns and if they are required
+///
+/// The description is generated from the structures the rows are read into, so it follows
+/// the features of the crate: the columns of the feature 'extensions' are only given with
+/// it. It can be serialized to build forms or documentation.
+///
+/// ```
+/// let stops = gtfs_structures::schema()
+///     .into_iter()
+///     .find(|table| table.file_name == "stops.txt")
+///     .unwrap();
+/// let stop_id = stops.fields.iter().find(|f| f.name == "stop_id").unwrap();
+/// assert_eq!(gtfs_structures::FieldType::Text, stop_id.field_type);
+/// assert!(stop_id.required);
+/// ```
+pub fn schema() -> Vec<TableSchema> {
+    GTFS_FILES
+        .iter()
+        .filter_map(|file_name| {
+            let (structure, fields) = fields_of_file(file_name)?;
+            Some(TableSchema {
+                file_name,
+                structure,
+                required: REQUIRED_FILES.contains(file_name),
+                fields: with_structure(file_name, Describe { fields })?,
+            })
+        })
+        .collect()
+}
+
+/// Describes the fields of a structure by deserializing rows of probe values
+struct Describe {
+    fields: &'static [&'static str],
+}
+
+impl StructureVisitor for Describe {
+    type Output = Vec<FieldSchema>;
+
+    fn visit<T: DeserializeOwned>(self) -> Vec<FieldSchema> {
+        let fields = self.fields;
+        // a sample accepted by each field, found by trying the next one for the field in error
+        let mut baseline = vec![0; fields.len()];
+        let mut kinds = vec![None; fields.len()];
+        while let Err(Some(i)) = probe::<T>(fields, &baseline, None, &mut kinds) {
+            if baseline[i] + 1 == SAMPLES.len() {
+                break;
+            }
+            baseline[i] += 1;
+        }
+        (0..fields.len())
+            .map(|i| {
+                let accepts = |sample: usize| {
+                    let mut row = baseline.clone();
+                    row[i] = sample;
+                    probe::<T>(fields, &row, None, &mut vec![None; fields.len()]).is_ok()
+                };
+                let field_type = match kinds[i] {
+                    Some(Kind::Text) | None => {
+                        let accepted: Vec<&str> = (0..SAMPLES.len())
+                            .filter(|sample| accepts(*sample))
+                            .map(|sample| SAMPLES[sample])
+                            .collect();
+                        text_type(&accepted)
+                    }
+                    Some(Kind::Other(field_type)) => field_type,
+                };
+                let required =
+                    probe::<T>(fields, &baseline, Some(i), &mut vec![None; fields.len()]).is_err();
+                FieldSchema {
+                    name: fields[i],
+                    field_type,
+                    required,
+                    extension: EXTENSION_FIELDS.contains(&fields[i]),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Type of a field read from a text, from the samples it accepts
+fn text_type(accepted: &[&str]) -> FieldType {
+    let accepts = |sample| accepted.contains(&sample);
+    if accepts("x") {
+        FieldType::Text
+    } else if accepts("0.5") {
+        FieldType::Float
+    } else if accepts("20240101") && accepts("0") {
+        FieldType::Integer
+    } else if accepts("0") && accepts("1") {
+        FieldType::Boolean
+    } else if accepts("00:00:00") {
+        FieldType::Time
+    } else if accepts("20240101") {
+        FieldType::Date
+    } else if accepts("FFFFFF") {
+        FieldType::Color
+    } else {
+        FieldType::Text
+    }
+}
+
+/// How a field is deserialized
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// From a text, the type depends on what is accepted
+    Text,
+    Other(FieldType),
+}
+
+/// Deserializes a row where each field has the sample of its index, without the field
+/// `skipped`, and records how each field is deserialized
+///
+/// On error, the index of the field whose value was rejected is given, if it is known.
+fn probe<T: DeserializeOwned>(
+    fields: &'static [&'static str],
+    samples: &[usize],
+    skipped: Option<usize>,
+    kinds: &mut [Option<Kind>],
+) -> Result<(), Option<usize>> {
+    let mut row = Row {
+        fields,
+        samples,
+        skipped,
+        kinds,
+        next: 0,
+        rejected: None,
+    };
+    match T::deserialize(&mut row) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(row.rejected),
+    }
+}
+
+#[derive(Debug)]
+struct Rejected;
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "rejected probe")
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+impl de::Error for Rejected {
+    fn custom<T: std::fmt::Display>(_: T) -> Self {
+        Rejected
+    }
+}
+
+struct Row<'a> {
+    fields: &'static [&'static str],
+    samples: &'a [usize],
+    skipped: Option<usize>,
+    kinds: &'a mut [Option<Kind>],
+    /// Index of the next field to give
+    next: usize,
+    rejected: Option<usize>,
+}
+
+impl<'de, 'a, 'b> Deserializer<'de> for &'b mut Row<'a> {
+    type Error = Rejected;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        visitor.visit_map(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de, 'a, 'b> MapAccess<'de> for &'b mut Row<'a> {
+    type Error = Rejected;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Rejected> {
+        if Some(self.next) == self.skipped {
+            self.next += 1;
+        }
+        match self.fields.get(self.next) {
+            Some(field) => seed.deserialize((*field).into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Rejected> {
+        let i = self.next;
+        self.next += 1;
+        let value = Value {
+            sample: SAMPLES[self.samples[i]],
+            kind: &mut self.kinds[i],
+        };
+        seed.deserialize(value)
+            .inspect_err(|_| self.rejected = Some(i))
+    }
+}
+
+/// The value of a field, recording how it is deserialized
+struct Value<'a> {
+    sample: &'static str,
+    kind: &'a mut Option<Kind>,
+}
+
+impl<'a> Value<'a> {
+    fn record(self, kind: Kind) -> &'static str {
+        *self.kind = Some(kind);
+        self.sample
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for Value<'a> {
+    type Error = Rejected;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        visitor.visit_str(self.record(Kind::Text))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.record(Kind::Other(FieldType::Boolean));
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.record(Kind::Other(FieldType::Integer));
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        self.record(Kind::Other(FieldType::Float));
+        visitor.visit_f64(0.)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Rejected> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Rejected> {
+        self.record(Kind::Other(FieldType::Enum(variants)));
+        let variant = variants.first().ok_or(Rejected)?;
+        visitor.visit_enum((*variant).into_deserializer())
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Rejected> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit_struct seq tuple tuple_struct map
+        struct identifier
+    }
+}
@@ -0,0 +1,222 @@
+//! Text search over the names of the stops, routes and trips
+
+use crate::objects::*;
+use crate::Gtfs;
+use std::collections::{BTreeMap, HashMap};
+
+/// Object found by [Gtfs::search]
+#[derive(Debug, Clone, Copy)]
+pub enum SearchResult<'a> {
+    Route(&'a Route),
+    Stop(&'a Stop),
+    Trip(&'a Trip),
+}
+
+impl SearchResult<'_> {
+    /// Id of the route, stop or trip
+    pub fn id(&self) -> &str {
+        match self {
+            SearchResult::Route(route) => &route.id,
+            SearchResult::Stop(stop) => &stop.id,
+            SearchResult::Trip(trip) => &trip.id,
+        }
+    }
+}
+
+/// Kind of the indexed objects, in the order of the results that have the same score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    Route,
+    Stop,
+    Trip,
+}
+
+#[derive(Debug)]
+pub(crate) struct Document {
+    pub(crate) kind: Kind,
+    pub(crate) id: String,
+    /// Number of words of the indexed fields, the shortest names are the most relevant
+    pub(crate) length: usize,
+}
+
+/// Inverted index of the words of the names of the stops, routes and trips, see [Gtfs::search]
+///
+/// Indexed fields: the name and code of the stops, the short and long names of the routes,
+/// and the short name and headsign of the trips.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    pub(crate) documents: Vec<Document>,
+    /// For each word, the indexes in `documents` of the objects having it, with the weight
+    /// of the field
+    pub(crate) words: BTreeMap<String, Vec<(usize, u32)>>,
+}
+
+/// Removes the accents of the latin letters, so that "etoile" finds "Étoile"
+fn fold(c: char) -> char {
+    match c {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ĺ' | 'ļ' | 'ľ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' => 't',
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+/// Lowercase words without accents of a text
+pub(crate) fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            word.chars()
+                .flat_map(char::to_lowercase)
+                .map(fold)
+                .collect()
+        })
+        .collect()
+}
+
+impl SearchIndex {
+    /// Indexes the stops, routes and trips of the GTFS
+    pub fn new(gtfs: &Gtfs) -> Self {
+        let mut index = Self::default();
+        for stop in gtfs.stops.values() {
+            index.add(
+                Kind::Stop,
+                &stop.id,
+                &[(Some(&stop.name), 3), (stop.code.as_ref(), 3)],
+            );
+        }
+        for route in gtfs.routes.values() {
+            index.add(
+                Kind::Route,
+                &route.id,
+                &[(Some(&route.short_name), 3), (Some(&route.long_name), 2)],
+            );
+        }
+        for trip in gtfs.trips.values() {
+            index.add(
+                Kind::Trip,
+                &trip.id,
+                &[
+                    (trip.trip_short_name.as_ref(), 3),
+                    (trip.trip_headsign.as_ref(), 2),
+                ],
+            );
+        }
+        index
+    }
+
+    fn add(&mut self, kind: Kind, id: &str, fields: &[(Option<&String>, u32)]) {
+        let document = self.documents.len();
+        let mut length = 0;
+        for (text, weight) in fields {
+            for word in text.map(|text| words(text)).unwrap_or_default() {
+                length += 1;
+                self.words
+                    .entry(word)
+                    .or_default()
+                    .push((document, *weight));
+            }
+        }
+        if length > 0 {
+            self.documents.push(Document {
+                kind,
+                id: id.to_owned(),
+                length,
+            });
+        }
+    }
+
+    /// Objects of the GTFS matching all the words of the query, the most relevant first
+    ///
+    /// See [Gtfs::search]. The objects removed from the GTFS after the index was built
+    /// are not returned.
+    pub fn search<'a>(&self, gtfs: &'a Gtfs, query: &str) -> Vec<SearchResult<'a>> {
+        let query = words(query);
+        // score and number of matched words of the query of each document
+        let mut matches: HashMap<usize, (u32, usize)> = HashMap::new();
+        for query_word in &query {
+            let mut scores: HashMap<usize, u32> = HashMap::new();
+            let prefixed = self
+                .words
+                .range(query_word.clone()..)
+                .take_while(|(word, _)| word.starts_with(query_word.as_str()));
+            for (word, postings) in prefixed {
+                // a complete word is worth twice a prefix of a word
+                let factor = if word == query_word { 2 } else { 1 };
+                for &(document, weight) in postings {
+                    let score = scores.entry(document).or_default();
+                    *score = (*score).max(weight * factor);
+                }
+            }
+            for (document, score) in scores {
+                let entry = matches.entry(document).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        let mut found: Vec<(u32, &Document)> = matches
+            .into_iter()
+            .filter(|(_, (_, count))| *count == query.len())
+            .map(|(document, (score, _))| (score, &self.documents[document]))
+            .collect();
+        found.sort_by(|(score_a, a), (score_b, b)| {
+            score_b
+                .cmp(score_a)
+                .then(a.length.cmp(&b.length))
+                .then(a.kind.cmp(&b.kind))
+                .then(a.id.cmp(&b.id))
+        });
+        found
+            .into_iter()
+            .filter_map(|(_, document)| match document.kind {
+                Kind::Route => gtfs
+                    .routes
+                    .get(&document.id)
+                    .map(|route| SearchResult::Route(route)),
+                Kind::Stop => gtfs
+                    .stops
+                    .get(&document.id)
+                    .map(|stop| SearchResult::Stop(stop)),
+                Kind::Trip => gtfs.trips.get(&document.id).map(SearchResult::Trip),
+            })
+            .collect()
+    }
+}
+
+impl Gtfs {
+    /// Builds the [Gtfs::search_index] used by [Gtfs::search]
+    ///
+    /// The index must be built again after objects are added or renamed.
+    pub fn build_search_index(&mut self) {
+        self.search_index = Some(SearchIndex::new(self));
+    }
+
+    /// Stops, routes and trips whose names match all the words of the query, the most
+    /// relevant first
+    ///
+    /// The case and the accents are ignored, and a word of the query can be the beginning
+    /// of a word of the name: "gare nor" finds "Gare du Nord". The stop names and codes and
+    /// the route and trip short names weigh more than the long names and headsigns.
+    ///
+    /// Uses the [Gtfs::search_index] if it was built with [Gtfs::build_search_index],
+    /// otherwise the objects are indexed for this search only.
+    pub fn search(&self, query: &str) -> Vec<SearchResult<'_>> {
+        match &self.search_index {
+            Some(index) => index.search(self, query),
+            None => SearchIndex::new(self).search(self, query),
+        }
+    }
+}
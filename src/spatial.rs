@@ -0,0 +1,406 @@
+use crate::flex::polygon_contains;
+use crate::objects::*;
+use crate::{Error, Gtfs, Ring};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Mean radius of the earth, in meters
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// Great-circle distance in meters between two points, given in degrees
+pub fn distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Position of a point on a sphere of radius 1
+///
+/// The straight distance between two positions grows with their great-circle distance, and
+/// unlike the latitudes and longitudes, it does not break near the poles or the antimeridian.
+fn to_xyz(lat: f64, lon: f64) -> [f64; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn squared_chord(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// A stop and its position on the sphere
+type Point = ([f64; 3], Arc<Stop>);
+
+/// Index of the positions of the stops, see [Gtfs::build_stop_index]
+///
+/// The stops are stored in a k-d tree, so [Gtfs::stops_near] and [Gtfs::nearest_stops] only
+/// look at the stops around the searched point. The stops without coordinates are not indexed.
+#[derive(Debug, Default, Clone)]
+pub struct StopIndex {
+    /// The stops as an implicit k-d tree: the middle point of each range splits the others
+    /// on the axis given by the depth of the range
+    pub(crate) points: Vec<Point>,
+}
+
+fn build(points: &mut [Point], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = points.len() / 2;
+    points.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    let (left, right) = points.split_at_mut(mid);
+    build(left, depth + 1);
+    build(&mut right[1..], depth + 1);
+}
+
+/// The range of the points on the same side of the middle point as the target, and the
+/// other one with the squared distance from the target to the splitting plane
+fn split<'a>(
+    points: &'a [Point],
+    depth: usize,
+    target: &[f64; 3],
+) -> (&'a Point, &'a [Point], &'a [Point], f64) {
+    let mid = points.len() / 2;
+    let point = &points[mid];
+    let diff = target[depth % 3] - point.0[depth % 3];
+    let (left, right) = (&points[..mid], &points[mid + 1..]);
+    match diff < 0. {
+        true => (point, left, right, diff * diff),
+        false => (point, right, left, diff * diff),
+    }
+}
+
+fn within<'a>(
+    points: &'a [Point],
+    depth: usize,
+    target: &[f64; 3],
+    max: f64,
+    found: &mut Vec<&'a Point>,
+) {
+    if points.is_empty() {
+        return;
+    }
+    let (point, near, far, plane) = split(points, depth, target);
+    if squared_chord(&point.0, target) <= max {
+        found.push(point);
+    }
+    within(near, depth + 1, target, max, found);
+    if plane <= max {
+        within(far, depth + 1, target, max, found);
+    }
+}
+
+/// Keeps in `best` the `n` points closest to the target, ordered by distance and stop id
+fn nearest<'a>(
+    points: &'a [Point],
+    depth: usize,
+    target: &[f64; 3],
+    n: usize,
+    best: &mut Vec<(f64, &'a Point)>,
+) {
+    if points.is_empty() {
+        return;
+    }
+    let (point, near, far, plane) = split(points, depth, target);
+    let candidate = (squared_chord(&point.0, target), point);
+    let position = best.partition_point(|b| {
+        b.0.total_cmp(&candidate.0)
+            .then_with(|| b.1 .1.id.cmp(&candidate.1 .1.id))
+            .is_lt()
+    });
+    if position < n {
+        best.insert(position, candidate);
+        best.truncate(n);
+    }
+    nearest(near, depth + 1, target, n, best);
+    if best.len() < n || best.last().is_some_and(|(worst, _)| plane <= *worst) {
+        nearest(far, depth + 1, target, n, best);
+    }
+}
+
+/// The stops with their distance in meters to the point, the closest first
+fn with_distances<'a>(
+    stops: impl Iterator<Item = &'a Stop>,
+    lat: f64,
+    lon: f64,
+) -> Vec<(&'a Stop, f64)> {
+    let mut res: Vec<_> = stops
+        .filter_map(|stop| {
+            let d = distance(lat, lon, stop.latitude?, stop.longitude?);
+            Some((stop, d))
+        })
+        .collect();
+    res.sort_by(|(a, da), (b, db)| da.total_cmp(db).then_with(|| a.id.cmp(&b.id)));
+    res
+}
+
+impl StopIndex {
+    /// Indexes the stops with coordinates
+    pub fn new(stops: &BTreeMap<String, Arc<Stop>>) -> Self {
+        let mut points: Vec<Point> = stops
+            .values()
+            .filter_map(|stop| Some((to_xyz(stop.latitude?, stop.longitude?), stop.clone())))
+            .collect();
+        build(&mut points, 0);
+        Self { points }
+    }
+
+    /// Number of indexed stops
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The stops at most `radius` meters away from the point, the closest first
+    pub fn within(&self, lat: f64, lon: f64, radius: f64) -> Vec<(&Stop, f64)> {
+        // the straight distance through the sphere for the radius along its surface
+        let chord = 2.
+            * (radius.max(0.) / (2. * EARTH_RADIUS))
+                .min(std::f64::consts::FRAC_PI_2)
+                .sin();
+        let mut found = Vec::new();
+        within(
+            &self.points,
+            0,
+            &to_xyz(lat, lon),
+            chord * chord,
+            &mut found,
+        );
+        with_distances(found.into_iter().map(|p| p.1.as_ref()), lat, lon)
+            .into_iter()
+            .filter(|(_, d)| *d <= radius)
+            .collect()
+    }
+
+    /// The `n` stops closest to the point, the closest first
+    pub fn nearest(&self, lat: f64, lon: f64, n: usize) -> Vec<(&Stop, f64)> {
+        let mut best = Vec::with_capacity(n + 1);
+        if n > 0 {
+            nearest(&self.points, 0, &to_xyz(lat, lon), n, &mut best);
+        }
+        with_distances(best.into_iter().map(|(_, p)| p.1.as_ref()), lat, lon)
+    }
+}
+
+/// A rectangle of longitudes and latitudes
+#[derive(Debug, Clone, Copy)]
+struct BBox {
+    min_lon: f64,
+    min_lat: f64,
+    max_lon: f64,
+    max_lat: f64,
+}
+
+impl BBox {
+    fn contains(&self, lon: f64, lat: f64) -> bool {
+        (self.min_lon..=self.max_lon).contains(&lon) && (self.min_lat..=self.max_lat).contains(&lat)
+    }
+
+    /// Tells if a segment crosses the rectangle, with the Liang–Barsky algorithm
+    fn intersects(&self, (x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> bool {
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let (mut t_min, mut t_max) = (0f64, 1f64);
+        for (p, q) in [
+            (-dx, x1 - self.min_lon),
+            (dx, self.max_lon - x1),
+            (-dy, y1 - self.min_lat),
+            (dy, self.max_lat - y1),
+        ] {
+            if p == 0. {
+                // parallel to this side of the rectangle, and outside of it
+                if q < 0. {
+                    return false;
+                }
+            } else if p < 0. {
+                t_min = t_min.max(q / p);
+            } else {
+                t_max = t_max.min(q / p);
+            }
+        }
+        t_min <= t_max
+    }
+}
+
+/// Distance in meters along the shape to the point of the shape closest to the given point
+///
+/// The shape points must be sorted by sequence. Returns None if the shape has no point.
+pub(crate) fn project_on_shape(shape: &[Shape], lat: f64, lon: f64) -> Option<f64> {
+    // the points are projected on a plane tangent to the earth at the given point
+    let cos_lat = lat.to_radians().cos();
+    let to_xy = |point: &Shape| {
+        (
+            (point.longitude - lon).to_radians() * cos_lat * EARTH_RADIUS,
+            (point.latitude - lat).to_radians() * EARTH_RADIUS,
+        )
+    };
+    if shape.len() == 1 {
+        return Some(0.);
+    }
+    let mut best: Option<(f64, f64)> = None;
+    let mut start = 0.;
+    for segment in shape.windows(2) {
+        let (ax, ay) = to_xy(&segment[0]);
+        let (bx, by) = to_xy(&segment[1]);
+        let (dx, dy) = (bx - ax, by - ay);
+        let length2 = dx * dx + dy * dy;
+        let t = if length2 > 0. {
+            (-(ax * dx + ay * dy) / length2).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let (px, py) = (ax + t * dx, ay + t * dy);
+        let distance2 = px * px + py * py;
+        let length = length2.sqrt();
+        if best.is_none_or(|(best_distance2, _)| distance2 < best_distance2) {
+            best = Some((distance2, start + t * length));
+        }
+        start += length;
+    }
+    best.map(|(_, along)| along)
+}
+
+impl Gtfs {
+    /// Builds the [Gtfs::stop_index] used by [Gtfs::stops_near] and [Gtfs::nearest_stops]
+    ///
+    /// The index must be built again after stops are added, moved or removed.
+    pub fn build_stop_index(&mut self) {
+        self.stop_index = Some(StopIndex::new(&self.stops));
+    }
+
+    /// Stops at most `radius` meters away from the point, with their distance, the closest
+    /// first
+    ///
+    /// Uses the [Gtfs::stop_index] if it was built with [Gtfs::build_stop_index], otherwise
+    /// all the stops are compared to the point. The stops without coordinates are ignored.
+    pub fn stops_near(&self, lat: f64, lon: f64, radius: f64) -> Vec<(&Stop, f64)> {
+        match &self.stop_index {
+            Some(index) => index.within(lat, lon, radius),
+            None => with_distances(self.stops.values().map(|s| s.as_ref()), lat, lon)
+                .into_iter()
+                .filter(|(_, d)| *d <= radius)
+                .collect(),
+        }
+    }
+
+    /// The `n` stops closest to the point, with their distance in meters, the closest first
+    ///
+    /// Like [Gtfs::stops_near], the [Gtfs::stop_index] is used if it was built.
+    pub fn nearest_stops(&self, lat: f64, lon: f64, n: usize) -> Vec<(&Stop, f64)> {
+        match &self.stop_index {
+            Some(index) => index.nearest(lat, lon, n),
+            None => {
+                let mut stops = with_distances(self.stops.values().map(|s| s.as_ref()), lat, lon);
+                stops.truncate(n);
+                stops
+            }
+        }
+    }
+
+    /// Stops inside a rectangle of coordinates, ordered by id
+    ///
+    /// The stops on the sides of the rectangle are included. A rectangle crossing the
+    /// antimeridian must be given as two rectangles.
+    pub fn stops_in_bbox(
+        &self,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> Vec<&Stop> {
+        let bbox = BBox {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        };
+        self.stops
+            .values()
+            .filter(|stop| match (stop.longitude, stop.latitude) {
+                (Some(lon), Some(lat)) => bbox.contains(lon, lat),
+                _ => false,
+            })
+            .map(|stop| stop.as_ref())
+            .collect()
+    }
+
+    /// Stops inside a polygon, ordered by id
+    ///
+    /// The first ring of the polygon is its outline, the next ones are its holes, like the
+    /// polygons of a [crate::Location].
+    pub fn stops_in_polygon(&self, polygon: &[Ring]) -> Vec<&Stop> {
+        self.stops
+            .values()
+            .filter(|stop| match (stop.longitude, stop.latitude) {
+                (Some(lon), Some(lat)) => polygon_contains(polygon, lon, lat),
+                _ => false,
+            })
+            .map(|stop| stop.as_ref())
+            .collect()
+    }
+
+    /// Shapes with at least a part inside a rectangle of coordinates, ordered by id
+    ///
+    /// A shape can cross the rectangle without having a point inside it, for example a
+    /// straight line between two distant points, it is then also given. Like for
+    /// [Gtfs::stops_in_bbox], a rectangle crossing the antimeridian must be split.
+    pub fn shapes_in_bbox(
+        &self,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> Vec<&[Shape]> {
+        let bbox = BBox {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        };
+        self.shapes
+            .values()
+            .filter(|shape| match shape.as_slice() {
+                [point] => bbox.contains(point.longitude, point.latitude),
+                points => points.windows(2).any(|segment| {
+                    bbox.intersects(
+                        (segment[0].longitude, segment[0].latitude),
+                        (segment[1].longitude, segment[1].latitude),
+                    )
+                }),
+            })
+            .map(|shape| shape.as_slice())
+            .collect()
+    }
+
+    /// Closest stop served by at least one trip of the route, with its distance in meters
+    ///
+    /// Only the stops of the stop times are considered (usually platforms, not stations),
+    /// and the stops without coordinates are ignored. An error is returned if the stop times
+    /// of a trip cannot be loaded, see [Gtfs::trip_stop_times].
+    pub fn nearest_stop_on_route(
+        &self,
+        lat: f64,
+        lon: f64,
+        route_id: &str,
+    ) -> Result<Option<(&Stop, f64)>, Error> {
+        let mut served = HashSet::new();
+        for trip in self.trips.values().filter(|trip| trip.route_id == route_id) {
+            let stop_times = self.trip_stop_times(&trip.id)?;
+            served.extend(stop_times.iter().map(|st| st.stop.id.clone()));
+        }
+        Ok(served
+            .into_iter()
+            .filter_map(|id| self.stops.get(&id))
+            .filter_map(|stop| match (stop.latitude, stop.longitude) {
+                (Some(stop_lat), Some(stop_lon)) => {
+                    Some((stop.as_ref(), distance(lat, lon, stop_lat, stop_lon)))
+                }
+                _ => None,
+            })
+            .min_by(|(a, da), (b, db)| da.total_cmp(db).then_with(|| a.id.cmp(&b.id))))
+    }
+}
@@ -0,0 +1,426 @@
+use crate::gtfs::{serves_location, stop_of_stop_time};
+use crate::objects::*;
+use crate::raw_gtfs::{deserialize_record, read_records, ReadContext};
+use crate::{Error, Gtfs};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+const FILE_NAME: &str = "stop_times.txt";
+
+/// Number of temporary files created by the process, to give them unique names
+static NB_TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Stop times of a trip, see [crate::Gtfs::trip_stop_times]
+///
+/// They are borrowed from the trip when they are in memory, so getting them does not copy them.
+#[derive(Debug, Clone)]
+pub enum TripStopTimes<'a> {
+    /// The [Trip::stop_times]
+    Borrowed(&'a [StopTime]),
+    /// Stop times loaded from a [StopTimeStore] or built from a shared stop pattern
+    Loaded(Arc<Vec<StopTime>>),
+}
+
+impl Default for TripStopTimes<'_> {
+    fn default() -> Self {
+        TripStopTimes::Borrowed(&[])
+    }
+}
+
+impl std::ops::Deref for TripStopTimes<'_> {
+    type Target = [StopTime];
+
+    fn deref(&self) -> &[StopTime] {
+        match self {
+            TripStopTimes::Borrowed(stop_times) => stop_times,
+            TripStopTimes::Loaded(stop_times) => stop_times,
+        }
+    }
+}
+
+/// Stop times kept in a temporary file, and loaded trip by trip when needed
+///
+/// The stop times are grouped by trip in the file. The file is removed when the store is dropped.
+/// The stop times of the last loaded trips are kept in memory, and the file stays open.
+pub struct StopTimeStore {
+    pub(crate) path: PathBuf,
+    /// The different headers of the rows (rows shorter than the file headers have their own)
+    headers: Vec<csv::StringRecord>,
+    /// Offset and length in the file of the rows of each trip
+    pub(crate) index: HashMap<String, (u64, usize)>,
+    /// Stops referenced by the stop times, to check them when the feed is read
+    pub(crate) stop_ids: HashSet<String>,
+    /// Drops the stop times referencing unknown stops, see [crate::UnknownStopPolicy::Skip]
+    pub(crate) skip_unknown_stops: bool,
+    cache: Mutex<Cache>,
+}
+
+/// The opened temporary file and the stop times of the last loaded trips
+struct Cache {
+    file: File,
+    trips: LruCache,
+}
+
+impl fmt::Debug for StopTimeStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StopTimeStore")
+            .field("path", &self.path)
+            .field("trips", &self.index.len())
+            .finish()
+    }
+}
+
+impl Drop for StopTimeStore {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Temporary file removed when dropped, even if an error happens while it is written
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new(kind: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "gtfs-structures-{}-{}-{}",
+            std::process::id(),
+            NB_TEMP_FILES.fetch_add(1, Ordering::Relaxed),
+            kind
+        ));
+        Self { path }
+    }
+
+    /// Keeps the file on the disk
+    fn persist(mut self) -> PathBuf {
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::NamedFileIO {
+        file_name: FILE_NAME.to_owned(),
+        source: e,
+    }
+}
+
+/// The temporary file was truncated or modified since it was written
+fn corrupted(what: &str) -> Error {
+    io_error(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{} in the temporary file of the stop times", what),
+    ))
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, Error> {
+    File::create(path).map(BufWriter::new).map_err(io_error)
+}
+
+/// A row is stored as the index of its headers, its number of fields, and each field
+/// preceded by its length
+fn encode_row(headers: u16, record: &csv::StringRecord, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&headers.to_le_bytes());
+    buf.extend_from_slice(&(record.len() as u32).to_le_bytes());
+    for field in record {
+        buf.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        buf.extend_from_slice(field.as_bytes());
+    }
+}
+
+/// Returns a [Error::NamedFileIO] of kind [std::io::ErrorKind::InvalidData] if the rows were
+/// truncated or corrupted in the temporary file
+fn decode_rows(mut buf: &[u8]) -> Result<Vec<(u16, csv::StringRecord)>, Error> {
+    fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8], Error> {
+        if buf.len() < n {
+            return Err(corrupted("truncated row"));
+        }
+        let (head, tail) = buf.split_at(n);
+        *buf = tail;
+        Ok(head)
+    }
+    fn take_u32(buf: &mut &[u8]) -> Result<usize, Error> {
+        let bytes = take(buf, 4)?.try_into().expect("4 bytes were taken");
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+    let mut rows = Vec::new();
+    while !buf.is_empty() {
+        let bytes = take(&mut buf, 2)?.try_into().expect("2 bytes were taken");
+        let headers = u16::from_le_bytes(bytes);
+        let nb_fields = take_u32(&mut buf)?;
+        let mut record = csv::StringRecord::new();
+        for _ in 0..nb_fields {
+            let len = take_u32(&mut buf)?;
+            let field = std::str::from_utf8(take(&mut buf, len)?)
+                .map_err(|_| corrupted("invalid UTF-8 field"))?;
+            record.push_field(field);
+        }
+        rows.push((headers, record));
+    }
+    Ok(rows)
+}
+
+impl StopTimeStore {
+    /// Writes the stop times of the reader to a temporary file
+    ///
+    /// The rows are written in the order of the file, then copied grouped by trip in a second file.
+    pub(crate) fn new<R: Read>(
+        reader: R,
+        ctx: &mut ReadContext,
+        cached_trips: usize,
+    ) -> Result<Self, Error> {
+        let unsorted = TempFile::new("unsorted-stop-times");
+        let mut writer = create(&unsorted.path)?;
+        let mut headers: Vec<csv::StringRecord> = Vec::new();
+        let mut rows_by_trip: BTreeMap<String, Vec<(u64, usize)>> = BTreeMap::new();
+        let mut stop_ids = HashSet::new();
+        let mut offset = 0;
+        let mut buf = Vec::new();
+        read_records(reader, FILE_NAME, ctx, |row_headers, record| {
+            let stop_time: RawStopTime = deserialize_record(row_headers, &record, FILE_NAME)?;
+            let headers_index = match headers.iter().rposition(|h| h == row_headers) {
+                Some(i) => i,
+                None => {
+                    headers.push(row_headers.clone());
+                    headers.len() - 1
+                }
+            };
+            buf.clear();
+            encode_row(headers_index as u16, &record, &mut buf);
+            writer.write_all(&buf).map_err(io_error)?;
+            if !serves_location(&stop_time) && !stop_ids.contains(&stop_time.stop_id) {
+                stop_ids.insert(stop_time.stop_id);
+            }
+            rows_by_trip
+                .entry(stop_time.trip_id)
+                .or_default()
+                .push((offset, buf.len()));
+            offset += buf.len() as u64;
+            Ok(())
+        })?;
+        writer.flush().map_err(io_error)?;
+        drop(writer);
+
+        let sorted = TempFile::new("stop-times");
+        let mut writer = create(&sorted.path)?;
+        let mut reader = File::open(&unsorted.path)
+            .map(BufReader::new)
+            .map_err(io_error)?;
+        let mut index = HashMap::with_capacity(rows_by_trip.len());
+        let mut offset = 0;
+        for (trip_id, rows) in rows_by_trip {
+            let mut len = 0;
+            for (row_offset, row_len) in rows {
+                buf.resize(row_len, 0);
+                reader
+                    .seek(SeekFrom::Start(row_offset))
+                    .and_then(|_| reader.read_exact(&mut buf))
+                    .and_then(|_| writer.write_all(&buf))
+                    .map_err(io_error)?;
+                len += row_len;
+            }
+            index.insert(trip_id, (offset, len));
+            offset += len as u64;
+        }
+        writer.flush().map_err(io_error)?;
+        let file = File::open(&sorted.path).map_err(io_error)?;
+
+        Ok(Self {
+            path: sorted.persist(),
+            headers,
+            index,
+            stop_ids,
+            skip_unknown_stops: false,
+            cache: Mutex::new(Cache {
+                file,
+                trips: LruCache::new(cached_trips),
+            }),
+        })
+    }
+
+    /// Ids of the trips having stop times
+    pub fn trip_ids(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(|id| id.as_str())
+    }
+
+    /// Returns a [Error::ReferenceError] if a stop time references an unknown trip, unless
+    /// those stop times are skipped, and gives the unknown stops referenced by the stop times,
+    /// ordered by id
+    ///
+    /// The stop times of the unknown trips are never loaded, as they are read by trip.
+    pub(crate) fn check_references(
+        &self,
+        trips: &BTreeMap<String, Trip>,
+        stops: &BTreeMap<String, Arc<Stop>>,
+        skip_unknown_trips: bool,
+    ) -> Result<Vec<String>, Error> {
+        let mut unknown_trips = self.trip_ids().filter(|id| !trips.contains_key(*id));
+        if let Some(id) = unknown_trips.next().filter(|_| !skip_unknown_trips) {
+            return Err(Error::ReferenceError(id.to_owned()));
+        }
+        let mut unknown_stops: Vec<String> = self
+            .stop_ids
+            .iter()
+            .filter(|id| !stops.contains_key(*id))
+            .cloned()
+            .collect();
+        unknown_stops.sort();
+        Ok(unknown_stops)
+    }
+
+    /// Reads the stop times of a trip from the disk, or from the cache if they were recently read
+    ///
+    /// The stop times are sorted by stop_sequence. A trip without stop times gives an empty vector.
+    /// A [Error::NamedFileIO] is returned if the temporary file cannot be read or was corrupted.
+    pub fn load(
+        &self,
+        trip_id: &str,
+        stops: &BTreeMap<String, Arc<Stop>>,
+    ) -> Result<Arc<Vec<StopTime>>, Error> {
+        let buf = {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(stop_times) = cache.trips.get(trip_id) {
+                return Ok(stop_times);
+            }
+            let (offset, len) = match self.index.get(trip_id) {
+                Some(position) => *position,
+                None => return Ok(Arc::new(Vec::new())),
+            };
+            let mut buf = vec![0; len];
+            let file = &mut cache.file;
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.read_exact(&mut buf))
+                .map_err(io_error)?;
+            buf
+        };
+
+        let placeholder = Arc::new(Stop::default());
+        let mut stop_times = Vec::new();
+        for (headers, record) in decode_rows(&buf)? {
+            let headers = self
+                .headers
+                .get(headers as usize)
+                .ok_or_else(|| corrupted("unknown headers"))?;
+            let raw: RawStopTime = deserialize_record(headers, &record, FILE_NAME)?;
+            if self.skip_unknown_stops
+                && !serves_location(&raw)
+                && !stops.contains_key(&raw.stop_id)
+            {
+                continue;
+            }
+            let stop = stop_of_stop_time(&raw, stops, &placeholder)?;
+            stop_times.push(StopTime::from(&raw, stop));
+        }
+        stop_times.sort_by_key(|stop_time| stop_time.stop_sequence);
+
+        let stop_times = Arc::new(stop_times);
+        self.cache
+            .lock()
+            .unwrap()
+            .trips
+            .insert(trip_id.to_owned(), Arc::clone(&stop_times));
+        Ok(stop_times)
+    }
+
+    /// Forgets the stop times of a trip, when they are moved to the trip or the trip is removed
+    pub(crate) fn remove(&mut self, trip_id: &str) {
+        self.index.remove(trip_id);
+        self.cache.get_mut().unwrap().trips.remove(trip_id);
+    }
+}
+
+impl Gtfs {
+    /// Moves the spilled stop times of the trips for which the predicate returns true to their
+    /// [Trip::stop_times], so they can be modified
+    ///
+    /// The trips having their own stop times are not considered, and nothing is done if the
+    /// stop times are not spilled to the disk.
+    pub(crate) fn unspill_stop_times<F>(&mut self, mut predicate: F) -> Result<(), Error>
+    where
+        F: FnMut(&Trip, &[StopTime]) -> bool,
+    {
+        let store = match &mut self.stop_time_store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        for trip in self
+            .trips
+            .values_mut()
+            .filter(|trip| trip.stop_times.is_empty())
+        {
+            let stop_times = store.load(&trip.id, &self.stops)?;
+            if predicate(trip, &stop_times) {
+                store.remove(&trip.id);
+                trip.stop_times = Arc::try_unwrap(stop_times).unwrap_or_else(|s| s.to_vec());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the last used values, up to a capacity
+struct LruCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, Arc<Vec<StopTime>>)>,
+    /// Keys by last use
+    uses: BTreeMap<u64, String>,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            uses: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Arc<Vec<StopTime>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (last_use, value) = self.entries.get_mut(key)?;
+        let key = self.uses.remove(last_use)?;
+        *last_use = tick;
+        self.uses.insert(tick, key);
+        Some(Arc::clone(value))
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((last_use, _)) = self.entries.remove(key) {
+            self.uses.remove(&last_use);
+        }
+    }
+
+    fn insert(&mut self, key: String, value: Arc<Vec<StopTime>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((last_use, _)) = self.entries.insert(key.clone(), (self.tick, value)) {
+            self.uses.remove(&last_use);
+        }
+        self.uses.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            match self.uses.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Navigation inside the stations, with their levels and pathways
+
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Walking speed used for the pathways without traversal_time, in meters per second
+const WALKING_SPEED: f64 = 1.3;
+
+/// A location of a station: a platform, an entrance, a generic node or a boarding area
+#[derive(Debug, Clone)]
+pub struct StationNode {
+    pub stop: Arc<Stop>,
+    pub level: Option<Arc<Level>>,
+}
+
+/// A way to go from a node of a station to another one, through a pathway
+///
+/// A bidirectional pathway gives two edges, one in each direction.
+#[derive(Debug, Clone)]
+pub struct StationEdge<'a> {
+    pub pathway: &'a Pathway,
+    /// Index of the node where the edge starts in [StationGraph::nodes]
+    pub from: usize,
+    /// Index of the node where the edge ends in [StationGraph::nodes]
+    pub to: usize,
+    /// The pathway is used from its to_stop_id to its from_stop_id. The stair_count and
+    /// max_slope of the pathway are then in the other direction.
+    pub reversed: bool,
+}
+
+impl<'a> StationEdge<'a> {
+    /// Time in seconds to walk through the pathway: its traversal_time, or else the time
+    /// to walk its length, and 0 when neither is known
+    pub fn traversal_time(&self) -> u32 {
+        self.pathway
+            .traversal_time
+            .or_else(|| {
+                self.pathway
+                    .length
+                    .map(|length| (length / WALKING_SPEED).ceil() as u32)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Tells if the pathway can be used without stairs nor escalators
+    pub fn is_step_free(&self) -> bool {
+        !matches!(
+            self.pathway.pathway_mode,
+            PathwayMode::Stairs | PathwayMode::Escalator
+        ) && self.pathway.stair_count.unwrap_or(0) == 0
+    }
+}
+
+/// The locations of a station linked by its pathways, see [Gtfs::station_graph]
+#[derive(Debug, Clone)]
+pub struct StationGraph<'a> {
+    pub station: Arc<Stop>,
+    /// The stops of the station, ordered by id
+    pub nodes: Vec<StationNode>,
+    pub edges: Vec<StationEdge<'a>>,
+}
+
+impl<'a> StationGraph<'a> {
+    /// Index of a stop in [StationGraph::nodes]
+    pub fn node(&self, stop_id: &str) -> Option<usize> {
+        self.nodes
+            .binary_search_by(|node| node.stop.id.as_str().cmp(stop_id))
+            .ok()
+    }
+
+    /// The edges leaving a node
+    pub fn edges_from(&self, node: usize) -> impl Iterator<Item = &StationEdge<'a>> {
+        self.edges.iter().filter(move |edge| edge.from == node)
+    }
+
+    /// The fastest way from a stop of the station to another one, as the edges to follow
+    ///
+    /// With `step_free`, only the pathways without stairs nor escalators are used, to check
+    /// the accessibility of the platforms. None when the stop cannot be reached.
+    pub fn fastest_path(
+        &self,
+        from_stop_id: &str,
+        to_stop_id: &str,
+        step_free: bool,
+    ) -> Option<Vec<&StationEdge<'a>>> {
+        let from = self.node(from_stop_id)?;
+        let to = self.node(to_stop_id)?;
+        let mut times = vec![u32::MAX; self.nodes.len()];
+        // the edge used to reach each node
+        let mut previous: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        times[from] = 0;
+        queue.push(Reverse((0, from)));
+        while let Some(Reverse((time, node))) = queue.pop() {
+            if node == to {
+                break;
+            }
+            if time > times[node] {
+                continue;
+            }
+            for (i, edge) in self.edges.iter().enumerate() {
+                if edge.from != node || (step_free && !edge.is_step_free()) {
+                    continue;
+                }
+                let arrival = time + edge.traversal_time();
+                if arrival < times[edge.to] {
+                    times[edge.to] = arrival;
+                    previous[edge.to] = Some(i);
+                    queue.push(Reverse((arrival, edge.to)));
+                }
+            }
+        }
+        if times[to] == u32::MAX {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = to;
+        while let Some(i) = previous[node] {
+            path.push(&self.edges[i]);
+            node = self.edges[i].from;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+impl Gtfs {
+    /// The stop, checking that it has the location type
+    fn stop_of_type(&self, stop_id: &str, expected: LocationType) -> Result<&Stop, Error> {
+        let stop = self.get_stop(stop_id)?;
+        match stop.location_type == expected {
+            true => Ok(stop),
+            false => Err(Error::InvalidLocationType {
+                stop_id: stop_id.to_owned(),
+                expected,
+                found: stop.location_type,
+            }),
+        }
+    }
+
+    /// The stops whose parent_station is the stop, ordered by id
+    ///
+    /// These are the platforms, entrances and generic nodes of a station, or the boarding
+    /// areas of a platform. The children of the children are not given.
+    pub fn children_of(&self, stop_id: &str) -> Result<Vec<&Stop>, Error> {
+        self.get_stop(stop_id)?;
+        Ok(self
+            .stops
+            .values()
+            .filter(|stop| stop.parent_station.as_deref() == Some(stop_id))
+            .map(|stop| stop.as_ref())
+            .collect())
+    }
+
+    /// The station of a stop: the stop itself if it is a station, or the station of its
+    /// parent, None if the stop is not in a station
+    pub fn station_of(&self, stop_id: &str) -> Result<Option<&Stop>, Error> {
+        let mut stop = self.get_stop(stop_id)?;
+        // the boarding areas are in a platform, which is in the station
+        for _ in 0..3 {
+            if stop.location_type == LocationType::StopArea {
+                return Ok(Some(stop));
+            }
+            match stop
+                .parent_station
+                .as_ref()
+                .and_then(|id| self.stops.get(id))
+            {
+                Some(parent) => stop = parent,
+                None => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The entrances of a station, ordered by id
+    ///
+    /// An [Error::InvalidLocationType] is returned if the stop is not a station.
+    pub fn entrances_of(&self, station_id: &str) -> Result<Vec<&Stop>, Error> {
+        self.stop_of_type(station_id, LocationType::StopArea)?;
+        Ok(self
+            .children_of(station_id)?
+            .into_iter()
+            .filter(|stop| stop.location_type == LocationType::StationEntrance)
+            .collect())
+    }
+
+    /// The boarding areas of a platform, ordered by id
+    ///
+    /// An [Error::InvalidLocationType] is returned if the stop is not a platform.
+    pub fn boarding_areas_of(&self, platform_id: &str) -> Result<Vec<&Stop>, Error> {
+        self.stop_of_type(platform_id, LocationType::StopPoint)?;
+        Ok(self
+            .children_of(platform_id)?
+            .into_iter()
+            .filter(|stop| stop.location_type == LocationType::BoardingArea)
+            .collect())
+    }
+
+    /// The platforms, entrances, generic nodes and boarding areas of a station, linked by the
+    /// pathways between them
+    ///
+    /// The pathways leading outside of the station are left out.
+    pub fn station_graph(&self, station_id: &str) -> Result<StationGraph<'_>, Error> {
+        let station = self
+            .stops
+            .get(station_id)
+            .ok_or_else(|| Error::ReferenceError(station_id.to_owned()))?;
+        let nodes: Vec<StationNode> = self
+            .stops
+            .values()
+            .filter(|stop| stop.id != station_id && self.is_in_station(stop, station_id))
+            .map(|stop| StationNode {
+                stop: stop.clone(),
+                level: stop.level.clone(),
+            })
+            .collect();
+        let mut graph = StationGraph {
+            station: station.clone(),
+            nodes,
+            edges: Vec::new(),
+        };
+        for pathway in self.pathways.values() {
+            let (from, to) = match (
+                graph.node(&pathway.from_stop_id),
+                graph.node(&pathway.to_stop_id),
+            ) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+            graph.edges.push(StationEdge {
+                pathway,
+                from,
+                to,
+                reversed: false,
+            });
+            if pathway.is_bidirectional {
+                graph.edges.push(StationEdge {
+                    pathway,
+                    from: to,
+                    to: from,
+                    reversed: true,
+                });
+            }
+        }
+        Ok(graph)
+    }
+}
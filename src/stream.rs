@@ -0,0 +1,172 @@
+//! Reading the largest files of a GTFS row by row
+
+use crate::objects::*;
+use crate::raw_gtfs::{deserialize_record, skip_bom};
+use crate::{Error, RawGtfs};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Iterator over the rows of a file of a GTFS, see [RawGtfs::stop_times_iter]
+///
+/// Each row is deserialized when the iterator advances, only the current row is in memory.
+pub struct Records<O> {
+    file_name: String,
+    reader: csv::Reader<Box<dyn Read + Send>>,
+    headers: csv::StringRecord,
+    record: csv::StringRecord,
+    object: PhantomData<O>,
+}
+
+impl<O> Iterator for Records<O>
+where
+    for<'de> O: Deserialize<'de>,
+{
+    type Item = Result<O, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(deserialize_record(
+                &self.headers,
+                &self.record,
+                &self.file_name,
+            )),
+            Ok(false) => None,
+            Err(e) => Some(Err(Error::CSVError {
+                file_name: self.file_name.clone(),
+                source: e,
+                line_in_error: None,
+            })),
+        }
+    }
+}
+
+/// Reader of the decompressed content of a file of a zip archive, checking its CRC32 at the end
+struct Crc32Reader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    expected: u32,
+}
+
+impl<R: Read> Read for Crc32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        if n == 0 && !buf.is_empty() && self.hasher.clone().finalize() != self.expected {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid checksum of the file of the zip archive",
+            ));
+        }
+        Ok(n)
+    }
+}
+
+/// Opens a file of a GTFS directory or zip archive
+///
+/// The stored and deflated files of the archive are decompressed while they are read: the [zip]
+/// crate cannot give a reader that owns the archive, so the data of the file is read directly,
+/// and its CRC32 is checked at the end. The files compressed with the other methods are
+/// decompressed in memory by the [zip] crate.
+fn open(path: &Path, file_name: &str) -> Result<Box<dyn Read + Send>, Error> {
+    if path.is_dir() {
+        let file = File::open(path.join(file_name))
+            .map_err(|e| Error::MissingFile(format!("Could not find file: {}", e)))?;
+        return Ok(Box::new(file));
+    }
+    let mut file = File::open(path)?;
+    let (compression, data_start, compressed_size, crc32) = {
+        let mut archive = zip::ZipArchive::new(&mut file)?;
+        let index = (0..archive.len())
+            .find(|i| {
+                archive.by_index(*i).ok().is_some_and(|f| {
+                    Path::new(f.name()).file_name() == Some(std::ffi::OsStr::new(file_name))
+                })
+            })
+            .ok_or_else(|| Error::MissingFile(file_name.to_owned()))?;
+        let mut zip_file = archive.by_index(index)?;
+        let compression = zip_file.compression();
+        if compression != zip::CompressionMethod::Stored
+            && compression != zip::CompressionMethod::Deflated
+        {
+            let mut data = Vec::with_capacity(zip_file.size() as usize);
+            zip_file.read_to_end(&mut data)?;
+            return Ok(Box::new(std::io::Cursor::new(data)));
+        }
+        (
+            compression,
+            zip_file.data_start(),
+            zip_file.compressed_size(),
+            zip_file.crc32(),
+        )
+    };
+    file.seek(SeekFrom::Start(data_start))?;
+    let data = file.take(compressed_size);
+    let inner: Box<dyn Read + Send> = match compression {
+        zip::CompressionMethod::Deflated => Box::new(flate2::read::DeflateDecoder::new(data)),
+        _ => Box::new(data),
+    };
+    Ok(Box::new(Crc32Reader {
+        inner,
+        hasher: crc32fast::Hasher::new(),
+        expected: crc32,
+    }))
+}
+
+fn records<O, P>(path: P, file_name: &str) -> Result<Records<O>, Error>
+where
+    P: AsRef<Path> + std::fmt::Display,
+{
+    let p = path.as_ref();
+    if !p.is_file() && !p.is_dir() {
+        return Err(Error::NotFileNorDirectory(format!("{}", p.display())));
+    }
+    let reader: Box<dyn Read + Send> = Box::new(skip_bom(open(p, file_name)?, file_name)?);
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|e| Error::CSVError {
+            file_name: file_name.to_owned(),
+            source: e,
+            line_in_error: None,
+        })?
+        .clone();
+    Ok(Records {
+        file_name: file_name.to_owned(),
+        reader,
+        headers,
+        record: csv::StringRecord::new(),
+        object: PhantomData,
+    })
+}
+
+impl RawGtfs {
+    /// Reads the stop times of a local zip archive or directory one by one
+    ///
+    /// Unlike [RawGtfs::from_path], the stop times are not all loaded in memory, so very large
+    /// files can be processed. The [crate::GtfsReaderOptions] are not applied.
+    ///
+    /// ```
+    /// let stop_times = gtfs_structures::RawGtfs::stop_times_iter("fixtures/basic").unwrap();
+    /// for stop_time in stop_times {
+    ///     println!("{}", stop_time.unwrap().stop_id);
+    /// }
+    /// ```
+    pub fn stop_times_iter<P>(path: P) -> Result<Records<RawStopTime>, Error>
+    where
+        P: AsRef<Path> + std::fmt::Display,
+    {
+        records(path, "stop_times.txt")
+    }
+
+    /// Reads the points of the shapes of a local zip archive or directory one by one,
+    /// see [RawGtfs::stop_times_iter]
+    pub fn shapes_iter<P>(path: P) -> Result<Records<Shape>, Error>
+    where
+        P: AsRef<Path> + std::fmt::Display,
+    {
+        records(path, "shapes.txt")
+    }
+}
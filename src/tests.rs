@@ -1,8 +1,17 @@
 use crate::objects::*;
+use crate::{FrequencyBand, ServiceBand};
 use crate::Gtfs;
+use crate::{FixedHolidays, HolidayKind, HolidayVariant, NoHolidays};
+use crate::{GtfsReaderOptions, ParseWarning, UnknownStopPolicy};
 use crate::RawGtfs;
-use chrono::NaiveDate;
+use crate::{ChangeEvent, ConnectionTable, IssueKind};
+use crate::{Error, FareError, FareLeg, GtfsBuilder, GtfsView, Money, SearchResult};
+use crate::{HashIds, IdGenerator, SequentialIds, UuidIds};
+use crate::{GtfsTime, HeaderMapping};
+use chrono::{NaiveDate, Weekday};
 use rgb::RGB8;
+use std::fs::File;
+use std::sync::Arc;
 
 #[test]
 fn read_calendar() {
@@ -145,21 +154,2921 @@ fn read_feed_info() {
     assert_eq!("SNCF", feed[0].name);
     assert_eq!("http://www.sncf.com", feed[0].url);
     assert_eq!("fr", feed[0].lang);
-    assert_eq!(Some(NaiveDate::from_ymd(2018, 07, 09)), feed[0].start_date);
-    assert_eq!(Some(NaiveDate::from_ymd(2018, 09, 27)), feed[0].end_date);
+    assert_eq!(Some(NaiveDate::from_ymd_opt(2018, 7, 9).unwrap()), feed[0].start_date);
+    assert_eq!(Some(NaiveDate::from_ymd_opt(2018, 9, 27).unwrap()), feed[0].end_date);
     assert_eq!(Some("0.3".to_string()), feed[0].version);
 }
 
 #[test]
 fn trip_days() {
     let gtfs = Gtfs::from_path("fixtures/basic/").unwrap();
-    let days = gtfs.trip_days(&"service1".to_owned(), NaiveDate::from_ymd(2017, 1, 1));
+    let days = gtfs.trip_days("service1", NaiveDate::from_ymd_opt(2017, 1, 1).unwrap());
     assert_eq!(vec![6, 7, 13, 14], days);
 
-    let days2 = gtfs.trip_days(&"service2".to_owned(), NaiveDate::from_ymd(2017, 1, 1));
+    let days2 = gtfs.trip_days("service2", NaiveDate::from_ymd_opt(2017, 1, 1).unwrap());
     assert_eq!(vec![0], days2);
 }
 
+#[test]
+fn route_departures() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let tuesday = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+
+    let departures = gtfs.route_departures("r71", Some(DirectionType::Outbound), tuesday).unwrap();
+    let trips: Vec<_> = departures.iter().map(|d| d.trip.id.as_str()).collect();
+    assert_eq!(trips, vec!["r71_0700", "r71_0730", "r71_1200"]);
+    assert_eq!(departures[0].stop.id, "central_a");
+    assert_eq!(departures[1].stop.id, "central_b");
+    assert_eq!(departures[0].departure_time, 7 * 3600);
+
+    assert_eq!(4, gtfs.route_departures("r71", None, tuesday).unwrap().len());
+
+    // the weekday service is removed on new year's day
+    let new_year = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    assert!(gtfs.route_departures("r71", None, new_year).unwrap().is_empty());
+    assert_eq!(1, gtfs.route_departures("t1", None, new_year).unwrap().len());
+
+    // the stop times of the trips sharing a stop pattern are rebuilt
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let shared = Gtfs::from_path_with_options("fixtures/timetable", options).unwrap();
+    assert!(!shared.stop_patterns.as_ref().unwrap().is_empty());
+    let departures = shared.route_departures("r71", Some(DirectionType::Outbound), tuesday).unwrap();
+    let trips: Vec<_> = departures.iter().map(|d| d.trip.id.as_str()).collect();
+    assert_eq!(trips, vec!["r71_0700", "r71_0730", "r71_1200"]);
+    assert_eq!(departures[1].stop.id, "central_b");
+}
+
+#[test]
+fn origin_departures() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let tuesday = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+    let departures = gtfs.origin_departures("central", tuesday, 0..24 * 3600).unwrap();
+    let trips: Vec<_> = departures.iter().map(|d| d.trip.id.as_str()).collect();
+    // r71_0800_back ends at the station
+    assert_eq!(trips, vec!["r71_0700", "r71_0730", "r71_1200"]);
+    assert_eq!(departures[1].stop.id, "central_b");
+    assert_eq!(departures[1].headsign.as_deref(), Some("University"));
+    assert_eq!(1, gtfs.origin_departures("market", tuesday, 8 * 3600..9 * 3600).unwrap().len());
+
+    // the trips of friday after midnight leave on saturday
+    let gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let friday = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+    let departures = gtfs.origin_departures("stop1", friday, 22 * 3600..24 * 3600).unwrap();
+    let times: Vec<_> = departures.iter().map(|d| d.departure_time / 60).collect();
+    assert_eq!(times, vec![22 * 60, 22 * 60 + 20, 22 * 60 + 40, 23 * 60 + 50]);
+    let saturday = friday.succ_opt().unwrap();
+    let departures = gtfs.origin_departures("stop1", saturday, 0..3600).unwrap();
+    assert_eq!(1, departures.len());
+    assert_eq!(departures[0].trip.id, "n_2430");
+    assert_eq!(departures[0].departure_time, 30 * 60);
+    assert_eq!(departures[0].service_date, friday);
+
+    // the stop times are loaded when they are spilled to the disk or shared
+    for options in [
+        GtfsReaderOptions::new().spill_stop_times(1),
+        GtfsReaderOptions::new().share_stop_patterns(true),
+    ] {
+        let gtfs = Gtfs::from_path_with_options("fixtures/night", options).unwrap();
+        let departures = gtfs.origin_departures("stop1", friday, 22 * 3600..24 * 3600).unwrap();
+        let times: Vec<_> = departures.iter().map(|d| d.departure_time / 60).collect();
+        assert_eq!(times, vec![22 * 60, 22 * 60 + 20, 22 * 60 + 40, 23 * 60 + 50]);
+    }
+}
+
+#[test]
+fn departures_from() {
+    let gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let friday = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+    let at = |date: NaiveDate, h, m| date.and_hms_opt(h, m, 0).unwrap();
+
+    let departures = gtfs.departures_from("stop1", at(friday, 23, 0), chrono::Duration::hours(2)).unwrap();
+    let trips: Vec<_> = departures.iter().map(|d| d.trip.id.as_str()).collect();
+    assert_eq!(trips, vec!["n_2350", "n_2430"]);
+    assert_eq!(departures[0].route.id, "r1");
+    // the trip after midnight is of the service of friday
+    let saturday = friday.succ_opt().unwrap();
+    assert_eq!(departures[1].departure, at(saturday, 0, 30));
+    assert_eq!(departures[1].service_date, friday);
+
+    // a trip repeated by frequencies
+    let departures = gtfs.departures_from("stop1", at(friday, 22, 30), chrono::Duration::minutes(20)).unwrap();
+    assert_eq!(1, departures.len());
+    assert_eq!(departures[0].trip.id, "n_2200");
+    assert_eq!(departures[0].departure, at(friday, 22, 40));
+
+    // no service on saturday
+    let departures = gtfs.departures_from("stop1", at(saturday, 22, 0), chrono::Duration::hours(1)).unwrap();
+    assert!(departures.is_empty());
+    assert!(gtfs.departures_from("unknown", at(friday, 22, 0), chrono::Duration::hours(1)).is_err());
+
+    let options = GtfsReaderOptions::new().spill_stop_times(1);
+    let gtfs = Gtfs::from_path_with_options("fixtures/night", options).unwrap();
+    let departures = gtfs.departures_from("stop1", at(friday, 23, 0), chrono::Duration::hours(2)).unwrap();
+    let trips: Vec<_> = departures.iter().map(|d| d.trip.id.as_str()).collect();
+    assert_eq!(trips, vec!["n_2350", "n_2430"]);
+}
+
+/// Timezone of Paris in 2024, with the daylight saving time from March 31 to October 27
+#[derive(Clone, Copy, Debug)]
+struct Paris2024;
+
+impl chrono::TimeZone for Paris2024 {
+    type Offset = chrono::FixedOffset;
+
+    fn from_offset(_: &chrono::FixedOffset) -> Self {
+        Paris2024
+    }
+
+    fn offset_from_local_date(
+        &self,
+        local: &NaiveDate,
+    ) -> chrono::LocalResult<chrono::FixedOffset> {
+        self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    fn offset_from_local_datetime(
+        &self,
+        local: &chrono::NaiveDateTime,
+    ) -> chrono::LocalResult<chrono::FixedOffset> {
+        let offsets: Vec<_> = [7200, 3600]
+            .iter()
+            .map(|&s| chrono::FixedOffset::east_opt(s).unwrap())
+            .filter(|offset| self.offset_from_utc_datetime(&(*local - *offset)) == *offset)
+            .collect();
+        match offsets.as_slice() {
+            [offset] => chrono::LocalResult::Single(*offset),
+            [first, last] => chrono::LocalResult::Ambiguous(*first, *last),
+            _ => chrono::LocalResult::None,
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> chrono::FixedOffset {
+        self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &chrono::NaiveDateTime) -> chrono::FixedOffset {
+        let change = |month, day| {
+            NaiveDate::from_ymd_opt(2024, month, day)
+                .and_then(|date| date.and_hms_opt(1, 0, 0))
+                .unwrap()
+        };
+        match change(3, 31) <= *utc && *utc < change(10, 27) {
+            true => chrono::FixedOffset::east_opt(7200).unwrap(),
+            false => chrono::FixedOffset::east_opt(3600).unwrap(),
+        }
+    }
+}
+
+#[test]
+fn departure_instant() {
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let paris = |name: &str| (name == "Europe/Paris").then_some(Paris2024);
+    let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+    let utc = |month, day, h, m| date(month, day).and_hms_opt(h, m, 0).unwrap();
+    let stop_time = gtfs.get_trip("n_2430").unwrap().stop_times[1].clone();
+    let departure = gtfs.departure_instant("n_2430", &stop_time, date(1, 5), paris).unwrap();
+    assert_eq!(Some(utc(1, 5, 23, 45)), departure.map(|d| d.naive_utc()));
+
+    // the clocks are set forward at 2:00 on the 31st of March, 1:30 is still counted from
+    // noon minus 12h, which is 23:00 the day before
+    let mut early = stop_time.clone();
+    early.departure_time = Some(5400);
+    let departure = gtfs.departure_instant("n_2430", &early, date(3, 31), paris).unwrap();
+    assert_eq!(Some(utc(3, 30, 23, 30)), departure.map(|d| d.naive_utc()));
+    early.departure_time = None;
+    early.arrival_time = None;
+    assert!(gtfs.departure_instant("n_2430", &early, date(3, 31), paris).unwrap().is_none());
+
+    // the instant is in the timezone of the stop
+    Arc::make_mut(gtfs.stops.get_mut("stop1").unwrap()).timezone = Some("Europe/London".into());
+    let fixed = |name: &str| match name {
+        "Europe/Paris" => chrono::FixedOffset::east_opt(3600),
+        "Europe/London" => chrono::FixedOffset::east_opt(0),
+        _ => None,
+    };
+    let stop_time = gtfs.get_trip("n_2430").unwrap().stop_times[0].clone();
+    let arrival = gtfs.arrival_instant("n_2430", &stop_time, date(1, 5), fixed).unwrap().unwrap();
+    assert_eq!("2024-01-05 23:30:00 +00:00", arrival.to_string());
+    assert_eq!(Some("Europe/London"), gtfs.stop_timezone("stop1").unwrap());
+    assert_eq!("Europe/Paris", gtfs.trip_timezone("n_2430").unwrap());
+    assert!(matches!(
+        gtfs.arrival_instant("n_2430", &stop_time, date(1, 5), paris),
+        Err(Error::UnknownTimezone(_))
+    ));
+}
+
+#[test]
+fn elapsed_between() {
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let minutes = |m| chrono::Duration::minutes(m);
+    let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+    let elapsed = |gtfs: &Gtfs, from, date| {
+        gtfs.elapsed_between("n_2350", from, 3, date, &Paris2024)
+            .unwrap()
+    };
+    // from the departure at 23:50 to the arrival at 24:10
+    assert_eq!(minutes(20), elapsed(&gtfs, 1, date(1, 5)));
+    assert_eq!(minutes(8), elapsed(&gtfs, 2, date(1, 5)));
+
+    // the trip now arrives at 27:10, after the clocks have changed during the night: the
+    // times are counted from noon minus 12h, so the trip still lasts 200 minutes while the
+    // clocks show 04:10 in March and 02:10 in October
+    gtfs.trips.get_mut("n_2350").unwrap().stop_times[2].arrival_time = Some(27 * 3600 + 600);
+    assert_eq!(minutes(200), elapsed(&gtfs, 1, date(1, 5)));
+    assert_eq!(minutes(200), elapsed(&gtfs, 1, date(3, 30)));
+    assert_eq!(minutes(200), elapsed(&gtfs, 1, date(10, 26)));
+
+    assert!(matches!(
+        gtfs.elapsed_between("n_2350", 1, 4, date(1, 5), &Paris2024),
+        Err(Error::InvalidStopSequence(_))
+    ));
+    assert!(matches!(
+        gtfs.elapsed_between("unknown", 1, 3, date(1, 5), &Paris2024),
+        Err(Error::ReferenceError(_))
+    ));
+}
+
+#[test]
+fn ical_export() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    let unfold = |ical: &str| ical.replace("\r\n ", "");
+
+    // no service on January 1
+    let ical = gtfs.trip_to_ical("r71_0700", date(1)..=date(3), &Paris2024).unwrap();
+    assert!(ical.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ical.ends_with("END:VCALENDAR\r\n"));
+    assert!(ical.lines().all(|line| line.len() <= 76));
+    assert_eq!(2, ical.matches("BEGIN:VEVENT").count());
+    let ical = unfold(&ical);
+    assert!(ical.contains("DTSTART:20240102T060000Z\r\nDTEND:20240102T063000Z\r\n"));
+    assert!(ical.contains("UID:r71_0700-20240103-25200@gtfs-structures\r\n"));
+    assert!(ical.contains("SUMMARY:71 University\r\nLOCATION:Central Station A\r\n"));
+    assert!(ical.contains("DESCRIPTION:07:00 Central Station A\\n07:11 Market\\n"));
+
+    let ical = gtfs.stop_departures_to_ical("central", date(2)..=date(2), &Paris2024).unwrap();
+    assert_eq!(3, ical.matches("BEGIN:VEVENT").count());
+    assert!(!ical.contains("DTEND"));
+    assert!(ical.contains("DTSTART:20240102T063000Z\r\nSUMMARY:71 University\r\n"));
+    assert!(ical.contains("LOCATION:Central Station B\r\n"));
+
+    // the text values are escaped, and each departure of the frequencies is an event
+    let trip = gtfs.trips.get_mut("r71_0700").unwrap();
+    trip.trip_headsign = Some("University, north; east".to_owned());
+    trip.frequencies.push(Frequency {
+        start_time: 7 * 3600,
+        end_time: 8 * 3600,
+        headway_secs: 1200,
+        exact_times: None,
+    });
+    let ical = unfold(&gtfs.trip_to_ical("r71_0700", date(2)..=date(2), &Paris2024).unwrap());
+    assert_eq!(3, ical.matches("BEGIN:VEVENT").count());
+    assert!(ical.contains("SUMMARY:71 University\\, north\\; east\r\n"));
+    assert!(ical.contains("DTSTART:20240102T064000Z\r\nDTEND:20240102T071000Z\r\n"));
+
+    assert!(gtfs.trip_to_ical("unknown", date(1)..=date(3), &Paris2024).is_err());
+    assert!(gtfs.stop_departures_to_ical("unknown", date(1)..=date(3), &Paris2024).is_err());
+}
+
+#[test]
+fn station_graph() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let graph = gtfs.station_graph("central").unwrap();
+    let nodes: Vec<_> = graph.nodes.iter().map(|n| n.stop.id.as_str()).collect();
+    assert_eq!(nodes, vec!["central_a", "central_b"]);
+    assert_eq!(graph.nodes[1].level.as_ref().unwrap().index, -1.);
+    // the stairs can be used in both directions, the escalator only from b to a
+    assert_eq!(3, graph.edges.len());
+    assert_eq!(1, graph.edges_from(graph.node("central_a").unwrap()).count());
+
+    let path = graph.fastest_path("central_b", "central_a", false).unwrap();
+    assert_eq!(path.iter().map(|e| e.pathway.id.as_str()).collect::<Vec<_>>(), vec!["escalator_ba"]);
+    let path = graph.fastest_path("central_a", "central_b", false).unwrap();
+    assert_eq!(path.len(), 1);
+    assert_eq!(path[0].traversal_time(), 30);
+    assert!(!path[0].reversed);
+    assert!(graph.fastest_path("central_a", "central_b", true).is_none());
+
+    assert!(gtfs.station_graph("unknown").is_err());
+}
+
+#[test]
+fn station_hierarchy() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    for (id, location_type, parent) in [
+        ("central_north", LocationType::StationEntrance, "central"),
+        ("central_a_front", LocationType::BoardingArea, "central_a"),
+    ] {
+        let stop = Stop {
+            id: id.to_owned(),
+            location_type,
+            parent_station: Some(parent.to_owned()),
+            ..Default::default()
+        };
+        gtfs.stops.insert(id.to_owned(), Arc::new(stop));
+    }
+    let ids = |stops: Vec<&Stop>| stops.iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        vec!["central_a", "central_b", "central_north"],
+        ids(gtfs.children_of("central").unwrap())
+    );
+    assert!(gtfs.children_of("market").unwrap().is_empty());
+    assert_eq!(
+        vec!["central_north"],
+        ids(gtfs.entrances_of("central").unwrap())
+    );
+    assert_eq!(
+        vec!["central_a_front"],
+        ids(gtfs.boarding_areas_of("central_a").unwrap())
+    );
+
+    let station = |id| gtfs.station_of(id).unwrap().map(|s| s.id.as_str());
+    assert_eq!(Some("central"), station("central_a_front"));
+    assert_eq!(Some("central"), station("central_north"));
+    assert_eq!(Some("central"), station("central"));
+    assert_eq!(None, station("market"));
+
+    assert!(matches!(
+        gtfs.entrances_of("central_a"),
+        Err(Error::InvalidLocationType {
+            expected: LocationType::StopArea,
+            found: LocationType::StopPoint,
+            ..
+        })
+    ));
+    assert!(matches!(
+        gtfs.boarding_areas_of("central"),
+        Err(Error::InvalidLocationType { .. })
+    ));
+    assert!(gtfs.station_of("unknown").is_err());
+}
+
+#[test]
+fn route_destinations() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    // one of the three outbound trips has another headsign, the inbound trip has none
+    gtfs.trips.get_mut("r71_1200").unwrap().trip_headsign = Some("Campus".to_owned());
+    gtfs.trips.get_mut("r71_0800_back").unwrap().trip_headsign = None;
+
+    let destinations = gtfs.route_destinations("r71").unwrap();
+    assert_eq!(2, destinations.len());
+    assert_eq!(Some(DirectionType::Outbound), destinations[0].direction_id);
+    assert_eq!(Some("University"), destinations[0].headsign.as_deref());
+    assert_eq!(3, destinations[0].trips);
+    assert_eq!(Some(DirectionType::Inbound), destinations[1].direction_id);
+    // the trip ends at a platform, the station gives the name
+    assert_eq!("central", destinations[1].last_stop.as_ref().unwrap().id);
+    assert_eq!(Some("Central Station"), destinations[1].headsign.as_deref());
+    assert!(gtfs.route_destinations("unknown").unwrap().is_empty());
+}
+
+#[test]
+fn string_table() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let table = gtfs.string_table();
+    // 1 agency, 6 stops, 2 routes, 5 trips, 2 services
+    assert_eq!(16, table.len());
+
+    let symbol = table.get("market").expect("stop id not interned");
+    assert_eq!(Some("market"), table.resolve(symbol));
+    assert_eq!(symbol, gtfs.string_table().get("market").unwrap());
+    assert!(table.get("Utopia").is_none());
+
+    let mut interner = crate::StringInterner::new();
+    let a = interner.intern("a");
+    assert_eq!(a, interner.intern("a"));
+    assert_eq!(1, interner.len());
+}
+
+#[test]
+fn memory_report() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let report = gtfs.memory_report();
+    assert!(report.stops > 6 * std::mem::size_of::<Stop>());
+    assert!(report.stop_times >= 19 * std::mem::size_of::<StopTime>());
+    assert_eq!(0, report.shapes);
+    assert_eq!(0, report.translations);
+    assert_eq!(
+        report.total(),
+        report.stops
+            + report.routes
+            + report.trips
+            + report.stop_times
+            + report.calendars
+            + report.fare_attributes
+            + report.other
+    );
+
+    // the stop times of the trips sharing a stop pattern are counted once
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let shared = Gtfs::from_path_with_options("fixtures/timetable", options).unwrap();
+    let shared_report = shared.memory_report();
+    assert!(shared_report.stop_times >= 15 * std::mem::size_of::<StopTime>());
+    assert!(shared_report.stop_times < report.stop_times);
+}
+
+#[test]
+fn retain_stops() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.retain_stops(|stop| stop.id != "market").unwrap();
+    assert_eq!(5, gtfs.stops.len());
+    assert_eq!(3, gtfs.get_trip("r71_0700").unwrap().stop_times.len());
+
+    // the platforms are removed with their station
+    gtfs.retain_stops(|stop| stop.id != "central").unwrap();
+    assert_eq!(2, gtfs.stops.len());
+    assert!(gtfs.get_stop("central_a").is_err());
+    // the tram only had harbour left
+    assert!(gtfs.get_trip("t1_0900").is_err());
+    assert_eq!(2, gtfs.get_trip("r71_0700").unwrap().stop_times.len());
+
+    // r71_0700 and r71_1200 share their stop times
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let mut gtfs = Gtfs::from_path_with_options("fixtures/timetable", options).unwrap();
+    gtfs.retain_stops(|stop| stop.id != "market").unwrap();
+    let stop_times = gtfs.trip_stop_times("r71_1200").unwrap();
+    let stop_ids: Vec<_> = stop_times.iter().map(|st| st.stop.id.as_str()).collect();
+    assert_eq!(vec!["central_a", "harbour", "university"], stop_ids);
+    assert_eq!(1, gtfs.stop_patterns.as_ref().unwrap().len());
+    gtfs.retain_stops(|stop| stop.id != "central").unwrap();
+    assert!(gtfs.trips.values().all(|trip| gtfs.trip_stop_times(&trip.id).unwrap().len() >= 2));
+    assert!(gtfs.get_trip("t1_0900").is_err());
+}
+
+#[test]
+fn mutations_of_spilled_stop_times() {
+    // the spilled stop times that are changed are moved to their trip
+    let options = || GtfsReaderOptions::new().spill_stop_times(1);
+    let mut gtfs = Gtfs::from_path_with_options("fixtures/timetable", options()).unwrap();
+    assert!(gtfs.trips["r71_0700"].stop_times.is_empty());
+    gtfs.retain_stops(|stop| stop.id != "market").unwrap();
+    assert_eq!(3, gtfs.trips["r71_0700"].stop_times.len());
+    gtfs.retain_stops(|stop| stop.id != "central").unwrap();
+    assert!(gtfs.get_trip("t1_0900").is_err());
+    let store = gtfs.stop_time_store.as_ref().unwrap();
+    assert!(store.trip_ids().all(|id| gtfs.trips.contains_key(id)));
+    let stop_times = gtfs.trip_stop_times("r71_0800_back").unwrap();
+    let stop_ids: Vec<_> = stop_times.iter().map(|st| st.stop.id.as_str()).collect();
+    assert_eq!(vec!["university", "harbour"], stop_ids);
+
+    let mut gtfs = Gtfs::from_path_with_options("fixtures/night", options()).unwrap();
+    assert_eq!(vec!["n_2350_next_day", "n_2430"], gtfs.split_trips_at_midnight().unwrap());
+    assert!(gtfs.trips.keys().all(|id| !gtfs.trip_with_stop_times(id).unwrap().crosses_midnight()));
+    assert_eq!(2, gtfs.trip_stop_times("n_2350").unwrap().len());
+    assert_eq!(Some(45 * 60), gtfs.trip_stop_times("n_2430").unwrap()[1].arrival_time);
+    // the trips that are not split stay on the disk
+    assert!(gtfs.trips["n_2200"].stop_times.is_empty());
+    assert_eq!(2, gtfs.trip_stop_times("n_2200").unwrap().len());
+
+    let mut gtfs = Gtfs::from_path_with_options("fixtures/interpolated_stop_times", options()).unwrap();
+    assert_eq!(1, gtfs.interpolate_stop_times().unwrap());
+    assert_eq!(Some(14 * 3600 + 1800), gtfs.trip_stop_times("trip1").unwrap()[1].arrival_time);
+}
+
+#[test]
+fn retain_routes_and_trips() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.retain_routes(|route| route.id != "t1");
+    assert_eq!(1, gtfs.routes.len());
+    assert_eq!(4, gtfs.trips.len());
+
+    gtfs.retain_trips(|trip| trip.direction_id == Some(DirectionType::Outbound));
+    assert_eq!(3, gtfs.trips.len());
+
+    // shapes not used by trips in the first place are kept
+    let mut gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    gtfs.retain_trips(|_| false);
+    assert!(gtfs.trips.is_empty());
+    assert_eq!(2, gtfs.shapes.len());
+}
+
+#[test]
+fn references() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let trip = gtfs.get_trip("r71_0700").unwrap();
+    assert_eq!(
+        trip.references(),
+        vec![(ObjectType::Route, "r71"), (ObjectType::Calendar, "week")]
+    );
+    assert_eq!(
+        gtfs.get_stop("central_b").unwrap().references(),
+        vec![(ObjectType::Stop, "central"), (ObjectType::Level, "underground")]
+    );
+
+    // the validator reports the references to unknown objects
+    let mut market = gtfs.get_stop("market").unwrap().clone();
+    market.level_id = Some("roof".to_owned());
+    gtfs.stops.insert("market".to_owned(), Arc::new(market));
+    let issues: Vec<_> = gtfs
+        .validate()
+        .unwrap()
+        .issues
+        .into_iter()
+        .filter(|issue| issue.kind == IssueKind::InvalidReference)
+        .collect();
+    assert_eq!(1, issues.len());
+    assert_eq!("market", issues[0].object_id);
+
+    // the transfers and attributions of a removed route are removed with it
+    gtfs.retain_routes(|route| route.id != "t1");
+    assert!(gtfs.transfers_from("central_b").is_empty());
+    assert_eq!(3, gtfs.transfers.values().flatten().count());
+    assert_eq!(2, gtfs.attributions.len());
+}
+
+#[test]
+fn stable_iteration_order() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let trips: Vec<_> = gtfs.trips.keys().map(|id| id.as_str()).collect();
+    assert_eq!(
+        trips,
+        vec!["r71_0700", "r71_0730", "r71_0800_back", "r71_1200", "t1_0900"]
+    );
+    let stops: Vec<_> = gtfs.stops.keys().map(|id| id.as_str()).collect();
+    assert_eq!(
+        stops,
+        vec!["central", "central_a", "central_b", "harbour", "market", "university"]
+    );
+}
+
+#[test]
+fn frequency_bands() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let bands = gtfs.frequency_bands("r71", NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()).unwrap();
+    assert_eq!(
+        bands,
+        vec![
+            FrequencyBand {
+                band: ServiceBand::Peak,
+                start_hour: 7,
+                end_hour: 8,
+                departures: 2,
+            },
+            FrequencyBand {
+                band: ServiceBand::OffPeak,
+                start_hour: 8,
+                end_hour: 13,
+                departures: 2,
+            },
+        ]
+    );
+    assert!(gtfs
+        .frequency_bands("r71", NaiveDate::from_ymd_opt(2024, 1, 6).unwrap())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn deduplicate_rows() {
+    let raw = RawGtfs::from_path("fixtures/duplicated_rows").expect("impossible to read gtfs");
+    assert_eq!(8, raw.stops.expect("stops").len());
+    assert_eq!(2, raw.stop_times.expect("stop times").len());
+    assert!(raw.warnings.is_empty());
+
+    let options = GtfsReaderOptions::new().deduplicate_rows(true);
+    let gtfs = Gtfs::from_path_with_options("fixtures/duplicated_rows", options)
+        .expect("impossible to read gtfs");
+    assert_eq!(1, gtfs.get_trip("trip1").unwrap().stop_times.len());
+    assert_eq!("StopPoint", gtfs.get_stop("stop2").unwrap().name);
+    assert_eq!(
+        vec![
+            ParseWarning {
+                file_name: "stops.txt".to_owned(),
+                line: Some(8),
+                column: None,
+                message: "duplicate of line 3, the row is ignored".to_owned(),
+            },
+            ParseWarning {
+                file_name: "stop_times.txt".to_owned(),
+                line: Some(3),
+                column: None,
+                message: "duplicate of line 2, the row is ignored".to_owned(),
+            },
+        ],
+        gtfs.warnings
+    );
+}
+
+#[test]
+fn operating_days() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    let days = &gtfs.operating_days;
+    assert_eq!(Some(date(1)), days.start_date());
+    assert_eq!(Some(date(31)), days.end_date());
+    assert!(!days.is_active("week", date(1)));
+    assert!(days.is_active("week", date(2)));
+    assert!(!days.is_active("week", NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+    assert_eq!(
+        vec![1, 6, 7, 13, 14, 20, 21, 27, 28].into_iter().map(date).collect::<Vec<_>>(),
+        days.active_dates("weekend")
+    );
+
+    let trip_ids = |day| -> Vec<&str> {
+        gtfs.trips_on(date(day)).iter().map(|t| t.id.as_str()).collect()
+    };
+    assert_eq!(vec!["t1_0900"], trip_ids(1));
+    assert_eq!(
+        vec!["r71_0700", "r71_0730", "r71_0800_back", "r71_1200"],
+        trip_ids(2)
+    );
+    let route_ids: Vec<&str> = gtfs.routes_on(date(2)).iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(vec!["r71"], route_ids);
+}
+
+#[test]
+fn schema() {
+    use crate::FieldType;
+    let schema = crate::schema();
+    let table = |name| schema.iter().find(|t| t.file_name == name).unwrap();
+    let field = |file, name| table(file).fields.iter().find(|f| f.name == name).unwrap();
+    assert!(table("stops.txt").required);
+    assert!(!table("calendar.txt").required);
+    assert_eq!("RawStopTime", table("stop_times.txt").structure);
+    assert_eq!(FieldType::Float, field("stops.txt", "stop_lat").field_type);
+    assert!(!field("stops.txt", "stop_lat").required);
+    assert_eq!(FieldType::Date, field("calendar.txt", "start_date").field_type);
+    assert_eq!(FieldType::Boolean, field("calendar.txt", "monday").field_type);
+    assert_eq!(FieldType::Time, field("frequencies.txt", "start_time").field_type);
+    assert_eq!(FieldType::Color, field("routes.txt", "route_color").field_type);
+    assert_eq!(FieldType::Integer, field("routes.txt", "route_type").field_type);
+    assert_eq!(
+        FieldType::Enum(&["1", "2"]),
+        field("calendar_dates.txt", "exception_type").field_type
+    );
+    assert!(field("translations.txt", "trans_id").extension);
+    #[cfg(feature = "extensions")]
+    assert!(field("trips.txt", "vehicle_type").extension);
+    #[cfg(not(feature = "extensions"))]
+    assert!(table("trips.txt").fields.iter().all(|f| !f.extension));
+}
+
+#[test]
+fn gtfs_time() {
+    let gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    let stop_time = &gtfs.get_trip("trip1").unwrap().stop_times[0];
+    let departure = stop_time.departure().unwrap();
+    assert_eq!(stop_time.departure_time, Some(departure.as_seconds()));
+    assert_eq!("14:00:00", departure.to_string());
+
+    let late = GtfsTime::from_hms(24, 30, 5);
+    assert_eq!((24, 30, 5), (late.hours(), late.minutes(), late.seconds()));
+    assert_eq!((chrono::NaiveTime::from_hms_opt(0, 30, 5).unwrap(), 1), late.to_naive_time());
+    assert_eq!(chrono::Duration::seconds(37805), late - departure);
+    assert_eq!(None, departure.checked_sub(late));
+    assert_eq!(late, "24:30:05".parse().unwrap());
+    assert_eq!(GtfsTime::from_hms(24, 31, 0), late + 55);
+    assert!("24:30".parse::<GtfsTime>().is_err());
+}
+
+#[test]
+fn route_service_heatmap() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    let heatmap = gtfs.route_service_heatmap("r71").unwrap();
+    assert_eq!(31, heatmap.len());
+    // the 1st of January is removed from the week service
+    assert_eq!((date(1), 0), heatmap[0]);
+    assert_eq!((date(2), 4), heatmap[1]);
+    assert_eq!((date(6), 0), heatmap[5]);
+    let heatmap = gtfs.route_service_heatmap("t1").unwrap();
+    assert_eq!((date(1), 1), heatmap[0]);
+    assert_eq!((date(2), 0), heatmap[1]);
+    assert!(gtfs.route_service_heatmap("unknown").is_err());
+}
+
+#[test]
+fn service_active() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    // the exceptions of calendar_dates.txt win over the weekdays of calendar.txt
+    assert!(!gtfs.service_active("week", date(1)));
+    assert!(gtfs.service_active("weekend", date(1)));
+    assert!(gtfs.service_active("week", date(2)));
+    assert!(!gtfs.service_active("week", NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+    assert!(!gtfs.service_active("unknown", date(2)));
+
+    // a service only defined by calendar_dates.txt and a removed date, added after the feed
+    // is read
+    gtfs.calendar_dates.insert(
+        "extra".to_owned(),
+        vec![CalendarDate {
+            service_id: "extra".to_owned(),
+            date: date(3),
+            exception_type: Exception::Added,
+        }],
+    );
+    gtfs.calendar_dates.get_mut("week").unwrap().push(CalendarDate {
+        service_id: "week".to_owned(),
+        date: date(4),
+        exception_type: Exception::Deleted,
+    });
+    gtfs.trips.get_mut("t1_0900").unwrap().service_id = "extra".to_owned();
+    assert!(!gtfs.service_active("extra", date(3)));
+    assert!(gtfs.service_active("week", date(4)));
+    gtfs.update_operating_days();
+    assert!(gtfs.service_active("extra", date(3)));
+    assert!(!gtfs.service_active("week", date(4)));
+    let trips: Vec<_> = gtfs.trips_on_date(date(3)).map(|t| t.id.as_str()).collect();
+    assert_eq!(vec!["r71_0700", "r71_0730", "r71_0800_back", "r71_1200", "t1_0900"], trips);
+    assert_eq!(trips.len(), gtfs.trips_on(date(3)).len());
+    assert_eq!(0, gtfs.trips_on_date(date(1)).count());
+    assert_eq!(0, gtfs.trips_on_date(date(4)).filter(|t| t.service_id == "week").count());
+}
+
+#[test]
+fn service_date_range() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+    assert_eq!(Some((date(1, 1), date(1, 31))), gtfs.service_date_range());
+
+    // an added date extends the range, but not beyond the end date of feed_info.txt
+    gtfs.calendar_dates.get_mut("week").unwrap().push(CalendarDate {
+        service_id: "week".to_owned(),
+        date: date(2, 3),
+        exception_type: Exception::Added,
+    });
+    assert_eq!(Some((date(1, 1), date(1, 31))), gtfs.service_date_range());
+    gtfs.feed_info[0].end_date = None;
+    assert_eq!(Some((date(1, 1), date(2, 3))), gtfs.service_date_range());
+    gtfs.feed_info[0].start_date = Some(date(1, 15));
+    assert_eq!(Some((date(1, 15), date(2, 3))), gtfs.service_date_range());
+
+    // without any service, only feed_info.txt gives the range
+    gtfs.calendar.clear();
+    gtfs.calendar_dates.clear();
+    assert_eq!(None, gtfs.service_date_range());
+    gtfs.feed_info[0].end_date = Some(date(3, 1));
+    assert_eq!(Some((date(1, 15), date(3, 1))), gtfs.service_date_range());
+}
+
+#[test]
+fn describe_service() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+    assert!(gtfs.describe_service("unknown", &NoHolidays).is_none());
+
+    let week = gtfs.describe_service("week", &NoHolidays).unwrap();
+    assert_eq!(Some(date(2)), week.start_date);
+    assert_eq!(Some(date(31)), week.end_date);
+    assert_eq!(
+        vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        week.weekdays
+    );
+    assert_eq!(22, week.nb_days);
+    assert_eq!(HolidayVariant::Regular, week.variant);
+
+    let holidays = FixedHolidays::new().date(date(1), HolidayKind::School);
+    let week = gtfs.describe_service("week", &holidays).unwrap();
+    assert_eq!(HolidayVariant::SchoolDaysOnly, week.variant);
+
+    let holidays = FixedHolidays::new().yearly(1, 1, HolidayKind::Public);
+    let week = gtfs.describe_service("week", &holidays).unwrap();
+    assert!(week.public_holidays.is_empty());
+    let weekend = gtfs.describe_service("weekend", &holidays).unwrap();
+    assert_eq!(HolidayVariant::Regular, weekend.variant);
+    assert_eq!(vec![date(1)], weekend.public_holidays);
+
+    let holidays = FixedHolidays::new().period(date(1), date(31), HolidayKind::School);
+    let week = gtfs.describe_service("week", &holidays).unwrap();
+    assert_eq!(HolidayVariant::HolidaysOnly, week.variant);
+}
+
+#[test]
+fn column_defaults() {
+    let gtfs = Gtfs::from_path("fixtures/only_required_fields").expect("impossible to read gtfs");
+    assert!(gtfs.get_trip("trip1").unwrap().stop_times[0].timepoint);
+    assert_eq!(
+        Availability::InformationNotAvailable,
+        gtfs.get_stop("stop1").unwrap().wheelchair_boarding
+    );
+
+    let options = GtfsReaderOptions::new()
+        .column_default("stop_times.txt", "timepoint", "0")
+        .column_default("stops.txt", "wheelchair_boarding", "1");
+    let gtfs = Gtfs::from_path_with_options("fixtures/only_required_fields", options)
+        .expect("impossible to read gtfs");
+    assert!(!gtfs.get_trip("trip1").unwrap().stop_times[0].timepoint);
+    for stop in gtfs.stops.values() {
+        assert_eq!(Availability::Available, stop.wheelchair_boarding);
+    }
+}
+
+#[test]
+fn single_agency_without_id() {
+    let gtfs = Gtfs::from_path("fixtures/single_agency").expect("impossible to read gtfs");
+    assert_eq!("réseau_côte_d_émeraude", gtfs.agencies[0].id());
+    assert_eq!(
+        Some("réseau_côte_d_émeraude"),
+        gtfs.get_route("r1").unwrap().agency_id.as_deref()
+    );
+
+    // with several agencies, the ids are not guessed
+    let gtfs = Gtfs::from_path("fixtures/only_required_fields").expect("impossible to read gtfs");
+    assert!(gtfs.agencies.iter().all(|agency| agency.id.is_none()));
+}
+
+#[test]
+fn trip_segment() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let trip = gtfs.get_trip("r71_0730").unwrap();
+    let stop_ids = |seg: &[StopTime]| -> Vec<String> {
+        seg.iter().map(|st| st.stop.id.clone()).collect()
+    };
+    assert_eq!(vec!["market", "harbour"], stop_ids(trip.segment(2, 3).unwrap()));
+    assert_eq!(4, trip.segment(1, 4).unwrap().len());
+    assert_eq!(vec!["university"], stop_ids(trip.segment(4, 4).unwrap()));
+    assert!(matches!(
+        trip.segment(3, 2),
+        Err(crate::Error::InvalidStopSequence(_))
+    ));
+    assert!(matches!(
+        trip.segment(1, 12),
+        Err(crate::Error::InvalidStopSequence(_))
+    ));
+}
+
+#[test]
+fn validation_fixes() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    assert!(gtfs.validate().unwrap().is_empty());
+
+    let route = Arc::make_mut(gtfs.routes.get_mut("t1").unwrap());
+    route.long_name = String::new();
+    route.route_color = None;
+    gtfs.trips.get_mut("r71_0730").unwrap().stop_times.reverse();
+    let report = gtfs.validate().unwrap();
+    let kinds: Vec<IssueKind> = report.issues.iter().map(|issue| issue.kind).collect();
+    assert_eq!(
+        vec![
+            IssueKind::EmptyRouteLongName,
+            IssueKind::MissingRouteColor,
+            IssueKind::UnsortedStopTimes
+        ],
+        kinds
+    );
+    assert!(report.has_errors());
+
+    let fixes = report.apply_fixes(&mut gtfs).unwrap();
+    assert_eq!(3, fixes.len());
+    assert_eq!("route_long_name set to 'Central Station - Harbour'", fixes[0].description);
+    let route = gtfs.get_route("t1").unwrap();
+    assert_eq!(Some(RGB8::new(255, 255, 255)), route.route_color);
+    assert_eq!(Some(RGB8::new(255, 255, 255)), route.route_text_color);
+    assert_eq!(1, gtfs.get_trip("r71_0730").unwrap().stop_times[0].stop_sequence);
+    assert!(gtfs.validate().unwrap().is_empty());
+
+    let issues = gtfs
+        .validate_with_holidays(&FixedHolidays::new().yearly(1, 1, HolidayKind::Public))
+        .unwrap()
+        .issues;
+    assert_eq!(1, issues.len());
+    assert_eq!(IssueKind::ServiceOnPublicHoliday, issues[0].kind);
+    assert_eq!("weekend", issues[0].object_id);
+}
+
+#[test]
+fn validation_fixes_shared_stop_times() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    // r71_0700 and r71_1200 share their pattern
+    for trip_id in ["r71_0700", "r71_1200"] {
+        gtfs.trips.get_mut(trip_id).unwrap().stop_times.reverse();
+    }
+    gtfs.share_stop_patterns();
+    assert!(gtfs.trips["r71_0700"].stop_times.is_empty());
+    let report = gtfs.validate().unwrap();
+    assert_eq!(2, report.issues.iter().filter(|issue| issue.kind == IssueKind::UnsortedStopTimes).count());
+    assert_eq!(2, report.apply_fixes(&mut gtfs).unwrap().len());
+    assert_eq!(1, gtfs.trip_stop_times("r71_0700").unwrap()[0].stop_sequence);
+    assert!(gtfs.validate().unwrap().is_empty());
+}
+
+#[test]
+fn validation_codes() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    Arc::make_mut(gtfs.stops.get_mut("market").unwrap()).latitude = Some(95.);
+    let trip = gtfs.trips.get_mut("r71_0700").unwrap();
+    trip.stop_times[1].arrival_time = Some(6 * 3600);
+    let report = gtfs.validate().unwrap();
+    let codes: Vec<&str> = report.issues.iter().map(|issue| issue.code()).collect();
+    assert_eq!(vec!["invalid_coordinates", "decreasing_stop_times"], codes);
+    assert_eq!("market", report.issues[0].object_id);
+    assert_eq!("r71_0700", report.issues[1].object_id);
+    assert!(report.has_errors());
+
+    let mut raw = RawGtfs::from_path("fixtures/duplicated_rows").expect("impossible to read gtfs");
+    raw.trips = Err(Error::MissingFile("trips.txt".to_owned()));
+    let issues = raw.validate().issues;
+    let found: Vec<(&str, &str)> = issues
+        .iter()
+        .map(|issue| (issue.code(), issue.object_id.as_str()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("missing_required_file", "trips.txt"),
+            ("duplicate_id", "stop2"),
+            ("duplicate_id", "trip1"),
+        ],
+        found
+    );
+    assert!(RawGtfs::from_path("fixtures/timetable").unwrap().validate().is_empty());
+}
+
+#[test]
+fn remap_route_types() {
+    assert_eq!(RouteType::Bus, RouteType::Coach.to_basic());
+    assert_eq!(RouteType::Subway, RouteType::Other(501).to_basic());
+    assert_eq!(RouteType::Air, RouteType::Air.to_basic());
+
+    let mut gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    gtfs.remap_route_types(RouteType::to_basic);
+    assert!(gtfs.routes.values().all(|r| r.original_route_type.is_none()));
+
+    gtfs.remap_route_types(|route_type| match route_type {
+        RouteType::Other(_) => RouteType::Bus,
+        route_type => route_type,
+    });
+    let route = gtfs.get_route("invalid_type").unwrap();
+    assert_eq!(RouteType::Bus, route.route_type);
+    assert_eq!(Some(RouteType::Other(42)), route.original_route_type);
+    assert_eq!(None, gtfs.get_route("1").unwrap().original_route_type);
+}
+
+#[test]
+#[cfg(feature = "extensions")]
+fn trip_extensions() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let trip = gtfs.get_trip("r71_0730").unwrap();
+    assert_eq!(Some("articulated_bus"), trip.vehicle_type.as_deref());
+    assert_eq!(Some(1), trip.cars_count);
+    assert_eq!(Some(150), trip.capacity);
+    let trip = gtfs.get_trip("r71_0700").unwrap();
+    assert_eq!((None, None, None), (trip.vehicle_type.as_deref(), trip.cars_count, trip.capacity));
+    assert_eq!(Some(7), gtfs.get_trip("t1_0900").unwrap().cars_count);
+}
+
+#[test]
+fn split_trips_at_midnight() {
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    assert!(!gtfs.get_trip("n_2200").unwrap().crosses_midnight());
+    assert!(gtfs.get_trip("n_2350").unwrap().crosses_midnight());
+    assert!(gtfs.get_trip("n_2430").unwrap().crosses_midnight());
+
+    assert_eq!(vec!["n_2350_next_day", "n_2430"], gtfs.split_trips_at_midnight().unwrap());
+    assert!(gtfs.trips.values().all(|trip| !trip.crosses_midnight()));
+
+    let before = gtfs.get_trip("n_2350").unwrap();
+    assert_eq!("friday", before.service_id);
+    assert_eq!(2, before.stop_times.len());
+    assert_eq!(Some(23 * 3600 + 58 * 60), before.stop_times[1].departure_time);
+    let after = gtfs.get_trip("n_2350_next_day").unwrap();
+    assert_eq!("friday_next_day", after.service_id);
+    assert_eq!(1, after.stop_times.len());
+    assert_eq!(Some(10 * 60), after.stop_times[0].arrival_time);
+    let moved = gtfs.get_trip("n_2430").unwrap();
+    assert_eq!("friday_next_day", moved.service_id);
+    assert_eq!(Some(45 * 60), moved.stop_times[1].arrival_time);
+
+    let saturdays = vec![
+        NaiveDate::from_ymd_opt(2024, 1, 6).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 1, 13).unwrap(),
+    ];
+    assert_eq!(saturdays, gtfs.operating_days.active_dates("friday_next_day"));
+
+    // the stop times of the trips with frequencies are a template of their departures
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let mut frequency = gtfs.get_trip("n_2200").unwrap().frequencies[0].clone();
+    frequency.start_time = 23 * 3600 + 50 * 60;
+    frequency.end_time = 25 * 3600;
+    gtfs.trips.get_mut("n_2350").unwrap().frequencies.push(frequency.clone());
+    assert_eq!(vec!["n_2430"], gtfs.split_trips_at_midnight().unwrap());
+    let trip = gtfs.get_trip("n_2350").unwrap();
+    assert_eq!(3, trip.stop_times.len());
+    assert_eq!(Some(24 * 3600 + 10 * 60), trip.stop_times[2].arrival_time);
+    assert_eq!(vec![frequency], trip.frequencies);
+    assert!(gtfs.get_trip("n_2350_next_day").is_err());
+
+    // n_2200 and n_2430 share their stop times
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let mut gtfs = Gtfs::from_path_with_options("fixtures/night", options).unwrap();
+    assert_eq!(2, gtfs.stop_patterns.as_ref().unwrap().nb_trips());
+    assert_eq!(vec!["n_2350_next_day", "n_2430"], gtfs.split_trips_at_midnight().unwrap());
+    assert_eq!(Some(45 * 60), gtfs.trip_stop_times("n_2430").unwrap()[1].arrival_time);
+    assert_eq!(Some(22 * 3600), gtfs.trip_stop_times("n_2200").unwrap()[0].departure_time);
+    assert_eq!(2, gtfs.trip_stop_times("n_2350").unwrap().len());
+}
+
+#[test]
+fn id_generators() {
+    let stop = |id: &str| Stop {
+        id: id.to_owned(),
+        name: "Stop".to_owned(),
+        ..Default::default()
+    };
+    let mut builder = GtfsBuilder::new()
+        .id_generator(SequentialIds::new("tag:"))
+        .stop(stop("tag:2"))
+        .stop(stop(""));
+    // tag:2 is already used
+    assert_eq!("tag:3", builder.new_id(ObjectType::Stop));
+    let mut gtfs = builder.stop(stop("")).build().unwrap();
+    let ids: Vec<_> = gtfs.stops.keys().cloned().collect();
+    assert_eq!(vec!["tag:1", "tag:2", "tag:4"], ids);
+    assert_eq!("tag:5", gtfs.new_id(ObjectType::Stop));
+    assert_eq!("tag:1", gtfs.new_id(ObjectType::Route));
+
+    let mut default = GtfsBuilder::new();
+    assert_eq!("stop_1", default.new_id(ObjectType::Stop));
+    let mut gtfs = default.stop(stop("")).stop(stop("")).build().unwrap();
+    assert_eq!("stop_3", gtfs.new_id(ObjectType::Stop));
+
+    // the trips split at midnight and their services get hash based ids
+    let mut hashed = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    hashed.id_generator = Some(Box::new(HashIds::new("h")));
+    let split = hashed.split_trips_at_midnight().unwrap();
+    assert_eq!(2, split.len());
+    assert!(split[0].len() == 17 && split[0].starts_with('h'));
+    // a trip moved as a whole to the next day keeps its id
+    assert_eq!("n_2430", split[1]);
+    let next_day = &hashed.get_trip(&split[0]).unwrap().service_id;
+    assert_ne!("friday_next_day", next_day);
+    assert_eq!(next_day, &hashed.get_trip("n_2430").unwrap().service_id);
+    let mut again = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    again.id_generator = Some(Box::new(HashIds::new("h")));
+    assert_eq!(split, again.split_trips_at_midnight().unwrap());
+
+    let mut uuids = UuidIds::new();
+    let a = uuids.generate(ObjectType::Trip, "");
+    assert_ne!(a, uuids.generate(ObjectType::Trip, ""));
+    assert_eq!(36, a.len());
+    assert_eq!(Some('4'), a.chars().nth(14));
+}
+
+#[test]
+fn feed_languages() {
+    let mut gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    assert_eq!(Some("fr"), gtfs.feed_lang());
+    assert_eq!(Some("fr"), gtfs.default_lang());
+    assert_eq!("Stop Area", gtfs.get_stop_translated("stop1", "it").unwrap().name);
+
+    gtfs.feed_info[0].lang = "mul".to_owned();
+    assert_eq!(None, gtfs.default_lang());
+    gtfs.feed_info[0].default_lang = Some("nl".to_owned());
+    assert_eq!(Some("nl"), gtfs.default_lang());
+    assert_eq!("Stop Gebied", gtfs.get_stop_translated("stop1", "it").unwrap().name);
+    assert_eq!("Arrêt Région", gtfs.get_stop_translated("stop1", "fr").unwrap().name);
+
+    // without feed_info.txt, the language of the agency is used
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.feed_info.clear();
+    assert_eq!(Some("fr"), gtfs.feed_lang());
+}
+
+#[test]
+fn expand_frequencies() {
+    let gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let trip = gtfs.get_trip("n_2200").unwrap();
+    assert_eq!(
+        vec![Frequency {
+            start_time: 22 * 3600,
+            end_time: 23 * 3600,
+            headway_secs: 1200,
+            exact_times: Some(ExactTimes::ScheduleBased),
+        }],
+        trip.frequencies
+    );
+
+    let trips = trip.expand_frequencies();
+    let ids: Vec<&str> = trips.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(vec!["n_2200_220000", "n_2200_222000", "n_2200_224000"], ids);
+    assert_eq!(Some(22 * 3600 + 50 * 60), trips[2].stop_times[1].arrival_time);
+    assert!(trips.iter().all(|t| t.frequencies.is_empty()));
+
+    let trip = gtfs.get_trip("n_2350").unwrap();
+    assert_eq!(1, trip.expand_frequencies().len());
+}
+
+#[test]
+fn stops_near() {
+    let mut gtfs = Gtfs::default();
+    // a grid of stops every 0.01 degree, and a few stops on both sides of the antimeridian
+    for i in 0..40 {
+        for j in 0..40 {
+            let id = format!("s{}_{}", i, j);
+            let stop = Stop {
+                id: id.clone(),
+                latitude: Some(48.5 + f64::from(i) * 0.01),
+                longitude: Some(2. + f64::from(j) * 0.01),
+                ..Stop::default()
+            };
+            gtfs.stops.insert(id, Arc::new(stop));
+        }
+    }
+    for (id, lon) in [("east", 179.999), ("west", -179.999), ("far", 170.)] {
+        let stop = Stop {
+            id: id.to_owned(),
+            latitude: Some(0.),
+            longitude: Some(lon),
+            ..Stop::default()
+        };
+        gtfs.stops.insert(id.to_owned(), Arc::new(stop));
+    }
+    let ids =
+        |stops: Vec<(&Stop, f64)>| stops.iter().map(|(s, _)| s.id.clone()).collect::<Vec<_>>();
+    let queries = [(48.7, 2.2), (48.705, 2.205), (48., 1.), (0., 180.)];
+
+    let linear: Vec<_> = queries
+        .iter()
+        .map(|&(lat, lon)| ids(gtfs.stops_near(lat, lon, 2000.)))
+        .collect();
+    let closest: Vec<_> = queries
+        .iter()
+        .map(|&(lat, lon)| ids(gtfs.nearest_stops(lat, lon, 5)))
+        .collect();
+    gtfs.build_stop_index();
+    assert_eq!(1603, gtfs.stop_index.as_ref().unwrap().len());
+    for (i, &(lat, lon)) in queries.iter().enumerate() {
+        assert_eq!(linear[i], ids(gtfs.stops_near(lat, lon, 2000.)));
+        assert_eq!(closest[i], ids(gtfs.nearest_stops(lat, lon, 5)));
+    }
+
+    let near = gtfs.stops_near(48.7, 2.2, 800.);
+    assert_eq!("s20_20", near[0].0.id);
+    assert!(near[0].1 < 1.);
+    assert!(near.iter().all(|(_, d)| *d <= 800.));
+    // the closest stops can be on the other side of the antimeridian
+    assert_eq!(
+        vec!["east", "west"],
+        ids(gtfs.stops_near(0., 179.9995, 1000.))
+    );
+    assert_eq!(
+        vec!["west", "east", "far"],
+        ids(gtfs.nearest_stops(0., -179.9995, 3))
+    );
+    assert_eq!(vec!["s0_0"], ids(gtfs.nearest_stops(0., 0., 1)));
+    assert!(gtfs.nearest_stops(0., 0., 0).is_empty());
+}
+
+#[test]
+fn stops_and_shapes_in_bbox() {
+    let gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    let ids = |stops: Vec<&Stop>| stops.iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        vec!["central", "central_a", "central_b"],
+        ids(gtfs.stops_in_bbox(5.71, 45.19, 5.72, 45.2))
+    );
+    assert!(gtfs.stops_in_bbox(6., 45., 7., 46.).is_empty());
+
+    // around the market, the harbour and the university
+    let rectangle = |min_lon, min_lat, max_lon, max_lat| {
+        let corners = [(min_lon, min_lat), (max_lon, min_lat), (max_lon, max_lat)];
+        [&corners[..], &[(min_lon, max_lat), (min_lon, min_lat)]].concat()
+    };
+    let polygon = vec![rectangle(5.72, 45.18, 5.78, 45.195)];
+    assert_eq!(
+        vec!["harbour", "market", "university"],
+        ids(gtfs.stops_in_polygon(&polygon))
+    );
+    let with_hole = vec![polygon[0].clone(), rectangle(5.73, 45.18, 5.74, 45.19)];
+    assert_eq!(
+        vec!["market", "university"],
+        ids(gtfs.stops_in_polygon(&with_hole))
+    );
+
+    let shape_ids =
+        |shapes: Vec<&[Shape]>| shapes.iter().map(|s| s[0].id.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        vec!["r71_back", "r71_out", "t1"],
+        shape_ids(gtfs.shapes_in_bbox(5.72, 45.18, 5.73, 45.19))
+    );
+    // only the segment from the harbour to the university of the r71 crosses this box
+    assert_eq!(
+        vec!["r71_back", "r71_out"],
+        shape_ids(gtfs.shapes_in_bbox(5.75, 45.18, 5.751, 45.195))
+    );
+    assert!(gtfs.shapes_in_bbox(5.75, 45.17, 5.751, 45.18).is_empty());
+}
+
+#[test]
+fn accessible_connections() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+    let ids = |trips: Vec<&Trip>| trips.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+    // central_b has no step-free access, and the trip at 12:00 has no room for a wheelchair
+    assert_eq!(
+        vec!["r71_0700"],
+        ids(gtfs.accessible_connections("central", "university", date).unwrap())
+    );
+    assert_eq!(
+        vec!["r71_0700", "r71_0730"],
+        ids(gtfs.accessible_connections("market", "university", date).unwrap())
+    );
+    assert_eq!(
+        vec!["r71_0800_back"],
+        ids(gtfs.accessible_connections("university", "market", date).unwrap())
+    );
+    // no information on the harbour
+    assert!(gtfs.accessible_connections("market", "harbour", date).unwrap().is_empty());
+    assert!(gtfs.accessible_connections("market", "unknown", date).is_err());
+
+    // a platform without information is as accessible as its station
+    let mut central_b = (*gtfs.stops["central_b"]).clone();
+    central_b.wheelchair_boarding = Availability::InformationNotAvailable;
+    assert_eq!(Availability::Available, gtfs.wheelchair_boarding(&central_b));
+    assert_eq!(Availability::NotAvailable, gtfs.wheelchair_boarding(&gtfs.stops["central_b"]));
+}
+
+#[test]
+fn nearest_stop_on_route() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let (stop, distance) = gtfs.nearest_stop_on_route(45.1911, 5.7141, "r71").unwrap().unwrap();
+    assert_eq!("central_a", stop.id);
+    assert!(distance < 1.);
+
+    // the tram does not stop at the platform A
+    let (stop, distance) = gtfs.nearest_stop_on_route(45.1911, 5.7141, "t1").unwrap().unwrap();
+    assert_eq!("central_b", stop.id);
+    assert!((distance - 27.2).abs() < 1., "{}", distance);
+
+    let (stop, _) = gtfs.nearest_stop_on_route(45.1920, 5.7680, "t1").unwrap().unwrap();
+    assert_eq!("harbour", stop.id);
+    assert!(gtfs.nearest_stop_on_route(45.1920, 5.7680, "unknown").unwrap().is_none());
+}
+
+#[test]
+fn spill_stop_times() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let options = GtfsReaderOptions::new().spill_stop_times(1);
+    let spilled = Gtfs::from_path_with_options("fixtures/timetable", options.clone())
+        .expect("impossible to read gtfs");
+    assert_eq!(gtfs.trips.len(), spilled.trips.len());
+    for (id, trip) in &gtfs.trips {
+        assert!(spilled.trips[id].stop_times.is_empty());
+        let stop_times = spilled.trip_stop_times(id).unwrap();
+        let expected: Vec<_> = trip
+            .stop_times
+            .iter()
+            .map(|st| (st.stop.id.as_str(), st.stop_sequence, st.departure_time))
+            .collect();
+        let found: Vec<_> = stop_times
+            .iter()
+            .map(|st| (st.stop.id.as_str(), st.stop_sequence, st.departure_time))
+            .collect();
+        assert_eq!(expected, found);
+    }
+
+    // the last loaded trip is kept in memory
+    let first = spilled.trip_stop_times("r71_0700").unwrap();
+    assert_eq!(first.as_ptr(), spilled.trip_stop_times("r71_0700").unwrap().as_ptr());
+    // the stop times in memory are not copied
+    let stop_times = gtfs.trip_stop_times("r71_0700").unwrap();
+    assert_eq!(gtfs.trips["r71_0700"].stop_times.as_ptr(), stop_times.as_ptr());
+    assert!(spilled.trip_stop_times("unknown").is_err());
+
+    // the analyses load the stop times of the trips
+    let (stop, _) = spilled.nearest_stop_on_route(45.1911, 5.7141, "t1").unwrap().unwrap();
+    assert_eq!("central_b", stop.id);
+    let destinations = spilled.route_destinations("r71").unwrap();
+    assert_eq!("university", destinations[0].last_stop.as_ref().unwrap().id);
+    assert_eq!("central", destinations[1].last_stop.as_ref().unwrap().id);
+    assert!(spilled.validate().unwrap().is_empty());
+
+    let zip = Gtfs::from_reader_with_options(File::open("fixtures/zips/gtfs.zip").unwrap(), options)
+        .expect("impossible to read gtfs");
+    assert!(!zip.trip_stop_times("trip1").unwrap().is_empty());
+
+    // a corrupted temporary file gives an error instead of wrong stop times
+    use std::io::{Seek, SeekFrom, Write};
+    let options = GtfsReaderOptions::new().spill_stop_times(1);
+    let corrupted = Gtfs::from_path_with_options("fixtures/timetable", options).unwrap();
+    let path = &corrupted.stop_time_store.as_ref().unwrap().path;
+    let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    // the length of the first field of r71_0700, the first trip in the file
+    file.seek(SeekFrom::Start(6)).and_then(|_| file.write_all(&[0xff; 4])).unwrap();
+    assert!(matches!(corrupted.trip_stop_times("r71_0700"), Err(crate::Error::NamedFileIO { .. })));
+    file.set_len(10).unwrap();
+    assert!(matches!(corrupted.trip_stop_times("r71_1200"), Err(crate::Error::NamedFileIO { .. })));
+}
+
+#[test]
+fn share_stop_patterns() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let shared = Gtfs::from_path_with_options("fixtures/timetable", options)
+        .expect("impossible to read gtfs");
+    // r71_0700 and r71_1200 call at the same stops, the other trips have their own pattern
+    let patterns = shared.stop_patterns.as_ref().unwrap();
+    assert_eq!(1, patterns.len());
+    assert_eq!(2, patterns.nb_trips());
+    assert!(shared.trips["r71_1200"].stop_times.is_empty());
+    assert!(!shared.trips["r71_0730"].stop_times.is_empty());
+    for (id, trip) in &gtfs.trips {
+        let expected: Vec<_> = trip
+            .stop_times
+            .iter()
+            .map(|st| (st.stop.id.as_str(), st.stop_sequence, st.arrival_time, st.departure_time))
+            .collect();
+        let stop_times = shared.trip_stop_times(id).unwrap();
+        let found: Vec<_> = stop_times
+            .iter()
+            .map(|st| (st.stop.id.as_str(), st.stop_sequence, st.arrival_time, st.departure_time))
+            .collect();
+        assert_eq!(expected, found);
+    }
+
+    // many trips of the same pattern use a fraction of the memory
+    let mut gtfs = gtfs;
+    let template = gtfs.trips["r71_0700"].clone();
+    for i in 0..100 {
+        let mut trip = template.clone();
+        trip.id = format!("r71_{}", i);
+        for stop_time in &mut trip.stop_times {
+            stop_time.arrival_time = stop_time.arrival_time.map(|t| t + i * 600);
+            stop_time.departure_time = stop_time.departure_time.map(|t| t + i * 600);
+        }
+        gtfs.trips.insert(trip.id.clone(), trip);
+    }
+    let before = gtfs.memory_report().stop_times;
+    gtfs.share_stop_patterns();
+    assert!(gtfs.memory_report().stop_times * 4 < before);
+    assert_eq!(Some(7 * 3600 + 50 * 600), gtfs.trip_stop_times("r71_50").unwrap()[0].departure_time);
+}
+
+#[test]
+fn transfers() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let transfers = gtfs.transfers_from("central_a");
+    assert_eq!(2, transfers.len());
+    assert_eq!("central_b", transfers[0].to_stop_id);
+    assert_eq!(TransferType::MinTime, transfers[0].transfer_type);
+    assert_eq!(Some(180), transfers[0].min_transfer_time);
+    assert_eq!(TransferType::Recommended, transfers[1].transfer_type);
+    assert_eq!(None, transfers[1].min_transfer_time);
+
+    let transfers = gtfs.transfers_from("central_b");
+    assert_eq!(TransferType::Timed, transfers[0].transfer_type);
+    assert_eq!(Some("t1".to_owned()), transfers[0].from_route_id);
+    assert_eq!(TransferType::Impossible, gtfs.transfers_from("market")[0].transfer_type);
+    assert!(gtfs.transfers_from("university").is_empty());
+}
+
+#[test]
+fn change_log() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.retain_stops(|stop| stop.id != "market").unwrap();
+    assert!(gtfs.take_changes().is_empty());
+
+    gtfs.record_changes(true);
+    gtfs.retain_stops(|stop| stop.id != "central").unwrap();
+    let changes = gtfs.take_changes();
+    let removed_stops: Vec<&str> = changes
+        .iter()
+        .filter_map(|change| match change {
+            ChangeEvent::StopRemoved(stop) => Some(stop.id.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec!["central", "central_a", "central_b"], removed_stops);
+    assert!(changes
+        .iter()
+        .any(|change| matches!(change, ChangeEvent::TripRemoved(trip) if trip.id == "t1_0900")));
+
+    for change in changes.iter().rev() {
+        gtfs.undo_change(change);
+    }
+    assert_eq!(5, gtfs.stops.len());
+    assert_eq!(3, gtfs.get_trip("r71_0700").unwrap().stop_times.len());
+    assert_eq!(2, gtfs.get_trip("t1_0900").unwrap().stop_times.len());
+
+    for change in &changes {
+        gtfs.redo_change(change);
+    }
+    assert_eq!(2, gtfs.stops.len());
+    assert!(gtfs.get_trip("t1_0900").is_err());
+    assert_eq!(2, gtfs.get_trip("r71_0700").unwrap().stop_times.len());
+
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    gtfs.record_changes(true);
+    gtfs.split_trips_at_midnight().unwrap();
+    for change in gtfs.take_changes().iter().rev() {
+        gtfs.undo_change(change);
+    }
+    assert_eq!(3, gtfs.trips.len());
+    assert!(gtfs.calendar_dates.is_empty());
+    assert!(gtfs.get_trip("n_2350").unwrap().crosses_midnight());
+    assert_eq!("friday", gtfs.get_trip("n_2430").unwrap().service_id);
+}
+
+#[test]
+fn pathways_and_levels() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let level = gtfs.get_stop("central_b").unwrap().level.as_ref().unwrap();
+    assert_eq!(-1., level.index);
+    assert_eq!(None, level.name);
+    assert_eq!(
+        Some("Ground floor"),
+        gtfs.get_level("ground").unwrap().name.as_deref()
+    );
+    assert!(gtfs.get_stop("market").unwrap().level.is_none());
+
+    let stairs = gtfs.get_pathway("stairs_ab").unwrap();
+    assert_eq!(PathwayMode::Stairs, stairs.pathway_mode);
+    assert!(stairs.is_bidirectional);
+    assert_eq!(Some(8.5), stairs.length);
+    assert_eq!(Some(-24), stairs.stair_count);
+    assert_eq!(None, stairs.max_slope);
+
+    let ids = |stop_id| -> Vec<&str> {
+        gtfs.pathways_from(stop_id)
+            .iter()
+            .map(|p| p.id.as_str())
+            .collect()
+    };
+    assert_eq!(vec!["stairs_ab"], ids("central_a"));
+    assert_eq!(vec!["escalator_ba", "stairs_ab"], ids("central_b"));
+}
+
+#[test]
+fn fare_rules() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    assert_eq!(2, gtfs.get_fare_attributes("tram").unwrap().rules.len());
+
+    let fares = gtfs.fare_rules_for_route("r71");
+    assert_eq!(1, fares.len());
+    assert_eq!("single", fares[0].0.id);
+
+    let fares = gtfs.fare_rules_for_route("t1");
+    assert_eq!(2, fares.len());
+    let (tram, rules) = &fares[1];
+    assert_eq!("2.00", tram.price);
+    assert_eq!(Some("centre"), rules[0].origin_id.as_deref());
+    assert_eq!(Some("port"), rules[1].contains_id.as_deref());
+    assert!(gtfs.fare_rules_for_route("unknown").is_empty());
+}
+
+#[test]
+fn matching_stops() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let mut other = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    let harbour = Arc::make_mut(other.stops.get_mut("harbour").unwrap());
+    harbour.name = "HARBOUR (ferry)".to_owned();
+    harbour.latitude = Some(45.1852);
+    let pairs = |matches: Vec<crate::StopMatch>| -> Vec<(String, String)> {
+        matches
+            .into_iter()
+            .map(|m| (m.stop.id.clone(), m.other.id.clone()))
+            .collect()
+    };
+
+    let matches = gtfs.matching_stops(&other, 50., 0.95);
+    assert_eq!(5, matches.len());
+    assert!(matches.iter().all(|m| m.stop.id == m.other.id && m.distance < 1.));
+    // the platforms A and B have close names, but the station is not matched with them
+    let matches = pairs(gtfs.matching_stops(&other, 50., 0.9));
+    assert_eq!(("central_a".to_owned(), "central_b".to_owned()), matches[2]);
+    assert_eq!(7, matches.len());
+
+    let matches = gtfs.matching_stops(&other, 50., 0.5);
+    let harbour = matches.iter().find(|m| m.stop.id == "harbour").unwrap();
+    assert!(harbour.distance > 20. && harbour.distance < 25.);
+    assert!(harbour.name_similarity > 0.5 && harbour.name_similarity < 0.9);
+    assert!(gtfs.matching_stops(&other, 10., 0.5).iter().all(|m| m.stop.id != "harbour"));
+}
+
+#[test]
+fn stop_fare_info() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    Arc::make_mut(gtfs.stops.get_mut("central").unwrap()).zone_id = Some("centre".to_owned());
+    let info = gtfs.get_stop("central_a").unwrap().fare_info(&gtfs);
+    assert_eq!(Some("centre"), info.zone_id);
+    let fares: Vec<&str> = info.fares.iter().map(|fare| fare.id.as_str()).collect();
+    assert_eq!(vec!["single", "tram"], fares);
+    let info = gtfs.get_stop("harbour").unwrap().fare_info(&gtfs);
+    assert_eq!(None, info.zone_id);
+    assert_eq!(1, info.fares.len());
+
+    let gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    let info = gtfs.get_stop("central_a").unwrap().fare_info(&gtfs);
+    assert_eq!(Some("z1"), info.zone_id);
+    let areas: Vec<&str> = info.areas.iter().map(|area| area.id.as_str()).collect();
+    assert_eq!(vec!["centre"], areas);
+    let products: Vec<&str> = info.fare_products.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(vec!["day", "single", "single"], products);
+    let info = gtfs.get_stop("harbour").unwrap().fare_info(&gtfs);
+    let products: Vec<&str> = info.fare_products.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(vec!["day"], products);
+    assert!(info.fares.is_empty());
+}
+
+#[test]
+fn trip_against_shape() {
+    // the stop times spilled to the disk are loaded to compare them with the shapes
+    for options in [GtfsReaderOptions::new(), GtfsReaderOptions::new().spill_stop_times(1)] {
+        let mut gtfs = Gtfs::from_path_with_options("fixtures/shapes", options)
+            .expect("impossible to read gtfs");
+        let report = gtfs.validate().unwrap();
+        let trips: Vec<&str> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::TripAgainstShape)
+            .map(|issue| issue.object_id.as_str())
+            .collect();
+        assert_eq!(vec!["r71_0800_back", "r71_1000_back", "t1_1000_back"], trips);
+
+        // the shape of the tram is also used in the other direction, it is not changed
+        let fixes: Vec<String> = report
+            .apply_fixes(&mut gtfs)
+            .unwrap()
+            .into_iter()
+            .filter(|fix| fix.kind == IssueKind::TripAgainstShape)
+            .map(|fix| fix.description)
+            .collect();
+        assert_eq!(vec!["shape 'r71_back' reversed"], fixes);
+        let shape = gtfs.get_shape("r71_back").unwrap();
+        assert_eq!(1, shape[0].sequence);
+        assert_eq!(45.192, shape[0].latitude);
+        let remaining: Vec<String> = gtfs
+            .validate()
+            .unwrap()
+            .issues
+            .into_iter()
+            .filter(|issue| issue.kind == IssueKind::TripAgainstShape)
+            .map(|issue| issue.object_id)
+            .collect();
+        assert_eq!(vec!["t1_1000_back"], remaining);
+    }
+}
+
+#[test]
+fn fares_v2() {
+    let gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    assert_eq!(3, gtfs.fare_media.len());
+    assert_eq!(FareMediaType::Cemv, gtfs.fare_media["contactless"].media_type);
+    assert_eq!(None, gtfs.fare_media["contactless"].name);
+
+    let single = gtfs.get_fare_product("single").unwrap();
+    assert_eq!(2, single.len());
+    assert_eq!("1.50", single[1].amount);
+    assert_eq!(None, gtfs.get_fare_product("transfer").unwrap()[0].fare_media_id);
+
+    assert_eq!(2, gtfs.fare_leg_rules.len());
+    assert_eq!(Some(1), gtfs.fare_leg_rules[1].rule_priority);
+    let transfer = &gtfs.fare_transfer_rules[0];
+    assert_eq!(Some(-1), transfer.transfer_count);
+    assert_eq!(
+        Some(DurationLimitType::DepartureToDeparture),
+        transfer.duration_limit_type
+    );
+    assert_eq!(FareTransferType::FirstLegPlusTransfer, transfer.fare_transfer_type);
+
+    let areas: Vec<&str> = gtfs
+        .areas_of_stop("market")
+        .iter()
+        .map(|area| area.id.as_str())
+        .collect();
+    assert_eq!(vec!["centre", "port"], areas);
+    let peak = &gtfs.timeframes["peak"];
+    assert_eq!(2, peak.len());
+    assert_eq!(Some(16 * 3600 + 30 * 60), peak[1].start_time);
+    assert_eq!(None, gtfs.timeframes["all_day"][0].end_time);
+}
+
+#[test]
+fn unused_services() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    assert!(gtfs.unused_services().is_empty());
+    gtfs.retain_routes(|route| route.id != "t1");
+    let usage = gtfs.service_usage();
+    assert_eq!(Some(&4), usage.get("week"));
+    assert_eq!(Some(&0), usage.get("weekend"));
+    assert_eq!(vec!["weekend"], gtfs.unused_services());
+
+    assert_eq!(vec!["weekend".to_owned()], gtfs.prune_unused_services());
+    assert!(gtfs.get_calendar("weekend").is_err());
+    assert!(gtfs.unused_services().is_empty());
+
+    // the weekend service is still used by a fare timeframe
+    let mut gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    gtfs.retain_routes(|route| route.id != "t1");
+    assert!(gtfs.prune_unused_services().is_empty());
+}
+
+#[test]
+fn stop_time_zone() {
+    let gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    let stop_times = &gtfs.get_trip("t1_0900").unwrap().stop_times;
+    assert_eq!(Some("z1"), stop_times[0].zone_id());
+    assert_eq!(Some("z2"), stop_times[2].zone_id());
+    #[cfg(feature = "extensions")]
+    assert_eq!(Some("z2"), stop_times[1].zone_id());
+    #[cfg(not(feature = "extensions"))]
+    assert_eq!(Some("z1"), stop_times[1].zone_id());
+}
+
+#[test]
+fn attributions() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    assert_eq!(3, gtfs.attributions.len());
+    let open_data = &gtfs.attributions[0];
+    assert!(open_data.is_producer && !open_data.is_operator && !open_data.is_authority);
+    assert_eq!(Some("https://data.example.org"), open_data.url.as_deref());
+
+    let names = |trip_id| -> Vec<&str> {
+        gtfs.attributions_for_trip(trip_id)
+            .iter()
+            .map(|a| a.organization_name.as_str())
+            .collect()
+    };
+    assert_eq!(vec!["Open Data Region", "TAG", "Tram Operator"], names("t1_0900"));
+    assert_eq!(vec!["Open Data Region", "TAG"], names("r71_0700"));
+}
+
+#[test]
+fn flex() {
+    let gtfs = Gtfs::from_path("fixtures/flex").expect("impossible to read gtfs");
+    let stop_times = &gtfs.get_trip("od1_morning").unwrap().stop_times;
+    assert_eq!("station", stop_times[0].stop.id);
+    let on_demand = &stop_times[1];
+    assert_eq!("", on_demand.stop.id);
+    assert_eq!(Some("villages"), on_demand.location_id.as_deref());
+    assert_eq!(Some(8 * 3600), on_demand.start_pickup_drop_off_window);
+    assert_eq!(Some(12 * 3600), on_demand.end_pickup_drop_off_window);
+
+    let rule_id = on_demand.drop_off_booking_rule_id.as_deref().unwrap();
+    let rule = gtfs.get_booking_rule(rule_id).unwrap();
+    assert_eq!(BookingType::PriorDays, rule.booking_type);
+    assert_eq!(Some(1), rule.prior_notice_last_day);
+    assert_eq!(Some(17 * 3600), rule.prior_notice_last_time);
+
+    let villages = gtfs.get_location("villages").unwrap();
+    assert_eq!(
+        Some("The villages north of the station – without the park"),
+        villages.description.as_deref()
+    );
+    assert!(villages.contains(45.23, 5.73));
+    assert!(!villages.contains(45.215, 5.715));
+    assert!(!villages.contains(45.191, 5.714));
+}
+
+#[test]
+fn trips_for_shape() {
+    let mut gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    let ids = |gtfs: &Gtfs, shape_id| -> Vec<String> {
+        gtfs.trips_for_shape(shape_id)
+            .iter()
+            .map(|trip| trip.id.clone())
+            .collect()
+    };
+    assert_eq!(vec!["r71_0800_back", "r71_1000_back"], ids(&gtfs, "r71_back"));
+    assert!(ids(&gtfs, "unknown").is_empty());
+
+    gtfs.record_changes(true);
+    gtfs.retain_trips(|trip| trip.id != "r71_0800_back");
+    assert_eq!(vec!["r71_1000_back"], ids(&gtfs, "r71_back"));
+    gtfs.retain_trips(|trip| trip.id != "r71_1000_back");
+    assert!(!gtfs.shape_index.contains_key("r71_back"));
+    assert!(!gtfs.shapes.contains_key("r71_back"));
+
+    for change in gtfs.take_changes().iter().rev() {
+        gtfs.undo_change(change);
+    }
+    assert_eq!(vec!["r71_0800_back", "r71_1000_back"], ids(&gtfs, "r71_back"));
+}
+
+#[test]
+fn routes_at_stop_and_stops_on_route() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let routes = |gtfs: &Gtfs, stop_id| -> Vec<String> {
+        gtfs.routes_at_stop(stop_id).iter().map(|r| r.id.clone()).collect()
+    };
+    let stops = |gtfs: &Gtfs, route_id| -> Vec<String> {
+        gtfs.stops_on_route(route_id).iter().map(|s| s.id.clone()).collect()
+    };
+    assert_eq!(vec!["r71", "t1"], routes(&gtfs, "market"));
+    assert_eq!(vec!["r71"], routes(&gtfs, "university"));
+    // only the platforms of the station are served
+    assert!(routes(&gtfs, "central").is_empty());
+    assert_eq!(vec!["central_b", "harbour", "market"], stops(&gtfs, "t1"));
+    assert!(stops(&gtfs, "unknown").is_empty());
+
+    gtfs.record_changes(true);
+    gtfs.retain_trips(|trip| trip.route_id != "t1");
+    assert_eq!(vec!["r71"], routes(&gtfs, "market"));
+    assert!(stops(&gtfs, "t1").is_empty());
+    gtfs.retain_stops(|stop| stop.id != "harbour").unwrap();
+    assert!(!stops(&gtfs, "r71").contains(&"harbour".to_owned()));
+
+    for change in gtfs.take_changes().iter().rev() {
+        gtfs.undo_change(change);
+    }
+    assert_eq!(vec!["r71", "t1"], routes(&gtfs, "harbour"));
+}
+
+#[test]
+fn block_chain() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let chain = |gtfs: &Gtfs, trip_id| -> Vec<String> {
+        gtfs.block_chain(trip_id).unwrap().iter().map(|t| t.id.clone()).collect()
+    };
+    assert_eq!(vec!["r71_0730", "r71_0800_back"], chain(&gtfs, "r71_0800_back"));
+    assert_eq!(vec!["r71_0700"], chain(&gtfs, "r71_0700"));
+    assert_eq!(2, gtfs.trips_of_block("b1").len());
+    assert!(gtfs.block_chain("unknown").is_err());
+
+    // the tram of the weekend is not made by the same vehicle
+    gtfs.trips.get_mut("t1_0900").unwrap().block_id = Some("b1".to_owned());
+    gtfs.update_block_index();
+    assert_eq!(3, gtfs.trips_of_block("b1").len());
+    assert_eq!(vec!["r71_0730", "r71_0800_back"], chain(&gtfs, "r71_0730"));
+    assert_eq!(vec!["t1_0900"], chain(&gtfs, "t1_0900"));
+
+    gtfs.retain_trips(|trip| trip.id != "r71_0730");
+    assert_eq!(vec!["r71_0800_back"], chain(&gtfs, "r71_0800_back"));
+}
+
+#[test]
+fn location_groups() {
+    let gtfs = Gtfs::from_path("fixtures/flex").expect("impossible to read gtfs");
+    let group = gtfs.get_location_group("centres").unwrap();
+    assert_eq!(Some("Village centres"), group.name.as_deref());
+    let stop_ids: Vec<String> = gtfs
+        .stops_in_location_group("centres")
+        .iter()
+        .map(|stop| stop.id.clone())
+        .collect();
+    assert_eq!(vec!["school", "church"], stop_ids);
+    assert!(gtfs.stops_in_location_group("unknown").is_empty());
+
+    let stop_time = &gtfs.get_trip("od1_morning").unwrap().stop_times[2];
+    assert_eq!(Some("centres"), stop_time.location_group_id.as_deref());
+}
+
+#[test]
+fn route_names() {
+    let gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    let mut route = gtfs.get_route("1").unwrap().clone();
+    assert_eq!("100", route.display_name());
+    let issues: Vec<String> = gtfs
+        .validate()
+        .unwrap()
+        .issues
+        .into_iter()
+        .filter(|issue| issue.kind == IssueKind::RouteLongNameRepeatsShortName)
+        .map(|issue| issue.object_id)
+        .collect();
+    assert_eq!(vec!["1", "invalid_type"], issues);
+
+    route.short_name = " ".to_owned();
+    route.long_name = "Central Station - University".to_owned();
+    assert_eq!("Central Station - University", route.display_name());
+}
+
+#[test]
+fn rider_categories() {
+    let gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    assert_eq!(3, gtfs.rider_categories.len());
+    let senior = gtfs.get_rider_category("senior").unwrap();
+    assert!(!senior.is_default);
+    assert_eq!(Some("https://example.com/senior"), senior.eligibility_url.as_deref());
+    assert_eq!("adult", gtfs.default_rider_category().unwrap().id);
+
+    let card = &gtfs.fare_containers["card"];
+    assert_eq!(Some("10.00"), card.minimum_initial_purchase.as_deref());
+    assert_eq!(Some("2.00"), card.amount.as_deref());
+
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    assert!(gtfs.rider_categories.is_empty() && gtfs.fare_containers.is_empty());
+    assert!(gtfs.default_rider_category().is_none());
+}
+
+#[test]
+fn route_accessibility() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let days = gtfs.route_accessibility("r71");
+    assert_eq!(7, days.len());
+    let monday = &days[0];
+    assert_eq!(chrono::Weekday::Mon, monday.weekday);
+    assert_eq!(4, monday.trips);
+    assert_eq!(Some(0.75), monday.wheelchair_ratio());
+    assert_eq!(Some(0.75), monday.bikes_ratio());
+    assert_eq!(None, days[6].wheelchair_ratio());
+
+    // the weekend service also runs on new year's day, a monday
+    let days = gtfs.route_accessibility("t1");
+    assert_eq!(1, days[0].trips);
+    assert_eq!(1, days[0].bikes_unknown);
+    assert_eq!(0, days[1].trips);
+    assert_eq!(Some(1.), days[5].wheelchair_ratio());
+    assert!(gtfs.route_accessibility("unknown").is_empty());
+}
+
+#[test]
+fn write_gtfs() {
+    let dir = format!(
+        "{}/gtfs-structures-write-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    for fixture in &["fixtures/timetable", "fixtures/flex", "fixtures/fares_v2", "fixtures/basic"] {
+        let gtfs = Gtfs::from_path(fixture).expect("impossible to read gtfs");
+        gtfs.write_to_dir(&dir).expect("impossible to write gtfs");
+        let written = Gtfs::from_path(&dir).expect("impossible to read the written gtfs");
+        assert_eq!(gtfs.stops.len(), written.stops.len());
+        assert_eq!(gtfs.routes.len(), written.routes.len());
+        assert_eq!(gtfs.trips.len(), written.trips.len());
+        assert_eq!(gtfs.calendar.len(), written.calendar.len());
+        for (id, fare) in &gtfs.fare_attributes {
+            assert_eq!(fare.rules, written.fare_attributes[id].rules);
+        }
+        assert_eq!(gtfs.levels.len(), written.levels.len());
+        assert_eq!(gtfs.pathways.len(), written.pathways.len());
+        assert_eq!(gtfs.locations, written.locations);
+        assert_eq!(gtfs.location_group_stops, written.location_group_stops);
+        assert_eq!(gtfs.rider_categories, written.rider_categories);
+        assert_eq!(gtfs.translations_by_id, written.translations_by_id);
+        assert_eq!(gtfs.translations_by_value, written.translations_by_value);
+        for (id, trip) in &gtfs.trips {
+            let times = |t: &Trip| -> Vec<_> {
+                t.stop_times
+                    .iter()
+                    .map(|st| (st.stop.id.clone(), st.arrival_time, st.departure_time))
+                    .collect()
+            };
+            assert_eq!(times(trip), times(&written.trips[id]));
+        }
+        for (id, stop) in &gtfs.stops {
+            assert_eq!(stop.location_type, written.stops[id].location_type);
+        }
+        for (id, calendar) in &gtfs.calendar {
+            assert_eq!(calendar.start_date, written.calendar[id].start_date);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    let zip = format!("{}.zip", dir);
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.write_to_zip(&zip).expect("impossible to write gtfs");
+    let written = Gtfs::from_path(&zip).expect("impossible to read the written gtfs");
+    std::fs::remove_file(&zip).unwrap();
+    assert_eq!(gtfs.trips.len(), written.trips.len());
+    assert_eq!(gtfs.transfers, written.transfers);
+    assert_eq!(gtfs.feed_info[0].start_date, written.feed_info[0].start_date);
+}
+
+#[test]
+fn keep_coordinate_text() {
+    let dir = format!(
+        "{}/gtfs-structures-coordinates-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    let written = |gtfs: &Gtfs, file_name| {
+        gtfs.write_to_dir(&dir).expect("impossible to write gtfs");
+        let content = std::fs::read_to_string(format!("{}/{}", dir, file_name)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        content
+    };
+    let gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    assert!(gtfs.stops["stop1"].coordinate_text.is_none());
+    assert!(written(&gtfs, "shapes.txt").contains("A_shp,37.6443,-122.4107,6,"));
+
+    let options = GtfsReaderOptions::new().keep_coordinate_text(true);
+    let mut gtfs =
+        Gtfs::from_path_with_options("fixtures/basic", options).expect("impossible to read gtfs");
+    let text = gtfs.stops["stop1"].coordinate_text.clone().unwrap();
+    assert_eq!(" 48.796058 ", text.latitude);
+    assert_eq!("2.449386", text.longitude);
+    assert!(text.matches(Some(48.796058), Some(2.449386)));
+    assert!(written(&gtfs, "shapes.txt").contains("A_shp,37.64430,-122.41070,6,"));
+    // the columns are written in the order of the fields of Stop
+    assert!(written(&gtfs, "stops.txt").contains(",2.449386, 48.796058 ,"));
+
+    // a modified coordinate is written from its value
+    let mut stop = gtfs.stops["stop2"].as_ref().clone();
+    stop.latitude = Some(48.5);
+    gtfs.stops.insert("stop2".to_owned(), Arc::new(stop));
+    let stops = written(&gtfs, "stops.txt");
+    assert!(stops.contains(",2.449386,48.5,"));
+    assert!(stops.contains(",2.449386, 48.796058 ,"));
+}
+
+#[test]
+fn sum_fares() {
+    let mut gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    let fare = |gtfs: &Gtfs, legs: &[FareLeg]| gtfs.sum_fares(legs).map(|m| m.to_string());
+    // the cheapest fare media of the single ticket
+    let single = FareLeg::new("r71_1200", "central_a", "harbour");
+    assert_eq!("1.50 EUR", fare(&gtfs, &[single]).unwrap());
+    // free transfer within an hour
+    let legs = [
+        FareLeg::new("r71_1200", "central_a", "market"),
+        FareLeg::new("r71_1200", "market", "harbour"),
+    ];
+    assert_eq!("1.50 EUR", fare(&gtfs, &legs).unwrap());
+    // the day pass has the priority during the peak hours
+    let peak = FareLeg::new("r71_0700", "central_a", "market");
+    assert_eq!("5.00 EUR", fare(&gtfs, std::slice::from_ref(&peak)).unwrap());
+    let late = FareLeg::new("r71_1200", "market", "harbour");
+    assert_eq!("6.50 EUR", fare(&gtfs, &[peak.clone(), late.clone()]).unwrap());
+
+    for product in gtfs.fare_products.get_mut("day").unwrap() {
+        product.currency = "CHF".to_owned();
+    }
+    assert!(matches!(
+        gtfs.sum_fares(&[peak.clone(), late]),
+        Err(FareError::MixedCurrencies { .. })
+    ));
+    // the cheapest line of a product is not looked for across currencies
+    let mut products = gtfs.fare_products.get("day").unwrap().clone();
+    products[0].currency = "EUR".to_owned();
+    products.push(FareProduct {
+        currency: "CHF".to_owned(),
+        amount: "0.50".to_owned(),
+        ..products[0].clone()
+    });
+    gtfs.fare_products.insert("day".to_owned(), products);
+    assert!(matches!(
+        gtfs.sum_fares(std::slice::from_ref(&peak)),
+        Err(FareError::MixedCurrencies { .. })
+    ));
+    assert!(matches!(gtfs.sum_fares(&[]), Err(FareError::NoLegs)));
+    assert!(matches!(
+        gtfs.sum_fares(&[FareLeg::new("r71_1200", "harbour", "central_a")]),
+        Err(FareError::InvalidLeg { .. })
+    ));
+
+    // fares of fare_attributes.txt, the single ticket allows no transfer
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let legs = [
+        FareLeg::new("r71_0700", "central_a", "market"),
+        FareLeg::new("t1_0900", "central_b", "market"),
+    ];
+    assert_eq!("3.40 EUR", fare(&gtfs, &legs).unwrap());
+
+    assert_eq!(1.7, Money::parse("1.7", "EUR").unwrap().amount());
+    assert_eq!("0.125 EUR", Money::parse(".125", "EUR").unwrap().to_string());
+    assert!(Money::parse("1,70", "EUR").is_err());
+}
+
+#[test]
+fn build_gtfs() {
+    let stop = |id: &str, lat: f64| Stop {
+        id: id.to_owned(),
+        name: id.to_uppercase(),
+        latitude: Some(lat),
+        longitude: Some(5.72),
+        ..Default::default()
+    };
+    let stop_time = |stop_id: &str, sequence: u16, time: u32| RawStopTime {
+        stop_id: stop_id.to_owned(),
+        stop_sequence: sequence,
+        arrival_time: Some(time),
+        departure_time: Some(time),
+        timepoint: true,
+        ..Default::default()
+    };
+    let builder = GtfsBuilder::new()
+        .agency(Agency {
+            id: Some("tag".to_owned()),
+            name: "TAG".to_owned(),
+            url: "http://www.tag.fr".to_owned(),
+            timezone: "Europe/Paris".to_owned(),
+            ..Default::default()
+        })
+        .stop(stop("a", 45.19))
+        .stop(stop("b", 45.20))
+        .route(Route {
+            id: "r1".to_owned(),
+            short_name: "1".to_owned(),
+            long_name: "A - B".to_owned(),
+            route_type: RouteType::Bus,
+            agency_id: Some("tag".to_owned()),
+            route_color: Some(RGB8::new(255, 0, 0)),
+            route_text_color: Some(RGB8::new(255, 255, 255)),
+            ..Default::default()
+        })
+        .calendar(Calendar {
+            id: "week".to_owned(),
+            monday: true,
+            tuesday: true,
+            wednesday: true,
+            thursday: true,
+            friday: true,
+            saturday: false,
+            sunday: false,
+            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        })
+        .trip(
+            RawTrip {
+                id: "t1".to_owned(),
+                service_id: "week".to_owned(),
+                route_id: "r1".to_owned(),
+                ..Default::default()
+            },
+            vec![stop_time("a", 1, 8 * 3600), stop_time("b", 2, 8 * 3600 + 600)],
+        );
+    let gtfs = builder.build().expect("impossible to build gtfs");
+    assert_eq!(2, gtfs.stops.len());
+    let trip = gtfs.get_trip("t1").unwrap();
+    assert_eq!(2, trip.stop_times.len());
+    assert_eq!("b", trip.stop_times[1].stop.id);
+    assert!(gtfs.validate().unwrap().is_empty());
+
+    let dir = format!(
+        "{}/gtfs-structures-build-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    gtfs.write_to_dir(&dir).expect("impossible to write gtfs");
+    let written = Gtfs::from_path(&dir).expect("impossible to read the written gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(1, written.trips.len());
+
+    let unknown_stop = GtfsBuilder::new()
+        .route(gtfs.routes["r1"].as_ref().clone())
+        .trip(
+            RawTrip {
+                id: "t1".to_owned(),
+                service_id: "week".to_owned(),
+                route_id: "r1".to_owned(),
+                ..Default::default()
+            },
+            vec![stop_time("unknown", 1, 0)],
+        )
+        .build();
+    assert!(matches!(unknown_stop, Err(Error::ReferenceError(id)) if id == "unknown"));
+}
+
+#[test]
+fn trip_route_and_calendar() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let trip = &gtfs.trips["r71_0700"];
+    assert!(Arc::ptr_eq(trip.route.as_ref().unwrap(), &gtfs.routes["r71"]));
+    assert!(Arc::ptr_eq(trip.calendar.as_ref().unwrap(), &gtfs.calendar["week"]));
+
+    // the trips follow the routes modified by the mutation methods
+    gtfs.remap_route_types(|_| RouteType::Rail);
+    let route = gtfs.trips["r71_0700"].route.as_ref().unwrap();
+    assert_eq!(RouteType::Rail, route.route_type);
+    assert!(Arc::ptr_eq(route, &gtfs.routes["r71"]));
+
+    Arc::make_mut(gtfs.routes.get_mut("r71").unwrap()).short_name = "71A".into();
+    gtfs.trips.get_mut("r71_0700").unwrap().service_id = "weekend".into();
+    gtfs.link_trips();
+    let trip = &gtfs.trips["r71_0700"];
+    assert_eq!("71A", trip.route.as_ref().unwrap().short_name);
+    assert_eq!("weekend", trip.calendar.as_ref().unwrap().id);
+}
+
+#[test]
+fn retain_time_window() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.retain_time_window(7 * 3600 + 45 * 60..8 * 3600 + 10 * 60);
+    let trip_ids: Vec<&str> = gtfs.trips.keys().map(String::as_str).collect();
+    assert_eq!(vec!["r71_0730", "r71_0800_back"], trip_ids);
+    // the weekend service was only used by the tram
+    assert!(!gtfs.calendar.contains_key("weekend"));
+
+    let night = || Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    let retained = |window| {
+        let mut gtfs = night();
+        gtfs.retain_time_window(window);
+        gtfs.trips.into_keys().collect::<Vec<_>>()
+    };
+    // 24:10:00 is at 0:10 on the clocks
+    assert_eq!(vec!["n_2350"], retained(0..20 * 60));
+    assert_eq!(vec!["n_2350", "n_2430"], retained(23 * 3600 + 55 * 60..24 * 3600 + 35 * 60));
+    assert_eq!(vec!["n_2430"], retained(24 * 3600 + 20 * 60..25 * 3600));
+}
+
+#[test]
+fn sample() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.sample(0.5, 42).unwrap();
+    // two of the four weekday buses, and the only weekend tram
+    let buses: Vec<&str> = gtfs
+        .trips
+        .values()
+        .filter(|t| t.route_id == "r71")
+        .map(|t| t.id.as_str())
+        .collect();
+    assert_eq!(2, buses.len());
+    assert!(gtfs.trips.contains_key("t1_0900"));
+    assert_eq!(2, gtfs.routes.len());
+
+    let mut again = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    again.sample(0.5, 42).unwrap();
+    assert_eq!(
+        gtfs.trips.keys().collect::<Vec<_>>(),
+        again.trips.keys().collect::<Vec<_>>()
+    );
+
+    // the sampled feed can be written and read back
+    let dir = format!(
+        "{}/gtfs-structures-sample-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    gtfs.write_to_dir(&dir).expect("impossible to write gtfs");
+    let written = Gtfs::from_path(&dir).expect("impossible to read the sampled gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(3, written.trips.len());
+
+    let mut empty = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    empty.sample(0.0, 42).unwrap();
+    assert!(empty.trips.is_empty());
+    assert!(empty.routes.is_empty());
+    assert!(empty.stops.is_empty());
+    assert!(empty.calendar.is_empty());
+}
+
+#[test]
+fn search() {
+    let stop = |id: &str, name: &str| Stop {
+        id: id.to_owned(),
+        name: name.to_owned(),
+        ..Default::default()
+    };
+    let mut gtfs = GtfsBuilder::new()
+        .agency(Agency {
+            name: "Transit".to_owned(),
+            url: "https://example.com".to_owned(),
+            timezone: "Europe/Paris".to_owned(),
+            ..Default::default()
+        })
+        .stop(stop("nord", "Gare du Nord"))
+        .stop(stop("est", "Gare de l'Est"))
+        .stop(stop("etoile", "Charles de Gaulle – Étoile"))
+        .route(Route {
+            id: "rer_b".to_owned(),
+            short_name: "B".to_owned(),
+            long_name: "Gare du Nord - Aéroport".to_owned(),
+            route_type: RouteType::Rail,
+            ..Default::default()
+        })
+        .calendar(Calendar {
+            id: "daily".to_owned(),
+            monday: true,
+            tuesday: true,
+            wednesday: true,
+            thursday: true,
+            friday: true,
+            saturday: true,
+            sunday: true,
+            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        })
+        .trip(
+            RawTrip {
+                id: "t1".to_owned(),
+                service_id: "daily".to_owned(),
+                route_id: "rer_b".to_owned(),
+                trip_headsign: Some("Aéroport CDG".to_owned()),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .build()
+        .expect("impossible to build gtfs");
+
+    let ids = |results: Vec<SearchResult>| -> Vec<String> {
+        results.iter().map(|r| r.id().to_owned()).collect()
+    };
+    // the stop name weighs more than the long name of the route
+    assert_eq!(vec!["nord", "rer_b"], ids(gtfs.search("gare du nord")));
+    // case, accents and prefixes
+    assert_eq!(vec!["etoile"], ids(gtfs.search("ETOI")));
+    assert_eq!(vec!["nord", "est", "rer_b"], ids(gtfs.search("gare")));
+    // the headsign weighs more than the long name of the route
+    assert_eq!(vec!["t1", "rer_b"], ids(gtfs.search("aeroport")));
+    assert!(matches!(gtfs.search("cdg")[..], [SearchResult::Trip(_)]));
+    assert!(gtfs.search("gare lyon").is_empty());
+    assert!(gtfs.search(" ").is_empty());
+
+    gtfs.build_search_index();
+    assert_eq!(vec!["nord", "rer_b"], ids(gtfs.search("nord")));
+    gtfs.stops.remove("nord");
+    assert_eq!(vec!["rer_b"], ids(gtfs.search("nord")));
+}
+
+#[test]
+fn read_selected_files() {
+    let options = GtfsReaderOptions::new().only_files(&["stops.txt", "routes.txt", "trips.txt"]);
+    let gtfs = Gtfs::from_path_with_options("fixtures/basic", options.clone())
+        .expect("impossible to read gtfs");
+    assert_eq!(6, gtfs.stops.len());
+    assert_eq!(2, gtfs.routes.len());
+    assert!(gtfs.get_trip("trip1").unwrap().stop_times.is_empty());
+    assert!(gtfs.agencies.is_empty());
+    assert!(gtfs.shapes.is_empty());
+    assert!(gtfs.calendar.is_empty());
+
+    // without stops.txt, the stop times are not read either
+    let raw = RawGtfs::from_reader_with_options(
+        File::open("fixtures/zips/gtfs.zip").unwrap(),
+        options.skip_file("stops.txt"),
+    )
+    .expect("impossible to read gtfs");
+    assert_eq!(0, raw.stops.unwrap().len());
+    assert_eq!(0, raw.stop_times.unwrap().len());
+    assert_eq!(1, raw.trips.unwrap().len());
+    assert!(raw.shapes.is_none());
+    assert!(raw.files.contains(&"shapes.txt".to_owned()));
+
+    let gtfs = Gtfs::from_path_with_options(
+        "fixtures/basic",
+        GtfsReaderOptions::new().skip_file("fare_attributes.txt"),
+    )
+    .expect("impossible to read gtfs");
+    assert!(gtfs.fare_attributes.is_empty());
+    assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+}
+
+#[test]
+fn read_stop_times_iter() {
+    let raw = RawGtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    let expected = raw.stop_times.unwrap();
+    for path in &["fixtures/basic", "fixtures/zips/gtfs.zip", "fixtures/zips/subdirectory.zip"] {
+        let stop_times: Vec<RawStopTime> = RawGtfs::stop_times_iter(path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .expect("impossible to read stop times");
+        assert_eq!(expected.len(), stop_times.len());
+        assert_eq!(expected[1].stop_id, stop_times[1].stop_id);
+        assert_eq!(expected[1].arrival_time, stop_times[1].arrival_time);
+    }
+    let shapes: Vec<Shape> = RawGtfs::shapes_iter("fixtures/zips/gtfs.zip")
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .expect("impossible to read shapes");
+    let zip = RawGtfs::from_path("fixtures/zips/gtfs.zip").expect("impossible to read gtfs");
+    assert_eq!(zip.shapes.unwrap().unwrap().len(), shapes.len());
+    assert!(matches!(
+        RawGtfs::shapes_iter("fixtures/timetable"),
+        Err(Error::MissingFile(_))
+    ));
+}
+
+#[test]
+fn read_lenient_colors() {
+    let dir = format!(
+        "{}/gtfs-structures-colors-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        format!("{}/routes.txt", dir),
+        "route_id,route_short_name,route_long_name,route_type,route_color,route_text_color\n\
+         1,1,,3,#ff8000,fff\n\
+         2,2,,3,00ff00,\n",
+    )
+    .unwrap();
+    let strict = RawGtfs::from_path(&dir).expect("impossible to read gtfs");
+    let lenient =
+        RawGtfs::from_path_with_options(&dir, GtfsReaderOptions::new().lenient_colors(true))
+            .expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(strict.routes.is_err());
+    let routes = lenient.routes.unwrap();
+    assert_eq!(Some(RGB8::new(255, 128, 0)), routes[0].route_color);
+    assert_eq!(Some(RGB8::new(255, 255, 255)), routes[0].route_text_color);
+    assert_eq!(Some(RGB8::new(0, 255, 0)), routes[1].route_color);
+    assert_eq!(None, routes[1].route_text_color);
+    assert_eq!(2, lenient.warnings.len());
+    assert_eq!(Some(2), lenient.warnings[0].line);
+
+    assert!(parse_color("#FFFFFF").is_err());
+    assert!(parse_color("€ab").is_err());
+    assert_eq!(RGB8::new(0x11, 0x22, 0x33), parse_color_lenient("#123").unwrap());
+    assert!(parse_color_lenient("#12").is_err());
+}
+
+#[test]
+fn lenient_reading() {
+    let dir = format!(
+        "{}/gtfs-structures-lenient-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap()))
+            .unwrap();
+    }
+    let append = |file_name: &str, row: &str| {
+        let path = format!("{}/{}", dir, file_name);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, format!("{}{}\n", content, row)).unwrap();
+    };
+    append("stops.txt", "stop7,\"Bad\",,north,2.449386,,,,,");
+    append("trips.txt", "route1,service1,trip2,,,7,,0,0,,");
+    append("stop_times.txt", "trip2,16:00:00,16:00:00,stop2,1,\"\",0,0");
+
+    assert!(Gtfs::from_path(&dir).is_err());
+    let gtfs = Gtfs::from_path_with_options(&dir, GtfsReaderOptions::new().lenient(true))
+        .expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(!gtfs.stops.contains_key("stop7"));
+    assert!(!gtfs.trips.contains_key("trip2"));
+    assert_eq!(3, gtfs.warnings.len());
+    let warning = |file_name: &str| gtfs.warnings.iter().find(|w| w.file_name == file_name);
+    let stop = warning("stops.txt").unwrap();
+    assert_eq!(
+        ("stops.txt", Some(8), Some("stop_lat")),
+        (stop.file_name.as_str(), stop.line, stop.column.as_deref())
+    );
+    assert!(stop.message.ends_with("the row is skipped"));
+    let trip = warning("trips.txt").unwrap();
+    assert_eq!(
+        ("trips.txt", Some(3), Some("direction_id")),
+        (trip.file_name.as_str(), trip.line, trip.column.as_deref())
+    );
+    assert_eq!(
+        "stop_times.txt: the stop time 1 references the unknown trip 'trip2', it is skipped",
+        warning("stop_times.txt").unwrap().to_string()
+    );
+}
+
+#[test]
+fn csv_error_location() {
+    let dir = format!(
+        "{}/gtfs-structures-error-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        format!("{}/stop_times.txt", dir),
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+         trip1,14:00:00,14:00:00,stop1,1\n\
+         trip1,15:xx:00,15:00:00,stop2,2\n",
+    )
+    .unwrap();
+    std::fs::write(
+        format!("{}/stops.txt", dir),
+        "stop_id,platform,stop_name,stop_lat,stop_lon,location_type\n\
+         stop1,,Gare,48.88,2.35,0\n\
+         stop2,A,Gare,48.x,2.35,0\n",
+    )
+    .unwrap();
+    let raw = RawGtfs::from_path(&dir).expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    let error = raw.stops.unwrap_err();
+    assert_eq!(Some(3), error.line());
+    assert_eq!(Some("stop_lat"), error.column());
+    assert_eq!(Some("48.x"), error.value());
+    let error = raw.stop_times.unwrap_err();
+    assert_eq!(Some("stop_times.txt"), error.file_name());
+    assert_eq!(Some(3), error.line());
+    assert_eq!(Some("arrival_time"), error.column());
+    assert_eq!(Some("15:xx:00"), error.value());
+    assert_eq!(
+        "impossible to read csv file 'stop_times.txt' at line 3, column 'arrival_time' \
+         (value '15:xx:00')",
+        error.to_string()
+    );
+    assert_eq!(None, Error::ReferenceError("stop1".to_owned()).file_name());
+}
+
+#[test]
+fn sanitize_texts() {
+    let dir = format!(
+        "{}/gtfs-structures-sanitize-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        format!("{}/stops.txt", dir),
+        "stop_id,stop_name,stop_desc,stop_lat,stop_lon\n\
+         stop\t1,\"  Gare\u{1b}  du\r\nNord \",Quai 1,48.88,2.35\n\
+         stop2,Caf\u{65}\u{301},,48.88,2.35\n",
+    )
+    .unwrap();
+    let raw = RawGtfs::from_path_with_options(&dir, GtfsReaderOptions::new().sanitize_texts(true))
+        .expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    let stops = raw.stops.unwrap();
+    // the ids are not changed
+    assert_eq!("stop\t1", stops[0].id);
+    assert_eq!("Gare du Nord", stops[0].name);
+    assert_eq!("Quai 1", stops[0].description);
+    assert_eq!(Some(2), raw.warnings[0].line);
+    assert_eq!(Some("stop_name"), raw.warnings[0].column.as_deref());
+    #[cfg(feature = "normalize-unicode")]
+    {
+        assert_eq!("Caf\u{e9}", stops[1].name);
+        assert_eq!(2, raw.warnings.len());
+    }
+    #[cfg(not(feature = "normalize-unicode"))]
+    assert_eq!(1, raw.warnings.len());
+}
+
+#[test]
+fn read_lazy() {
+    use std::convert::TryFrom;
+    let dir = format!(
+        "{}/gtfs-structures-lazy-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap())).unwrap();
+    }
+    let gtfs = RawGtfs::lazy(&dir).expect("impossible to open gtfs");
+    assert_eq!(6, gtfs.stops().as_ref().unwrap().len());
+    // the files are only read when they are accessed
+    std::fs::remove_file(format!("{}/stops.txt", dir)).unwrap();
+    std::fs::remove_file(format!("{}/routes.txt", dir)).unwrap();
+    assert_eq!(6, gtfs.stops().as_ref().unwrap().len());
+    assert!(gtfs.routes().is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let options = GtfsReaderOptions::default().skip_file("shapes.txt");
+    let gtfs = RawGtfs::lazy_with_options("fixtures/zips/gtfs.zip", options).expect("impossible to open gtfs");
+    assert!(gtfs.shapes().is_none());
+    let expected = RawGtfs::from_path("fixtures/zips/gtfs.zip").unwrap();
+    assert_eq!(expected.trips.as_ref().unwrap().len(), gtfs.trips().as_ref().unwrap().len());
+    let raw: RawGtfs = gtfs.into();
+    assert_eq!(expected.stop_times.unwrap().len(), raw.stop_times.as_ref().unwrap().len());
+    assert!(raw.shapes.is_none());
+    assert!(Gtfs::try_from(raw).is_ok());
+}
+
+#[test]
+fn read_lazy_skipped_files() {
+    let gtfs = RawGtfs::lazy("fixtures/flex").expect("impossible to open gtfs");
+    assert!(gtfs.locations().as_ref().unwrap().is_ok());
+    let options = GtfsReaderOptions::default()
+        .skip_file("locations.geojson")
+        .skip_file("stops.txt");
+    let gtfs = RawGtfs::lazy_with_options("fixtures/flex", options.clone()).expect("impossible to open gtfs");
+    assert!(gtfs.locations().is_none());
+    assert!(gtfs.stops().as_ref().unwrap().is_empty());
+    let raw = RawGtfs::from_path_with_options("fixtures/flex", options).unwrap();
+    assert!(raw.locations.is_none());
+    assert!(raw.stops.unwrap().is_empty());
+}
+
+#[test]
+fn trip_geometry() {
+    let mut gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    assert_eq!(5, gtfs.shape_points("r71_out").unwrap().len());
+    assert!(gtfs.shape_points("unknown").is_err());
+    let length = gtfs.shape_length("r71_out").unwrap();
+    assert!((4450. ..4500.).contains(&length), "{}", length);
+
+    let trip = gtfs.get_trip("r71_0700").unwrap();
+    assert_eq!((5.714, 45.191), trip.geometry(&gtfs).unwrap()[0]);
+    // cut at the points of the shape closest to market and harbour
+    let part = trip.geometry_between(&gtfs, 2, 3).unwrap();
+    assert_eq!(2, part.len());
+    assert!((part[0].0 - 5.724).abs() < 1e-6 && (part[0].1 - 45.188).abs() < 1e-6);
+    assert!((part[1].0 - 5.735).abs() < 1e-6 && (part[1].1 - 45.185).abs() < 1e-6);
+    assert!(trip.geometry_between(&gtfs, 3, 2).is_err());
+    assert!(trip.geometry_between(&gtfs, 2, 9).is_err());
+
+    // cut at the shape_dist_traveled
+    for (i, point) in gtfs.shapes.get_mut("r71_out").unwrap().iter_mut().enumerate() {
+        point.dist_traveled = Some(i as f32);
+    }
+    let trip = gtfs.trips.get_mut("r71_0700").unwrap();
+    trip.stop_times[1].shape_dist_traveled = Some(2.5);
+    trip.stop_times[2].shape_dist_traveled = Some(3.5);
+    let trip = gtfs.get_trip("r71_0700").unwrap();
+    let part = trip.geometry_between(&gtfs, 2, 3).unwrap();
+    assert_eq!(3, part.len());
+    assert!((part[0].0 - 5.7295).abs() < 1e-6 && (part[0].1 - 45.1865).abs() < 1e-6);
+    assert_eq!((5.735, 45.185), part[1]);
+    assert!((part[2].0 - 5.7515).abs() < 1e-6 && (part[2].1 - 45.1885).abs() < 1e-6);
+}
+
+#[test]
+fn stop_points_and_distances() {
+    let gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    let market = gtfs.get_stop("market").unwrap();
+    let harbour = gtfs.get_stop("harbour").unwrap();
+    assert_eq!(Some((5.724, 45.188)), market.point());
+    let distance = market.distance_to(harbour).unwrap();
+    assert!((900. ..950.).contains(&distance), "{}", distance);
+    assert_eq!(None, market.distance_to(&Stop::default()));
+    let line = gtfs.shape_line("t1").unwrap();
+    assert_eq!(vec![(5.7139, 45.1909), (5.724, 45.188), (5.735, 45.185)], line);
+}
+
+#[test]
+fn par_map_trips() {
+    let gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    let expected: Vec<(String, usize)> = gtfs
+        .trips
+        .values()
+        .map(|trip| (trip.id.clone(), trip.stop_times.len()))
+        .collect();
+    assert_eq!(expected, gtfs.par_map_trips(|trip| (trip.id.clone(), trip.stop_times.len())));
+
+    let options = GtfsReaderOptions::new().spill_stop_times(2);
+    let spilled = Gtfs::from_path_with_options("fixtures/timetable", options).unwrap();
+    let counts = spilled
+        .par_map_stop_times(|trip, stop_times| (trip.id.clone(), stop_times.len()))
+        .unwrap();
+    assert_eq!(expected, counts);
+}
+
+#[test]
+fn compress_to_frequencies() {
+    let mut gtfs = Gtfs::from_path("fixtures/timetable").expect("impossible to read gtfs");
+    gtfs.record_changes(true);
+    let trip = gtfs.get_trip("r71_0700").unwrap().clone();
+    // departures every 10 minutes from 7:00, one 5 seconds late, then a gap
+    for (i, shift) in [(1, 600), (2, 1205), (3, 1800), (4, 4800)] {
+        let mut copy = trip.clone();
+        copy.id = format!("copy_{}", i);
+        for stop_time in &mut copy.stop_times {
+            stop_time.arrival_time = stop_time.arrival_time.map(|t| t + shift);
+            stop_time.departure_time = stop_time.departure_time.map(|t| t + shift);
+        }
+        gtfs.trips.insert(copy.id.clone(), copy);
+    }
+    let mut other = gtfs.get_trip("copy_1").unwrap().clone();
+    other.id = "other".to_owned();
+    other.trip_headsign = Some("Market".to_owned());
+    gtfs.trips.insert(other.id.clone(), other);
+    let nb_trips = gtfs.trips.len();
+
+    assert!(gtfs.compress_to_frequencies(0).unwrap().is_empty());
+    assert_eq!(vec!["r71_0700"], gtfs.compress_to_frequencies(10).unwrap());
+    assert_eq!(nb_trips - 3, gtfs.trips.len());
+    assert!(!gtfs.trips.contains_key("copy_1") && !gtfs.trips.contains_key("copy_3"));
+    assert!(gtfs.trips.contains_key("copy_4") && gtfs.trips.contains_key("other"));
+    let trip = gtfs.get_trip("r71_0700").unwrap();
+    let frequency = &trip.frequencies[0];
+    assert_eq!(
+        (7 * 3600, 7 * 3600 + 2400, 600),
+        (frequency.start_time, frequency.end_time, frequency.headway_secs)
+    );
+    assert_eq!(Some(ExactTimes::ScheduleBased), frequency.exact_times);
+    let departures: Vec<Option<u32>> = trip
+        .expand_frequencies()
+        .iter()
+        .map(|trip| trip.stop_times[0].departure_time)
+        .collect();
+    assert_eq!(vec![Some(25200), Some(25800), Some(26400), Some(27000)], departures);
+
+    let changes = gtfs.change_log.clone().unwrap();
+    for change in changes.iter().rev() {
+        gtfs.undo_change(change);
+    }
+    assert_eq!(nb_trips, gtfs.trips.len());
+    assert!(gtfs.get_trip("r71_0700").unwrap().frequencies.is_empty());
+
+    // the stop times shared in patterns are compared, and recorded with the removed trips
+    gtfs.share_stop_patterns();
+    assert!(gtfs.trips["copy_1"].stop_times.is_empty());
+    assert_eq!(vec!["r71_0700"], gtfs.compress_to_frequencies(10).unwrap());
+    assert_eq!(nb_trips - 3, gtfs.trips.len());
+    let removed = gtfs.take_changes().into_iter().find_map(|change| match change {
+        ChangeEvent::TripRemoved(trip) if trip.id == "copy_1" => Some(trip),
+        _ => None,
+    });
+    assert_eq!(4, removed.unwrap().stop_times.len());
+}
+
+#[test]
+fn trip_with_stop_times() {
+    let options = GtfsReaderOptions::new().share_stop_patterns(true);
+    let gtfs = Gtfs::from_path_with_options("fixtures/night", options).expect("impossible to read gtfs");
+    // n_2200 and n_2430 share their pattern
+    assert!(gtfs.trips["n_2430"].stop_times.is_empty());
+    assert!(!gtfs.trips["n_2430"].crosses_midnight());
+    let trip = gtfs.trip_with_stop_times("n_2430").unwrap();
+    assert!(matches!(trip, std::borrow::Cow::Owned(_)));
+    assert!(trip.crosses_midnight());
+    let segment = trip.segment(1, 2).unwrap();
+    assert_eq!(vec![Some(24 * 3600 + 1800), Some(24 * 3600 + 2700)], segment.iter().map(|st| st.arrival_time).collect::<Vec<_>>());
+    assert_eq!(2, trip.expand_frequencies()[0].stop_times.len());
+    // the trips with their own stop times are borrowed
+    let trip = gtfs.trip_with_stop_times("n_2350").unwrap();
+    assert!(matches!(trip, std::borrow::Cow::Borrowed(_)));
+    assert!(trip.crosses_midnight());
+    assert!(gtfs.trip_with_stop_times("unknown").is_err());
+
+    let options = GtfsReaderOptions::new().spill_stop_times(1);
+    let gtfs = Gtfs::from_path_with_options("fixtures/night", options).expect("impossible to read gtfs");
+    assert!(gtfs.trip_with_stop_times("n_2430").unwrap().crosses_midnight());
+}
+
+#[test]
+fn unknown_stops() {
+    let dir = format!(
+        "{}/gtfs-structures-unknown-stops-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap())).unwrap();
+    }
+    let mut stop_times = std::fs::read_to_string(format!("{}/stop_times.txt", dir)).unwrap();
+    stop_times.push_str("trip1,16:00:00,16:00:00,unknown,2,\"\",0,0\n");
+    std::fs::write(format!("{}/stop_times.txt", dir), stop_times).unwrap();
+    let read = |policy| {
+        Gtfs::from_path_with_options(dir.as_str(), GtfsReaderOptions::new().unknown_stops(policy))
+    };
+
+    assert!(matches!(read(UnknownStopPolicy::Error), Err(Error::ReferenceError(id)) if id == "unknown"));
+
+    let gtfs = read(UnknownStopPolicy::Skip).unwrap();
+    assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+    assert!(!gtfs.stops.contains_key("unknown"));
+    assert!(gtfs.warnings.iter().any(|w| w.message.contains("'unknown'")));
+    let options = GtfsReaderOptions::new()
+        .unknown_stops(UnknownStopPolicy::Skip)
+        .spill_stop_times(10);
+    let gtfs = Gtfs::from_path_with_options(dir.as_str(), options).unwrap();
+    assert_eq!(2, gtfs.trip_stop_times("trip1").unwrap().len());
+
+    let gtfs = read(UnknownStopPolicy::Placeholder).unwrap();
+    let stop_times = &gtfs.get_trip("trip1").unwrap().stop_times;
+    assert_eq!(3, stop_times.len());
+    assert!(stop_times[2].stop.missing);
+    assert!(gtfs.get_stop("unknown").unwrap().missing);
+    assert!(!gtfs.get_stop("stop2").unwrap().missing);
+    let report = gtfs.validate().unwrap();
+    let issue = report
+        .issues
+        .iter()
+        .find(|issue| issue.kind == IssueKind::MissingStop)
+        .unwrap();
+    assert_eq!("trip1", issue.object_id);
+    assert!(report.has_errors());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Protocol buffers encoding of GTFS-Realtime messages
+#[cfg(feature = "realtime")]
+mod protobuf {
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+    pub fn int(number: u64, value: i64) -> Vec<u8> {
+        [varint(number << 3), varint(value as u64)].concat()
+    }
+    pub fn float(number: u64, value: f32) -> Vec<u8> {
+        [varint(number << 3 | 5), value.to_le_bytes().to_vec()].concat()
+    }
+    pub fn bytes(number: u64, value: &[u8]) -> Vec<u8> {
+        [varint(number << 3 | 2), varint(value.len() as u64), value.to_vec()].concat()
+    }
+}
+
+#[test]
+#[cfg(feature = "realtime")]
+fn apply_trip_updates() {
+    use crate::realtime::{FeedMessage, TripScheduleRelationship};
+    use chrono::{FixedOffset, TimeZone};
+    use protobuf::{bytes, int};
+    fn trip_update(trip: Vec<u8>, stop_time_updates: Vec<Vec<u8>>) -> Vec<u8> {
+        let update = [bytes(1, &trip), stop_time_updates.iter().flat_map(|u| bytes(2, u)).collect()].concat();
+        bytes(2, &[bytes(1, b"entity"), bytes(3, &update)].concat())
+    }
+    let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+    let local = |h, m| offset.with_ymd_and_hms(2026, 10, 14, h, m, 0).unwrap().timestamp();
+
+    let feed = [
+        bytes(1, &[bytes(1, b"2.0"), int(3, local(8, 0))].concat()),
+        trip_update(
+            [bytes(1, b"r71_0700"), bytes(3, b"20261014")].concat(),
+            vec![
+                [int(1, 2), bytes(2, &int(1, 120))].concat(),
+                [int(1, 3), int(5, 1)].concat(),
+            ],
+        ),
+        trip_update([bytes(1, b"r71_1200"), int(4, 3)].concat(), vec![]),
+        trip_update(
+            bytes(1, b"r71_0730"),
+            vec![[int(1, 1), bytes(3, &int(2, local(7, 29)))].concat()],
+        ),
+        trip_update(
+            [bytes(1, b"extra"), bytes(5, b"r71"), int(4, 1)].concat(),
+            vec![
+                [bytes(4, b"market"), bytes(2, &int(2, local(9, 0)))].concat(),
+                [bytes(4, b"nowhere"), bytes(2, &int(2, local(9, 10)))].concat(),
+            ],
+        ),
+        trip_update(bytes(1, b"ghost"), vec![]),
+    ]
+    .concat();
+    let feed = FeedMessage::decode(&feed).expect("impossible to decode the feed");
+    assert_eq!("2.0", feed.header.gtfs_realtime_version);
+    assert_eq!(5, feed.entity.len());
+    assert!(FeedMessage::decode(&[0x0a, 0x05]).is_err());
+
+    let gtfs = Gtfs::from_path("fixtures/timetable").unwrap();
+    let trips = gtfs.apply_trip_updates(&feed, |_| Some(offset)).unwrap();
+    assert_eq!(4, trips.len());
+
+    let delayed = &trips[0];
+    assert_eq!(NaiveDate::from_ymd_opt(2026, 10, 14), delayed.start_date);
+    let times: Vec<_> = delayed
+        .stop_times
+        .iter()
+        .map(|s| (s.stop_time.arrival_time, s.stop_time.departure_time, s.arrival_delay, s.skipped))
+        .collect();
+    assert_eq!(
+        vec![
+            (Some(7 * 3600), Some(7 * 3600), None, false),
+            (Some(7 * 3600 + 12 * 60), Some(7 * 3600 + 13 * 60), Some(120), false),
+            (Some(7 * 3600 + 20 * 60), Some(7 * 3600 + 20 * 60), None, true),
+            (Some(7 * 3600 + 32 * 60), Some(7 * 3600 + 32 * 60), Some(120), false),
+        ],
+        times
+    );
+
+    assert_eq!(TripScheduleRelationship::Canceled, trips[1].schedule_relationship);
+    assert!(trips[1].stop_times.iter().all(|s| s.skipped));
+
+    // the absolute time gives a negative delay, applied to the next stops
+    let early = &trips[2];
+    assert_eq!(Some(-60), early.stop_times[0].departure_delay);
+    assert_eq!(Some(7 * 3600 + 39 * 60), early.stop_times[1].stop_time.arrival_time);
+
+    let added = &trips[3];
+    assert_eq!(TripScheduleRelationship::Added, added.schedule_relationship);
+    assert_eq!(Some("r71".to_owned()), added.route_id);
+    assert_eq!(1, added.stop_times.len());
+    assert_eq!("market", added.stop_times[0].stop_time.stop.id);
+    assert_eq!(Some(9 * 3600), added.stop_times[0].stop_time.departure_time);
+
+    // the offset of the timezone of the agency is the one at the date of the trip, 07:29 is
+    // 06:29 UTC in winter and 05:29 UTC in summer
+    let paris = |name: &str| (name == "Europe/Paris").then_some(Paris2024);
+    for (date, utc_hour) in [("20240115", 6), ("20240715", 5)] {
+        let utc = NaiveDate::parse_from_str(date, "%Y%m%d").unwrap().and_hms_opt(utc_hour, 29, 0).unwrap();
+        let feed = [
+            bytes(1, &bytes(1, b"2.0")),
+            trip_update(
+                [bytes(1, b"r71_0730"), bytes(3, date.as_bytes())].concat(),
+                vec![[int(1, 1), bytes(3, &int(2, utc.and_utc().timestamp()))].concat()],
+            ),
+        ]
+        .concat();
+        let trips = gtfs.apply_trip_updates(&FeedMessage::decode(&feed).unwrap(), paris).unwrap();
+        assert_eq!(Some(-60), trips[0].stop_times[0].departure_delay);
+    }
+    assert!(matches!(
+        gtfs.apply_trip_updates(&feed, |_| None::<FixedOffset>),
+        Err(crate::Error::UnknownTimezone(_))
+    ));
+}
+
+#[test]
+fn fare_context() {
+    use crate::FareContext;
+    let mut gtfs = Gtfs::from_path("fixtures/fares_v2").expect("impossible to read gtfs");
+    assert_eq!(Some("Tramway"), gtfs.networks["tram"].name.as_deref());
+    assert_eq!(Some("tram"), gtfs.get_route("t1").unwrap().network_id.as_deref());
+
+    let tram = FareLeg::new("t1_0900", "central_b", "harbour");
+    let resolved = FareContext::new(&gtfs).resolve(&tram).unwrap();
+    assert_eq!(Some("tram"), resolved.network_id);
+    assert_eq!(vec!["centre"], resolved.from_area_ids);
+    assert_eq!(vec!["port"], resolved.to_area_ids);
+    let products: Vec<&str> = resolved.rules.iter().map(|r| r.fare_product_id.as_str()).collect();
+    assert_eq!(vec!["single"], products);
+
+    // without rule_priority, the rule of the network of the leg replaces the rules without network
+    for rule in &mut gtfs.fare_leg_rules {
+        rule.rule_priority = None;
+    }
+    gtfs.fare_leg_rules.push(FareLegRule {
+        network_id: Some("tram".to_owned()),
+        fare_product_id: "day".to_owned(),
+        ..Default::default()
+    });
+    let context = FareContext::new(&gtfs);
+    let products: Vec<&str> = context.resolve(&tram).unwrap().rules.iter().map(|r| r.fare_product_id.as_str()).collect();
+    assert_eq!(vec!["day"], products);
+    let bus = FareLeg::new("r71_1200", "central_a", "harbour");
+    let products: Vec<&str> = context.resolve(&bus).unwrap().rules.iter().map(|r| r.fare_product_id.as_str()).collect();
+    assert_eq!(vec!["single"], products);
+    assert_eq!("5.00 EUR", gtfs.sum_fares(&[tram]).unwrap().to_string());
+}
+
+#[test]
+#[cfg(feature = "realtime")]
+fn alert_entities() {
+    use crate::realtime::FeedMessage;
+    use protobuf::{bytes, int};
+    let alert = |alert: Vec<u8>| bytes(2, &[bytes(1, b"alert"), bytes(5, &alert)].concat());
+    let feed = [
+        alert(
+            [
+                bytes(5, &bytes(5, b"central")),
+                bytes(5, &int(3, 0)),
+                bytes(5, &[bytes(2, b"r71"), int(6, 1)].concat()),
+                bytes(10, &bytes(1, &[bytes(1, b"Works"), bytes(2, b"en")].concat())),
+            ]
+            .concat(),
+        ),
+        alert(bytes(5, &bytes(1, b"tag"))),
+        alert(bytes(5, &bytes(4, &bytes(1, b"r71_0700")))),
+    ]
+    .concat();
+    let feed = FeedMessage::decode(&feed).expect("impossible to decode the feed");
+    let gtfs = Gtfs::from_path("fixtures/timetable").unwrap();
+    let ids = |entities: crate::realtime::AffectedEntities| {
+        (
+            entities.routes.iter().map(|r| r.id.clone()).collect::<Vec<_>>(),
+            entities.stops.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+            entities.trips.iter().map(|t| t.id.clone()).collect::<Vec<_>>(),
+        )
+    };
+
+    let alert = feed.entity[0].alert.as_ref().unwrap();
+    assert_eq!(Some("Works"), alert.header_text.as_ref().unwrap().text("en"));
+    let (routes, stops, trips) = ids(gtfs.alert_entities(alert));
+    assert_eq!(vec!["r71", "t1"], routes);
+    // the stops of the station are affected too
+    assert_eq!(vec!["central", "central_a", "central_b"], stops);
+    assert_eq!(vec!["r71_0800_back", "t1_0900"], trips);
+
+    // the whole network of the agency
+    let (routes, stops, trips) = ids(gtfs.alert_entities(feed.entity[1].alert.as_ref().unwrap()));
+    assert_eq!(vec!["r71", "t1"], routes);
+    assert!(stops.is_empty());
+    assert_eq!(5, trips.len());
+
+    let (routes, _, trips) = ids(gtfs.alert_entities(feed.entity[2].alert.as_ref().unwrap()));
+    assert_eq!(vec!["r71"], routes);
+    assert_eq!(vec!["r71_0700"], trips);
+}
+
+#[test]
+#[cfg(feature = "realtime")]
+fn resolve_vehicle_positions() {
+    use crate::realtime::{FeedMessage, VehicleStopStatus};
+    use protobuf::{bytes, float, int};
+    let vehicle = |vehicle: Vec<u8>| bytes(2, &[bytes(1, b"vehicle"), bytes(4, &vehicle)].concat());
+    let feed = [
+        // stopped at market
+        vehicle(
+            [
+                bytes(1, &bytes(1, b"r71_0700")),
+                bytes(2, &[float(1, 45.5), float(2, 4.25), float(5, 8.5)].concat()),
+                int(3, 2),
+                int(4, 1),
+                bytes(8, &bytes(1, b"bus_12")),
+            ]
+            .concat(),
+        ),
+        // going to the last stop
+        vehicle([bytes(1, &bytes(1, b"r71_0730")), bytes(7, b"university")].concat()),
+        // unknown trip of a known route
+        vehicle([bytes(1, &[bytes(1, b"ghost"), bytes(5, b"t1")].concat()), bytes(7, b"harbour")].concat()),
+    ]
+    .concat();
+    let feed = FeedMessage::decode(&feed).expect("impossible to decode the feed");
+    let position = feed.entity[0].vehicle.as_ref().unwrap().position.as_ref().unwrap();
+    assert_eq!((45.5, 4.25, Some(8.5)), (position.latitude, position.longitude, position.speed));
+
+    let gtfs = Gtfs::from_path("fixtures/timetable").unwrap();
+    let vehicles = gtfs.resolve_vehicle_positions(&feed);
+    assert_eq!(3, vehicles.len());
+
+    let stopped = &vehicles[0];
+    assert_eq!(VehicleStopStatus::StoppedAt, stopped.vehicle.current_status);
+    assert_eq!(Some("bus_12"), stopped.vehicle.vehicle.as_ref().unwrap().id.as_deref());
+    assert_eq!("r71_0700", stopped.trip.unwrap().id);
+    assert_eq!("r71", stopped.route.unwrap().id);
+    assert_eq!("market", stopped.current_stop.as_ref().unwrap().id);
+    assert_eq!("harbour", stopped.next_stop.as_ref().unwrap().id);
+    assert_eq!(Some(7 * 3600 + 20 * 60), stopped.next_stop_time.as_ref().unwrap().arrival_time);
+
+    let moving = &vehicles[1];
+    assert!(moving.current_stop.is_none());
+    assert_eq!("university", moving.next_stop.as_ref().unwrap().id);
+    assert_eq!(4, moving.next_stop_time.as_ref().unwrap().stop_sequence);
+
+    let unknown = &vehicles[2];
+    assert!(unknown.trip.is_none());
+    assert_eq!("t1", unknown.route.unwrap().id);
+    assert_eq!("harbour", unknown.next_stop.as_ref().unwrap().id);
+    assert!(unknown.next_stop_time.is_none());
+}
+
 #[test]
 fn read_from_gtfs() {
     let gtfs = Gtfs::from_path("fixtures/zips/gtfs.zip").unwrap();
@@ -238,6 +3147,20 @@ fn path_files() {
     assert_eq!(gtfs.files.len(), 11);
 }
 
+#[test]
+fn read_from_bytes() {
+    let bytes = std::fs::read("fixtures/zips/gtfs.zip").unwrap();
+    let gtfs = Gtfs::from_bytes(&bytes).expect("impossible to read gtfs");
+    assert_eq!(5, gtfs.stops.len());
+    assert_eq!(1, gtfs.trips.len());
+    let raw = RawGtfs::from_bytes(&bytes).expect("impossible to read gtfs");
+    assert_eq!(
+        raw.sha256,
+        Some("4a262ae109101ffbd1629b67e080a2b074afdaa60d57684db0e1a31c0a1e75b0".to_owned())
+    );
+    assert!(Gtfs::from_bytes(b"not a zip").is_err());
+}
+
 #[test]
 fn zip_files() {
     let gtfs = RawGtfs::from_path("fixtures/zips/gtfs.zip").expect("impossible to read gtfs");
@@ -253,6 +3176,57 @@ fn zip_subdirectory_files() {
     assert!(gtfs.files.contains(&"subdirectory/agency.txt".to_owned()));
 }
 
+#[test]
+fn record_counts() {
+    let counts = RawGtfs::record_counts("fixtures/basic").expect("impossible to count records");
+    assert_eq!(11, counts.len());
+    assert_eq!(Some(&6), counts.get("stops.txt"));
+    assert_eq!(Some(&2), counts.get("stop_times.txt"));
+
+    let counts =
+        RawGtfs::record_counts("fixtures/zips/subdirectory.zip").expect("impossible to count records");
+    assert_eq!(Some(&5), counts.get("stops.txt"));
+
+    let counts = RawGtfs::record_counts("fixtures/zips/macosx.zip").expect("impossible to count records");
+    assert_eq!(Some(&2), counts.get("agency.txt"));
+}
+
+#[test]
+fn header_mapping() {
+    let headers = RawGtfs::headers("fixtures/zips/subdirectory.zip", "stops.txt").unwrap();
+    assert_eq!("stop_id", headers[0]);
+    assert!(RawGtfs::headers("fixtures/basic", "unknown.txt").is_err());
+
+    let mapping = RawGtfs::header_mapping("fixtures/basic", "stops.txt").unwrap();
+    assert_eq!(Some("Stop"), mapping.structure);
+    assert_eq!(Some("stop_id"), mapping.columns[0].field);
+    assert_eq!(0, mapping.ignored().count());
+    assert!(mapping.missing.contains(&"level_id"));
+
+    let headers = ["stop_id", "Stop_Name", "stop_desk", " stop_lon", "my_column"];
+    let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let mapping = HeaderMapping::new("stops.txt", &headers);
+    let ignored: Vec<_> = mapping.ignored().map(|c| (c.header.as_str(), c.suggestion)).collect();
+    assert_eq!(
+        vec![
+            ("Stop_Name", Some("stop_name")),
+            ("stop_desk", Some("stop_desc")),
+            (" stop_lon", Some("stop_lon")),
+            ("my_column", None),
+        ],
+        ignored
+    );
+    assert!(mapping.to_string().contains("\"Stop_Name\" is ignored, did you mean \"stop_name\"?"));
+
+    // every csv file read by the crate is a structure with the columns of the specification
+    for file_name in crate::raw_gtfs::GTFS_FILES.iter().filter(|f| f.ends_with(".txt")) {
+        let mapping = HeaderMapping::new(file_name, &[]);
+        assert!(mapping.structure.is_some(), "{}", file_name);
+        assert!(!mapping.missing.is_empty(), "{}", file_name);
+    }
+    assert_eq!(None, HeaderMapping::new("notes.txt", &headers).structure);
+}
+
 #[test]
 fn compute_sha256() {
     let gtfs = RawGtfs::from_path("fixtures/zips/gtfs.zip").expect("impossible to read gtfs");
@@ -296,6 +3270,61 @@ fn read_interpolated_stops() {
     assert!(gtfs.trips["trip1"].stop_times[1].arrival_time.is_none());
 }
 
+#[test]
+fn interpolate_stop_times() {
+    let mut gtfs =
+        Gtfs::from_path("fixtures/interpolated_stop_times").expect("impossible to read gtfs");
+    let mut trip = gtfs.trips["trip1"].clone();
+    assert_eq!(1, trip.interpolate_stop_times());
+    assert_eq!(Some(14 * 3600 + 1800), trip.stop_times[1].arrival_time);
+    assert_eq!(Some(14 * 3600 + 1800), trip.stop_times[1].departure_time);
+    assert!(trip.stop_times[1].interpolated);
+    assert!(!trip.stop_times[1].is_exact());
+    assert!(!trip.stop_times[0].interpolated);
+
+    // the time is proportional to the distance traveled
+    let mut trip = gtfs.trips["trip1"].clone();
+    for (stop_time, distance) in trip.stop_times.iter_mut().zip(&[0.0, 1.0, 4.0]) {
+        stop_time.shape_dist_traveled = Some(*distance);
+    }
+    trip.interpolate_stop_times();
+    assert_eq!(Some(14 * 3600 + 900), trip.stop_times[1].arrival_time);
+
+    // the untimed stop times at the ends stay untimed
+    let mut trip = gtfs.trips["trip1"].clone();
+    trip.stop_times[0].arrival_time = None;
+    trip.stop_times[0].departure_time = None;
+    trip.stop_times[2].departure_time = None;
+    assert_eq!(0, trip.interpolate_stop_times());
+    assert_eq!(None, trip.stop_times[0].departure_time);
+    assert_eq!(None, trip.stop_times[1].departure_time);
+    assert_eq!(Some(15 * 3600), trip.stop_times[2].departure_time);
+
+    gtfs.record_changes(true);
+    assert_eq!(1, gtfs.interpolate_stop_times().unwrap());
+    assert_eq!(1, gtfs.take_changes().len());
+    assert_eq!(0, gtfs.interpolate_stop_times().unwrap());
+}
+
+#[test]
+fn exact_stop_times() {
+    let gtfs =
+        Gtfs::from_path("fixtures/interpolated_stop_times").expect("impossible to read gtfs");
+    let stop_times = &gtfs.trips["trip1"].stop_times;
+    assert!(stop_times[0].is_exact());
+    assert!(!stop_times[1].is_exact());
+    let interpolated = StopTime {
+        interpolated: true,
+        ..stop_times[0].clone()
+    };
+    assert!(!interpolated.is_exact());
+    let not_timepoint = StopTime {
+        timepoint: false,
+        ..stop_times[0].clone()
+    };
+    assert!(!not_timepoint.is_exact());
+}
+
 #[test]
 fn read_only_required_fields() {
     let gtfs = Gtfs::from_path("fixtures/only_required_fields").expect("impossible to read gtfs");
@@ -317,7 +3346,7 @@ fn sorted_shapes() {
     let shape = &gtfs.shapes.get("Unordered_shp").unwrap();
 
     let points = shape
-        .into_iter()
+        .iter()
         .map(|s| (s.sequence, s.latitude, s.longitude))
         .collect::<Vec<_>>();
 
@@ -348,6 +3377,497 @@ fn translations() {
 }
 
 #[test]
+fn translate_many() {
+    let gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    let stops: Vec<&Stop> = gtfs.stops.values().map(|stop| stop.as_ref()).collect();
+    for language in &["nl", "fr", "en"] {
+        let names: Vec<String> = gtfs
+            .translate_many(&stops, language)
+            .into_iter()
+            .map(|stop| stop.name)
+            .collect();
+        let expected: Vec<String> = stops
+            .iter()
+            .map(|stop| stop.translate(&gtfs, language).name)
+            .collect();
+        assert_eq!(expected, names);
+    }
+    assert_eq!("Stop Gebied", gtfs.translate_many(&stops, "nl")[0].name);
+
+    let routes: Vec<&Route> = gtfs.routes.values().map(|r| r.as_ref()).collect();
+    let translated = gtfs.translate_many_routes(&routes, "nl");
+    assert_eq!(routes.len(), translated.len());
+    assert_eq!(routes[0].long_name, translated[0].long_name);
+}
+
+#[test]
+fn translate_agency_feed_info_and_stop_headsigns() {
+    let dir = format!(
+        "{}/gtfs-structures-translated-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap()))
+            .unwrap();
+    }
+    std::fs::write(
+        format!("{}/stop_times.txt", dir),
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence,stop_headsign\n\
+         trip1,14:00:00,14:00:00,stop2,1,Centre\n\
+         trip1,15:00:00,15:00:00,stop3,2,\n",
+    )
+    .unwrap();
+    let path = format!("{}/translations.txt", dir);
+    let content = std::fs::read_to_string(&path).unwrap();
+    let rows = "agency,agency_name,nl,Bibus Bretagne,BIBUS,,\n\
+                agency,agency_url,nl,http://www.bibus.nl,http://www.bibus.fr,,\n\
+                feed_info,feed_publisher_name,nl,NS,,,\n\
+                stop_times,stop_headsign,nl,Centrum,,trip1,1\n";
+    std::fs::write(&path, format!("{}{}", content, rows)).unwrap();
+    let gtfs = Gtfs::from_path(&dir).expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let agency = gtfs.agencies[0].translate(&gtfs, "nl");
+    assert_eq!(("Bibus Bretagne", "http://www.bibus.nl"), (&*agency.name, &*agency.url));
+    assert_eq!("Ter", gtfs.agencies[1].translate(&gtfs, "nl").name);
+    let feed_info = gtfs.feed_info[0].translate(&gtfs, "nl");
+    assert_eq!(("NS", Some("0.3")), (&*feed_info.name, feed_info.version.as_deref()));
+    assert_eq!("SNCF", gtfs.feed_info[0].translate(&gtfs, "en").name);
+
+    let trip = gtfs.get_trip("trip1").unwrap();
+    assert_eq!(
+        vec![Some("Centrum".to_owned()), None],
+        trip.translated_stop_headsigns(&gtfs, "nl").unwrap()
+    );
+    assert_eq!(
+        vec![Some("Centre".to_owned()), None],
+        trip.translated_stop_headsigns(&gtfs, "en").unwrap()
+    );
+    let translated = trip.translate(&gtfs, "nl");
+    assert_eq!(Some("Centrum"), translated.stop_times[0].stop_headsign.as_deref());
+}
+
+#[test]
+fn translation_dialects() {
+    assert_eq!(
+        Some(TranslationDialect::Nmbs),
+        TranslationDialect::detect(vec!["trans_id", "lang", "translation"])
+    );
+    assert_eq!(None, TranslationDialect::detect(vec!["trans_id", "translation"]));
+
+    let read = |content: &str| -> Vec<Translation> {
+        csv::Reader::from_reader(content.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+    let legacy = read("table_name,field_name,lang,translation,record_id,comment\n\
+                       stops,stop_name,nl,Stop Gebied,stop1,unused column\n");
+    assert_eq!(
+        vec![Translation::Gtfs(GtfsTranslation {
+            table_name: "stops".to_owned(),
+            field_name: "stop_name".to_owned(),
+            language: "nl".to_owned(),
+            translation: "Stop Gebied".to_owned(),
+            record_id: Some("stop1".to_owned()),
+            record_sub_id: None,
+            field_value: None,
+        })],
+        legacy
+    );
+    let nmbs = read("trans_id,lang,translation\nLierre,nl,Lier\n");
+    assert!(matches!(&nmbs[0], Translation::Nmbs(t) if t.translation == "Lier"));
+
+    let error = csv::Reader::from_reader("table_name,translation\nstops,Gebied\n".as_bytes())
+        .deserialize::<Translation>()
+        .next()
+        .unwrap();
+    assert!(error.is_err());
+}
+
+#[test]
+fn partial_translation_rows() {
+    let read = |content: &str| -> Vec<Result<Translation, String>> {
+        csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(content.as_bytes())
+            .deserialize()
+            .map(|translation| translation.map_err(|e: csv::Error| e.to_string()))
+            .collect()
+    };
+    let rows = read(
+        "table_name,field_name,language,translation,record_id,record_sub_id,field_value\n\
+         stops,stop_name,nl,Stop Gebied,stop1\n\
+         stops,stop_name,fr,Arr\u{ea}t R\u{e9}gion,,,Stop Area\n\
+         stops,stop_name,,Gebied,stop1,,\n\
+         stops,stop_name\n\
+         feed_info,feed_publisher_name,nl,Uitgever,,,\n",
+    );
+    assert_eq!(5, rows.len());
+    let translation = |i: usize| match &rows[i] {
+        Ok(Translation::Gtfs(translation)) => translation.clone(),
+        row => panic!("unexpected row {:?}", row),
+    };
+    // the optional columns missing at the end of a short row are empty
+    assert_eq!(Some("stop1"), translation(0).record_id.as_deref());
+    assert_eq!(None, translation(0).field_value);
+    assert_eq!(Some("Stop Area"), translation(1).field_value.as_deref());
+    assert_eq!(None, translation(1).record_id);
+    let error = rows[2].as_ref().unwrap_err();
+    assert!(error.contains("the required column language is empty"), "{}", error);
+    // a short row has all the columns, its missing values being empty
+    let error = rows[3].as_ref().unwrap_err();
+    assert!(error.contains("the required column language is empty"), "{}", error);
+    assert_eq!(None, translation(4).record_id);
+
+    let error = read("table_name,field_name,language\nstops,stop_name,nl\n")
+        .remove(0)
+        .unwrap_err();
+    assert!(error.contains("with the columns field_name, language, table_name"), "{}", error);
+    let error = read("trans_id,lang,translation\nLierre,nl\n").remove(0).unwrap_err();
+    assert!(error.contains("the required column translation is empty"), "{}", error);
+    let error = read("trans_id,lang,translation,record_id\nLierre,,Lier,x\n")
+        .remove(0)
+        .unwrap_err();
+    assert!(error.contains("the required column lang is empty"), "{}", error);
+
+    let dir = format!(
+        "{}/gtfs-structures-translations-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap()))
+            .unwrap();
+    }
+    let path = format!("{}/translations.txt", dir);
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, format!("{}stops,stop_name,de\n", content)).unwrap();
+    let error = Gtfs::from_path(&dir).err().expect("the row should be an error");
+    let gtfs = Gtfs::from_path_with_options(&dir, GtfsReaderOptions::new().lenient(true))
+        .expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(Some("translations.txt"), error.file_name());
+    assert_eq!(Some(4), error.line());
+    assert_eq!(1, gtfs.warnings.len());
+    assert_eq!(2, gtfs.translations_by_id.len() + gtfs.translations_by_value.len());
+}
+
+#[test]
+fn connection_table() {
+    let mut gtfs = Gtfs::from_path("fixtures/night").expect("impossible to read gtfs");
+    gtfs.build_connection_table().unwrap();
+    let table = gtfs.connection_table.as_ref().unwrap();
+    let departures: Vec<(u32, &str, usize)> = table
+        .connections()
+        .iter()
+        .map(|c| (c.departure_time, c.departure_stop.id.as_str(), c.run))
+        .collect();
+    // the trip repeated by frequencies gives a run for each of its departures
+    assert_eq!(
+        vec![
+            (22 * 3600, "stop1", 0),
+            (22 * 3600 + 1200, "stop1", 1),
+            (22 * 3600 + 2400, "stop1", 2),
+            (23 * 3600 + 50 * 60, "stop1", 3),
+            (24 * 3600 + 2 * 60, "stop2", 3),
+            (24 * 3600 + 30 * 60, "stop1", 4),
+        ],
+        departures
+    );
+    let second = &table.connections()[1];
+    assert_eq!(("n_2200", "stop3"), (&*second.trip_id, second.arrival_stop.id.as_str()));
+    assert_eq!(22 * 3600 + 1800, second.arrival_time);
+    assert_eq!("friday", &*table.services()[second.service]);
+    assert_eq!(3, table.departing_after(23 * 3600).len());
+
+    let friday = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+    let saturday = NaiveDate::from_ymd_opt(2024, 1, 6).unwrap();
+    assert_eq!(3, table.running_on(&gtfs, friday, 23 * 3600).count());
+    assert_eq!(0, table.running_on(&gtfs, saturday, 0).count());
+
+    let gtfs = Gtfs::from_path("fixtures/basic").expect("impossible to read gtfs");
+    let table = ConnectionTable::new(&gtfs).unwrap();
+    assert_eq!(1, table.len());
+    let connection = &table.connections()[0];
+    assert_eq!((14 * 3600, 15 * 3600), (connection.departure_time, connection.arrival_time));
+    assert!(connection.pickup && connection.drop_off);
+}
+
+#[test]
+fn report_defaults() {
+    let dir = format!(
+        "{}/gtfs-structures-defaults-{}",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap()))
+            .unwrap();
+    }
+    let path = format!("{}/stops.txt", dir);
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, format!("{}stop7,\"Quay\",,48.79,2.44,,,quay,,1\n", content)).unwrap();
+
+    let gtfs = Gtfs::from_path(&dir).expect("impossible to read gtfs");
+    assert!(gtfs.warnings.is_empty());
+    let options = GtfsReaderOptions::new()
+        .report_defaults(true)
+        .column_default("stops.txt", "wheelchair_boarding", "0");
+    let gtfs = Gtfs::from_path_with_options(&dir, options).expect("impossible to read gtfs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(LocationType::StopPoint, gtfs.stops["stop7"].location_type);
+    let notes: Vec<(Option<u64>, Option<&str>, &str)> = gtfs
+        .warnings
+        .iter()
+        .filter(|w| w.file_name == "stops.txt")
+        .map(|w| (w.line, w.column.as_deref(), w.message.as_str()))
+        .collect();
+    // the explicit 0 and the values set by column_default are not reported
+    assert_eq!(
+        vec![
+            (Some(3), Some("location_type"), "empty location_type, read as 0"),
+            (Some(5), Some("location_type"), "empty location_type, read as 0"),
+            (Some(8), Some("location_type"), "unknown location_type \"quay\", read as 0"),
+        ],
+        notes
+    );
+
+    let options = GtfsReaderOptions::new().report_defaults(true);
+    let gtfs = Gtfs::from_path_with_options("fixtures/timetable", options)
+        .expect("impossible to read gtfs");
+    let transfer = gtfs.warnings.iter().find(|w| w.file_name == "transfers.txt").unwrap();
+    assert_eq!(
+        (Some(3), Some("transfer_type")),
+        (transfer.line, transfer.column.as_deref())
+    );
+    assert_eq!("transfers.txt:3 (transfer_type): empty transfer_type, read as 0", transfer.to_string());
+}
+
+/// Zip archive of the files of fixtures/basic, compressed with the method
+fn basic_zip(compression: zip::CompressionMethod) -> Vec<u8> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for entry in std::fs::read_dir("fixtures/basic").unwrap() {
+        let path = entry.unwrap().path();
+        let options = zip::write::FileOptions::default().compression_method(compression);
+        zip.start_file(path.file_name().unwrap().to_str().unwrap(), options).unwrap();
+        zip.write_all(&std::fs::read(&path).unwrap()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn zip_compression_and_checksum() {
+    let path = format!(
+        "{}/gtfs-structures-bzip2-{}.zip",
+        std::env::temp_dir().display(),
+        std::process::id()
+    );
+    std::fs::write(&path, basic_zip(zip::CompressionMethod::Bzip2)).unwrap();
+    let gtfs = Gtfs::from_path(&path).expect("impossible to read gtfs");
+    let stop_times: Vec<RawStopTime> =
+        RawGtfs::stop_times_iter(&path).unwrap().map(Result::unwrap).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(6, gtfs.stops.len());
+    assert_eq!(2, stop_times.len());
+
+    // a letter of a column of stop_times.txt changed in the stored files
+    let mut bytes = basic_zip(zip::CompressionMethod::Stored);
+    let column = bytes.windows(14).position(|w| w == b"stop_time_desc").unwrap();
+    bytes[column] = b'X';
+    let raw = RawGtfs::from_reader(std::io::Cursor::new(&bytes)).unwrap();
+    assert!(raw.stop_times.is_err());
+    assert!(raw.stops.is_ok());
+    std::fs::write(&path, &bytes).unwrap();
+    let stop_times: Result<Vec<RawStopTime>, Error> =
+        RawGtfs::stop_times_iter(&path).unwrap().collect();
+    std::fs::remove_file(&path).unwrap();
+    assert!(stop_times.is_err());
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn parallel_reading() {
+    for entry in std::fs::read_dir("fixtures/zips").unwrap() {
+        let path = entry.unwrap().path().display().to_string();
+        let sequential = GtfsReaderOptions::new().sequential(true);
+        let mut parallel = RawGtfs::from_path(&path).expect("impossible to read gtfs");
+        let mut sequential =
+            RawGtfs::from_path_with_options(&path, sequential).expect("impossible to read gtfs");
+        parallel.read_duration = 0;
+        sequential.read_duration = 0;
+        assert_eq!(format!("{:?}", sequential), format!("{:?}", parallel), "{}", path);
+    }
+}
+
+#[test]
+#[cfg(feature = "read-url")]
+fn read_from_url_async() {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/gtfs.zip", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let body = std::fs::read("fixtures/zips/gtfs.zip").unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let gtfs = runtime
+        .block_on(Gtfs::from_url_async(&url))
+        .expect("impossible to read gtfs");
+    server.join().unwrap();
+    assert_eq!(gtfs.trips.len(), Gtfs::from_path("fixtures/zips/gtfs.zip").unwrap().trips.len());
+}
+
+#[test]
+#[cfg(feature = "read-url")]
+fn read_from_url_if_modified() {
+    use crate::{CacheValidators, Fetched};
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/gtfs.zip", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let body = std::fs::read("fixtures/zips/gtfs.zip").unwrap();
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let length = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..length]).to_lowercase();
+            if request.contains("if-none-match: \"v1\"") {
+                write!(stream, "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n").unwrap();
+            } else {
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: Wed, 14 Oct 2026 08:00:00 GMT\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        }
+    });
+
+    let validators = match Gtfs::from_url_if_modified(&url, &CacheValidators::default()).unwrap() {
+        Fetched::Modified { feed, validators } => {
+            assert_eq!(feed.trips.len(), Gtfs::from_path("fixtures/zips/gtfs.zip").unwrap().trips.len());
+            validators
+        }
+        Fetched::NotModified => panic!("the feed should be downloaded"),
+    };
+    assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+    assert_eq!(validators.last_modified.as_deref(), Some("Wed, 14 Oct 2026 08:00:00 GMT"));
+
+    let fetched = Gtfs::from_url_if_modified(&url, &validators).unwrap();
+    assert!(matches!(fetched, Fetched::NotModified));
+    server.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "geojson")]
+fn route_to_geojson() {
+    let gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    let geojson = crate::json::parse(&gtfs.route_to_geojson("r71").unwrap()).unwrap();
+    assert_eq!(geojson.get("type").and_then(|t| t.as_str()), Some("FeatureCollection"));
+    let features = geojson.get("features").and_then(|f| f.as_array()).unwrap();
+    assert_eq!(features.len(), 2);
+
+    // the 2 trips going back use the same shape, given only once
+    let back = &features[1];
+    let properties = back.get("properties").unwrap();
+    assert_eq!(properties.get("direction_id").and_then(|d| d.as_f64()), Some(1.));
+    assert_eq!(properties.get("route_short_name").and_then(|n| n.as_str()), Some("71"));
+    assert_eq!(properties.get("route_color").and_then(|c| c.as_str()), Some("#FF0000"));
+    let geometry = back.get("geometry").unwrap();
+    assert_eq!(geometry.get("type").and_then(|t| t.as_str()), Some("MultiLineString"));
+    let lines = geometry.get("coordinates").and_then(|c| c.as_array()).unwrap();
+    assert_eq!(lines.len(), 1);
+    let first_point = lines[0].as_array().unwrap()[0].as_array().unwrap();
+    assert_eq!(first_point[0].as_f64(), Some(5.714));
+    assert_eq!(first_point[1].as_f64(), Some(45.191));
+
+    assert!(gtfs.route_to_geojson("unknown").is_err());
+}
+
+#[test]
+#[cfg(feature = "geojson")]
+fn stops_and_shapes_to_geojson() {
+    let gtfs = Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs");
+    let geojson = crate::json::parse(&gtfs.stops_to_geojson()).unwrap();
+    let features = geojson.get("features").and_then(|f| f.as_array()).unwrap();
+    assert_eq!(6, features.len());
+    let central = &features[0];
+    let properties = central.get("properties").unwrap();
+    assert_eq!(properties.get("stop_id").and_then(|s| s.as_str()), Some("central"));
+    assert_eq!(properties.get("stop_name").and_then(|s| s.as_str()), Some("Central Station"));
+    assert_eq!(properties.get("location_type").and_then(|l| l.as_f64()), Some(1.));
+    assert_eq!(properties.get("parent_station"), Some(&crate::json::Json::Null));
+    let geometry = central.get("geometry").unwrap();
+    assert_eq!(geometry.get("type").and_then(|t| t.as_str()), Some("Point"));
+    let point = geometry.get("coordinates").and_then(|c| c.as_array()).unwrap();
+    assert_eq!(point[0].as_f64(), Some(5.714));
+    assert_eq!(point[1].as_f64(), Some(45.191));
+
+    let geojson = crate::json::parse(&gtfs.shapes_to_geojson()).unwrap();
+    let features = geojson.get("features").and_then(|f| f.as_array()).unwrap();
+    assert_eq!(3, features.len());
+    let properties = features[1].get("properties").unwrap();
+    assert_eq!(properties.get("shape_id").and_then(|s| s.as_str()), Some("r71_out"));
+    let route_ids = properties.get("route_ids").and_then(|r| r.as_array()).unwrap();
+    assert_eq!(route_ids[0].as_str(), Some("r71"));
+    assert_eq!(properties.get("route_color").and_then(|c| c.as_str()), Some("#FF0000"));
+    let geometry = features[1].get("geometry").unwrap();
+    assert_eq!(geometry.get("type").and_then(|t| t.as_str()), Some("LineString"));
+    assert_eq!(5, geometry.get("coordinates").and_then(|c| c.as_array()).unwrap().len());
+
+    let route = gtfs.get_route("r71").unwrap();
+    assert_eq!(gtfs.route_to_geojson("r71").unwrap(), route.to_geojson(&gtfs).unwrap());
+}
+
+#[test]
+fn gtfs_view() {
+    let gtfs = Arc::new(Gtfs::from_path("fixtures/shapes").expect("impossible to read gtfs"));
+
+    let view = GtfsView::for_agency(gtfs.clone(), "tag").unwrap();
+    assert_eq!(view.trips().count(), 5);
+    // the parent station of the stops is part of the view
+    assert!(view.get_stop("central").is_ok());
+    assert_eq!(view.stops().count(), 6);
+    assert!(GtfsView::for_agency(gtfs.clone(), "unknown").is_err());
+
+    let view = GtfsView::within(gtfs.clone(), 45.19, 5.76, 45.195, 5.77).unwrap();
+    assert_eq!(view.stops().map(|s| s.id.as_str()).collect::<Vec<_>>(), vec!["university"]);
+    assert_eq!(view.routes().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["r71"]);
+    assert!(view.get_route("t1").is_err());
+    assert!(view.get_trip("t1_0900").is_err());
+    assert_eq!(view.trip_stop_times("r71_0700").unwrap().len(), 4);
+    assert!(view.get_shape("r71_out").is_ok());
+    assert_eq!(view.agencies().count(), 1);
+    // the objects are shared with the feed
+    assert!(Arc::ptr_eq(view.gtfs(), &gtfs));
+}
+
+#[test]
+#[cfg(feature = "read-url")]
 fn nmbs_data() {
     let gtfs = Gtfs::from_url("https://sncb-opendata.hafas.de/gtfs/static/c21ac6758dd25af84cca5b707f3cb3de").expect("Invalid URL");
 
@@ -0,0 +1,274 @@
+//! Times of the service days, which can go past 24:00:00
+
+use crate::gtfs::local_instant;
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::ops::{Add, Sub};
+
+/// A time of a service day, like the arrival_time of a stop time
+///
+/// The times are counted from “noon minus 12h” of the service day, which is midnight except
+/// on the days of a daylight saving time change. A trip running after midnight keeps the
+/// date of the service day, so the times can go past 24:00:00, like 25:10:00 for 1:10 the
+/// next morning. The fields of the objects stay in seconds, [StopTime::arrival] and
+/// [StopTime::departure] give them as a [GtfsTime].
+///
+/// ```
+/// use gtfs_structures::GtfsTime;
+///
+/// let time: GtfsTime = "25:10:00".parse().unwrap();
+/// assert_eq!((25, 10, 0), (time.hours(), time.minutes(), time.seconds()));
+/// let (clock, days) = time.to_naive_time();
+/// assert_eq!(("01:10:00".parse().unwrap(), 1), (clock, days));
+/// assert_eq!("25:20:00", (time + 600).to_string());
+/// ```
+///
+/// [StopTime::arrival]: crate::StopTime::arrival
+/// [StopTime::departure]: crate::StopTime::departure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GtfsTime(u32);
+
+impl GtfsTime {
+    pub fn from_seconds(seconds: u32) -> Self {
+        Self(seconds)
+    }
+
+    /// The time of the hours, minutes and seconds, the hours can be 24 or more
+    pub fn from_hms(hours: u32, minutes: u32, seconds: u32) -> Self {
+        Self(hours * 3600 + minutes * 60 + seconds)
+    }
+
+    /// Seconds since the start of the service day, the value of the fields of the objects
+    pub fn as_seconds(self) -> u32 {
+        self.0
+    }
+
+    /// Hours since the start of the service day, 24 or more after midnight
+    pub fn hours(self) -> u32 {
+        self.0 / 3600
+    }
+
+    pub fn minutes(self) -> u32 {
+        self.0 % 3600 / 60
+    }
+
+    pub fn seconds(self) -> u32 {
+        self.0 % 60
+    }
+
+    /// The time shown by a clock, and the number of days after the service day, like
+    /// (01:10:00, 1) for 25:10:00
+    pub fn to_naive_time(self) -> (NaiveTime, u32) {
+        let days = self.0 / 86400;
+        let time = NaiveTime::from_num_seconds_from_midnight_opt(self.0 % 86400, 0)
+            .unwrap_or(NaiveTime::MIN);
+        (time, days)
+    }
+
+    /// The instant of the time on the service day, in the timezone of the agency
+    ///
+    /// As the times are counted from “noon minus 12h”, a time before 2:00 on the day the
+    /// clocks are set forward is one hour later than shown by the clocks at that time.
+    pub fn on<Tz: TimeZone>(self, service_date: NaiveDate, tz: &Tz) -> Option<DateTime<Tz>> {
+        let noon = local_instant(tz, service_date.and_hms_opt(12, 0, 0)?);
+        Some(noon - Duration::hours(12) + Duration::seconds(self.0.into()))
+    }
+
+    /// Elapsed time since an earlier time, None if the other time is later
+    pub fn checked_sub(self, other: Self) -> Option<Duration> {
+        self.0
+            .checked_sub(other.0)
+            .map(|seconds| Duration::seconds(seconds.into()))
+    }
+}
+
+impl std::fmt::Display for GtfsTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.hours(),
+            self.minutes(),
+            self.seconds()
+        )
+    }
+}
+
+impl std::str::FromStr for GtfsTime {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        parse_time(s).map(Self)
+    }
+}
+
+impl From<u32> for GtfsTime {
+    fn from(seconds: u32) -> Self {
+        Self(seconds)
+    }
+}
+
+impl From<GtfsTime> for u32 {
+    fn from(time: GtfsTime) -> Self {
+        time.0
+    }
+}
+
+/// Adds seconds
+impl Add<u32> for GtfsTime {
+    type Output = Self;
+
+    fn add(self, seconds: u32) -> Self {
+        Self(self.0 + seconds)
+    }
+}
+
+/// Removes seconds, panics if the result is before the start of the service day
+impl Sub<u32> for GtfsTime {
+    type Output = Self;
+
+    fn sub(self, seconds: u32) -> Self {
+        Self(self.0 - seconds)
+    }
+}
+
+/// Elapsed time between two times, negative if the other time is later
+impl Sub for GtfsTime {
+    type Output = Duration;
+
+    fn sub(self, other: Self) -> Duration {
+        Duration::seconds(i64::from(self.0) - i64::from(other.0))
+    }
+}
+
+impl Serialize for GtfsTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for GtfsTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Gtfs {
+    /// The agency_timezone of the agency of the route of a trip
+    ///
+    /// A route without agency_id belongs to the only agency of the feed. The specification
+    /// requires all the agencies of a feed to have the same timezone.
+    pub fn trip_timezone(&self, trip_id: &str) -> Result<&str, Error> {
+        let trip = self.get_trip(trip_id)?;
+        self.route_timezone(&trip.route_id)
+    }
+
+    /// The agency_timezone of the agency of a route, see [Gtfs::trip_timezone]
+    pub(crate) fn route_timezone(&self, route_id: &str) -> Result<&str, Error> {
+        let route = self.get_route(route_id)?;
+        let agency = match &route.agency_id {
+            Some(agency_id) => self
+                .agencies
+                .iter()
+                .find(|agency| agency.id.as_ref() == Some(agency_id))
+                .ok_or_else(|| Error::ReferenceError(agency_id.to_owned()))?,
+            None => self
+                .agencies
+                .first()
+                .ok_or_else(|| Error::ReferenceError(route_id.to_owned()))?,
+        };
+        Ok(&agency.timezone)
+    }
+
+    /// The timezone of the clocks at a stop: its stop_timezone, or the one of its parent
+    /// station, None when the stop is in the timezone of the agency
+    pub fn stop_timezone(&self, stop_id: &str) -> Result<Option<&str>, Error> {
+        let stop = self.get_stop(stop_id)?;
+        let parent = stop
+            .parent_station
+            .as_ref()
+            .and_then(|parent| self.stops.get(parent));
+        Ok(stop
+            .timezone
+            .as_deref()
+            .or_else(|| parent.and_then(|parent| parent.timezone.as_deref()))
+            .filter(|timezone| !timezone.trim().is_empty()))
+    }
+
+    /// The instant of the arrival at a stop time of a trip on a service day, see
+    /// [Gtfs::departure_instant]
+    pub fn arrival_instant<Tz, F>(
+        &self,
+        trip_id: &str,
+        stop_time: &StopTime,
+        service_date: NaiveDate,
+        timezone: F,
+    ) -> Result<Option<DateTime<Tz>>, Error>
+    where
+        Tz: TimeZone,
+        F: Fn(&str) -> Option<Tz>,
+    {
+        let time = stop_time.arrival().or_else(|| stop_time.departure());
+        self.instant(trip_id, stop_time, time, service_date, timezone)
+    }
+
+    /// The instant of the departure from a stop time of a trip on a service day, None if
+    /// the stop time has no time
+    ///
+    /// The times of stop_times.txt are in the timezone of the agency, see
+    /// [Gtfs::trip_timezone] and [GtfsTime::on]. The instant is given in the timezone of
+    /// the stop (see [Gtfs::stop_timezone]), to show the time of the clocks at the stop.
+    /// `timezone` gives the timezone of a name of the tz database, like
+    /// `|name| name.parse::<chrono_tz::Tz>().ok()` with the crate `chrono-tz`, an
+    /// [Error::UnknownTimezone] is returned if it does not know a name. Whether the trip
+    /// runs on the service day is not checked.
+    pub fn departure_instant<Tz, F>(
+        &self,
+        trip_id: &str,
+        stop_time: &StopTime,
+        service_date: NaiveDate,
+        timezone: F,
+    ) -> Result<Option<DateTime<Tz>>, Error>
+    where
+        Tz: TimeZone,
+        F: Fn(&str) -> Option<Tz>,
+    {
+        let time = stop_time.departure().or_else(|| stop_time.arrival());
+        self.instant(trip_id, stop_time, time, service_date, timezone)
+    }
+
+    fn instant<Tz, F>(
+        &self,
+        trip_id: &str,
+        stop_time: &StopTime,
+        time: Option<GtfsTime>,
+        service_date: NaiveDate,
+        timezone: F,
+    ) -> Result<Option<DateTime<Tz>>, Error>
+    where
+        Tz: TimeZone,
+        F: Fn(&str) -> Option<Tz>,
+    {
+        let resolve = |name: &str| {
+            timezone(name.trim()).ok_or_else(|| Error::UnknownTimezone(name.to_owned()))
+        };
+        let agency_timezone = resolve(self.trip_timezone(trip_id)?)?;
+        let instant = match time.and_then(|time| time.on(service_date, &agency_timezone)) {
+            Some(instant) => instant,
+            None => return Ok(None),
+        };
+        // the stops of GTFS-Flex locations are not in the stops
+        let stop_timezone = match self.stops.contains_key(&stop_time.stop.id) {
+            true => self.stop_timezone(&stop_time.stop.id)?,
+            false => None,
+        };
+        Ok(Some(match stop_timezone {
+            Some(name) => instant.with_timezone(&resolve(name)?),
+            None => instant,
+        }))
+    }
+}
@@ -0,0 +1,684 @@
+use crate::objects::*;
+use crate::spatial::project_on_shape;
+use crate::{Error, Gtfs, HolidayKind, HolidayProvider, NoHolidays, RawGtfs, TripStopTimes};
+use rgb::RGB8;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Importance of an [Issue]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// The feed is valid, but some consumers might not handle it well
+    Warning,
+    /// The feed does not follow the specification
+    Error,
+}
+
+/// The kind of problem found by [Gtfs::validate] or [RawGtfs::validate]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueKind {
+    /// The route has no route_color, consumers have to pick one
+    MissingRouteColor,
+    /// The route has no route_long_name, the endpoints of its trips can be used instead
+    EmptyRouteLongName,
+    /// The route has neither a route_short_name nor a route_long_name
+    MissingRouteName,
+    /// The route_long_name of the route is the same as its route_short_name
+    RouteLongNameRepeatsShortName,
+    /// The stop times of the trip are not ordered by stop_sequence
+    UnsortedStopTimes,
+    /// The service runs on a public holiday, usually a missing exception in calendar_dates.txt
+    ServiceOnPublicHoliday,
+    /// The stops of the trip are met in the reverse order of its shape points
+    TripAgainstShape,
+    /// The object references an object that is not in the feed
+    InvalidReference,
+    /// The trip calls at a stop that is not in stops.txt, replaced by a placeholder, see
+    /// [crate::UnknownStopPolicy::Placeholder]
+    MissingStop,
+    /// Several objects of a file have the same id, found by [RawGtfs::validate] as the [Gtfs]
+    /// only keeps the last one
+    DuplicateId,
+    /// The stop has no coordinates while its location type requires them, or they are out of
+    /// the range of the latitudes and longitudes
+    InvalidCoordinates,
+    /// A stop time of the trip has a time before the one of the previous stop time
+    DecreasingStopTimes,
+    /// A file required by the specification is missing, the object id is the file name
+    MissingRequiredFile,
+}
+
+impl IssueKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            IssueKind::MissingRouteName
+            | IssueKind::UnsortedStopTimes
+            | IssueKind::InvalidReference
+            | IssueKind::MissingStop
+            | IssueKind::DuplicateId
+            | IssueKind::InvalidCoordinates
+            | IssueKind::DecreasingStopTimes
+            | IssueKind::MissingRequiredFile => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    /// Identifier of the kind for the programs reading the issues, like "missing_route_color"
+    pub fn code(self) -> &'static str {
+        match self {
+            IssueKind::MissingRouteColor => "missing_route_color",
+            IssueKind::EmptyRouteLongName => "empty_route_long_name",
+            IssueKind::MissingRouteName => "missing_route_name",
+            IssueKind::RouteLongNameRepeatsShortName => "route_long_name_repeats_short_name",
+            IssueKind::UnsortedStopTimes => "unsorted_stop_times",
+            IssueKind::ServiceOnPublicHoliday => "service_on_public_holiday",
+            IssueKind::TripAgainstShape => "trip_against_shape",
+            IssueKind::InvalidReference => "invalid_reference",
+            IssueKind::MissingStop => "missing_stop",
+            IssueKind::DuplicateId => "duplicate_id",
+            IssueKind::InvalidCoordinates => "invalid_coordinates",
+            IssueKind::DecreasingStopTimes => "decreasing_stop_times",
+            IssueKind::MissingRequiredFile => "missing_required_file",
+        }
+    }
+}
+
+/// A problem found in a feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(kind: IssueKind, object_type: ObjectType, object_id: &str, message: String) -> Self {
+        Self {
+            kind,
+            object_type,
+            object_id: object_id.to_owned(),
+            message,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+
+    /// See [IssueKind::code]
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} on {:?} '{}': {}",
+            self.severity(),
+            self.object_type,
+            self.object_id,
+            self.message
+        )
+    }
+}
+
+/// A change made by [ValidationReport::apply_fixes]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub kind: IssueKind,
+    pub object_type: ObjectType,
+    pub object_id: String,
+    /// What was changed
+    pub description: String,
+}
+
+/// All the issues found in a feed, see [Gtfs::validate]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity() == Severity::Error)
+    }
+
+    /// Repairs in place the issues that can be fixed automatically, and returns what was changed
+    ///
+    /// - a missing route color is set to white, with a black text, as the specification suggests
+    /// - an empty route long name is built from the first and last stops (or their stations)
+    ///   of the longest trip of the route
+    /// - unsorted stop times are sorted by stop_sequence
+    /// - a shape followed backwards is reversed, unless another trip follows it forwards
+    ///
+    /// The other issues are left as is. The report should be computed again after the fixes.
+    /// An error is returned if the stop times of a trip cannot be loaded, see
+    /// [Gtfs::trip_stop_times].
+    pub fn apply_fixes(&self, gtfs: &mut Gtfs) -> Result<Vec<Fix>, Error> {
+        let mut fixes = Vec::new();
+        for issue in &self.issues {
+            let description = match issue.kind {
+                IssueKind::MissingRouteColor => fix_route_color(gtfs, &issue.object_id),
+                IssueKind::EmptyRouteLongName => fix_route_long_name(gtfs, &issue.object_id)?,
+                IssueKind::UnsortedStopTimes => fix_stop_times_order(gtfs, &issue.object_id)?,
+                IssueKind::TripAgainstShape => fix_shape_direction(gtfs, &issue.object_id)?,
+                IssueKind::MissingRouteName
+                | IssueKind::RouteLongNameRepeatsShortName
+                | IssueKind::ServiceOnPublicHoliday
+                | IssueKind::InvalidReference
+                | IssueKind::MissingStop
+                | IssueKind::DuplicateId
+                | IssueKind::InvalidCoordinates
+                | IssueKind::DecreasingStopTimes
+                | IssueKind::MissingRequiredFile => None,
+            };
+            if let Some(description) = description {
+                fixes.push(Fix {
+                    kind: issue.kind,
+                    object_type: issue.object_type,
+                    object_id: issue.object_id.clone(),
+                    description,
+                });
+            }
+        }
+        Ok(fixes)
+    }
+}
+
+fn fix_route_color(gtfs: &mut Gtfs, route_id: &str) -> Option<String> {
+    let route = gtfs.routes.get_mut(route_id)?;
+    if route.route_color.is_some() {
+        return None;
+    }
+    let route = Arc::make_mut(route);
+    route.route_color = Some(RGB8::new(255, 255, 255));
+    route.route_text_color.get_or_insert(RGB8::new(0, 0, 0));
+    gtfs.link_trips();
+    Some("route_color set to FFFFFF".to_owned())
+}
+
+fn fix_route_long_name(gtfs: &mut Gtfs, route_id: &str) -> Result<Option<String>, Error> {
+    let mut longest: Option<TripStopTimes> = None;
+    for trip in gtfs.trips.values().filter(|trip| trip.route_id == route_id) {
+        let stop_times = gtfs.trip_stop_times(&trip.id)?;
+        if longest.as_ref().is_none_or(|longest| stop_times.len() >= longest.len()) {
+            longest = Some(stop_times);
+        }
+    }
+    let long_name = {
+        // a platform is named after its station
+        let name = |stop: &Stop| {
+            let station = stop
+                .parent_station
+                .as_ref()
+                .and_then(|id| gtfs.stops.get(id));
+            station
+                .map_or(stop.name.trim(), |s| s.name.trim())
+                .to_owned()
+        };
+        match longest.as_ref().and_then(|st| st.first().zip(st.last())) {
+            Some((first, last)) => format!("{} - {}", name(&first.stop), name(&last.stop)),
+            None => return Ok(None),
+        }
+    };
+    let route = match gtfs.routes.get_mut(route_id) {
+        Some(route) if route.long_name.trim().is_empty() => route,
+        _ => return Ok(None),
+    };
+    let description = format!("route_long_name set to '{}'", long_name);
+    Arc::make_mut(route).long_name = long_name;
+    gtfs.link_trips();
+    Ok(Some(description))
+}
+
+fn fix_stop_times_order(gtfs: &mut Gtfs, trip_id: &str) -> Result<Option<String>, Error> {
+    if !gtfs.trips.contains_key(trip_id) {
+        return Ok(None);
+    }
+    // the stop times shared in a pattern or spilled become the own stop times of the trip
+    let mut stop_times = gtfs.trip_stop_times(trip_id)?.to_vec();
+    if is_sorted(&stop_times) {
+        return Ok(None);
+    }
+    stop_times.sort_by_key(|stop_time| stop_time.stop_sequence);
+    if let Some(trip) = gtfs.trips.get_mut(trip_id) {
+        trip.stop_times = stop_times;
+    }
+    Ok(Some("stop times sorted by stop_sequence".to_owned()))
+}
+
+fn fix_shape_direction(gtfs: &mut Gtfs, trip_id: &str) -> Result<Option<String>, Error> {
+    let shape_id = match gtfs.trips.get(trip_id).and_then(|trip| trip.shape_id.clone()) {
+        Some(shape_id) => shape_id,
+        None => return Ok(None),
+    };
+    let shape = match gtfs.shapes.get(&shape_id) {
+        Some(shape) => shape,
+        None => return Ok(None),
+    };
+    if !runs_against_shape(&gtfs.trip_stop_times(trip_id)?, shape) {
+        return Ok(None);
+    }
+    for trip in gtfs.trips_for_shape(&shape_id) {
+        if runs_along_shape(&gtfs.trip_stop_times(&trip.id)?, shape) {
+            return Ok(None);
+        }
+    }
+
+    let points = match gtfs.shapes.get_mut(&shape_id) {
+        Some(points) => points,
+        None => return Ok(None),
+    };
+    let sequences: Vec<usize> = points.iter().map(|point| point.sequence).collect();
+    let total = points.last().and_then(|point| point.dist_traveled);
+    points.reverse();
+    for (point, sequence) in points.iter_mut().zip(sequences) {
+        point.sequence = sequence;
+        point.dist_traveled = point.dist_traveled.zip(total).map(|(d, total)| total - d);
+    }
+    Ok(Some(format!("shape '{}' reversed", shape_id)))
+}
+
+/// Number of consecutive stops of a trip going forwards and backwards along the shape
+fn shape_progress(stop_times: &[StopTime], shape: &[Shape]) -> (usize, usize) {
+    let positions: Vec<f64> = stop_times
+        .iter()
+        .filter_map(|st| match (st.stop.latitude, st.stop.longitude) {
+            (Some(lat), Some(lon)) => project_on_shape(shape, lat, lon),
+            _ => None,
+        })
+        .collect();
+    let forwards = positions.windows(2).filter(|w| w[1] > w[0]).count();
+    let backwards = positions.windows(2).filter(|w| w[1] < w[0]).count();
+    (forwards, backwards)
+}
+
+fn runs_against_shape(stop_times: &[StopTime], shape: &[Shape]) -> bool {
+    let (forwards, backwards) = shape_progress(stop_times, shape);
+    backwards > forwards
+}
+
+fn runs_along_shape(stop_times: &[StopTime], shape: &[Shape]) -> bool {
+    let (forwards, backwards) = shape_progress(stop_times, shape);
+    forwards > backwards
+}
+
+/// Why the coordinates of a stop are invalid, None if they are valid
+fn coordinates_issue(stop: &Stop) -> Option<String> {
+    let required = matches!(
+        stop.location_type,
+        LocationType::StopPoint | LocationType::StopArea | LocationType::StationEntrance
+    );
+    match (stop.latitude, stop.longitude) {
+        (Some(lat), Some(lon))
+            if !(-90. ..=90.).contains(&lat) || !(-180. ..=180.).contains(&lon) =>
+        {
+            Some(format!("the coordinates ({}, {}) are out of range", lat, lon))
+        }
+        (Some(_), Some(_)) => None,
+        _ if required => Some(format!("a {:?} must have coordinates", stop.location_type)),
+        _ => None,
+    }
+}
+
+/// The first stop time with a time before the last time of the previous stop times
+fn first_decreasing_time(stop_times: &[StopTime]) -> Option<&StopTime> {
+    let mut previous = 0;
+    stop_times.iter().find(|stop_time| {
+        let times = [stop_time.arrival_time, stop_time.departure_time];
+        let decreasing = times.iter().flatten().any(|time| *time < previous);
+        previous = times.iter().flatten().fold(previous, |max, time| max.max(*time));
+        decreasing
+    })
+}
+
+/// The file was not in the feed
+fn is_missing<T>(objects: Option<&Result<T, Error>>) -> bool {
+    objects.is_none_or(|objects| matches!(objects, Err(Error::MissingFile(_))))
+}
+
+fn ids<'a, T>(
+    objects: &'a Result<Vec<T>, Error>,
+    id: impl Fn(&'a T) -> &'a str,
+) -> Vec<&'a str> {
+    objects.iter().flatten().map(id).collect()
+}
+
+/// The ids given more than once, in the order of their second occurrence
+fn duplicates<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    ids.filter(|id| !seen.insert(*id) && reported.insert(*id))
+        .collect()
+}
+
+fn is_sorted(stop_times: &[StopTime]) -> bool {
+    stop_times
+        .windows(2)
+        .all(|w| w[0].stop_sequence <= w[1].stop_sequence)
+}
+
+impl Gtfs {
+    /// Tells if the feed has an object of this type and id
+    pub(crate) fn has_object(&self, object_type: ObjectType, id: &str) -> bool {
+        match object_type {
+            ObjectType::Agency => self.agencies.iter().any(|a| a.id.as_deref() == Some(id)),
+            ObjectType::Stop => self.stops.contains_key(id),
+            ObjectType::Route => self.routes.contains_key(id),
+            ObjectType::Trip => self.trips.contains_key(id),
+            ObjectType::Calendar => {
+                self.calendar.contains_key(id) || self.calendar_dates.contains_key(id)
+            }
+            ObjectType::Shape => self.shapes.contains_key(id),
+            ObjectType::Fare => self.fare_attributes.contains_key(id),
+            ObjectType::Level => self.levels.contains_key(id),
+            ObjectType::Pathway => self.pathways.contains_key(id),
+            ObjectType::StopTime | ObjectType::FeedInfo => false,
+        }
+    }
+
+    /// Reports the references of the objects to objects that are not in the feed
+    fn check_references<'a, O>(&self, objects: impl Iterator<Item = &'a O>, issues: &mut Vec<Issue>)
+    where
+        O: Id + Type + References + 'a,
+    {
+        for object in objects {
+            for (object_type, id) in object.references() {
+                if !self.has_object(object_type, id) {
+                    issues.push(Issue::new(
+                        IssueKind::InvalidReference,
+                        object.object_type(),
+                        object.id(),
+                        format!("the {:?} '{}' does not exist", object_type, id),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Checks the feed for common problems
+    ///
+    /// See [Gtfs::validate_with_holidays] to also check the services on public holidays.
+    pub fn validate(&self) -> Result<ValidationReport, Error> {
+        self.validate_with_holidays(&NoHolidays)
+    }
+
+    /// Checks the feed for common problems, using the holidays of the area of the feed
+    ///
+    /// An error is returned if the stop times of a trip cannot be loaded, see
+    /// [Gtfs::trip_stop_times].
+    pub fn validate_with_holidays(
+        &self,
+        holidays: &dyn HolidayProvider,
+    ) -> Result<ValidationReport, Error> {
+        let mut issues = Vec::new();
+        for route in self.routes.values() {
+            let has_long_name = !route.long_name.trim().is_empty();
+            if route.short_name.trim().is_empty() && !has_long_name {
+                issues.push(Issue::new(
+                    IssueKind::MissingRouteName,
+                    ObjectType::Route,
+                    &route.id,
+                    "the route has neither a short name nor a long name".to_owned(),
+                ));
+            } else if !has_long_name {
+                issues.push(Issue::new(
+                    IssueKind::EmptyRouteLongName,
+                    ObjectType::Route,
+                    &route.id,
+                    "the route has no long name".to_owned(),
+                ));
+            } else if route.long_name.trim().eq_ignore_ascii_case(route.short_name.trim()) {
+                issues.push(Issue::new(
+                    IssueKind::RouteLongNameRepeatsShortName,
+                    ObjectType::Route,
+                    &route.id,
+                    format!("the long name repeats the short name '{}'", route.short_name),
+                ));
+            }
+            if route.route_color.is_none() {
+                issues.push(Issue::new(
+                    IssueKind::MissingRouteColor,
+                    ObjectType::Route,
+                    &route.id,
+                    "the route has no color".to_owned(),
+                ));
+            }
+        }
+
+        for stop in self.stops.values() {
+            if let Some(message) = coordinates_issue(stop) {
+                issues.push(Issue::new(
+                    IssueKind::InvalidCoordinates,
+                    ObjectType::Stop,
+                    &stop.id,
+                    message,
+                ));
+            }
+        }
+
+        for trip in self.trips.values() {
+            let stop_times = self.trip_stop_times(&trip.id)?;
+            if !is_sorted(&stop_times) {
+                issues.push(Issue::new(
+                    IssueKind::UnsortedStopTimes,
+                    ObjectType::Trip,
+                    &trip.id,
+                    "the stop times are not sorted by stop_sequence".to_owned(),
+                ));
+            } else if let Some(stop_time) = first_decreasing_time(&stop_times) {
+                // the times are only compared once the stop times are in order
+                issues.push(Issue::new(
+                    IssueKind::DecreasingStopTimes,
+                    ObjectType::Trip,
+                    &trip.id,
+                    format!(
+                        "the stop time of stop_sequence {} is before the previous one",
+                        stop_time.stop_sequence
+                    ),
+                ));
+            }
+            let shape = trip.shape_id.as_ref().and_then(|id| self.shapes.get(id));
+            if shape.is_some_and(|shape| runs_against_shape(&stop_times, shape)) {
+                issues.push(Issue::new(
+                    IssueKind::TripAgainstShape,
+                    ObjectType::Trip,
+                    &trip.id,
+                    format!(
+                        "the stops are in the reverse order of the shape '{}'",
+                        trip.shape_id.as_deref().unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+
+        if self.stops.values().any(|stop| stop.missing) {
+            for trip in self.trips.values() {
+                let stop_times = self.trip_stop_times(&trip.id)?;
+                let mut missing: Vec<&str> = stop_times
+                    .iter()
+                    .filter(|stop_time| stop_time.stop.missing)
+                    .map(|stop_time| stop_time.stop.id.as_str())
+                    .collect();
+                missing.dedup();
+                if !missing.is_empty() {
+                    issues.push(Issue::new(
+                        IssueKind::MissingStop,
+                        ObjectType::Trip,
+                        &trip.id,
+                        format!(
+                            "the trip calls at stops not in stops.txt: {}",
+                            missing.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+
+        self.check_references(self.stops.values().map(|stop| stop.as_ref()), &mut issues);
+        self.check_references(self.routes.values().map(|route| route.as_ref()), &mut issues);
+        self.check_references(self.trips.values(), &mut issues);
+        self.check_references(self.pathways.values(), &mut issues);
+        self.check_references(self.fare_attributes.values(), &mut issues);
+        for fare in self.fare_attributes.values() {
+            for rule in &fare.rules {
+                for (object_type, id) in rule.references() {
+                    if !self.has_object(object_type, id) {
+                        issues.push(Issue::new(
+                            IssueKind::InvalidReference,
+                            ObjectType::Fare,
+                            &fare.id,
+                            format!(
+                                "a rule references the {:?} '{}', which does not exist",
+                                object_type, id
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        if self.calendar.is_empty() && self.calendar_dates.is_empty() {
+            issues.push(Issue::new(
+                IssueKind::MissingRequiredFile,
+                ObjectType::Calendar,
+                "calendar.txt",
+                "the feed has neither calendar.txt nor calendar_dates.txt".to_owned(),
+            ));
+        }
+
+        let mut service_ids: Vec<&String> = self
+            .calendar
+            .keys()
+            .chain(self.calendar_dates.keys())
+            .collect();
+        service_ids.sort();
+        service_ids.dedup();
+        for service_id in service_ids {
+            for date in self.operating_days.active_dates(service_id) {
+                if holidays.holiday(date) == Some(HolidayKind::Public) {
+                    issues.push(Issue::new(
+                        IssueKind::ServiceOnPublicHoliday,
+                        ObjectType::Calendar,
+                        service_id,
+                        format!("the service runs on the public holiday {}", date),
+                    ));
+                }
+            }
+        }
+
+        Ok(ValidationReport { issues })
+    }
+}
+
+impl RawGtfs {
+    /// Checks the problems that are only visible in the files: the required files that are
+    /// missing and the ids given to several objects
+    ///
+    /// The other problems are found by [Gtfs::validate] once the feed is built.
+    pub fn validate(&self) -> ValidationReport {
+        let mut issues = Vec::new();
+        let mut missing = |file_name: &str, object_type: ObjectType, missing: bool| {
+            if missing {
+                issues.push(Issue::new(
+                    IssueKind::MissingRequiredFile,
+                    object_type,
+                    file_name,
+                    format!("the feed has no {}", file_name),
+                ));
+            }
+        };
+        missing("agency.txt", ObjectType::Agency, is_missing(Some(&self.agencies)));
+        missing("stops.txt", ObjectType::Stop, is_missing(Some(&self.stops)));
+        missing("routes.txt", ObjectType::Route, is_missing(Some(&self.routes)));
+        missing("trips.txt", ObjectType::Trip, is_missing(Some(&self.trips)));
+        // the stop times spilled to the disk are not in stop_times
+        missing(
+            "stop_times.txt",
+            ObjectType::StopTime,
+            self.stop_time_store.is_none() && is_missing(Some(&self.stop_times)),
+        );
+        missing(
+            "calendar.txt",
+            ObjectType::Calendar,
+            is_missing(self.calendar.as_ref()) && is_missing(self.calendar_dates.as_ref()),
+        );
+
+        let mut duplicated = |object_type: ObjectType, ids: Vec<&str>, what: &str| {
+            for id in duplicates(ids.into_iter()) {
+                issues.push(Issue::new(
+                    IssueKind::DuplicateId,
+                    object_type,
+                    id,
+                    format!("several {} have the id '{}'", what, id),
+                ));
+            }
+        };
+        duplicated(ObjectType::Stop, ids(&self.stops, |s: &Stop| &s.id), "stops");
+        duplicated(ObjectType::Route, ids(&self.routes, |r: &Route| &r.id), "routes");
+        duplicated(ObjectType::Trip, ids(&self.trips, |t: &RawTrip| &t.id), "trips");
+        let agency_ids = self
+            .agencies
+            .iter()
+            .flatten()
+            .filter_map(|agency| agency.id.as_deref())
+            .collect();
+        duplicated(ObjectType::Agency, agency_ids, "agencies");
+        if let Some(calendar) = &self.calendar {
+            let service_ids = ids(calendar, |c: &Calendar| &c.id);
+            duplicated(ObjectType::Calendar, service_ids, "calendars");
+        }
+        if let Some(fares) = &self.fare_attributes {
+            let fare_ids = ids(fares, |f: &FareAttribute| &f.id);
+            duplicated(ObjectType::Fare, fare_ids, "fares");
+        }
+        if let Some(levels) = &self.levels {
+            duplicated(ObjectType::Level, ids(levels, |l: &Level| &l.id), "levels");
+        }
+        if let Some(pathways) = &self.pathways {
+            let pathway_ids = ids(pathways, |p: &Pathway| &p.id);
+            duplicated(ObjectType::Pathway, pathway_ids, "pathways");
+        }
+
+        // the stop times and the shape points are identified by their sequence
+        let mut sequences = |object_type: ObjectType, keys: Vec<(&str, String)>, what: &str| {
+            let mut seen = HashSet::new();
+            let mut reported = HashSet::new();
+            for (id, sequence) in &keys {
+                if !seen.insert((id, sequence)) && reported.insert((id, sequence)) {
+                    issues.push(Issue::new(
+                        IssueKind::DuplicateId,
+                        object_type,
+                        id,
+                        format!("several {} have the sequence {}", what, sequence),
+                    ));
+                }
+            }
+        };
+        let stop_time_keys = self
+            .stop_times
+            .iter()
+            .flatten()
+            .map(|st| (st.trip_id.as_str(), st.stop_sequence.to_string()))
+            .collect();
+        sequences(ObjectType::Trip, stop_time_keys, "stop times");
+        if let Some(shapes) = &self.shapes {
+            let point_keys = shapes
+                .iter()
+                .flatten()
+                .map(|point| (point.id.as_str(), point.sequence.to_string()))
+                .collect();
+            sequences(ObjectType::Shape, point_keys, "points");
+        }
+        ValidationReport { issues }
+    }
+}
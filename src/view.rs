@@ -0,0 +1,229 @@
+//! Parts of a GTFS sharing the data of the whole feed
+
+use crate::objects::*;
+use crate::{Error, Gtfs, TripStopTimes};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// A part of a [Gtfs], like the routes of an agency or the stops of an area
+///
+/// The view only keeps the ids of its objects, the objects themselves are shared with the
+/// [Gtfs], so many views of a large feed can be kept in memory. Cloning a view is cheap too.
+///
+/// The getters give a [Error::ReferenceError] for the objects that are not part of the view.
+///
+/// ```
+/// # use std::sync::Arc;
+/// use gtfs_structures::{Gtfs, GtfsView};
+///
+/// let gtfs = Arc::new(Gtfs::new("fixtures/shapes").unwrap());
+/// let view = GtfsView::for_agency(gtfs.clone(), "tag").unwrap();
+/// assert_eq!(view.routes().count(), 2);
+/// ```
+#[derive(Clone)]
+pub struct GtfsView {
+    gtfs: Arc<Gtfs>,
+    /// Indexes in [Gtfs::agencies]
+    agencies: Vec<usize>,
+    stops: BTreeSet<String>,
+    routes: BTreeSet<String>,
+    trips: BTreeSet<String>,
+    services: BTreeSet<String>,
+    shapes: BTreeSet<String>,
+}
+
+impl GtfsView {
+    /// The routes of an agency, with their trips and the stops (and their parent stations)
+    /// served by them
+    pub fn for_agency(gtfs: Arc<Gtfs>, agency_id: &str) -> Result<Self, Error> {
+        let agency = gtfs
+            .agencies
+            .iter()
+            .position(|a| a.id.as_deref() == Some(agency_id))
+            .ok_or_else(|| Error::ReferenceError(agency_id.to_owned()))?;
+        // without agency_id, the route belongs to the only agency of the feed
+        let single_agency = gtfs.agencies.len() == 1;
+        let trips = gtfs
+            .trips
+            .values()
+            .filter(|trip| {
+                gtfs.routes
+                    .get(&trip.route_id)
+                    .is_some_and(|route| match &route.agency_id {
+                        Some(id) => id == agency_id,
+                        None => single_agency,
+                    })
+            })
+            .map(|trip| trip.id.clone())
+            .collect::<BTreeSet<_>>();
+
+        let mut stops = BTreeSet::new();
+        for trip_id in &trips {
+            for stop_time in gtfs.trip_stop_times(trip_id)?.iter() {
+                let mut stop = Some(stop_time.stop.as_ref());
+                while let Some(s) = stop {
+                    if !stops.insert(s.id.clone()) {
+                        break;
+                    }
+                    stop = s
+                        .parent_station
+                        .as_ref()
+                        .and_then(|id| gtfs.stops.get(id))
+                        .map(|parent| parent.as_ref());
+                }
+            }
+        }
+        let mut view = Self::with_trips(gtfs, trips, stops);
+        view.agencies = vec![agency];
+        Ok(view)
+    }
+
+    /// The stops inside a bounding box, with the trips stopping at least once at one of them
+    /// and their routes and agencies
+    pub fn within(
+        gtfs: Arc<Gtfs>,
+        min_lat: f64,
+        min_lon: f64,
+        max_lat: f64,
+        max_lon: f64,
+    ) -> Result<Self, Error> {
+        let stops = gtfs
+            .stops
+            .values()
+            .filter(|stop| match (stop.latitude, stop.longitude) {
+                (Some(lat), Some(lon)) => {
+                    (min_lat..=max_lat).contains(&lat) && (min_lon..=max_lon).contains(&lon)
+                }
+                _ => false,
+            })
+            .map(|stop| stop.id.clone())
+            .collect::<BTreeSet<_>>();
+        let mut trips = BTreeSet::new();
+        for trip in gtfs.trips.values() {
+            if gtfs
+                .trip_stop_times(&trip.id)?
+                .iter()
+                .any(|st| stops.contains(&st.stop.id))
+            {
+                trips.insert(trip.id.clone());
+            }
+        }
+        let mut view = Self::with_trips(gtfs, trips, stops);
+        let agency_ids = view
+            .routes()
+            .map(|route| route.agency_id.as_deref())
+            .collect::<BTreeSet<_>>();
+        let single_agency = view.gtfs.agencies.len() == 1;
+        view.agencies = (0..view.gtfs.agencies.len())
+            .filter(|&i| {
+                agency_ids.contains(&view.gtfs.agencies[i].id.as_deref())
+                    || (single_agency && agency_ids.contains(&None))
+            })
+            .collect();
+        Ok(view)
+    }
+
+    /// View of the trips, with their routes, services and shapes
+    fn with_trips(gtfs: Arc<Gtfs>, trips: BTreeSet<String>, stops: BTreeSet<String>) -> Self {
+        let mut routes = BTreeSet::new();
+        let mut services = BTreeSet::new();
+        let mut shapes = BTreeSet::new();
+        for trip in trips.iter().filter_map(|id| gtfs.trips.get(id)) {
+            routes.insert(trip.route_id.clone());
+            services.insert(trip.service_id.clone());
+            if let Some(shape_id) = &trip.shape_id {
+                shapes.insert(shape_id.clone());
+            }
+        }
+        Self {
+            gtfs,
+            agencies: Vec::new(),
+            stops,
+            routes,
+            trips,
+            services,
+            shapes,
+        }
+    }
+
+    /// The whole feed
+    pub fn gtfs(&self) -> &Arc<Gtfs> {
+        &self.gtfs
+    }
+
+    pub fn agencies(&self) -> impl Iterator<Item = &Agency> {
+        self.agencies.iter().map(move |&i| &self.gtfs.agencies[i])
+    }
+
+    /// Stops of the view, ordered by id
+    pub fn stops(&self) -> impl Iterator<Item = &Arc<Stop>> {
+        self.stops
+            .iter()
+            .filter_map(move |id| self.gtfs.stops.get(id))
+    }
+
+    /// Routes of the view, ordered by id
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes
+            .iter()
+            .filter_map(move |id| self.gtfs.routes.get(id))
+            .map(|route| route.as_ref())
+    }
+
+    /// Trips of the view, ordered by id
+    pub fn trips(&self) -> impl Iterator<Item = &Trip> {
+        self.trips
+            .iter()
+            .filter_map(move |id| self.gtfs.trips.get(id))
+    }
+
+    pub fn get_stop<'a>(&'a self, id: &str) -> Result<&'a Stop, Error> {
+        match self.stops.contains(id) {
+            true => self.gtfs.get_stop(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_route<'a>(&'a self, id: &str) -> Result<&'a Route, Error> {
+        match self.routes.contains(id) {
+            true => self.gtfs.get_route(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_trip<'a>(&'a self, id: &str) -> Result<&'a Trip, Error> {
+        match self.trips.contains(id) {
+            true => self.gtfs.get_trip(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    /// Stop times of a trip of the view, see [Gtfs::trip_stop_times]
+    ///
+    /// All the stop times of the trip are given, even the ones at a stop outside of the view.
+    pub fn trip_stop_times(&self, trip_id: &str) -> Result<TripStopTimes<'_>, Error> {
+        self.get_trip(trip_id)?;
+        self.gtfs.trip_stop_times(trip_id)
+    }
+
+    pub fn get_calendar<'a>(&'a self, id: &str) -> Result<&'a Calendar, Error> {
+        match self.services.contains(id) {
+            true => self.gtfs.get_calendar(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_calendar_date<'a>(&'a self, id: &str) -> Result<&'a Vec<CalendarDate>, Error> {
+        match self.services.contains(id) {
+            true => self.gtfs.get_calendar_date(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+
+    pub fn get_shape<'a>(&'a self, id: &str) -> Result<&'a Vec<Shape>, Error> {
+        match self.shapes.contains(id) {
+            true => self.gtfs.get_shape(id),
+            false => Err(Error::ReferenceError(id.to_owned())),
+        }
+    }
+}
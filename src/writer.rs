@@ -0,0 +1,415 @@
+//! Writing a [Gtfs] back to csv files
+
+use crate::flex::write_locations;
+use crate::objects::*;
+use crate::{Error, Gtfs};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Content of a csv file, with a header taken from the first row
+fn to_csv<T, I>(file_name: &str, rows: I) -> Result<Vec<u8>, Error>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row).map_err(|e| Error::CSVError {
+            file_name: file_name.to_owned(),
+            source: e,
+            line_in_error: None,
+        })?;
+    }
+    writer.into_inner().map_err(|e| Error::NamedFileIO {
+        file_name: file_name.to_owned(),
+        source: e.into_error(),
+    })
+}
+
+/// Writes the coordinates as they were read instead of their values, in the columns of a csv
+/// file written by [to_csv], see [crate::GtfsReaderOptions::keep_coordinate_text]
+///
+/// `texts` gives for each row the text of its coordinates, if it still matches their values.
+fn restore_coordinates<'a>(
+    file_name: &str,
+    content: Vec<u8>,
+    columns: [&str; 2],
+    texts: impl Iterator<Item = Option<&'a CoordinateText>>,
+) -> Result<Vec<u8>, Error> {
+    let texts: Vec<Option<&CoordinateText>> = texts.collect();
+    if texts.iter().all(Option::is_none) {
+        return Ok(content);
+    }
+    let csv_error = |e| Error::CSVError {
+        file_name: file_name.to_owned(),
+        source: e,
+        line_in_error: None,
+    };
+    let mut reader = csv::Reader::from_reader(content.as_slice());
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let position = |column| headers.iter().position(|h| h == column);
+    let (latitude, longitude) = (position(columns[0]), position(columns[1]));
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers).map_err(csv_error)?;
+    for (record, text) in reader.records().zip(texts) {
+        let record = record.map_err(csv_error)?;
+        let fields = record.iter().enumerate().map(|(i, field)| match text {
+            Some(text) if Some(i) == latitude => text.latitude.as_str(),
+            Some(text) if Some(i) == longitude => text.longitude.as_str(),
+            _ => field,
+        });
+        writer.write_record(fields).map_err(csv_error)?;
+    }
+    writer.into_inner().map_err(|e| Error::NamedFileIO {
+        file_name: file_name.to_owned(),
+        source: e.into_error(),
+    })
+}
+
+fn raw_trip(trip: &Trip) -> RawTrip {
+    RawTrip {
+        id: trip.id.clone(),
+        service_id: trip.service_id.clone(),
+        route_id: trip.route_id.clone(),
+        shape_id: trip.shape_id.clone(),
+        trip_headsign: trip.trip_headsign.clone(),
+        trip_short_name: trip.trip_short_name.clone(),
+        direction_id: trip.direction_id,
+        block_id: trip.block_id.clone(),
+        wheelchair_accessible: trip.wheelchair_accessible,
+        bikes_allowed: trip.bikes_allowed,
+        #[cfg(feature = "extensions")]
+        vehicle_type: trip.vehicle_type.clone(),
+        #[cfg(feature = "extensions")]
+        cars_count: trip.cars_count,
+        #[cfg(feature = "extensions")]
+        capacity: trip.capacity,
+    }
+}
+
+fn raw_stop_time(trip_id: &str, stop_time: &StopTime) -> RawStopTime {
+    RawStopTime {
+        trip_id: trip_id.to_owned(),
+        arrival_time: stop_time.arrival_time,
+        departure_time: stop_time.departure_time,
+        // the placeholder stop of the GTFS-Flex stop times has an empty id
+        stop_id: stop_time.stop.id.clone(),
+        stop_sequence: stop_time.stop_sequence,
+        stop_headsign: stop_time.stop_headsign.clone(),
+        pickup_type: stop_time.pickup_type,
+        drop_off_type: stop_time.drop_off_type,
+        continuous_pickup: stop_time.continuous_pickup,
+        continuous_drop_off: stop_time.continuous_drop_off,
+        shape_dist_traveled: stop_time.shape_dist_traveled,
+        timepoint: stop_time.timepoint,
+        location_group_id: stop_time.location_group_id.clone(),
+        location_id: stop_time.location_id.clone(),
+        start_pickup_drop_off_window: stop_time.start_pickup_drop_off_window,
+        end_pickup_drop_off_window: stop_time.end_pickup_drop_off_window,
+        pickup_booking_rule_id: stop_time.pickup_booking_rule_id.clone(),
+        drop_off_booking_rule_id: stop_time.drop_off_booking_rule_id.clone(),
+        #[cfg(feature = "extensions")]
+        local_zone_id: stop_time.local_zone_id.clone(),
+    }
+}
+
+fn raw_frequency(trip_id: &str, frequency: &Frequency) -> RawFrequency {
+    RawFrequency {
+        trip_id: trip_id.to_owned(),
+        start_time: frequency.start_time,
+        end_time: frequency.end_time,
+        headway_secs: frequency.headway_secs,
+        exact_times: frequency.exact_times,
+    }
+}
+
+fn translation_key(
+    t: &GtfsTranslation,
+) -> (&str, &str, &str, Option<&str>, Option<&str>, Option<&str>) {
+    (
+        &t.table_name,
+        &t.field_name,
+        &t.language,
+        t.record_id.as_deref(),
+        t.record_sub_id.as_deref(),
+        t.field_value.as_deref(),
+    )
+}
+
+impl Gtfs {
+    /// The translations, as lines of translations.txt in the format of the specification
+    fn translation_rows(&self) -> Vec<GtfsTranslation> {
+        let by_id = self
+            .translations_by_id
+            .iter()
+            .map(|(key, translation)| GtfsTranslation {
+                table_name: key.table_name.clone(),
+                field_name: key.field_name.clone(),
+                language: key.language.clone(),
+                translation: translation.clone(),
+                record_id: Some(key.record_id.clone()),
+                record_sub_id: key.record_sub_id.clone(),
+                field_value: None,
+            });
+        let by_value =
+            self.translations_by_value
+                .iter()
+                .map(|(key, translation)| GtfsTranslation {
+                    table_name: key.table_name.clone(),
+                    field_name: key.field_name.clone(),
+                    language: key.language.clone(),
+                    translation: translation.clone(),
+                    record_id: None,
+                    record_sub_id: None,
+                    field_value: Some(key.field_value.clone()),
+                });
+        let mut rows: Vec<GtfsTranslation> = by_id.chain(by_value).collect();
+        // the translations are in hash maps, they are sorted to always write the same file
+        rows.sort_by(|a, b| translation_key(a).cmp(&translation_key(b)));
+        rows
+    }
+
+    /// Name and content of the files of the GTFS
+    ///
+    /// The required files are always written, the optional ones only when they have lines.
+    fn files(&self) -> Result<Vec<(&'static str, Vec<u8>)>, Error> {
+        let mut stop_times = Vec::new();
+        for trip in self.trips.values() {
+            // the stop times can be in the store of spilled stop times
+            for stop_time in self.trip_stop_times(&trip.id)?.iter() {
+                stop_times.push(raw_stop_time(&trip.id, stop_time));
+            }
+        }
+        let frequencies: Vec<RawFrequency> = self
+            .trips
+            .values()
+            .flat_map(|t| t.frequencies.iter().map(move |f| raw_frequency(&t.id, f)))
+            .collect();
+        let fare_rules: Vec<&FareRule> = self
+            .fare_attributes
+            .values()
+            .flat_map(|f| f.rules.iter())
+            .collect();
+        let stop_areas: Vec<StopArea> = self
+            .stop_areas
+            .iter()
+            .flat_map(|(area_id, stop_ids)| {
+                stop_ids.iter().map(move |stop_id| StopArea {
+                    area_id: area_id.clone(),
+                    stop_id: stop_id.clone(),
+                })
+            })
+            .collect();
+        let location_group_stops: Vec<LocationGroupStop> = self
+            .location_group_stops
+            .iter()
+            .flat_map(|(group_id, stop_ids)| {
+                stop_ids.iter().map(move |stop_id| LocationGroupStop {
+                    location_group_id: group_id.clone(),
+                    stop_id: stop_id.clone(),
+                })
+            })
+            .collect();
+        let translations = self.translation_rows();
+
+        let mut files = vec![
+            ("agency.txt", to_csv("agency.txt", &self.agencies)?),
+            (
+                "stops.txt",
+                restore_coordinates(
+                    "stops.txt",
+                    to_csv("stops.txt", self.stops.values().map(|s| s.as_ref()))?,
+                    ["stop_lat", "stop_lon"],
+                    self.stops.values().map(|stop| {
+                        stop.coordinate_text
+                            .as_deref()
+                            .filter(|text| text.matches(stop.latitude, stop.longitude))
+                    }),
+                )?,
+            ),
+            ("routes.txt", to_csv("routes.txt", self.routes.values().map(|r| r.as_ref()))?),
+            (
+                "trips.txt",
+                to_csv("trips.txt", self.trips.values().map(raw_trip))?,
+            ),
+            ("stop_times.txt", to_csv("stop_times.txt", &stop_times)?),
+        ];
+        let mut optional = |file_name: &'static str, is_empty: bool, content: Vec<u8>| {
+            if !is_empty {
+                files.push((file_name, content));
+            }
+        };
+        optional(
+            "calendar.txt",
+            self.calendar.is_empty(),
+            to_csv("calendar.txt", self.calendar.values().map(|c| c.as_ref()))?,
+        );
+        optional(
+            "calendar_dates.txt",
+            self.calendar_dates.values().all(Vec::is_empty),
+            to_csv("calendar_dates.txt", self.calendar_dates.values().flatten())?,
+        );
+        optional(
+            "shapes.txt",
+            self.shapes.values().all(Vec::is_empty),
+            restore_coordinates(
+                "shapes.txt",
+                to_csv("shapes.txt", self.shapes.values().flatten())?,
+                ["shape_pt_lat", "shape_pt_lon"],
+                self.shapes.values().flatten().map(|point| {
+                    point
+                        .coordinate_text
+                        .as_deref()
+                        .filter(|text| text.matches(Some(point.latitude), Some(point.longitude)))
+                }),
+            )?,
+        );
+        optional(
+            "frequencies.txt",
+            frequencies.is_empty(),
+            to_csv("frequencies.txt", &frequencies)?,
+        );
+        optional(
+            "fare_attributes.txt",
+            self.fare_attributes.is_empty(),
+            to_csv("fare_attributes.txt", self.fare_attributes.values())?,
+        );
+        optional(
+            "fare_rules.txt",
+            fare_rules.is_empty(),
+            to_csv("fare_rules.txt", &fare_rules)?,
+        );
+        optional(
+            "fare_media.txt",
+            self.fare_media.is_empty(),
+            to_csv("fare_media.txt", self.fare_media.values())?,
+        );
+        optional(
+            "rider_categories.txt",
+            self.rider_categories.is_empty(),
+            to_csv("rider_categories.txt", self.rider_categories.values())?,
+        );
+        optional(
+            "fare_containers.txt",
+            self.fare_containers.is_empty(),
+            to_csv("fare_containers.txt", self.fare_containers.values())?,
+        );
+        optional(
+            "fare_products.txt",
+            self.fare_products.values().all(Vec::is_empty),
+            to_csv("fare_products.txt", self.fare_products.values().flatten())?,
+        );
+        optional(
+            "fare_leg_rules.txt",
+            self.fare_leg_rules.is_empty(),
+            to_csv("fare_leg_rules.txt", &self.fare_leg_rules)?,
+        );
+        optional(
+            "fare_transfer_rules.txt",
+            self.fare_transfer_rules.is_empty(),
+            to_csv("fare_transfer_rules.txt", &self.fare_transfer_rules)?,
+        );
+        optional(
+            "areas.txt",
+            self.areas.is_empty(),
+            to_csv("areas.txt", self.areas.values())?,
+        );
+        optional(
+            "stop_areas.txt",
+            stop_areas.is_empty(),
+            to_csv("stop_areas.txt", &stop_areas)?,
+        );
+        optional(
+            "networks.txt",
+            self.networks.is_empty(),
+            to_csv("networks.txt", self.networks.values())?,
+        );
+        optional(
+            "timeframes.txt",
+            self.timeframes.values().all(Vec::is_empty),
+            to_csv("timeframes.txt", self.timeframes.values().flatten())?,
+        );
+        optional(
+            "transfers.txt",
+            self.transfers.values().all(Vec::is_empty),
+            to_csv("transfers.txt", self.transfers.values().flatten())?,
+        );
+        optional(
+            "pathways.txt",
+            self.pathways.is_empty(),
+            to_csv("pathways.txt", self.pathways.values())?,
+        );
+        optional(
+            "levels.txt",
+            self.levels.is_empty(),
+            to_csv("levels.txt", self.levels.values().map(|l| l.as_ref()))?,
+        );
+        optional(
+            "feed_info.txt",
+            self.feed_info.is_empty(),
+            to_csv("feed_info.txt", &self.feed_info)?,
+        );
+        optional(
+            "attributions.txt",
+            self.attributions.is_empty(),
+            to_csv("attributions.txt", &self.attributions)?,
+        );
+        optional(
+            "translations.txt",
+            translations.is_empty(),
+            to_csv("translations.txt", &translations)?,
+        );
+        optional(
+            "booking_rules.txt",
+            self.booking_rules.is_empty(),
+            to_csv("booking_rules.txt", self.booking_rules.values())?,
+        );
+        optional(
+            "location_groups.txt",
+            self.location_groups.is_empty(),
+            to_csv("location_groups.txt", self.location_groups.values())?,
+        );
+        optional(
+            "location_group_stops.txt",
+            location_group_stops.is_empty(),
+            to_csv("location_group_stops.txt", &location_group_stops)?,
+        );
+        optional(
+            "locations.geojson",
+            self.locations.is_empty(),
+            write_locations(self.locations.values()).into_bytes(),
+        );
+        Ok(files)
+    }
+
+    /// Writes the GTFS as csv files in a directory, created if it does not exist
+    ///
+    /// The files of the directory that are not part of the GTFS are left untouched.
+    pub fn write_to_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        fs::create_dir_all(path)?;
+        for (file_name, content) in self.files()? {
+            fs::write(path.join(file_name), content).map_err(|e| Error::NamedFileIO {
+                file_name: file_name.to_owned(),
+                source: e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes the GTFS as a zip archive of csv files
+    pub fn write_to_zip<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        for (file_name, content) in self.files()? {
+            zip.start_file(file_name, zip::write::FileOptions::default())?;
+            zip.write_all(&content).map_err(|e| Error::NamedFileIO {
+                file_name: file_name.to_owned(),
+                source: e,
+            })?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+}